@@ -1,17 +1,44 @@
-use std::str::FromStr;
+use std::f64::consts::PI;
 
 use eframe::{CreationContext, Frame};
 use egui::{
-    Color32, ColorImage, Context, Image, Rgba, TextureHandle, TextureOptions, Ui,
+    Color32, ColorImage, Context, Image, Rect, Sense, TextureHandle, TextureOptions, Ui,
     load::SizedTexture,
 };
-use hashlife::{HashLife, render};
+use hashlife::{
+    HashLife, HistoryConfig, Hyperspeed, UniverseConfig, patterns,
+    render::{self, Rgba},
+};
+
+use crate::{session::Session, stepper::Stepper};
 
 pub struct App {
     hl: HashLife,
     log_2_steps: usize,
     texture: Option<TextureHandle>,
     view: render::View,
+    cache: render::RenderCache,
+    /// The step in progress, if any. The pattern can't be edited meanwhile.
+    stepper: Option<Stepper>,
+    /// Picks the step sizes when stepping as fast as possible.
+    hyperspeed: Option<Hyperspeed>,
+    /// Editing together with another instance, if hosting or joined.
+    session: Option<Session>,
+    /// Where to host or join a session.
+    address: String,
+    /// Why hosting or joining last failed.
+    session_error: Option<String>,
+}
+
+/// Tracks what the app shows and can rewind.
+pub fn configure(hl: &mut HashLife) {
+    hl.track_ages(true);
+    hl.track_history(Some(HistoryConfig::default()));
+    hl.track_rewind(1 << 10, 64);
+    hl.set_universe_config(UniverseConfig {
+        max_nodes: Some(1 << 24),
+        max_cache_entries: None,
+    });
 }
 
 impl App {
@@ -19,22 +46,8 @@ impl App {
     pub fn new(cc: &CreationContext<'_>) -> Self {
         // This is also where you can customize the look and feel of egui using
         // `cc.egui_ctx.set_visuals` and `cc.egui_ctx.set_fonts`.
-        // let mut hl = HashLife::from_str(
-        //     "
-        //         oo       o
-        //         o o       o
-        //          o      ooo",
-        // )
-        // .unwrap();
-        // hl.step(10);
-        let mut hl = HashLife::new();
-        for y in -100..100isize {
-            for x in -100..100isize {
-                if x.rem_euclid(2) == 0 && y.rem_euclid(2) == 0 {
-                    hl.set_bit((y, x));
-                }
-            }
-        }
+        let mut hl = patterns::gosper_gun();
+        configure(&mut hl);
         Self {
             hl,
             log_2_steps: 0,
@@ -42,21 +55,46 @@ impl App {
             view: render::View {
                 center: (0., 0.),
                 zoom: 0.5,
+                rotation: 0.,
             },
+            cache: render::RenderCache::default(),
+            stepper: None,
+            hyperspeed: None,
+            session: None,
+            address: "127.0.0.1:7878".to_string(),
+            session_error: None,
         }
     }
 
-    fn render_hashlife(&mut self, ctx: &Context, ui: &mut Ui) -> Image<'_> {
-        let rect = ui.max_rect();
-        let settings = render::Settings {
+    fn settings(&self, rect: Rect) -> render::Settings {
+        render::Settings {
             height: rect.height() as usize,
             width: rect.width() as usize,
             cell_size: 1.,
             dead_rgba: Rgba::WHITE,
             alive_rgba: Rgba::BLACK,
-        };
-        let pixels = self.hl.render(settings, self.view);
-        let pixels: Vec<Color32> = pixels.into_iter().map(From::from).collect();
+            row_offset: self.hl.rule().neighbourhood().row_offset(),
+            grid: Some(render::Grid {
+                rgba: Rgba::from_rgb(0.8, 0.8, 0.8),
+                min_pixels_per_cell: 8.,
+            }),
+            ages: Some(render::AgeRamp {
+                newborn_rgba: Rgba::from_rgb(0.9, 0.3, 0.1),
+                old_rgba: Rgba::BLACK,
+                old_age: 100,
+            }),
+            heatmap: None,
+            envelope: None,
+        }
+    }
+
+    fn render_hashlife(&mut self, ctx: &Context, ui: &mut Ui) -> Image<'_> {
+        let settings = self.settings(ui.max_rect());
+        let pixels = self.hl.render_cached(settings, self.view, &mut self.cache);
+        let pixels: Vec<Color32> = pixels
+            .into_iter()
+            .map(|rgba| egui::Rgba::from(rgba).into())
+            .collect();
         let image = ColorImage::new([settings.width, settings.height], pixels);
         let options = TextureOptions::NEAREST;
         let texture = match self.texture.take() {
@@ -71,16 +109,120 @@ impl App {
         self.texture = Some(texture);
         Image::new(sized_texture).fit_to_exact_size(size)
     }
+
+    /// Hosts or joins a session, or shows how the current one is going.
+    fn session_ui(&mut self, ctx: &Context, ui: &mut Ui) {
+        if let Some(session) = &self.session {
+            ui.label(session.status());
+            if ui.button("Leave").clicked() {
+                self.session = None;
+            }
+            return;
+        }
+        ui.label("Session");
+        ui.text_edit_singleline(&mut self.address);
+        let started = if ui.button("Host").clicked() {
+            Some(Session::host(&self.address, ctx.clone()))
+        } else if ui.button("Join").clicked() {
+            Some(Session::join(&self.address, ctx.clone()))
+        } else {
+            None
+        };
+        match started {
+            Some(Ok(session)) => {
+                self.session = Some(session);
+                self.session_error = None;
+                self.hyperspeed = None;
+            }
+            Some(Err(e)) => self.session_error = Some(e.to_string()),
+            None => {}
+        }
+        if let Some(error) = &self.session_error {
+            ui.colored_label(Color32::RED, error);
+        }
+    }
 }
 
 impl eframe::App for App {
     fn update(&mut self, ctx: &Context, frame: &mut Frame) {
+        if let Some(done) = self.stepper.as_mut().and_then(Stepper::poll) {
+            self.stepper = None;
+            if let Some(hl) = done {
+                self.hl = hl;
+                if let Some(session) = &mut self.session {
+                    session.stepped(&mut self.hl);
+                }
+                if let Some(hyperspeed) = &mut self.hyperspeed {
+                    hyperspeed.observe(&self.hl);
+                    self.log_2_steps = hyperspeed.log2_steps();
+                }
+            }
+        }
+        // The other side's edits and steps wait for this side's step.
+        if self.stepper.is_none()
+            && let Some(session) = &mut self.session
+            && let Some(log_2_steps) = session.poll(&mut self.hl)
+        {
+            let hl = self.hl.clone();
+            self.stepper = Some(Stepper::start(hl, log_2_steps, ctx.clone()));
+        }
+        // Edits, rewinding and step sizes picked locally would differ between
+        // the sides of a session.
+        let shared = self.session.is_some();
+        let can_edit = self.session.as_ref().is_none_or(Session::can_edit);
+        if let Some(hyperspeed) = &self.hyperspeed
+            && self.stepper.is_none()
+        {
+            let (hl, log_2_steps) = (self.hl.clone(), hyperspeed.log2_steps());
+            self.stepper = Some(Stepper::start(hl, log_2_steps, ctx.clone()));
+        }
+        let mut fit = false;
         egui::TopBottomPanel::top("top").show(ctx, |ui| {
             ui.horizontal(|ui| {
                 ui.add(egui::Slider::new(&mut self.log_2_steps, 0..=1000).logarithmic(true));
-                if ui.button("Step (log 2)").clicked() {
-                    self.hl.step(self.log_2_steps);
+                let step = egui::Button::new("Step (log 2)");
+                let can_step = self.stepper.is_none() && can_edit;
+                if ui.add_enabled(can_step, step).clicked() {
+                    let log_2_steps = match &mut self.session {
+                        Some(session) => session.step(self.log_2_steps),
+                        None => Some(self.log_2_steps),
+                    };
+                    if let Some(log_2_steps) = log_2_steps {
+                        let hl = self.hl.clone();
+                        self.stepper = Some(Stepper::start(hl, log_2_steps, ctx.clone()));
+                    }
                 }
+                let limit = self.hl.rewind_limit().unwrap_or(0);
+                let back = 1u64.checked_shl(self.log_2_steps as u32);
+                let back = back.filter(|&back| back <= limit && self.stepper.is_none() && !shared);
+                let back_button = egui::Button::new("Back");
+                if ui.add_enabled(back.is_some(), back_button).clicked() {
+                    self.hl.step_back(back.unwrap()).unwrap();
+                }
+                if let Some(stepper) = &self.stepper {
+                    ui.spinner();
+                    ui.label(format!("{:.1}s", stepper.elapsed().as_secs_f64()));
+                    if !shared && ui.button("Cancel").clicked() {
+                        stepper.cancel();
+                        self.hyperspeed = None;
+                    }
+                }
+                let mut fast = self.hyperspeed.is_some();
+                let fast_checkbox = egui::Checkbox::new(&mut fast, "As fast as possible");
+                if ui.add_enabled(!shared, fast_checkbox).changed() {
+                    self.hyperspeed = fast.then(Hyperspeed::default);
+                    if let Some(stepper) = &self.stepper {
+                        stepper.cancel();
+                    }
+                }
+                let idle = self.stepper.is_none() && !shared;
+                if ui.add_enabled(idle, egui::Button::new("Undo")).clicked() {
+                    self.hl.undo();
+                }
+                if ui.add_enabled(idle, egui::Button::new("Redo")).clicked() {
+                    self.hl.redo();
+                }
+                fit = ui.button("Fit").clicked();
             });
             ui.add(
                 egui::Slider::new(&mut self.view.zoom, 1e-10..=2.)
@@ -88,10 +230,32 @@ impl eframe::App for App {
                     .show_value(false)
                     .text("Zoom"),
             );
+            ui.add(egui::Slider::new(&mut self.view.rotation, -PI..=PI).text("Rotation"));
+            ui.horizontal(|ui| self.session_ui(ctx, ui));
         });
         egui::CentralPanel::default().show(ctx, |ui| {
+            let settings = self.settings(ui.max_rect());
+            if fit && let Some(bounding_box) = self.hl.bounding_box() {
+                self.view = render::View::fit(bounding_box, settings);
+            }
             let image = self.render_hashlife(ctx, ui);
-            ui.add(image);
+            let response = ui.add(image.sense(Sense::click()));
+            if let Some(pos) = response.interact_pointer_pos()
+                && response.clicked()
+                && self.stepper.is_none()
+                && can_edit
+            {
+                let pos = pos - response.rect.min;
+                let (y, x) = self
+                    .view
+                    .screen_to_cell((pos.y as f64, pos.x as f64), settings);
+                let p = (y.floor() as isize, x.floor() as isize);
+                self.hl.toggle(p);
+                self.hl.checkpoint();
+                if let Some(session) = &mut self.session {
+                    session.toggled(p);
+                }
+            }
         });
     }
 }
@@ -1,7 +1,10 @@
 use crate::app::App;
 
 mod app;
+mod session;
+mod stepper;
 
+#[cfg(not(target_arch = "wasm32"))]
 fn main() -> eframe::Result {
     env_logger::init(); // Log to stderr (if you run with `RUST_LOG=debug`).
 
@@ -17,3 +20,29 @@ fn main() -> eframe::Result {
         Box::new(|cc| Ok(Box::new(App::new(cc)))),
     )
 }
+
+#[cfg(target_arch = "wasm32")]
+fn main() {
+    use eframe::wasm_bindgen::JsCast as _;
+
+    eframe::WebLogger::init(log::LevelFilter::Debug).ok(); // Log to the console.
+
+    wasm_bindgen_futures::spawn_local(async {
+        let canvas = web_sys::window()
+            .and_then(|window| window.document())
+            .and_then(|document| document.get_element_by_id("the_canvas_id"))
+            .expect("index.html has a canvas with id the_canvas_id")
+            .dyn_into::<web_sys::HtmlCanvasElement>()
+            .expect("the_canvas_id is a canvas");
+        let started = eframe::WebRunner::new()
+            .start(
+                canvas,
+                eframe::WebOptions::default(),
+                Box::new(|cc| Ok(Box::new(App::new(cc)))),
+            )
+            .await;
+        if let Err(e) = started {
+            log::error!("Failed to start: {e:?}");
+        }
+    });
+}
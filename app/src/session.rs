@@ -0,0 +1,426 @@
+//! Two instances of the app editing and stepping one pattern together over
+//! TCP, one hosting and the other joining.
+//!
+//! Edits are cell toggles, which give the same pattern whatever order they're
+//! made in, so each side applies its own at once and the other's as they
+//! arrive. Steps can't be reordered like that, so the host decides when they
+//! happen: it steps after the guest's toggles it has seen so far, and tells
+//! the guest how many those were. The guest takes back any it made since,
+//! steps, then makes them again, as the host does when they reach it.
+//!
+//! Lines sent by the host:
+//!
+//! - `pattern LEN`, then LEN bytes of macrocell, for the guest to start from.
+//! - `toggle Y X`.
+//! - `step LOG2 TOGGLES`, stepping 2^LOG2 generations after TOGGLES of the
+//!   guest's toggles.
+//!
+//! and by the guest:
+//!
+//! - `toggle Y X`.
+//! - `step LOG2`, asking the host to step.
+
+use std::{
+    collections::VecDeque,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    str::FromStr,
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::Duration,
+};
+
+use egui::Context;
+use hashlife::{HashLife, formats};
+
+/// How long joining waits for the host to answer.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The most bytes of macrocell a guest will take from the host.
+const MAX_PATTERN: usize = 64 << 20;
+
+/// How far from the origin the other side may toggle, well inside the largest
+/// universe so stepping can still grow around it.
+const MAX_COORDINATE: isize = 1 << 60;
+
+/// The largest step the other side may ask for, 2^62 generations, past which
+/// the generation count would overflow.
+const MAX_LOG2_STEPS: usize = 62;
+
+enum Event {
+    /// A guest has joined the host.
+    Joined(TcpStream),
+    Pattern(Box<HashLife>),
+    Toggle((isize, isize)),
+    /// A step: 2^log2 generations, after this many of the guest's toggles.
+    Step(usize, u64),
+    /// The guest asking for a step.
+    StepRequest(usize),
+    Closed(String),
+}
+
+enum Role {
+    Host {
+        /// How many of the guest's toggles have been made.
+        toggles: u64,
+    },
+    Guest {
+        /// Whether the host's pattern has arrived, before which edits would
+        /// be lost.
+        started: bool,
+        /// How many toggles have been sent.
+        sent: u64,
+        /// The toggles sent that the host hasn't stepped after yet, numbered
+        /// from 1.
+        unstepped: VecDeque<(u64, (isize, isize))>,
+        /// The toggles taken back for a step, to make again after it.
+        retoggle: Vec<(isize, isize)>,
+    },
+}
+
+pub struct Session {
+    role: Role,
+    /// Where to send, once connected.
+    stream: Option<TcpStream>,
+    events: Receiver<Event>,
+    /// What's happening, to show.
+    status: String,
+    /// Why the session ended, if it has.
+    closed: Option<String>,
+}
+
+impl Session {
+    /// Waits on `address` for a guest to join, in the background.
+    pub fn host(address: &str, ctx: Context) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        let status = format!("hosting on {}, waiting", listener.local_addr()?);
+        let (tx, events) = mpsc::channel();
+        thread::spawn(move || {
+            // Only the one guest.
+            let joined = listener.accept();
+            drop(listener);
+            let joined = joined.and_then(|(stream, _)| {
+                tx.send(Event::Joined(stream.try_clone()?)).ok();
+                Ok(stream)
+            });
+            match joined {
+                Ok(stream) => read(stream, true, tx, ctx),
+                Err(e) => close(&tx, &ctx, e.to_string()),
+            }
+        });
+        Ok(Self {
+            role: Role::Host { toggles: 0 },
+            stream: None,
+            events,
+            status,
+            closed: None,
+        })
+    }
+
+    /// Joins the host at `address`, whose pattern replaces this one.
+    pub fn join(address: &str, ctx: Context) -> io::Result<Self> {
+        let address = std::net::ToSocketAddrs::to_socket_addrs(address)?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no such address"))?;
+        let stream = TcpStream::connect_timeout(&address, TIMEOUT)?;
+        let reader = stream.try_clone()?;
+        let (tx, events) = mpsc::channel();
+        thread::spawn(move || read(reader, false, tx, ctx));
+        Ok(Self {
+            role: Role::Guest {
+                started: false,
+                sent: 0,
+                unstepped: VecDeque::new(),
+                retoggle: vec![],
+            },
+            stream: Some(stream),
+            events,
+            status: format!("joining {address}"),
+            closed: None,
+        })
+    }
+
+    pub fn status(&self) -> &str {
+        self.closed.as_deref().unwrap_or(&self.status)
+    }
+
+    /// Whether this side may edit yet.
+    pub fn can_edit(&self) -> bool {
+        !matches!(self.role, Role::Guest { started: false, .. })
+    }
+
+    /// Sends a toggle just made to `p`.
+    pub fn toggled(&mut self, p: (isize, isize)) {
+        if let Role::Guest {
+            sent, unstepped, ..
+        } = &mut self.role
+        {
+            *sent += 1;
+            unstepped.push_back((*sent, p));
+        }
+        self.send(format!("toggle {} {}\n", p.0, p.1).as_bytes());
+    }
+
+    /// Asks for a step of 2^`log2_steps` generations. The host steps at once,
+    /// so gets the step to start back, and the guest waits for the host.
+    pub fn step(&mut self, log2_steps: usize) -> Option<usize> {
+        match self.role {
+            Role::Host { toggles } => {
+                self.send(format!("step {log2_steps} {toggles}\n").as_bytes());
+                Some(log2_steps)
+            }
+            Role::Guest { .. } => {
+                self.send(format!("step {log2_steps}\n").as_bytes());
+                None
+            }
+        }
+    }
+
+    /// Acts on what's arrived, while no step is running, until a step is
+    /// needed, which it returns for the app to start.
+    pub fn poll(&mut self, hl: &mut HashLife) -> Option<usize> {
+        while let Ok(event) = self.events.try_recv() {
+            match event {
+                Event::Joined(stream) => {
+                    self.stream = Some(stream);
+                    let mc = formats::write_macrocell(hl, &Default::default());
+                    self.send(format!("pattern {}\n{mc}", mc.len()).as_bytes());
+                    self.status = "hosting, joined".to_string();
+                }
+                Event::Pattern(pattern) => {
+                    *hl = *pattern;
+                    if let Role::Guest { started, .. } = &mut self.role {
+                        *started = true;
+                    }
+                    self.status = "joined".to_string();
+                }
+                Event::Toggle(p) => {
+                    hl.toggle(p);
+                    if let Role::Host { toggles } = &mut self.role {
+                        *toggles += 1;
+                    }
+                }
+                Event::Step(log2_steps, stepped_toggles) => {
+                    if let Role::Guest {
+                        unstepped,
+                        retoggle,
+                        ..
+                    } = &mut self.role
+                    {
+                        unstepped.retain(|&(n, _)| n > stepped_toggles);
+                        // Taken back, to make after the step as the host will.
+                        retoggle.extend(unstepped.iter().map(|&(_, p)| p));
+                        for &p in retoggle.iter() {
+                            hl.toggle(p);
+                        }
+                    }
+                    return Some(log2_steps);
+                }
+                Event::StepRequest(log2_steps) => return self.step(log2_steps),
+                Event::Closed(why) => self.closed = Some(why),
+            }
+        }
+        None
+    }
+
+    /// Makes again the toggles taken back for the step just finished.
+    pub fn stepped(&mut self, hl: &mut HashLife) {
+        if let Role::Guest { retoggle, .. } = &mut self.role {
+            for p in retoggle.drain(..) {
+                hl.toggle(p);
+            }
+        }
+    }
+
+    fn send(&mut self, bytes: &[u8]) {
+        let Some(stream) = &mut self.stream else {
+            return;
+        };
+        if let Err(e) = stream.write_all(bytes) {
+            self.closed = Some(format!("disconnected: {e}"));
+            self.stream = None;
+        }
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        if let Some(stream) = &self.stream {
+            // Ends the other side's session too.
+            stream.shutdown(std::net::Shutdown::Both).ok();
+        }
+    }
+}
+
+/// Reads lines from the other side until it goes away.
+fn read(stream: TcpStream, host: bool, tx: Sender<Event>, ctx: Context) {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        let event = match reader.read_line(&mut line) {
+            Ok(0) => Err("the other side left".to_string()),
+            Ok(_) => parse(&line, host, &mut reader),
+            Err(e) => Err(e.to_string()),
+        };
+        match event {
+            Ok(event) => {
+                if tx.send(event).is_err() {
+                    return;
+                }
+                ctx.request_repaint();
+            }
+            Err(e) => return close(&tx, &ctx, e),
+        }
+    }
+}
+
+fn close(tx: &Sender<Event>, ctx: &Context, why: String) {
+    tx.send(Event::Closed(format!("disconnected: {why}"))).ok();
+    ctx.request_repaint();
+}
+
+fn parse(line: &str, host: bool, reader: &mut impl Read) -> Result<Event, String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let event = match (host, words.as_slice()) {
+        (_, ["toggle", y, x]) => Event::Toggle((coordinate(y)?, coordinate(x)?)),
+        (true, ["step", log2_steps]) => Event::StepRequest(log2(log2_steps)?),
+        (false, ["step", log2_steps, toggles]) => Event::Step(log2(log2_steps)?, number(toggles)?),
+        (false, ["pattern", len]) => {
+            let len = number(len)?;
+            if len > MAX_PATTERN {
+                return Err(format!("pattern of {len} bytes is too big"));
+            }
+            let mut mc = vec![0; len];
+            reader.read_exact(&mut mc).map_err(|e| e.to_string())?;
+            let mut pattern = formats::load(&mc).map_err(|e| e.to_string())?.life;
+            crate::app::configure(&mut pattern);
+            Event::Pattern(Box::new(pattern))
+        }
+        _ => return Err(format!("unexpected {:?}", line.trim())),
+    };
+    Ok(event)
+}
+
+fn number<T: FromStr>(word: &str) -> Result<T, String> {
+    word.parse().map_err(|_| format!("bad number {word:?}"))
+}
+
+fn coordinate(word: &str) -> Result<isize, String> {
+    let n: isize = number(word)?;
+    if n.unsigned_abs() > MAX_COORDINATE.unsigned_abs() {
+        return Err(format!("coordinate {n} is too far out"));
+    }
+    Ok(n)
+}
+
+fn log2(word: &str) -> Result<usize, String> {
+    let n = number(word)?;
+    if n > MAX_LOG2_STEPS {
+        return Err(format!(
+            "can't step more than 2^{MAX_LOG2_STEPS} generations"
+        ));
+    }
+    Ok(n)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+
+    /// Acts on what's arrived, running any steps as the app would.
+    fn poll(session: &mut Session, hl: &mut HashLife) {
+        while let Some(log2_steps) = session.poll(hl) {
+            hl.step(log2_steps);
+            session.stepped(hl);
+        }
+    }
+
+    /// Waits a few seconds at most for `done`.
+    fn wait(mut done: impl FnMut() -> bool) {
+        let start = Instant::now();
+        while !done() {
+            assert!(start.elapsed() < TIMEOUT, "timed out");
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    fn cells(hl: &HashLife) -> (u64, Vec<(isize, isize)>) {
+        let mut cells: Vec<_> = hl.iter_cells().collect();
+        cells.sort();
+        (hl.generation(), cells)
+    }
+
+    #[test]
+    fn test_parse() {
+        let check = |line: &str, host| parse(line, host, &mut io::empty()).map(|_| ());
+        assert!(check("toggle -3 4\n", true).is_ok());
+        assert!(check("toggle 1152921504606846976 0\n", false).is_ok());
+        assert!(check("toggle 0 1152921504606846977\n", false).is_err());
+        assert!(check("toggle -9223372036854775808 0\n", true).is_err());
+        assert!(check("step 62\n", true).is_ok());
+        assert!(check("step 63\n", true).is_err());
+        assert!(check("step 64 0\n", false).is_err());
+        assert!(check("step 2\n", false).is_err());
+        assert_eq!(
+            check(&format!("pattern {}\n", MAX_PATTERN + 1), false).err(),
+            Some(format!("pattern of {} bytes is too big", MAX_PATTERN + 1))
+        );
+        // Short of the length given.
+        assert!(check("pattern 10\n", false).is_err());
+    }
+
+    #[test]
+    fn test_session() {
+        let ctx = Context::default();
+        let mut host = Session::host("127.0.0.1:0", ctx.clone()).unwrap();
+        let address = host.status()["hosting on ".len()..]
+            .trim_end_matches(", waiting")
+            .to_string();
+        let glider = HashLife::from_iter([(0, 1), (1, 2), (2, 0), (2, 1), (2, 2)]);
+        let mut host_hl = glider.clone();
+        let mut guest = Session::join(&address, ctx).unwrap();
+        let mut guest_hl = HashLife::new();
+        assert!(!guest.can_edit());
+        wait(|| {
+            poll(&mut host, &mut host_hl);
+            poll(&mut guest, &mut guest_hl);
+            guest.can_edit()
+        });
+        assert_eq!(cells(&guest_hl), cells(&glider));
+
+        // Both sides toggle at once, and the guest asks for a step between its
+        // toggles before seeing any of the host's.
+        host_hl.toggle((5, 5));
+        host.toggled((5, 5));
+        guest_hl.toggle((5, 6));
+        guest.toggled((5, 6));
+        assert_eq!(guest.step(2), None);
+        host_hl.toggle((5, 7));
+        host.toggled((5, 7));
+        guest_hl.toggle((-5, -5));
+        guest.toggled((-5, -5));
+
+        let mut expected = glider.clone();
+        for p in [(5, 5), (5, 6), (5, 7)] {
+            expected.toggle(p);
+        }
+        expected.step(2);
+        expected.toggle((-5, -5));
+        let expected = cells(&expected);
+        wait(|| {
+            poll(&mut host, &mut host_hl);
+            poll(&mut guest, &mut guest_hl);
+            cells(&host_hl) == expected && cells(&guest_hl) == expected
+        });
+        assert!(host.closed.is_none() && guest.closed.is_none());
+
+        drop(host);
+        wait(|| {
+            poll(&mut guest, &mut guest_hl);
+            guest.closed.is_some()
+        });
+    }
+}
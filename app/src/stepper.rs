@@ -0,0 +1,103 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+};
+
+use egui::Context;
+use hashlife::HashLife;
+use web_time::{Duration, Instant};
+
+/// How long a slice of a step may take on the web, leaving the rest of the
+/// frame to draw.
+#[cfg(target_arch = "wasm32")]
+const SLICE: Duration = Duration::from_millis(12);
+
+/// A step running on another thread, so the UI keeps drawing meanwhile. On
+/// the web, where there are no threads, it's tried again each frame until it
+/// fits in a slice of one, which it does since the work towards it is kept.
+pub struct Stepper {
+    cancel: Arc<AtomicBool>,
+    /// The stepped pattern, or nothing if the step was cancelled.
+    #[cfg(not(target_arch = "wasm32"))]
+    done: Receiver<Option<HashLife>>,
+    /// The pattern still to step, and what's needed to carry on.
+    #[cfg(target_arch = "wasm32")]
+    pending: Option<(HashLife, usize, Context)>,
+    started: Instant,
+}
+
+impl Stepper {
+    /// Steps a copy of the pattern by 2^`log_2_steps` generations.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn start(mut hl: HashLife, log_2_steps: usize, ctx: Context) -> Self {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let (tx, done) = mpsc::channel();
+        let worker_cancel = cancel.clone();
+        thread::spawn(move || {
+            let stepped = hl.try_step(log_2_steps, &worker_cancel);
+            // The app may have stopped waiting.
+            let _ = tx.send(stepped.then_some(hl));
+            ctx.request_repaint();
+        });
+        Self {
+            cancel,
+            done,
+            started: Instant::now(),
+        }
+    }
+
+    /// Steps a copy of the pattern by 2^`log_2_steps` generations.
+    #[cfg(target_arch = "wasm32")]
+    pub fn start(hl: HashLife, log_2_steps: usize, ctx: Context) -> Self {
+        ctx.request_repaint();
+        Self {
+            cancel: Arc::new(AtomicBool::new(false)),
+            pending: Some((hl, log_2_steps, ctx)),
+            started: Instant::now(),
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Whether the step is over, and the stepped pattern if it wasn't
+    /// cancelled.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn poll(&mut self) -> Option<Option<HashLife>> {
+        match self.done.try_recv() {
+            Ok(hl) => Some(hl),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(None),
+        }
+    }
+
+    /// Whether the step is over, and the stepped pattern if it wasn't
+    /// cancelled. Takes the next slice of the step if it isn't.
+    #[cfg(target_arch = "wasm32")]
+    pub fn poll(&mut self) -> Option<Option<HashLife>> {
+        let cancel = &self.cancel;
+        let Some((hl, log_2_steps, ctx)) = &mut self.pending else {
+            return Some(None);
+        };
+        if cancel.load(Ordering::Relaxed) {
+            return Some(None);
+        }
+        let deadline = Instant::now() + SLICE;
+        let stop = || Instant::now() >= deadline || cancel.load(Ordering::Relaxed);
+        if !hl.step_unless(*log_2_steps, &stop) {
+            ctx.request_repaint();
+            return None;
+        }
+        self.pending.take().map(|(hl, ..)| Some(hl))
+    }
+}
@@ -0,0 +1,400 @@
+//! `gol api`: a REST API for stepping patterns and getting them back as
+//! pictures or files, a request to a connection.
+//!
+//! - `POST /patterns`, with a pattern in any format as the body, keeps it and
+//!   describes it, with the `id` to use for it from then on.
+//! - `GET /patterns/ID` describes it: its `generation`, `population`, `rule`
+//!   and `bounds`, as [[top, bottom], [left, right]] if it has any cells.
+//! - `POST /patterns/ID/step?steps=N` steps it N generations, 1 by default,
+//!   and describes it. Steps taking more than 10 seconds are given up on.
+//! - `GET /patterns/ID/png?size=WxH&viewport=Y0..Y1,X0..X1` draws it, as
+//!   `gol render` would, in up to 4096x4096 pixels' worth.
+//! - `GET /patterns/ID.rle`, `.mc` or `.cells` downloads it.
+//! - `DELETE /patterns/ID` forgets it.
+//!
+//! Anything that fails gets JSON with an `error` back.
+
+use std::{
+    collections::BTreeMap,
+    io::{self, BufReader, ErrorKind},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use hashlife::{
+    HashLife,
+    formats::{self, Format, Pattern},
+};
+
+use crate::{
+    bench::json_string,
+    http::{self, Request},
+};
+
+/// The largest pattern that may be uploaded, in bytes.
+const MAX_BODY: usize = 64 << 20;
+
+/// The most pixels a picture may have, at four bytes each.
+const MAX_PIXELS: usize = 4096 * 4096;
+
+/// How long a step may hold its pattern, and every request for it, up for.
+const MAX_STEP_TIME: Duration = Duration::from_secs(10);
+
+const JSON: &str = "application/json";
+
+/// The patterns uploaded, each locked on its own so that stepping one doesn't
+/// hold up requests for the others.
+#[derive(Default)]
+struct Store {
+    patterns: BTreeMap<u64, Arc<Mutex<Pattern>>>,
+    next_id: u64,
+}
+
+type Shared = Arc<Mutex<Store>>;
+
+/// A status, content type and body.
+type Response = (u16, &'static str, Vec<u8>);
+
+/// A status, and why.
+type Failure = (u16, String);
+
+/// Serves the API on `host`:`port` until killed.
+pub fn api(host: &str, port: u16) -> Result<(), String> {
+    let listener = TcpListener::bind((host, port)).map_err(|e| format!("{host}:{port}: {e}"))?;
+    let address = listener.local_addr().map_err(|e| e.to_string())?;
+    eprintln!("serving on http://{address}/patterns");
+    let store = Shared::default();
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let store = store.clone();
+        // Clients going away mid-request isn't worth reporting.
+        thread::spawn(move || connection(stream, &store).ok());
+    }
+    Ok(())
+}
+
+fn connection(stream: TcpStream, store: &Shared) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let Some(request) = Request::read(&mut reader)? else {
+        return Ok(());
+    };
+    let (status, content_type, body) =
+        handle(&request, &mut reader, store).unwrap_or_else(|(status, error)| {
+            let body = format!("{{\"error\":{}}}\n", json_string(&error));
+            (status, JSON, body.into_bytes())
+        });
+    let mut w = stream;
+    http::respond(&mut w, status, content_type, &body)
+}
+
+fn handle(
+    request: &Request,
+    reader: &mut BufReader<TcpStream>,
+    store: &Shared,
+) -> Result<Response, Failure> {
+    let (path, query) = request.query();
+    let param = |name: &str| {
+        let mut values = query.iter().filter(|(n, _)| n == name);
+        let value = values.next().map(|(_, value)| value.as_str());
+        match values.next() {
+            Some(_) => Err((400, format!("{name} given more than once"))),
+            None => Ok(value),
+        }
+    };
+    let bad = |e: String| (400, e);
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    match (request.method.as_str(), segments.as_slice()) {
+        ("POST", ["patterns"]) => {
+            let body = request.read_body(reader, MAX_BODY).map_err(|e| {
+                let status = if e.kind() == ErrorKind::FileTooLarge {
+                    413
+                } else {
+                    400
+                };
+                (status, e.to_string())
+            })?;
+            let pattern = formats::load(&body).map_err(|e| bad(e.to_string()))?;
+            let id = {
+                let mut store = lock_store(store);
+                store.next_id += 1;
+                store.next_id - 1
+            };
+            // Big patterns take a while to describe, so not while holding
+            // up every other request.
+            let description = describe(id, &pattern.life);
+            let pattern = Arc::new(Mutex::new(pattern));
+            lock_store(store).patterns.insert(id, pattern);
+            Ok((201, JSON, description))
+        }
+        ("GET", ["patterns", file]) => match file.split_once('.') {
+            Some((id, extension)) => {
+                let format = Format::from_extension(extension)
+                    .ok_or((404, format!("unknown format {extension:?}")))?;
+                let pattern = find(store, id)?;
+                let pattern = lock(&pattern)?;
+                let mut bytes = vec![];
+                formats::write(&mut bytes, &pattern.life, &pattern.meta, format)
+                    .map_err(|e| bad(e.to_string()))?;
+                Ok((200, "text/plain", bytes))
+            }
+            None => {
+                let pattern = find(store, file)?;
+                let life = &lock(&pattern)?.life;
+                Ok((200, JSON, describe(parse_id(file)?, life)))
+            }
+        },
+        ("POST", ["patterns", id, "step"]) => {
+            let steps = param("steps")?.map(crate::parse_steps).transpose();
+            let steps = steps.map_err(bad)?.unwrap_or(1);
+            let pattern = find(store, id)?;
+            let life = &mut lock(&pattern)?.life;
+            step_for(life, steps, MAX_STEP_TIME)?;
+            Ok((200, JSON, describe(parse_id(id)?, life)))
+        }
+        ("GET", ["patterns", id, "png"]) => {
+            let size = param("size")?.map(crate::parse_size).transpose();
+            let viewport = param("viewport")?.map(crate::parse_viewport).transpose();
+            let (size, viewport) = (size.map_err(bad)?, viewport.map_err(bad)?);
+            if let Some((height, width)) = size
+                && height.saturating_mul(width) > MAX_PIXELS
+            {
+                return Err((413, format!("pictures may have up to {MAX_PIXELS} pixels")));
+            }
+            let pattern = find(store, id)?;
+            let life = &lock(&pattern)?.life;
+            let mut png = vec![];
+            crate::write_png(life, size, viewport, &mut png).map_err(|e| (500, e.to_string()))?;
+            Ok((200, "image/png", png))
+        }
+        ("DELETE", ["patterns", id]) => {
+            let id = parse_id(id)?;
+            let pattern = lock_store(store).patterns.remove(&id);
+            let pattern = pattern.ok_or((404, format!("no pattern {id}")))?;
+            let life = &lock(&pattern)?.life;
+            Ok((200, JSON, describe(id, life)))
+        }
+        (_, ["patterns"] | ["patterns", _] | ["patterns", _, "step" | "png"]) => {
+            Err((405, format!("can't {} {path}", request.method)))
+        }
+        _ => Err((404, format!("no such path {path}"))),
+    }
+}
+
+/// Steps as `crate::step_by` does, unless it takes longer than `budget`, in
+/// which case the pattern is put back as it was.
+fn step_for(life: &mut HashLife, steps: u64, budget: Duration) -> Result<(), Failure> {
+    let deadline = Instant::now() + budget;
+    let stop = || Instant::now() >= deadline;
+    let before = life.clone();
+    for log2 in (0..u64::BITS as usize).rev() {
+        if steps >> log2 & 1 == 0 {
+            continue;
+        }
+        let stepped = life.step_checked_unless(log2, &stop);
+        if !stepped.map_err(|e| (400, e.to_string()))? {
+            *life = before;
+            let seconds = budget.as_secs_f64();
+            return Err((503, format!("stepping took more than {seconds} seconds")));
+        }
+    }
+    Ok(())
+}
+
+fn parse_id(id: &str) -> Result<u64, Failure> {
+    id.parse().map_err(|_| (404, format!("no pattern {id:?}")))
+}
+
+fn find(store: &Shared, id: &str) -> Result<Arc<Mutex<Pattern>>, Failure> {
+    let id = parse_id(id)?;
+    let pattern = lock_store(store).patterns.get(&id).cloned();
+    pattern.ok_or((404, format!("no pattern {id}")))
+}
+
+/// The store, even if a request panicked while holding it, as it's only held
+/// to look patterns up and add or remove them whole.
+fn lock_store(store: &Shared) -> MutexGuard<'_, Store> {
+    store.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// A pattern, unless a request panicked part way through changing it.
+fn lock(pattern: &Mutex<Pattern>) -> Result<MutexGuard<'_, Pattern>, Failure> {
+    let broken = |_| (500, "pattern broken by an earlier request".to_string());
+    pattern.lock().map_err(broken)
+}
+
+fn describe(id: u64, life: &HashLife) -> Vec<u8> {
+    let bounds = match life.bounding_box() {
+        Some((ys, xs)) => format!("[[{},{}],[{},{}]]", ys.start, ys.end, xs.start, xs.end),
+        None => "null".to_string(),
+    };
+    format!(
+        "{{\"id\":{id},\"generation\":{},\"population\":{},\"rule\":{},\"bounds\":{bounds}}}\n",
+        life.generation(),
+        life.population(),
+        json_string(&life.rule().to_string()),
+    )
+    .into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Read, io::Write, net::SocketAddr};
+
+    use super::*;
+
+    /// Serves the API on a free port for the rest of the test.
+    fn start(store: Shared) -> SocketAddr {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let store = store.clone();
+                thread::spawn(move || connection(stream.unwrap(), &store).ok());
+            }
+        });
+        address
+    }
+
+    /// The status and body of the response to a request.
+    fn request(address: SocketAddr, method: &str, path: &str, body: &[u8]) -> (u16, Vec<u8>) {
+        let mut stream = TcpStream::connect(address).unwrap();
+        let head = format!(
+            "{method} {path} HTTP/1.1\r\nHost: test\r\nContent-Length: {}\r\n\r\n",
+            body.len()
+        );
+        stream.write_all(head.as_bytes()).unwrap();
+        stream.write_all(body).unwrap();
+        let mut response = vec![];
+        stream.read_to_end(&mut response).unwrap();
+        let end = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+        let head = String::from_utf8(response[..end].to_vec()).unwrap();
+        let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+        (status, response[end + 4..].to_vec())
+    }
+
+    fn text(address: SocketAddr, method: &str, path: &str, body: &str) -> (u16, String) {
+        let (status, body) = request(address, method, path, body.as_bytes());
+        (status, String::from_utf8(body).unwrap())
+    }
+
+    #[test]
+    fn test_api() {
+        let address = start(Shared::default());
+        let glider = "x = 3, y = 3, rule = B3/S23\nbo$2bo$3o!\n";
+        let described = |id: u64, generation: u64, bounds: &str| {
+            format!(
+                "{{\"id\":{id},\"generation\":{generation},\"population\":5,\"rule\":\"B3/S23\",\"bounds\":{bounds}}}\n"
+            )
+        };
+        let created = text(address, "POST", "/patterns", glider);
+        assert_eq!(created, (201, described(0, 0, "[[-1,2],[-1,2]]")));
+        let other = text(address, "POST", "/patterns", "x = 1, y = 1\no!");
+        assert_eq!(other.0, 201);
+        assert!(other.1.starts_with("{\"id\":1,"));
+        let stepped = text(address, "POST", "/patterns/0/step?steps=2%5E2", "");
+        assert_eq!(stepped, (200, described(0, 4, "[[0,3],[0,3]]")));
+        let got = text(address, "GET", "/patterns/0", "");
+        assert_eq!(got, (200, described(0, 4, "[[0,3],[0,3]]")));
+        let mut expected = formats::load(glider.as_bytes()).unwrap();
+        expected.life.step(2);
+        let mut rle = vec![];
+        formats::write(&mut rle, &expected.life, &expected.meta, Format::Rle).unwrap();
+        let downloaded = request(address, "GET", "/patterns/0.rle", b"");
+        assert_eq!(downloaded, (200, rle));
+        let (status, png) = request(address, "GET", "/patterns/0/png?size=8x4", b"");
+        assert_eq!(status, 200);
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+        let deleted = text(address, "DELETE", "/patterns/0", "");
+        assert_eq!(deleted, (200, described(0, 4, "[[0,3],[0,3]]")));
+        // Failures say why, as JSON.
+        let error = |status: u16, message: &str| {
+            (status, format!("{{\"error\":{}}}\n", json_string(message)))
+        };
+        assert_eq!(
+            text(address, "GET", "/patterns/0", ""),
+            error(404, "no pattern 0")
+        );
+        assert_eq!(
+            text(address, "GET", "/patterns/1.gif", ""),
+            error(404, "unknown format \"gif\"")
+        );
+        assert_eq!(
+            text(address, "POST", "/patterns/1/step?steps=1&steps=2", ""),
+            error(400, "steps given more than once")
+        );
+        assert_eq!(
+            text(address, "GET", "/patterns/1/png?size=0x1", ""),
+            error(400, "bad size \"0x1\", expected WxH")
+        );
+        assert_eq!(
+            text(address, "GET", "/patterns/1/png?size=100000x100000", ""),
+            error(413, "pictures may have up to 16777216 pixels")
+        );
+        assert_eq!(
+            text(address, "PUT", "/patterns/1", ""),
+            error(405, "can't PUT /patterns/1")
+        );
+        assert_eq!(text(address, "GET", "/", ""), error(404, "no such path /"));
+        assert_eq!(text(address, "POST", "/patterns", "x = bogus").0, 400);
+    }
+
+    #[test]
+    fn test_step_for() {
+        let mut seed = 1u64;
+        let soup = (0..10_000).filter_map(|k| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+            (seed >> 63 == 1).then_some((k / 100, k % 100))
+        });
+        let mut life = HashLife::from_iter(soup);
+        let before = life.clone();
+        let slow = (503, "stepping took more than 0 seconds".to_string());
+        assert_eq!(step_for(&mut life, 1 << 20, Duration::ZERO), Err(slow));
+        assert!(life == before);
+        assert_eq!(step_for(&mut life, 3, MAX_STEP_TIME), Ok(()));
+        assert_eq!(life.generation(), 3);
+        let too_large = step_for(&mut life, u64::MAX, MAX_STEP_TIME);
+        assert_eq!(too_large.map_err(|(status, _)| status), Err(400));
+        assert_eq!(life.generation(), 3);
+    }
+
+    #[test]
+    fn test_poisoned() {
+        let store = Shared::default();
+        let poison = |held: &(dyn Fn() + Sync)| {
+            thread::scope(|scope| scope.spawn(held).join().unwrap_err());
+        };
+        let address = start(store.clone());
+        assert_eq!(
+            text(address, "POST", "/patterns", "x = 1, y = 1\no!").0,
+            201
+        );
+        assert_eq!(
+            text(address, "POST", "/patterns", "x = 1, y = 1\no!").0,
+            201
+        );
+        let pattern = find(&store, "0").unwrap();
+        poison(&|| {
+            let _held = pattern.lock();
+            panic!("stepping went wrong");
+        });
+        poison(&|| {
+            let _held = store.lock();
+            panic!("storing went wrong");
+        });
+        let broken = "{\"error\":\"pattern broken by an earlier request\"}\n";
+        let got = text(address, "GET", "/patterns/0", "");
+        assert_eq!(got, (500, broken.to_string()));
+        // The rest carry on, and deleting the broken one still forgets it.
+        assert_eq!(text(address, "GET", "/patterns/1", "").0, 200);
+        assert_eq!(
+            text(address, "POST", "/patterns", "x = 1, y = 1\no!").0,
+            201
+        );
+        assert_eq!(text(address, "DELETE", "/patterns/0", "").0, 500);
+        assert_eq!(text(address, "GET", "/patterns/0", "").0, 404);
+    }
+}
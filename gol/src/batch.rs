@@ -0,0 +1,215 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use hashlife::{HashLife, RuleError, StepError, UniverseConfig, formats};
+
+use crate::toml::{self, Table, Value};
+
+/// A pattern to step and what to write out along the way.
+#[derive(Clone, Debug)]
+struct Job {
+    name: String,
+    input: PathBuf,
+    /// Replaces the pattern's own rule.
+    rule: Option<String>,
+    steps: u64,
+    /// How many generations apart the outputs are written, as well as at the
+    /// start and the end.
+    every: Option<u64>,
+    /// Where to write the outputs, with `{name}` and `{gen}` filled in.
+    snapshot: Option<String>,
+    png: Option<String>,
+    png_size: Option<(usize, usize)>,
+    census: Option<String>,
+    max_nodes: Option<usize>,
+    max_seconds: Option<f64>,
+}
+
+/// Runs every `[[job]]` in the file at `path`, one after another. Keys
+/// before the first job apply to all of them, and paths are relative to the
+/// file. A job that fails is reported and the rest still run.
+pub fn batch(path: &str) -> Result<(), String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+    let document = toml::parse(&text).map_err(|e| format!("{path}: {e}"))?;
+    let dir = Path::new(path).parent().unwrap_or(Path::new("."));
+    let tables = document.arrays.get("job").map_or(&[][..], |jobs| jobs);
+    if let Some(name) = document.arrays.keys().find(|&name| name != "job") {
+        return Err(format!("{path}: unknown table [[{name}]]"));
+    }
+    let mut jobs = vec![];
+    for (i, table) in tables.iter().enumerate() {
+        let mut table = table.clone();
+        for (key, value) in &document.top {
+            table.entry(key.clone()).or_insert_with(|| value.clone());
+        }
+        let job =
+            Job::from_table(table, dir, i).map_err(|e| format!("{path}: job {}: {e}", i + 1))?;
+        jobs.push(job);
+    }
+    let mut failed = 0;
+    for job in &jobs {
+        let started = Instant::now();
+        match job.run(dir) {
+            Ok(life) => println!(
+                "{}: generation {}, population {}, {:.3}s",
+                job.name,
+                life.generation(),
+                life.population(),
+                started.elapsed().as_secs_f64()
+            ),
+            Err(e) => {
+                eprintln!("{}: {e}", job.name);
+                failed += 1;
+            }
+        }
+    }
+    match failed {
+        0 => Ok(()),
+        _ => Err(format!("{failed} of {} jobs failed", jobs.len())),
+    }
+}
+
+impl Job {
+    fn from_table(mut table: Table, dir: &Path, i: usize) -> Result<Self, String> {
+        let mut take = |key: &str| table.remove(key);
+        let string = |key: &str, value: Option<Value>| match value {
+            None => Ok(None),
+            Some(Value::String(s)) => Ok(Some(s)),
+            Some(_) => Err(format!("{key} should be a string")),
+        };
+        // Step counts can be numbers or strings like "2^20".
+        let steps = |key: &str, value: Option<Value>| match value {
+            None => Ok(None),
+            Some(Value::Integer(n)) if n >= 0 => Ok(Some(n as u64)),
+            Some(Value::String(s)) => crate::parse_steps(&s).map(Some),
+            Some(_) => Err(format!("{key} should be a number of generations")),
+        };
+        let name = string("name", take("name"))?.unwrap_or(format!("job {}", i + 1));
+        let input = string("input", take("input"))?.ok_or("missing input")?;
+        let job = Job {
+            name,
+            input: dir.join(input),
+            rule: string("rule", take("rule"))?,
+            steps: steps("steps", take("steps"))?.unwrap_or(0),
+            every: steps("every", take("every"))?.filter(|&every| every > 0),
+            snapshot: string("snapshot", take("snapshot"))?,
+            png: string("png", take("png"))?,
+            png_size: string("png_size", take("png_size"))?
+                .map(|size| crate::parse_size(&size))
+                .transpose()?,
+            census: string("census", take("census"))?,
+            max_nodes: match take("max_nodes") {
+                None => None,
+                Some(Value::Integer(n)) if n > 0 => Some(n as usize),
+                Some(_) => return Err("max_nodes should be a positive number".to_string()),
+            },
+            max_seconds: match take("max_seconds") {
+                None => None,
+                Some(Value::Integer(n)) if n > 0 => Some(n as f64),
+                Some(Value::Float(n)) if n > 0. => Some(n),
+                Some(_) => return Err("max_seconds should be a positive number".to_string()),
+            },
+        };
+        match table.keys().next() {
+            Some(key) => Err(format!("unknown key {key}")),
+            None => Ok(job),
+        }
+    }
+
+    /// Steps the pattern, writing the outputs every so often. Returns the
+    /// pattern as it ended up.
+    fn run(&self, dir: &Path) -> Result<HashLife, String> {
+        let input = self.input.display();
+        let mut life = formats::load_file(&self.input)
+            .map_err(|e| format!("{input}: {e}"))?
+            .life;
+        if let Some(rule) = &self.rule {
+            life.set_rule(rule.parse().map_err(|e: RuleError| e.to_string())?);
+        }
+        life.set_universe_config(UniverseConfig {
+            max_nodes: self.max_nodes,
+            ..life.universe_config()
+        });
+        let deadline = self
+            .max_seconds
+            .map(|seconds| Instant::now() + Duration::from_secs_f64(seconds));
+        let start = life.generation();
+        let mut stepped = 0;
+        loop {
+            self.write_outputs(&life, dir)?;
+            if stepped == self.steps {
+                return Ok(life);
+            }
+            let chunk = self.every.unwrap_or(u64::MAX).min(self.steps - stepped);
+            advance(&mut life, chunk, deadline)
+                .map_err(|e| format!("{e} at generation {}", life.generation()))?;
+            stepped = life.generation() - start;
+        }
+    }
+
+    fn write_outputs(&self, life: &HashLife, dir: &Path) -> Result<(), String> {
+        let fill = |template: &String| {
+            let path = template
+                .replace("{name}", &self.name)
+                .replace("{gen}", &life.generation().to_string());
+            let path = dir.join(path);
+            // Writing will say if this failed.
+            let _ = std::fs::create_dir_all(path.parent().unwrap_or(dir));
+            path.to_string_lossy().into_owned()
+        };
+        if let Some(path) = self.snapshot.as_ref().map(fill) {
+            crate::save(life, &Default::default(), &path, None)?;
+        }
+        if let Some(path) = self.png.as_ref().map(fill) {
+            crate::render_png(life, self.png_size, None, &path)?;
+        }
+        if let Some(path) = self.census.as_ref().map(fill) {
+            let json = census_json(life);
+            std::fs::write(&path, json).map_err(|e| format!("{path}: {e}"))?;
+        }
+        Ok(())
+    }
+}
+
+/// Steps a power of two at a time like `crate::step_by`, giving up at the
+/// deadline if there is one.
+fn advance(life: &mut HashLife, steps: u64, deadline: Option<Instant>) -> Result<(), String> {
+    let Some(deadline) = deadline else {
+        return crate::step_by(life, steps);
+    };
+    for log2 in (0..u64::BITS as usize).rev() {
+        if steps >> log2 & 1 == 0 {
+            continue;
+        }
+        let left = deadline.saturating_duration_since(Instant::now());
+        if life.step_for(1 << log2, left) == 0 {
+            return Err("out of time".to_string());
+        }
+        if let Some(max) = life.universe_config().max_nodes
+            && life.node_count() > max
+        {
+            let nodes = life.node_count();
+            return Err(StepError::OutOfNodes { nodes, max }.to_string());
+        }
+    }
+    Ok(())
+}
+
+/// The objects in the pattern, as JSON.
+fn census_json(life: &HashLife) -> String {
+    let census = life.census();
+    let counts = census
+        .counts
+        .iter()
+        .map(|(name, count)| format!("{}: {count}", crate::bench::json_string(name)))
+        .collect::<Vec<_>>();
+    format!(
+        "{{\"generation\": {}, \"population\": {}, \"objects\": {{{}}}, \"unrecognized\": {}}}\n",
+        life.generation(),
+        life.population(),
+        counts.join(", "),
+        census.unrecognized.len()
+    )
+}
@@ -0,0 +1,99 @@
+use std::{fmt::Write, time::Instant};
+
+use hashlife::{HashLife, Search, patterns};
+
+/// A pattern and how far to step it.
+struct Case {
+    name: String,
+    life: HashLife,
+    steps: u64,
+}
+
+/// The patterns every run of `gol bench` steps, for the same number of
+/// generations each time, so runs on different machines or commits can be
+/// compared.
+fn suite() -> Vec<Case> {
+    let case = |name: &str, life, steps| Case {
+        name: name.to_string(),
+        life,
+        steps,
+    };
+    // Many copies of the same thing, where hashlife should find nearly every
+    // step cached, as it does in metacells.
+    let mut tiled = HashLife::new();
+    for y in 0..32 {
+        for x in 0..32 {
+            tiled.paste(&patterns::pulsar(), (y * 20, x * 20));
+        }
+    }
+    let soup = |size| Search {
+        size,
+        ..Search::default()
+    };
+    vec![
+        case("glider", patterns::glider(), 1 << 30),
+        // Grows without end, like a breeder, though only linearly.
+        case("gosper_gun", patterns::gosper_gun(), 1 << 20),
+        case("tiled", tiled, 1 << 20),
+        case("r_pentomino", patterns::r_pentomino(), 1 << 12),
+        case("soup_64", soup(64).soup(0), 1 << 12),
+        case("soup_256", soup(256).soup(0), 1 << 12),
+    ]
+}
+
+/// Steps the standard suite, then each of `extra` for `steps` generations,
+/// and reports how long each took and what it left in its universe, as JSON.
+pub fn bench(extra: Vec<(String, HashLife)>, steps: u64) -> Result<String, String> {
+    let extra = extra
+        .into_iter()
+        .map(|(name, life)| Case { name, life, steps });
+    let mut results = vec![];
+    for Case {
+        name,
+        mut life,
+        steps,
+    } in suite().into_iter().chain(extra)
+    {
+        let started = Instant::now();
+        crate::step_by(&mut life, steps).map_err(|e| format!("{name}: {e}"))?;
+        let seconds = started.elapsed().as_secs_f64();
+        let stats = life.stats();
+        let lookups = stats.cache_hits + stats.cache_misses;
+        let hit_rate = stats.cache_hits as f64 / lookups.max(1) as f64;
+        let mut result = String::new();
+        write!(
+            result,
+            "{{\"name\": {}, \"steps\": {steps}, \"seconds\": {seconds:.6}, \
+             \"population\": {}, \"nodes\": {}, \"cache_hits\": {}, \
+             \"cache_misses\": {}, \"hit_rate\": {hit_rate:.4}, \"bytes\": {}}}",
+            json_string(&name),
+            life.population(),
+            stats.nodes,
+            stats.cache_hits,
+            stats.cache_misses,
+            stats.bytes,
+        )
+        .unwrap();
+        results.push(result);
+    }
+    Ok(format!(
+        "{{\"version\": {}, \"cases\": [\n  {}\n]}}",
+        json_string(env!("CARGO_PKG_VERSION")),
+        results.join(",\n  ")
+    ))
+}
+
+/// `s` as a quoted JSON string.
+pub fn json_string(s: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if c.is_control() => write!(quoted, "\\u{:04x}", c as u32).unwrap(),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,225 @@
+//! Just enough HTTP/1.1 for `gol serve` and `gol api`: reading requests and
+//! writing whole responses.
+
+use std::io::{self, BufRead, Read, Write};
+
+/// The longest request line or header that's read.
+const MAX_LINE: usize = 8 << 10;
+
+/// The most headers a request may have.
+const MAX_HEADERS: usize = 100;
+
+pub struct Request {
+    pub method: String,
+    /// The path, with any query string.
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+}
+
+impl Request {
+    /// Reads a request's line and headers, leaving `r` at its body. Returns
+    /// nothing if the connection closes first.
+    pub fn read(r: &mut impl BufRead) -> io::Result<Option<Self>> {
+        let Some(line) = read_line(r)? else {
+            return Ok(None);
+        };
+        let mut words = line.split(' ');
+        let (Some(method), Some(path), Some(_version), None) =
+            (words.next(), words.next(), words.next(), words.next())
+        else {
+            return Err(invalid(format!("bad request line {line:?}")));
+        };
+        let mut request = Request {
+            method: method.to_string(),
+            path: path.to_string(),
+            headers: vec![],
+        };
+        loop {
+            let line = read_line(r)?.ok_or_else(|| invalid("unfinished request".to_string()))?;
+            if line.is_empty() {
+                return Ok(Some(request));
+            }
+            if request.headers.len() == MAX_HEADERS {
+                return Err(invalid("too many headers".to_string()));
+            }
+            let (name, value) = line
+                .split_once(':')
+                .ok_or_else(|| invalid(format!("bad header {line:?}")))?;
+            request
+                .headers
+                .push((name.to_string(), value.trim().to_string()));
+        }
+    }
+
+    /// The path without its query string, and the query's `name=value` pairs,
+    /// decoded.
+    pub fn query(&self) -> (&str, Vec<(String, String)>) {
+        let (path, query) = self.path.split_once('?').unwrap_or((&self.path, ""));
+        let pairs = query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+                (decode(name), decode(value))
+            })
+            .collect();
+        (path, pairs)
+    }
+
+    /// Reads the body that follows the head, up to `max` bytes long.
+    pub fn read_body(&self, r: &mut impl Read, max: usize) -> io::Result<Vec<u8>> {
+        let len = match self.header("content-length") {
+            Some(len) => len
+                .parse()
+                .map_err(|_| invalid(format!("bad content length {len:?}")))?,
+            None => 0,
+        };
+        if len > max {
+            return Err(io::Error::new(
+                io::ErrorKind::FileTooLarge,
+                format!("bodies may be at most {max} bytes"),
+            ));
+        }
+        let mut body = vec![0; len];
+        r.read_exact(&mut body)?;
+        Ok(body)
+    }
+
+    /// The value of the header `name`, whatever its case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// A line without its line ending, or nothing at the end of the stream.
+fn read_line(r: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut line = vec![];
+    Read::take(&mut *r, MAX_LINE as u64 + 2).read_until(b'\n', &mut line)?;
+    if line.is_empty() {
+        return Ok(None);
+    }
+    if line.pop() != Some(b'\n') {
+        return Err(invalid("line too long".to_string()));
+    }
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    String::from_utf8(line)
+        .map(Some)
+        .map_err(|_| invalid("line isn't UTF-8".to_string()))
+}
+
+/// Undoes percent-encoding, and `+` for spaces.
+fn decode(s: &str) -> String {
+    let mut bytes = vec![];
+    let mut rest = s.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        // Both digits, as parsing alone would take a sign in place of one.
+        let hex = tail
+            .get(..2)
+            .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (byte, hex) {
+            (b'%', Some(byte)) => {
+                bytes.push(byte);
+                rest = &tail[2..];
+                continue;
+            }
+            (b'+', _) => bytes.push(b' '),
+            (byte, _) => bytes.push(byte),
+        }
+        rest = tail;
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Writes a response with `body`, and asks for the connection to be closed.
+pub fn respond(w: &mut impl Write, status: u16, content_type: &str, body: &[u8]) -> io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        201 => "Created",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Content Too Large",
+        500 => "Internal Server Error",
+        _ => "",
+    };
+    write!(
+        w,
+        "HTTP/1.1 {status} {reason}\r\n\
+         Content-Type: {content_type}\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n",
+        body.len()
+    )?;
+    w.write_all(body)?;
+    w.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(request: &str) -> io::Result<Option<Request>> {
+        Request::read(&mut request.as_bytes())
+    }
+
+    #[test]
+    fn test_read() {
+        let mut r =
+            &b"POST /patterns?x=1 HTTP/1.1\r\nHost: a\r\nContent-Length:  3 \r\n\r\nabcdef"[..];
+        let request = Request::read(&mut r).unwrap().unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/patterns?x=1");
+        assert_eq!(request.header("content-length"), Some("3"));
+        assert_eq!(request.header("HOST"), Some("a"));
+        assert_eq!(request.header("accept"), None);
+        assert_eq!(request.read_body(&mut r, 3).unwrap(), b"abc");
+        assert_eq!(
+            request.read_body(&mut r, 2).unwrap_err().kind(),
+            io::ErrorKind::FileTooLarge
+        );
+        // Bare newlines will do.
+        let request = read("GET / HTTP/1.0\n\n").unwrap().unwrap();
+        assert!(request.headers.is_empty());
+        assert!(read("").unwrap().is_none());
+        assert!(read("GET /\r\n\r\n").is_err());
+        assert!(read("GET / HTTP/1.1\r\nHost: a\r\n").is_err());
+        assert!(read("GET / HTTP/1.1\r\nno colon\r\n\r\n").is_err());
+        let long = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_LINE));
+        assert!(read(&long).is_err());
+        let headers = |n: usize| format!("GET / HTTP/1.1\r\n{}\r\n", "A: b\r\n".repeat(n));
+        assert!(read(&headers(MAX_HEADERS)).is_ok());
+        assert!(read(&headers(MAX_HEADERS + 1)).is_err());
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode("a%20b+c%2Bd"), "a b c+d");
+        assert_eq!(decode("%e2%9C%93"), "\u{2713}");
+        // Anything that isn't an escape is left as it is, signs included.
+        assert_eq!(decode("%+f%-1%zz%4"), "% f%-1%zz%4");
+        let request = read("GET /a/b?size=2x3&&flag&v=1..2%2C3..4 HTTP/1.1\r\n\r\n");
+        let request = request.unwrap().unwrap();
+        let (path, query) = request.query();
+        assert_eq!(path, "/a/b");
+        let pair = |name: &str, value: &str| (name.to_string(), value.to_string());
+        assert_eq!(
+            query,
+            [
+                pair("size", "2x3"),
+                pair("flag", ""),
+                pair("v", "1..2,3..4")
+            ]
+        );
+    }
+}
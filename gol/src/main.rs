@@ -0,0 +1,504 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    ops::Range,
+    path::Path,
+    process::ExitCode,
+};
+
+use hashlife::{
+    HashLife, ParseError,
+    formats::{self, Format, Pattern, PatternMeta},
+    render::{self, Rgba, Settings, View, terminal},
+};
+
+mod api;
+mod batch;
+mod bench;
+mod http;
+mod serve;
+mod term;
+mod toml;
+mod tui;
+mod watch;
+mod websocket;
+
+const USAGE: &str = "\
+usage:
+    gol run <pattern> [--steps N] [--out <file>] [--format F]
+    gol convert <in> <out> [--format F]
+    gol render <in> <out.png> [--steps N] [--size WxH] [--viewport Y0..Y1,X0..X1]
+    gol info <pattern>
+    gol watch <pattern> [--gps N] [--glyphs braille|blocks]
+    gol bench [<pattern>...] [--steps N]
+    gol tui [<pattern>] [--engine hashlife|hybrid|bitlife|basic]
+    gol batch <jobs.toml>
+    gol serve [<pattern>] [--port N] [--host H] [--fps N]
+    gol api [--port N] [--host H]
+
+Step counts may be written as powers of two, like 2^30, or of ten, like 1e6.
+A file of - is stdin or stdout. Patterns to read may also be URLs, or
+lifewiki:NAME for LifeWiki's NAME.rle, if gol was built with
+--features fetch. Patterns are written as RLE, or as macrocells for files
+ending in .mc, or as plain text for files ending in .cells, unless --format
+says otherwise with one of rle, mc or cells. `run` writes to stdout unless
+given --out. `bench` steps a fixed suite of patterns, and any others given
+for --steps generations, 2^20 by default, and writes the timings and cache
+statistics as JSON. `tui` edits and runs a pattern full screen, and saves it
+back to its file. `batch` runs the [[job]]s in a TOML file, each with an
+input, steps, and optionally a rule, an interval to write outputs every,
+snapshot, png and census paths where {gen} is the generation, a png_size,
+max_nodes and max_seconds. `serve` runs a pattern for WebSocket clients on
+port 8080 of localhost by default, with a viewer at /, streaming the live
+cells of each client's view as it changes, and taking view, pan, zoom, step,
+run and pause commands from them. `api` serves a REST API on the same port,
+where patterns POSTed to /patterns can be stepped, drawn as PNGs and
+downloaded as RLE or macrocells.";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("gol: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    let Some((command, args)) = args.split_first() else {
+        return Err(format!("missing command\n{USAGE}"));
+    };
+    let args = Args::parse(args)?;
+    match command.as_str() {
+        "run" => {
+            let [path] = args.positional()?;
+            args.check_flags(&["steps", "out", "format"])?;
+            let mut pattern = load(path)?;
+            step(&mut pattern.life, args.flag("steps")?)?;
+            // Kept off stdout, where the pattern may be going.
+            for line in describe(&pattern.meta) {
+                eprintln!("{line}");
+            }
+            eprintln!(
+                "generation {}, population {}",
+                pattern.life.generation(),
+                pattern.life.population()
+            );
+            let out = args.flag("out")?.unwrap_or("-");
+            save(&pattern.life, &pattern.meta, out, args.flag("format")?)
+        }
+        "convert" => {
+            let [input, output] = args.positional()?;
+            args.check_flags(&["format"])?;
+            let pattern = load(input)?;
+            save(&pattern.life, &pattern.meta, output, args.flag("format")?)
+        }
+        "render" => {
+            let [input, output] = args.positional()?;
+            args.check_flags(&["steps", "size", "viewport"])?;
+            let mut pattern = load(input)?;
+            step(&mut pattern.life, args.flag("steps")?)?;
+            let size = args.flag("size")?.map(parse_size).transpose()?;
+            let viewport = args.flag("viewport")?.map(parse_viewport).transpose()?;
+            render_png(&pattern.life, size, viewport, output)
+        }
+        "info" => {
+            let [path] = args.positional()?;
+            args.check_flags(&[])?;
+            info(&load(path)?);
+            Ok(())
+        }
+        "watch" => {
+            let [path] = args.positional()?;
+            args.check_flags(&["gps", "glyphs"])?;
+            if path == "-" {
+                return Err("watch reads keys from stdin, so needs a pattern file".to_string());
+            }
+            let gps = match args.flag("gps")? {
+                Some(gps) => gps
+                    .parse()
+                    .ok()
+                    .filter(|&gps: &f64| gps > 0. && gps.is_finite())
+                    .ok_or(format!("bad generations per second {gps:?}"))?,
+                None => 30.,
+            };
+            let glyphs = match args.flag("glyphs")? {
+                None | Some("braille") => terminal::Glyphs::Braille,
+                Some("blocks") => terminal::Glyphs::HalfBlocks,
+                Some(glyphs) => return Err(format!("unknown glyphs {glyphs:?}")),
+            };
+            watch::watch(load(path)?.life, gps, glyphs)
+        }
+        "bench" => {
+            args.check_flags(&["steps"])?;
+            let steps = args.flag("steps")?.map_or(Ok(1 << 20), parse_steps)?;
+            let mut extra = vec![];
+            for &path in &args.positional {
+                extra.push((path.to_string(), load(path)?.life));
+            }
+            println!("{}", bench::bench(extra, steps)?);
+            Ok(())
+        }
+        "tui" => {
+            args.check_flags(&["engine"])?;
+            let engine = args.flag("engine")?.unwrap_or("hashlife");
+            if !tui::ENGINES.contains(&engine) {
+                return Err(format!("unknown engine {engine:?}"));
+            }
+            let (pattern, path) = match args.positional.as_slice() {
+                [] => {
+                    let empty = Pattern {
+                        format: Format::Rle,
+                        life: HashLife::new(),
+                        meta: PatternMeta::default(),
+                    };
+                    (Ok(empty), None)
+                }
+                ["-"] => {
+                    return Err("tui reads keys from stdin, so needs a pattern file".to_string());
+                }
+                &[path] => (formats::load_file(path), Some(path)),
+                _ => return Err(format!("expected at most 1 pattern\n{USAGE}")),
+            };
+            let pattern = pattern.map_err(|e| e.to_string())?;
+            tui::tui(pattern, engine, path)
+        }
+        "batch" => {
+            let [path] = args.positional()?;
+            args.check_flags(&[])?;
+            batch::batch(path)
+        }
+        "serve" => {
+            args.check_flags(&["port", "host", "fps"])?;
+            let life = match args.positional.as_slice() {
+                [] => HashLife::new(),
+                &[path] => load(path)?.life,
+                _ => return Err(format!("expected at most 1 pattern\n{USAGE}")),
+            };
+            let port = port(args.flag("port")?)?;
+            let fps = match args.flag("fps")? {
+                Some(fps) => fps
+                    .parse()
+                    .ok()
+                    .filter(|&fps: &f64| fps > 0. && fps.is_finite())
+                    .ok_or(format!("bad frames per second {fps:?}"))?,
+                None => 30.,
+            };
+            let host = args.flag("host")?.unwrap_or("127.0.0.1");
+            serve::serve(life, host, port, fps)
+        }
+        "api" => {
+            let [] = args.positional()?;
+            args.check_flags(&["port", "host"])?;
+            let host = args.flag("host")?.unwrap_or("127.0.0.1");
+            api::api(host, port(args.flag("port")?)?)
+        }
+        "help" | "--help" | "-h" => {
+            println!("{USAGE}");
+            Ok(())
+        }
+        _ => Err(format!("unknown command {command:?}\n{USAGE}")),
+    }
+}
+
+/// Arguments after the command, with `--name value` flags picked out.
+struct Args<'a> {
+    positional: Vec<&'a str>,
+    flags: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> Args<'a> {
+    fn parse(args: &'a [String]) -> Result<Self, String> {
+        let mut parsed = Args {
+            positional: vec![],
+            flags: vec![],
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.strip_prefix("--") {
+                Some(name) => {
+                    let value = args.next().ok_or(format!("--{name} needs a value"))?;
+                    parsed.flags.push((name, value));
+                }
+                None => parsed.positional.push(arg),
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn positional<const N: usize>(&self) -> Result<[&'a str; N], String> {
+        self.positional.as_slice().try_into().map_err(|_| {
+            format!(
+                "expected {N} arguments, got {}\n{USAGE}",
+                self.positional.len()
+            )
+        })
+    }
+
+    /// The value of the flag `--name`, if it was given. Flags given more than
+    /// once are mistakes.
+    fn flag(&self, name: &str) -> Result<Option<&'a str>, String> {
+        let mut values = self.flags.iter().filter(|(n, _)| *n == name);
+        let value = values.next().map(|&(_, value)| value);
+        match values.next() {
+            Some(_) => Err(format!("--{name} given more than once")),
+            None => Ok(value),
+        }
+    }
+
+    fn check_flags(&self, known: &[&str]) -> Result<(), String> {
+        match self.flags.iter().find(|(name, _)| !known.contains(name)) {
+            Some((name, _)) => Err(format!("unknown flag --{name}\n{USAGE}")),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Where LifeWiki keeps its pattern files.
+const LIFEWIKI: &str = "https://conwaylife.com/patterns/";
+
+fn load(path: &str) -> Result<Pattern, String> {
+    let pattern = match path {
+        "-" => formats::read(io::stdin().lock()),
+        path if path.starts_with("http://") || path.starts_with("https://") => load_url(path),
+        path => match path.strip_prefix("lifewiki:") {
+            Some(name) => load_url(&format!("{LIFEWIKI}{}.rle", name.to_lowercase())),
+            None => formats::load_file(path),
+        },
+    };
+    pattern.map_err(|e| format!("{path}: {e}"))
+}
+
+#[cfg(feature = "fetch")]
+fn load_url(url: &str) -> Result<Pattern, ParseError> {
+    formats::load_url(url)
+}
+
+#[cfg(not(feature = "fetch"))]
+fn load_url(_: &str) -> Result<Pattern, ParseError> {
+    let e = "can't download patterns without the fetch feature";
+    Err(ParseError::Io(io::Error::new(
+        io::ErrorKind::Unsupported,
+        e,
+    )))
+}
+
+/// Writes to the file at `path`, or to stdout if it's `-`.
+fn create(path: &str, write: impl FnOnce(&mut dyn Write) -> io::Result<()>) -> Result<(), String> {
+    let written = match path {
+        "-" => {
+            let mut w = io::stdout().lock();
+            write(&mut w).and_then(|()| w.flush())
+        }
+        path => File::create(path).and_then(|file| {
+            let mut w = BufWriter::new(file);
+            write(&mut w).and_then(|()| w.flush())
+        }),
+    };
+    written.map_err(|e| format!("{path}: {e}"))
+}
+
+/// Writes the pattern in `format`, or the format of the file's extension, or
+/// else as RLE.
+fn save(
+    life: &HashLife,
+    meta: &PatternMeta,
+    path: &str,
+    format: Option<&str>,
+) -> Result<(), String> {
+    let format = match format {
+        Some(name) => Format::from_extension(name).ok_or(format!("unknown format {name:?}"))?,
+        None => Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .and_then(Format::from_extension)
+            .unwrap_or(Format::Rle),
+    };
+    // Written out first, so that formats without writers leave no file.
+    let mut bytes = vec![];
+    formats::write(&mut bytes, life, meta, format).map_err(|e| format!("{path}: {e}"))?;
+    create(path, |w| w.write_all(&bytes))
+}
+
+/// A number of generations, like `1000`, `2^30` or `1e6`.
+fn parse_steps(s: &str) -> Result<u64, String> {
+    let steps = if let Some(("2", log2)) = s.split_once('^') {
+        log2.parse()
+            .ok()
+            .and_then(|log2: u32| 1u64.checked_shl(log2))
+    } else if let Some((mantissa, exponent)) = s.split_once(['e', 'E']) {
+        let mantissa: Option<u64> = mantissa.parse().ok();
+        let power = exponent
+            .parse()
+            .ok()
+            .and_then(|e: u32| 10u64.checked_pow(e));
+        mantissa
+            .zip(power)
+            .and_then(|(mantissa, power)| mantissa.checked_mul(power))
+    } else {
+        s.parse().ok()
+    };
+    steps.ok_or(format!("bad step count {s:?}"))
+}
+
+fn step(life: &mut HashLife, steps: Option<&str>) -> Result<(), String> {
+    step_by(life, steps.map(parse_steps).transpose()?.unwrap_or(0))
+}
+
+/// Steps a power of two at a time, so running out of room is an error rather
+/// than a panic.
+fn step_by(life: &mut HashLife, steps: u64) -> Result<(), String> {
+    for log2 in (0..u64::BITS as usize).rev() {
+        if steps >> log2 & 1 == 1 {
+            life.step_checked(log2).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+/// The port to serve on, 8080 by default.
+fn port(port: Option<&str>) -> Result<u16, String> {
+    match port {
+        Some(port) => port.parse().map_err(|_| format!("bad port {port:?}")),
+        None => Ok(8080),
+    }
+}
+
+/// A size in pixels, like `640x480`, as (height, width).
+fn parse_size(s: &str) -> Result<(usize, usize), String> {
+    let size = s.split_once('x').and_then(|(w, h)| {
+        let (w, h) = (w.parse().ok()?, h.parse().ok()?);
+        (w > 0 && h > 0).then_some((h, w))
+    });
+    size.ok_or(format!("bad size {s:?}, expected WxH"))
+}
+
+/// Rows and columns of cells, like `-10..10,0..100`.
+fn parse_viewport(s: &str) -> Result<(Range<isize>, Range<isize>), String> {
+    let range = |s: &str| {
+        let (start, end) = s.split_once("..")?;
+        let range = start.parse().ok()?..end.parse().ok()?;
+        (!range.is_empty()).then_some(range)
+    };
+    let viewport = s
+        .split_once(',')
+        .and_then(|(ys, xs)| Some((range(ys)?, range(xs)?)));
+    viewport.ok_or(format!("bad viewport {s:?}, expected Y0..Y1,X0..X1"))
+}
+
+fn render_png(
+    life: &HashLife,
+    size: Option<(usize, usize)>,
+    viewport: Option<(Range<isize>, Range<isize>)>,
+    path: &str,
+) -> Result<(), String> {
+    create(path, |w| write_png(life, size, viewport, w))
+}
+
+/// Draws the cells of `viewport`, or the whole pattern, to fit the image.
+fn write_png(
+    life: &HashLife,
+    size: Option<(usize, usize)>,
+    viewport: Option<(Range<isize>, Range<isize>)>,
+    w: &mut dyn Write,
+) -> io::Result<()> {
+    let (height, width) = size.unwrap_or((512, 512));
+    let settings = settings(life, height, width);
+    let viewport = viewport.or(life.bounding_box()).unwrap_or((0..1, 0..1));
+    let view = View::fit(viewport, settings);
+    render::write_png(life, settings, view, w)
+}
+
+/// Black cells on white, a pixel to a cell at a zoom of 1.
+fn settings(life: &HashLife, height: usize, width: usize) -> Settings {
+    Settings {
+        height,
+        width,
+        cell_size: 1.,
+        dead_rgba: Rgba::WHITE,
+        alive_rgba: Rgba::BLACK,
+        row_offset: life.rule().neighbourhood().row_offset(),
+        grid: None,
+        ages: None,
+        heatmap: None,
+        envelope: None,
+    }
+}
+
+/// The name, author and comments a pattern file came with, a line each.
+fn describe(meta: &PatternMeta) -> Vec<String> {
+    let name = meta.name.iter().map(|name| format!("name {name}"));
+    let author = meta.author.iter().map(|author| format!("author {author}"));
+    let comments = meta
+        .comments
+        .iter()
+        .map(|comment| format!("comment {comment}"));
+    name.chain(author).chain(comments).collect()
+}
+
+fn info(pattern: &Pattern) {
+    let Pattern { format, life, meta } = pattern;
+    println!("format {format:?}");
+    for line in describe(meta) {
+        println!("{line}");
+    }
+    println!("rule {}", life.rule());
+    println!("generation {}", life.generation());
+    println!("population {}", life.population());
+    if let Some((ys, xs)) = life.bounding_box() {
+        println!("size {}x{}", xs.len(), ys.len());
+        println!("bounds {}..{},{}..{}", ys.start, ys.end, xs.start, xs.end);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_steps() {
+        assert_eq!(parse_steps("1000"), Ok(1000));
+        assert_eq!(parse_steps("2^30"), Ok(1 << 30));
+        assert_eq!(parse_steps("2^63"), Ok(1 << 63));
+        assert_eq!(parse_steps("1e6"), Ok(1_000_000));
+        assert_eq!(parse_steps("3E2"), Ok(300));
+        for bad in ["", "-1", "2^64", "3^2", "1e20", "1.5e3", "e3", "lots"] {
+            assert_eq!(parse_steps(bad), Err(format!("bad step count {bad:?}")));
+        }
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("640x480"), Ok((480, 640)));
+        for bad in ["640", "0x480", "640x0", "-1x2", "axb", "1x2x3"] {
+            assert!(parse_size(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_parse_viewport() {
+        assert_eq!(parse_viewport("-10..10,0..100"), Ok((-10..10, 0..100)));
+        for bad in ["-10..10", "0..0,0..1", "5..1,0..1", "0..1,a..b", "0-1,0-1"] {
+            assert!(parse_viewport(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_args() {
+        let strings = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        let given = strings(&["in.rle", "--steps", "2^10", "out.png", "--size", "4x3"]);
+        let args = Args::parse(&given).unwrap();
+        assert_eq!(args.positional, ["in.rle", "out.png"]);
+        assert_eq!(args.positional::<2>(), Ok(["in.rle", "out.png"]));
+        assert!(args.positional::<1>().is_err());
+        assert_eq!(args.flag("steps"), Ok(Some("2^10")));
+        assert_eq!(args.flag("viewport"), Ok(None));
+        assert!(args.check_flags(&["steps", "size"]).is_ok());
+        assert!(args.check_flags(&["steps"]).is_err());
+        let twice = strings(&["--port", "1", "--port", "2"]);
+        assert!(Args::parse(&twice).unwrap().flag("port").is_err());
+        assert!(Args::parse(&strings(&["--out"])).is_err());
+    }
+}
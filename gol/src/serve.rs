@@ -0,0 +1,530 @@
+//! `gol serve`: one universe stepped on the server and watched over
+//! WebSockets, each client through its own viewport.
+//!
+//! Clients send text commands:
+//!
+//! - `view TOP LEFT ROWS COLS [SCALE]` watches ROWS by COLS blocks of
+//!   2^SCALE by 2^SCALE cells, from the cell (TOP, LEFT).
+//! - `pan DY DX` moves the view by whole blocks.
+//! - `zoom in` or `zoom out` halves or doubles the blocks, keeping the middle.
+//! - `step [N]` steps N generations, 1 by default, at once.
+//! - `run [LOG2]` steps 2^LOG2 generations a frame, and `pause` stops.
+//!
+//! and get JSON back. After each command that changes the view, a `frame`
+//! lists every block with a live cell, as [row, column] from the top-left
+//! one. After that, each `delta` lists the blocks `born` and `died` since.
+//! Both carry the `generation`, `population` and whether it's `running`.
+//! Commands that fail get an `error` with a `message`.
+
+use std::{
+    collections::BTreeSet,
+    fmt::Write as _,
+    io::{self, BufReader},
+    net::{TcpListener, TcpStream},
+    ops::Range,
+    panic::{self, AssertUnwindSafe},
+    str::FromStr,
+    sync::{
+        Arc, Mutex, MutexGuard, PoisonError,
+        mpsc::{self, RecvTimeoutError},
+    },
+    thread,
+    time::Duration,
+};
+
+use hashlife::HashLife;
+
+use crate::{
+    bench::json_string,
+    http::{self, Request},
+    websocket::{self, Message},
+};
+
+/// A page that watches the universe on a canvas, served at `/`.
+const VIEWER: &str = include_str!("viewer.html");
+
+/// The most blocks a view may have.
+const MAX_BLOCKS: usize = 256 * 256;
+
+/// How far from the origin views may reach, so their edges can't overflow.
+const MAX_COORDINATE: isize = 1 << 60;
+
+/// The universe, shared by the thread stepping it and the clients.
+struct Universe {
+    life: HashLife,
+    /// How many generations to step each frame, as a power of two, or
+    /// nothing if paused.
+    log2_steps: Option<usize>,
+    /// Counts changes to `life`, so clients can tell when to send a delta.
+    version: u64,
+}
+
+type Shared = Arc<Mutex<Universe>>;
+
+/// Serves `life` on `host`:`port` until killed, stepping it `fps` times a
+/// second while running.
+pub fn serve(life: HashLife, host: &str, port: u16, fps: f64) -> Result<(), String> {
+    let listener = TcpListener::bind((host, port)).map_err(|e| format!("{host}:{port}: {e}"))?;
+    let address = listener.local_addr().map_err(|e| e.to_string())?;
+    eprintln!("serving on http://{address}/");
+    let universe = Arc::new(Mutex::new(Universe {
+        life,
+        log2_steps: Some(0),
+        version: 0,
+    }));
+    let frame = Duration::from_secs_f64(1. / fps);
+    let stepped = universe.clone();
+    thread::spawn(move || step(&stepped, frame));
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let universe = universe.clone();
+        // Clients going away mid-message isn't worth reporting.
+        thread::spawn(move || connection(stream, &universe, frame).ok());
+    }
+    Ok(())
+}
+
+fn step(universe: &Shared, frame: Duration) {
+    loop {
+        thread::sleep(frame);
+        let mut universe = lock(universe);
+        let Some(log2_steps) = universe.log2_steps else {
+            continue;
+        };
+        // Pauses rather than run out of room, or if a step panics.
+        let life = &mut universe.life;
+        let stepped = panic::catch_unwind(AssertUnwindSafe(|| life.step_checked(log2_steps)));
+        if !matches!(stepped, Ok(Ok(()))) {
+            universe.log2_steps = None;
+        }
+        universe.version += 1;
+    }
+}
+
+/// The universe, even if a thread panicked while holding it, as steps only
+/// change the pattern once they're done.
+fn lock(universe: &Shared) -> MutexGuard<'_, Universe> {
+    universe.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Answers a request: the viewer at `/`, and WebSockets anywhere.
+fn connection(stream: TcpStream, universe: &Shared, frame: Duration) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let Some(request) = Request::read(&mut reader)? else {
+        return Ok(());
+    };
+    let mut w = stream;
+    let upgrade = request
+        .header("upgrade")
+        .is_some_and(|u| u.eq_ignore_ascii_case("websocket"));
+    match (request.method.as_str(), request.header("sec-websocket-key")) {
+        ("GET", Some(key)) if upgrade => {
+            websocket::accept(&mut w, key)?;
+            client(reader, w, universe, frame)
+        }
+        ("GET", _) if request.path == "/" => {
+            http::respond(&mut w, 200, "text/html", VIEWER.as_bytes())
+        }
+        _ => http::respond(&mut w, 404, "text/plain", b"not found\n"),
+    }
+}
+
+/// Talks to a client until it closes, sending updates as often as frames.
+fn client(
+    reader: BufReader<TcpStream>,
+    mut w: TcpStream,
+    universe: &Shared,
+    frame: Duration,
+) -> io::Result<()> {
+    // Read on another thread, so waiting for commands doesn't hold up updates.
+    let (tx, messages) = mpsc::channel();
+    thread::spawn(move || {
+        let mut reader = websocket::Reader::new(reader);
+        loop {
+            let message = reader.read();
+            let last = !matches!(
+                message,
+                Ok(Message::Text(_) | Message::Binary | Message::Ping(_))
+            );
+            if tx.send(message).is_err() || last {
+                break;
+            }
+        }
+    });
+    let mut client = Client::new(&lock(universe).life);
+    loop {
+        match messages.recv_timeout(frame) {
+            Ok(Ok(Message::Text(command))) => {
+                if let Err(e) = client.command(&command, universe) {
+                    let error = format!("{{\"type\":\"error\",\"message\":{}}}", json_string(&e));
+                    websocket::write(&mut w, websocket::TEXT, error.as_bytes())?;
+                }
+            }
+            Ok(Ok(Message::Binary)) | Err(RecvTimeoutError::Timeout) => {}
+            Ok(Ok(Message::Ping(payload))) => websocket::write(&mut w, websocket::PONG, &payload)?,
+            Ok(Ok(Message::Close)) | Err(RecvTimeoutError::Disconnected) => {
+                return websocket::write(&mut w, websocket::CLOSE, &[]);
+            }
+            Ok(Err(e)) => return Err(e),
+        }
+        if let Some(update) = client.update(universe) {
+            websocket::write(&mut w, websocket::TEXT, update.as_bytes())?;
+        }
+    }
+}
+
+/// Which blocks of cells a client is watching.
+#[derive(Clone, Copy, PartialEq)]
+struct Viewport {
+    top: isize,
+    left: isize,
+    rows: usize,
+    columns: usize,
+    /// Blocks are 2^scale cells square.
+    scale: u32,
+}
+
+impl Viewport {
+    /// The cells in the block at `(row, column)`.
+    fn block(&self, row: usize, column: usize) -> (Range<isize>, Range<isize>) {
+        let size = 1 << self.scale;
+        let y = self.top + ((row as isize) << self.scale);
+        let x = self.left + ((column as isize) << self.scale);
+        (y..y + size, x..x + size)
+    }
+
+    fn check(self) -> Result<Self, String> {
+        let blocks = self.rows.checked_mul(self.columns);
+        if !blocks.is_some_and(|blocks| (1..=MAX_BLOCKS).contains(&blocks)) {
+            return Err(format!("views may have 1 to {MAX_BLOCKS} blocks"));
+        }
+        let far = |start: isize, blocks: usize| {
+            let end = (blocks as isize) << self.scale;
+            start.abs() > MAX_COORDINATE || end > MAX_COORDINATE
+        };
+        if self.scale > 40 || far(self.top, self.rows) || far(self.left, self.columns) {
+            return Err("view out of range".to_string());
+        }
+        Ok(self)
+    }
+
+    /// The blocks with a live cell in them.
+    fn live(&self, life: &HashLife) -> BTreeSet<(usize, usize)> {
+        if self.scale == 0 {
+            let ys = self.top..self.top + self.rows as isize;
+            let xs = self.left..self.left + self.columns as isize;
+            return life
+                .cells_in_rect(ys, xs)
+                .map(|(y, x)| ((y - self.top) as usize, (x - self.left) as usize))
+                .collect();
+        }
+        let mut live = BTreeSet::new();
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                let (ys, xs) = self.block(row, column);
+                if life.population_in_rect(ys, xs) > 0 {
+                    live.insert((row, column));
+                }
+            }
+        }
+        live
+    }
+}
+
+struct Client {
+    view: Viewport,
+    /// The version of the universe and the blocks last sent, or nothing if
+    /// the view has changed since.
+    shown: Option<(u64, BTreeSet<(usize, usize)>)>,
+}
+
+impl Client {
+    /// Watches the middle of the pattern.
+    fn new(life: &HashLife) -> Self {
+        let (rows, columns) = (64, 128);
+        let (ys, xs) = life.bounding_box().unwrap_or((0..0, 0..0));
+        let middle = |range: Range<isize>, blocks: usize| {
+            range.start + range.len() as isize / 2 - blocks as isize / 2
+        };
+        Self {
+            view: Viewport {
+                top: middle(ys, rows),
+                left: middle(xs, columns),
+                rows,
+                columns,
+                scale: 0,
+            },
+            shown: None,
+        }
+    }
+
+    fn command(&mut self, command: &str, universe: &Shared) -> Result<(), String> {
+        let words: Vec<&str> = command.split_whitespace().collect();
+        let view = self.view;
+        let moved = match words.as_slice() {
+            ["view", top, left, rows, columns, scale @ ..] => Viewport {
+                top: number(top)?,
+                left: number(left)?,
+                rows: number(rows)?,
+                columns: number(columns)?,
+                scale: match scale {
+                    [] => 0,
+                    [scale] => number(scale)?,
+                    _ => return Err(format!("unknown command {command:?}")),
+                },
+            },
+            ["pan", dy, dx] => {
+                let (dy, dx): (isize, isize) = (number(dy)?, number(dx)?);
+                let by = |d: isize| {
+                    d.checked_mul(1 << view.scale.min(40))
+                        .ok_or("view out of range")
+                };
+                Viewport {
+                    top: view.top.saturating_add(by(dy)?),
+                    left: view.left.saturating_add(by(dx)?),
+                    ..view
+                }
+            }
+            ["zoom", direction] => {
+                let scale = match *direction {
+                    "in" => view.scale.checked_sub(1).ok_or("can't zoom in further")?,
+                    "out" => view.scale + 1,
+                    _ => return Err(format!("can't zoom {direction:?}")),
+                };
+                let middle = |start: isize, blocks: usize| {
+                    let half = |scale| ((blocks as isize) << scale) / 2;
+                    start + half(view.scale) - half(scale)
+                };
+                Viewport {
+                    top: middle(view.top, view.rows),
+                    left: middle(view.left, view.columns),
+                    scale,
+                    ..view
+                }
+            }
+            ["step", steps @ ..] => {
+                let steps = match steps {
+                    [] => 1,
+                    [steps] => crate::parse_steps(steps)?,
+                    _ => return Err(format!("unknown command {command:?}")),
+                };
+                let mut universe = lock(universe);
+                universe.version += 1;
+                return crate::step_by(&mut universe.life, steps);
+            }
+            ["run", log2_steps @ ..] => {
+                let log2_steps = match log2_steps {
+                    [] => 0,
+                    [log2_steps] => number(log2_steps)?,
+                    _ => return Err(format!("unknown command {command:?}")),
+                };
+                if log2_steps > 62 {
+                    return Err("can't run more than 2^62 generations a frame".to_string());
+                }
+                let mut universe = lock(universe);
+                universe.log2_steps = Some(log2_steps);
+                universe.version += 1;
+                return Ok(());
+            }
+            ["pause"] => {
+                let mut universe = lock(universe);
+                universe.log2_steps = None;
+                universe.version += 1;
+                return Ok(());
+            }
+            _ => return Err(format!("unknown command {command:?}")),
+        };
+        self.view = moved.check()?;
+        self.shown = None;
+        Ok(())
+    }
+
+    /// A frame or delta, if anything's changed since the last.
+    fn update(&mut self, universe: &Shared) -> Option<String> {
+        let universe = lock(universe);
+        if self
+            .shown
+            .as_ref()
+            .is_some_and(|&(version, _)| version == universe.version)
+        {
+            return None;
+        }
+        let live = self.view.live(&universe.life);
+        let mut update = String::new();
+        let view = self.view;
+        match &self.shown {
+            None => write!(
+                update,
+                "{{\"type\":\"frame\",\"top\":{},\"left\":{},\"rows\":{},\"columns\":{},\"scale\":{},\"cells\":{}",
+                view.top,
+                view.left,
+                view.rows,
+                view.columns,
+                view.scale,
+                blocks(&live)
+            ),
+            Some((_, shown)) => write!(
+                update,
+                "{{\"type\":\"delta\",\"born\":{},\"died\":{}",
+                blocks(live.difference(shown)),
+                blocks(shown.difference(&live))
+            ),
+        }
+        .unwrap();
+        write!(
+            update,
+            ",\"generation\":{},\"population\":{},\"running\":{}}}",
+            universe.life.generation(),
+            universe.life.population(),
+            universe.log2_steps.is_some()
+        )
+        .unwrap();
+        self.shown = Some((universe.version, live));
+        Some(update)
+    }
+}
+
+fn number<T: FromStr>(word: &str) -> Result<T, String> {
+    word.parse().map_err(|_| format!("bad number {word:?}"))
+}
+
+/// Blocks as a JSON array of [row, column] pairs.
+fn blocks<'a>(blocks: impl IntoIterator<Item = &'a (usize, usize)>) -> String {
+    let pairs: Vec<String> = blocks
+        .into_iter()
+        .map(|(row, column)| format!("[{row},{column}]"))
+        .collect();
+    format!("[{}]", pairs.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+
+    use super::*;
+
+    /// Sends a text message, masked as clients must, if only with zeros.
+    fn send(stream: &mut TcpStream, text: &str) {
+        let mut frame = vec![0x80 | websocket::TEXT, 0x80 | text.len() as u8, 0, 0, 0, 0];
+        frame.extend(text.as_bytes());
+        stream.write_all(&frame).unwrap();
+    }
+
+    /// The opcode and payload of the next message from the server.
+    fn receive(stream: &mut TcpStream) -> (u8, String) {
+        let mut head = [0; 2];
+        stream.read_exact(&mut head).unwrap();
+        let len = match head[1] {
+            126 => {
+                let mut len = [0; 2];
+                stream.read_exact(&mut len).unwrap();
+                u16::from_be_bytes(len) as usize
+            }
+            len => len as usize,
+        };
+        let mut payload = vec![0; len];
+        stream.read_exact(&mut payload).unwrap();
+        (head[0] & 0x0f, String::from_utf8(payload).unwrap())
+    }
+
+    /// A paused glider, so only the client's commands step it.
+    fn glider() -> Shared {
+        let glider = HashLife::from_iter([(-1, 0), (0, 1), (1, -1), (1, 0), (1, 1)]);
+        Arc::new(Mutex::new(Universe {
+            life: glider,
+            log2_steps: None,
+            version: 0,
+        }))
+    }
+
+    /// A WebSocket to a server of `universe`, and the head of its answer.
+    fn connect(universe: Shared) -> (TcpStream, String) {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            connection(stream, &universe, Duration::from_millis(10))
+        });
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(
+            stream,
+            "GET /live HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n"
+        )
+        .unwrap();
+        let mut head = vec![];
+        while !head.ends_with(b"\r\n\r\n") {
+            let mut byte = [0];
+            stream.read_exact(&mut byte).unwrap();
+            head.push(byte[0]);
+        }
+        (stream, String::from_utf8(head).unwrap())
+    }
+
+    #[test]
+    fn test_serve() {
+        let (mut stream, head) = connect(glider());
+        assert!(head.starts_with("HTTP/1.1 101 "));
+        assert!(head.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+        let (opcode, first) = receive(&mut stream);
+        assert_eq!(opcode, websocket::TEXT);
+        assert!(first.starts_with("{\"type\":\"frame\",\"top\":-32,\"left\":-64,"));
+        send(&mut stream, "view -2 -2 8 8");
+        assert_eq!(
+            receive(&mut stream).1,
+            "{\"type\":\"frame\",\"top\":-2,\"left\":-2,\"rows\":8,\"columns\":8,\"scale\":0,\
+             \"cells\":[[1,2],[2,3],[3,1],[3,2],[3,3]],\
+             \"generation\":0,\"population\":5,\"running\":false}"
+        );
+        send(&mut stream, "step 4");
+        assert_eq!(
+            receive(&mut stream).1,
+            "{\"type\":\"delta\",\"born\":[[3,4],[4,2],[4,3],[4,4]],\
+             \"died\":[[1,2],[3,1],[3,2],[3,3]],\
+             \"generation\":4,\"population\":5,\"running\":false}"
+        );
+        send(&mut stream, "zoom in");
+        assert_eq!(
+            receive(&mut stream).1,
+            "{\"type\":\"error\",\"message\":\"can't zoom in further\"}"
+        );
+        send(&mut stream, "view 0 0 1000 1000");
+        assert_eq!(
+            receive(&mut stream).1,
+            format!(
+                "{{\"type\":\"error\",\"message\":\"views may have 1 to {MAX_BLOCKS} blocks\"}}"
+            )
+        );
+        stream
+            .write_all(&[0x80 | websocket::CLOSE, 0x80, 0, 0, 0, 0])
+            .unwrap();
+        assert_eq!(receive(&mut stream), (websocket::CLOSE, String::new()));
+    }
+
+    #[test]
+    fn test_poisoned() {
+        let universe = glider();
+        let held = universe.clone();
+        thread::spawn(move || {
+            let _held = held.lock();
+            panic!("stepping went wrong");
+        })
+        .join()
+        .unwrap_err();
+        assert!(universe.is_poisoned());
+        // Clients still get frames, and can still step it.
+        let (mut stream, _) = connect(universe.clone());
+        assert!(receive(&mut stream).1.starts_with("{\"type\":\"frame\","));
+        send(&mut stream, "step 4");
+        assert!(receive(&mut stream).1.contains("\"generation\":4,"));
+        // As does the stepping thread.
+        send(&mut stream, "run");
+        assert!(receive(&mut stream).1.contains("\"generation\":4,"));
+        let stepped = universe.clone();
+        thread::spawn(move || step(&stepped, Duration::from_millis(1)));
+        let update = receive(&mut stream).1;
+        assert!(update.contains("\"running\":true}") && !update.contains("\"generation\":4,"));
+    }
+}
@@ -0,0 +1,88 @@
+//! Just enough terminal handling for the full screen commands, going through
+//! `stty` rather than a terminal library.
+
+use std::{
+    io::{self, Read},
+    process::{Command, Stdio},
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+/// The rows and columns of the terminal, as `stty` sees them.
+pub fn size() -> (usize, usize) {
+    let size = stty(&["size"]).ok().and_then(|size| {
+        let (rows, columns) = size.trim().split_once(' ')?;
+        Some((rows.parse().ok()?, columns.parse().ok()?))
+    });
+    size.unwrap_or((24, 80))
+}
+
+/// Bytes typed at the terminal, as they're typed.
+pub fn read_keys() -> Receiver<u8> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for byte in io::stdin().lock().bytes() {
+            if byte.ok().is_none_or(|byte| tx.send(byte).is_err()) {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+/// The keys in bytes read from the terminal, with the arrow keys turned into
+/// `h`, `j`, `k` and `l` like vi's.
+pub fn keys(bytes: &[u8]) -> Vec<u8> {
+    let mut keys = vec![];
+    let mut bytes = bytes.iter().copied();
+    while let Some(byte) = bytes.next() {
+        let key = match byte {
+            // The arrow keys, as `ESC [ A` to `ESC [ D`.
+            b'\x1b' => match (bytes.next(), bytes.next()) {
+                (Some(b'['), Some(b'A')) => b'k',
+                (Some(b'['), Some(b'B')) => b'j',
+                (Some(b'['), Some(b'C')) => b'l',
+                (Some(b'['), Some(b'D')) => b'h',
+                _ => continue,
+            },
+            key => key,
+        };
+        keys.push(key);
+    }
+    keys
+}
+
+/// Keys reach the program as they're pressed rather than a line at a time,
+/// without being echoed, until this is dropped. Ctrl-C arrives as a key
+/// rather than a signal, so that the terminal can always be put back.
+pub struct RawMode {
+    /// The terminal's settings before, to go back to.
+    saved: String,
+}
+
+impl RawMode {
+    pub fn enter() -> io::Result<Self> {
+        let saved = stty(&["-g"])?.trim().to_string();
+        stty(&["-icanon", "-echo", "-isig", "min", "1"])?;
+        Ok(Self { saved })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+/// Runs `stty` on the terminal that stdin reads from.
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other("stdin isn't a terminal"));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
@@ -0,0 +1,210 @@
+//! Reads the part of TOML that job files need: `key = value` lines, where
+//! values are strings, numbers, booleans or arrays of them on one line,
+//! grouped into `[[table]]`s.
+
+use std::collections::BTreeMap;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+    Array(Vec<Value>),
+}
+
+pub type Table = BTreeMap<String, Value>;
+
+/// A TOML document, as the keys before any table and the tables of each
+/// name in order.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Document {
+    pub top: Table,
+    pub arrays: BTreeMap<String, Vec<Table>>,
+}
+
+pub fn parse(text: &str) -> Result<Document, String> {
+    let mut document = Document::default();
+    // The array of tables being added to, if any.
+    let mut current: Option<String> = None;
+    for (i, line) in text.lines().enumerate() {
+        let error = |e: &str| format!("line {}: {e}", i + 1);
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix("[[").and_then(|l| l.strip_suffix("]]")) {
+            let name = name.trim().to_string();
+            document
+                .arrays
+                .entry(name.clone())
+                .or_default()
+                .push(Table::new());
+            current = Some(name);
+            continue;
+        }
+        if line.starts_with('[') {
+            return Err(error("only [[arrays]] of tables are supported"));
+        }
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| error("expected key = value"))?;
+        let key = key.trim().trim_matches('"').to_string();
+        let mut rest = value.trim();
+        let value = parse_value(&mut rest).map_err(|e| error(&e))?;
+        if !rest.trim().is_empty() {
+            return Err(error(&format!("unexpected {:?}", rest.trim())));
+        }
+        let table = match &current {
+            Some(name) => document.arrays.get_mut(name).unwrap().last_mut().unwrap(),
+            None => &mut document.top,
+        };
+        if table.insert(key.clone(), value).is_some() {
+            return Err(error(&format!("{key} given twice")));
+        }
+    }
+    Ok(document)
+}
+
+/// The line before any `#` that isn't in a string.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') => escaped = !escaped,
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') => return &line[..i],
+            _ => escaped = false,
+        }
+    }
+    line
+}
+
+/// Parses the value at the start of `s`, leaving `s` after it.
+fn parse_value(s: &mut &str) -> Result<Value, String> {
+    let text = *s;
+    if let Some(rest) = text.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = rest.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    *s = &rest[i + 1..];
+                    return Ok(Value::String(value));
+                }
+                '\\' => value.push(match chars.next().map(|(_, c)| c) {
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some(c @ ('"' | '\\')) => c,
+                    c => return Err(format!("unknown escape {c:?}")),
+                }),
+                c => value.push(c),
+            }
+        }
+        return Err("unterminated string".to_string());
+    }
+    if let Some(rest) = text.strip_prefix('\'') {
+        let (value, rest) = rest.split_once('\'').ok_or("unterminated string")?;
+        *s = rest;
+        return Ok(Value::String(value.to_string()));
+    }
+    if let Some(rest) = text.strip_prefix('[') {
+        let mut values = vec![];
+        *s = rest.trim_start();
+        loop {
+            if let Some(rest) = s.strip_prefix(']') {
+                *s = rest;
+                return Ok(Value::Array(values));
+            }
+            values.push(parse_value(s)?);
+            *s = s.trim_start();
+            match s.strip_prefix(',') {
+                Some(rest) => *s = rest.trim_start(),
+                None if s.starts_with(']') => {}
+                None => return Err("expected , or ] in array".to_string()),
+            }
+        }
+    }
+    let end = text.find([',', ']']).unwrap_or(text.len());
+    let (word, rest) = text.split_at(end);
+    *s = rest;
+    let word = word.trim();
+    let number = word.replace('_', "");
+    match word {
+        "true" => Ok(Value::Bool(true)),
+        "false" => Ok(Value::Bool(false)),
+        _ => match number.parse() {
+            Ok(integer) => Ok(Value::Integer(integer)),
+            Err(_) => number
+                .parse()
+                .map(Value::Float)
+                .map_err(|_| format!("bad value {word:?}")),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let document = parse(
+            r#"
+            # Jobs to run.
+            dir = "out" # where outputs go
+            [[job]]
+            name = "gun # 1"
+            steps = 1_000
+            png_size = [640, 480]
+            [[job]]
+            name = 'a\b'
+            every = 2.5e3
+            census = true
+            nested = [["a", -1], [], [false]]
+            "#,
+        )
+        .unwrap();
+        let string = |s: &str| Value::String(s.to_string());
+        assert_eq!(
+            document.top,
+            Table::from([("dir".to_string(), string("out"))])
+        );
+        let jobs = &document.arrays["job"];
+        assert_eq!(jobs.len(), 2);
+        assert_eq!(jobs[0]["name"], string("gun # 1"));
+        assert_eq!(jobs[0]["steps"], Value::Integer(1000));
+        let size = [Value::Integer(640), Value::Integer(480)];
+        assert_eq!(jobs[0]["png_size"], Value::Array(size.to_vec()));
+        assert_eq!(jobs[1]["name"], string("a\\b"));
+        assert_eq!(jobs[1]["every"], Value::Float(2500.));
+        assert_eq!(jobs[1]["census"], Value::Bool(true));
+        let nested = Value::Array(vec![
+            Value::Array(vec![string("a"), Value::Integer(-1)]),
+            Value::Array(vec![]),
+            Value::Array(vec![Value::Bool(false)]),
+        ]);
+        assert_eq!(jobs[1]["nested"], nested);
+        let escaped = parse(r#"s = "a\"b\\c\nd""#).unwrap();
+        assert_eq!(escaped.top["s"], string("a\"b\\c\nd"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |text: &str| parse(text).unwrap_err();
+        assert_eq!(error("a = 1\nb"), "line 2: expected key = value");
+        assert_eq!(error("a = 1\na = 2"), "line 2: a given twice");
+        assert_eq!(
+            error("[table]"),
+            "line 1: only [[arrays]] of tables are supported"
+        );
+        assert_eq!(error("a = \"open"), "line 1: unterminated string");
+        assert_eq!(error("a = \"\\q\""), "line 1: unknown escape Some('q')");
+        assert_eq!(error("a = [1 2]"), "line 1: bad value \"1 2\"");
+        assert_eq!(error("a = [1, 2"), "line 1: expected , or ] in array");
+        assert_eq!(error("a = 1]"), "line 1: unexpected \"]\"");
+        assert_eq!(error("a = yes"), "line 1: bad value \"yes\"");
+    }
+}
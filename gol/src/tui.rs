@@ -0,0 +1,220 @@
+use std::{
+    io::{self, Write},
+    thread,
+    time::{Duration, Instant},
+};
+
+use hashlife::{
+    BasicState, BitLife, HashLife, Hybrid, LifeEngine, Rule,
+    formats::Pattern,
+    render::terminal::{self, Glyphs},
+};
+
+use crate::term::{self, RawMode};
+
+const HELP: &str = "arrows/hjkl move  HJKL jump  enter toggle  space run  n step  \
+                    +/- speed  z/x zoom  c centre  w save  q quit";
+
+/// How often the screen is redrawn, and the pattern stepped while running.
+const FRAME: Duration = Duration::from_millis(33);
+
+/// From the most cells to a character to the fewest.
+const ZOOMS: [Glyphs; 3] = [Glyphs::Braille, Glyphs::HalfBlocks, Glyphs::Blocks];
+
+/// The engines `gol tui --engine` can run patterns on.
+pub const ENGINES: [&str; 4] = ["hashlife", "hybrid", "bitlife", "basic"];
+
+/// Shows a pattern full screen for editing and running, stepping it with the
+/// engine named `engine`. Edits are saved back to `path` with `w`.
+pub fn tui(pattern: Pattern, engine: &str, path: Option<&str>) -> Result<(), String> {
+    let Pattern { life, meta, .. } = pattern;
+    let rule = life.rule().clone();
+    let centre = life.bounding_box().map_or((0, 0), |(ys, xs)| {
+        ((ys.start + ys.end) / 2, (xs.start + xs.end) / 2)
+    });
+    let cells = || life.iter_cells();
+    let engine: Box<dyn LifeEngine> = match engine {
+        "hashlife" => Box::new(life.clone()),
+        "hybrid" => Box::new(Hybrid::new(life.clone())),
+        _ if rule != Rule::life() => {
+            return Err(format!("{engine} only runs B3/S23, not {rule}"));
+        }
+        "bitlife" => Box::new(cells().collect::<BitLife>()),
+        "basic" => Box::new(cells().collect::<BasicState>()),
+        _ => return Err(format!("unknown engine {engine:?}")),
+    };
+    let _raw = RawMode::enter().map_err(|e| format!("can't read keys: {e}"))?;
+    let keys = term::read_keys();
+    let mut screen = io::stdout().lock();
+    write!(screen, "\x1b[?1049h\x1b[?25l").map_err(|e| e.to_string())?;
+    let mut tui = Tui {
+        engine,
+        generation: 0,
+        cursor: centre,
+        top_left: (0, 0),
+        zoom: 1,
+        log2_steps: 0,
+        running: false,
+        message: None,
+    };
+    // A line is kept for the status.
+    let screen_size = || {
+        let (rows, columns) = term::size();
+        (rows.saturating_sub(1).max(1), columns.max(1))
+    };
+    tui.follow_cursor(screen_size(), true);
+    let mut result = Ok(());
+    loop {
+        let started = Instant::now();
+        let screen_size = screen_size();
+        tui.follow_cursor(screen_size, false);
+        let drawn =
+            write!(screen, "\x1b[H{}\x1b[J", tui.draw(screen_size)).and_then(|()| screen.flush());
+        if let Err(e) = drawn {
+            result = Err(e.to_string());
+            break;
+        }
+        let pressed: Vec<u8> = keys.try_iter().collect();
+        let mut quit = false;
+        for key in term::keys(&pressed) {
+            match key {
+                // `q`, or Ctrl-C, since the terminal no longer sends signals.
+                b'q' | b'\x03' => quit = true,
+                b'w' => {
+                    tui.message = Some(match path {
+                        Some(path) => {
+                            let mut life = HashLife::with_rule(rule.clone());
+                            life.set_cells(tui.engine.iter_cells());
+                            match crate::save(&life, &meta, path, None) {
+                                Ok(()) => format!("saved {path}"),
+                                Err(e) => e,
+                            }
+                        }
+                        None => "no file to save to".to_string(),
+                    })
+                }
+                key => tui.press(key, screen_size),
+            }
+        }
+        if quit {
+            break;
+        }
+        if tui.running {
+            tui.step();
+        }
+        thread::sleep(FRAME.saturating_sub(started.elapsed()));
+    }
+    let restored = write!(screen, "\x1b[?25h\x1b[?1049l").and_then(|()| screen.flush());
+    result.and(restored.map_err(|e| e.to_string()))
+}
+
+struct Tui {
+    engine: Box<dyn LifeEngine>,
+    /// How many generations the engine has been stepped here.
+    generation: u64,
+    cursor: (isize, isize),
+    /// The cell drawn in the top left corner of the screen.
+    top_left: (isize, isize),
+    /// Which of `ZOOMS` to draw with.
+    zoom: usize,
+    /// How many generations to step at a time, as a power of two.
+    log2_steps: usize,
+    running: bool,
+    /// Shown in the status line until the next key.
+    message: Option<String>,
+}
+
+impl Tui {
+    fn glyphs(&self) -> Glyphs {
+        ZOOMS[self.zoom]
+    }
+
+    /// How many cells tall and wide the screen shows.
+    fn cells(&self, (rows, columns): (usize, usize)) -> (isize, isize) {
+        let (dy, dx) = self.glyphs().dots();
+        ((rows * dy) as isize, (columns * dx) as isize)
+    }
+
+    fn step(&mut self) {
+        self.engine.step_by(1 << self.log2_steps);
+        self.generation += 1 << self.log2_steps;
+    }
+
+    fn press(&mut self, key: u8, screen_size: (usize, usize)) {
+        self.message = None;
+        let (y, x) = self.cursor;
+        match key {
+            b'h' => self.cursor = (y, x - 1),
+            b'j' => self.cursor = (y + 1, x),
+            b'k' => self.cursor = (y - 1, x),
+            b'l' => self.cursor = (y, x + 1),
+            b'H' => self.cursor = (y, x - 8),
+            b'J' => self.cursor = (y + 8, x),
+            b'K' => self.cursor = (y - 8, x),
+            b'L' => self.cursor = (y, x + 8),
+            b'\r' | b'\n' => {
+                let alive = self.engine.get(self.cursor);
+                self.engine.set(self.cursor, !alive);
+            }
+            b' ' => self.running = !self.running,
+            b'n' => self.step(),
+            b'+' | b'=' => self.log2_steps = (self.log2_steps + 1).min(62),
+            b'-' | b'_' => self.log2_steps = self.log2_steps.saturating_sub(1),
+            b'z' => self.zoom = (self.zoom + 1).min(ZOOMS.len() - 1),
+            b'x' => self.zoom = self.zoom.saturating_sub(1),
+            b'c' => self.follow_cursor(screen_size, true),
+            _ => {}
+        }
+        self.follow_cursor(screen_size, false);
+    }
+
+    /// Scrolls the screen just enough to show the cursor, or to put it in
+    /// the middle if `centre`.
+    fn follow_cursor(&mut self, screen_size: (usize, usize), centre: bool) {
+        let (height, width) = self.cells(screen_size);
+        let (y, x) = self.cursor;
+        let (top, left) = &mut self.top_left;
+        if centre {
+            (*top, *left) = (y - height / 2, x - width / 2);
+        }
+        *top = (*top).clamp(y - height + 1, y);
+        *left = (*left).clamp(x - width + 1, x);
+    }
+
+    /// The screen's lines, with the character over the cursor in reverse
+    /// video, then the status line.
+    fn draw(&self, (rows, columns): (usize, usize)) -> String {
+        let glyphs = self.glyphs();
+        let text = terminal::render_engine(&*self.engine, self.top_left, rows, columns, glyphs);
+        let (dy, dx) = glyphs.dots();
+        let cursor_row = (self.cursor.0 - self.top_left.0) as usize / dy;
+        let cursor_column = (self.cursor.1 - self.top_left.1) as usize / dx;
+        let mut out = String::new();
+        for (i, line) in text.lines().enumerate() {
+            for (j, c) in line.chars().enumerate() {
+                match (i, j) == (cursor_row, cursor_column) {
+                    true => out.push_str(&format!("\x1b[7m{c}\x1b[0m")),
+                    false => out.push(c),
+                }
+            }
+            out.push('\n');
+        }
+        let state = match self.running {
+            true => format!("running 2^{}", self.log2_steps),
+            false => format!("paused, steps 2^{}", self.log2_steps),
+        };
+        let nodes = match self.engine.node_count() {
+            Some(nodes) => format!("  nodes {nodes}"),
+            None => String::new(),
+        };
+        let (y, x) = self.cursor;
+        let status = format!(
+            "gen {}  pop {}{nodes}  ({y}, {x})  {state}  {}",
+            self.generation,
+            self.engine.population(),
+            self.message.as_deref().unwrap_or(HELP),
+        );
+        out.extend(status.chars().take(columns));
+        out
+    }
+}
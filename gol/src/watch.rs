@@ -0,0 +1,142 @@
+use std::{
+    io::{self, Write},
+    thread,
+    time::{Duration, Instant},
+};
+
+use hashlife::{
+    HashLife,
+    render::{
+        Settings, View,
+        terminal::{self, Glyphs, Style},
+    },
+};
+
+use crate::term::{self, RawMode};
+
+const HELP: &str = "space pause  +/- speed  arrows/hjkl pan  z/x zoom  f fit  q quit";
+
+/// How far a key press pans the view, as a fraction of the screen.
+const PAN: f64 = 0.125;
+
+/// Animates `life` in the terminal at `gps` frames a second until `q` is
+/// pressed, stepping a power of two generations each frame.
+pub fn watch(mut life: HashLife, gps: f64, glyphs: Glyphs) -> Result<(), String> {
+    let _raw = RawMode::enter().map_err(|e| format!("can't read keys: {e}"))?;
+    let keys = term::read_keys();
+    let mut screen = io::stdout().lock();
+    // Draw on the alternate screen, without the cursor.
+    write!(screen, "\x1b[?1049h\x1b[?25l").map_err(|e| e.to_string())?;
+    let mut watch = Watch {
+        view: None,
+        log2_steps: 0,
+        paused: false,
+        error: None,
+    };
+    let frame = Duration::from_secs_f64(1. / gps);
+    let mut result = Ok(());
+    loop {
+        let started = Instant::now();
+        let (rows, columns) = term::size();
+        let style = Style {
+            glyphs,
+            truecolor: false,
+        };
+        // A line is kept for the status, and the view is measured in dots.
+        let settings = crate::settings(&life, rows.saturating_sub(1).max(1), columns.max(1));
+        let (dy, dx) = glyphs.dots();
+        let dots = Settings {
+            height: settings.height * dy,
+            width: settings.width * dx,
+            ..settings
+        };
+        let view = *watch.view.get_or_insert_with(|| fit(&life, dots));
+        let text = terminal::render(&life, settings, view, style);
+        let status = watch.status(&life);
+        let status: String = status.chars().take(settings.width).collect();
+        let drawn = write!(screen, "\x1b[H{text}\n{status}\x1b[J").and_then(|()| screen.flush());
+        if let Err(e) = drawn {
+            result = Err(e.to_string());
+            break;
+        }
+        let pressed: Vec<u8> = keys.try_iter().collect();
+        if !watch.press(&pressed, &life, dots) {
+            break;
+        }
+        if !watch.paused
+            && let Err(e) = life.step_checked(watch.log2_steps)
+        {
+            watch.error = Some(e.to_string());
+            watch.paused = true;
+        }
+        thread::sleep(frame.saturating_sub(started.elapsed()));
+    }
+    let restored = write!(screen, "\x1b[?25h\x1b[?1049l").and_then(|()| screen.flush());
+    result.and(restored.map_err(|e| e.to_string()))
+}
+
+/// What the keys have changed.
+struct Watch {
+    /// Fitted to the pattern on the first frame, and when `f` is pressed.
+    view: Option<View>,
+    /// How many generations to step each frame, as a power of two.
+    log2_steps: usize,
+    paused: bool,
+    /// Why stepping stopped, if it did.
+    error: Option<String>,
+}
+
+impl Watch {
+    /// Acts on the bytes read since the last frame. Returns whether to carry
+    /// on watching.
+    fn press(&mut self, pressed: &[u8], life: &HashLife, dots: Settings) -> bool {
+        for key in term::keys(pressed) {
+            let Some(view) = &mut self.view else {
+                continue;
+            };
+            let pan = |dy: f64, dx: f64| {
+                let (h, w) = (dots.height as f64, dots.width as f64);
+                let p = (h / 2. + dy * h * PAN, w / 2. + dx * w * PAN);
+                View {
+                    center: view.screen_to_cell(p, dots),
+                    ..*view
+                }
+            };
+            match key {
+                // `q`, or Ctrl-C, since the terminal no longer sends signals.
+                b'q' | b'\x03' => return false,
+                b' ' => self.paused = !self.paused,
+                b'+' | b'=' => self.log2_steps = (self.log2_steps + 1).min(62),
+                b'-' | b'_' => self.log2_steps = self.log2_steps.saturating_sub(1),
+                b'k' | b'w' => *view = pan(-1., 0.),
+                b'j' | b's' => *view = pan(1., 0.),
+                b'l' | b'd' => *view = pan(0., 1.),
+                b'h' | b'a' => *view = pan(0., -1.),
+                b'z' => view.zoom *= 2.,
+                b'x' => view.zoom /= 2.,
+                b'f' => *view = fit(life, dots),
+                _ => {}
+            }
+        }
+        true
+    }
+
+    fn status(&self, life: &HashLife) -> String {
+        let state = match (&self.error, self.paused) {
+            (Some(error), _) => format!("stopped: {error}"),
+            (None, true) => "paused".to_string(),
+            (None, false) => format!("2^{}/frame", self.log2_steps),
+        };
+        format!(
+            "gen {}  pop {}  {state}  {HELP}",
+            life.generation(),
+            life.population()
+        )
+    }
+}
+
+/// The whole pattern, or around the origin if it's empty.
+fn fit(life: &HashLife, dots: Settings) -> View {
+    let bounds = life.bounding_box().unwrap_or((-8..8, -8..8));
+    View::fit(bounds, dots)
+}
@@ -0,0 +1,306 @@
+//! The server's side of WebSockets (RFC 6455), on a connection whose upgrade
+//! request has been read.
+
+use std::io::{self, Read, Write};
+
+/// Appended to the client's key to make the accepting key.
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// The longest message that's read.
+const MAX_MESSAGE: usize = 1 << 20;
+
+const CONTINUATION: u8 = 0x0;
+pub const TEXT: u8 = 0x1;
+const BINARY: u8 = 0x2;
+pub const CLOSE: u8 = 0x8;
+const PING: u8 = 0x9;
+pub const PONG: u8 = 0xa;
+
+/// Accepts an upgrade request that sent `key` as its Sec-WebSocket-Key.
+pub fn accept(w: &mut impl Write, key: &str) -> io::Result<()> {
+    let accept = base64(&sha1(format!("{key}{GUID}").as_bytes()));
+    write!(
+        w,
+        "HTTP/1.1 101 Switching Protocols\r\n\
+         Upgrade: websocket\r\n\
+         Connection: Upgrade\r\n\
+         Sec-WebSocket-Accept: {accept}\r\n\r\n"
+    )?;
+    w.flush()
+}
+
+pub enum Message {
+    Text(String),
+    /// Binary messages, which nothing here sends, so their bytes are dropped.
+    Binary,
+    /// To be answered with a pong carrying the same bytes.
+    Ping(Vec<u8>),
+    Close,
+}
+
+/// Reads messages from a client, joining fragmented ones.
+pub struct Reader<R> {
+    inner: R,
+    /// The opcode and bytes so far of a fragmented message.
+    partial: Option<(u8, Vec<u8>)>,
+}
+
+impl<R: Read> Reader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            partial: None,
+        }
+    }
+
+    pub fn read(&mut self) -> io::Result<Message> {
+        loop {
+            let mut head = [0; 2];
+            self.inner.read_exact(&mut head)?;
+            let fin = head[0] & 0x80 != 0;
+            let opcode = head[0] & 0x0f;
+            let len = match head[1] & 0x7f {
+                126 => {
+                    let mut len = [0; 2];
+                    self.inner.read_exact(&mut len)?;
+                    u16::from_be_bytes(len) as u64
+                }
+                127 => {
+                    let mut len = [0; 8];
+                    self.inner.read_exact(&mut len)?;
+                    u64::from_be_bytes(len)
+                }
+                len => len as u64,
+            };
+            let so_far = self.partial.as_ref().map_or(0, |(_, bytes)| bytes.len());
+            if len > (MAX_MESSAGE - so_far) as u64 {
+                return Err(invalid("message too long"));
+            }
+            // Clients always mask what they send.
+            if head[1] & 0x80 == 0 {
+                return Err(invalid("unmasked frame"));
+            }
+            let mut mask = [0; 4];
+            self.inner.read_exact(&mut mask)?;
+            let mut payload = vec![0; len as usize];
+            self.inner.read_exact(&mut payload)?;
+            for (i, byte) in payload.iter_mut().enumerate() {
+                *byte ^= mask[i % 4];
+            }
+            let (opcode, payload) = match (opcode, self.partial.take()) {
+                (CLOSE, _) => return Ok(Message::Close),
+                (PING, partial) => {
+                    self.partial = partial;
+                    return Ok(Message::Ping(payload));
+                }
+                (PONG, partial) => {
+                    self.partial = partial;
+                    continue;
+                }
+                (CONTINUATION, Some((opcode, mut bytes))) => {
+                    bytes.extend(payload);
+                    (opcode, bytes)
+                }
+                (TEXT | BINARY, None) => (opcode, payload),
+                _ => return Err(invalid("unexpected frame")),
+            };
+            if !fin {
+                self.partial = Some((opcode, payload));
+                continue;
+            }
+            return match opcode {
+                TEXT => String::from_utf8(payload)
+                    .map(Message::Text)
+                    .map_err(|_| invalid("text isn't UTF-8")),
+                _ => Ok(Message::Binary),
+            };
+        }
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Writes `payload` as a single unmasked frame.
+pub fn write(w: &mut impl Write, opcode: u8, payload: &[u8]) -> io::Result<()> {
+    let mut head = vec![0x80 | opcode];
+    match payload.len() {
+        len @ ..126 => head.push(len as u8),
+        len @ ..0x10000 => {
+            head.push(126);
+            head.extend((len as u16).to_be_bytes());
+        }
+        len => {
+            head.push(127);
+            head.extend((len as u64).to_be_bytes());
+        }
+    }
+    w.write_all(&head)?;
+    w.write_all(payload)?;
+    w.flush()
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0];
+    let mut padded = data.to_vec();
+    padded.push(0x80);
+    // Padded to 8 bytes short of a block, for the length in bits.
+    padded.resize((padded.len() + 8).next_multiple_of(64) - 8, 0);
+    padded.extend((data.len() as u64 * 8).to_be_bytes());
+    for block in padded.chunks(64) {
+        let mut w = [0u32; 80];
+        for (w, word) in w.iter_mut().zip(block.chunks(4)) {
+            *w = u32::from_be_bytes(word.try_into().unwrap());
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, w) in w.into_iter().enumerate() {
+            let (f, k) = match i {
+                0..20 => ((b & c) | (!b & d), 0x5a827999),
+                20..40 => (b ^ c ^ d, 0x6ed9eba1),
+                40..60 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+                _ => (b ^ c ^ d, 0xca62c1d6),
+            };
+            let t = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(w);
+            (a, b, c, d, e) = (t, a, b.rotate_left(30), c, d);
+        }
+        for (h, v) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(v);
+        }
+    }
+    let mut digest = [0; 20];
+    for (bytes, h) in digest.chunks_mut(4).zip(h) {
+        bytes.copy_from_slice(&h.to_be_bytes());
+    }
+    digest
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const DIGITS: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let mut group = [0; 3];
+        group[..chunk.len()].copy_from_slice(chunk);
+        let n = u32::from_be_bytes([0, group[0], group[1], group[2]]);
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => encoded.push(DIGITS[(n >> (18 - 6 * i) & 63) as usize] as char),
+                false => encoded.push('='),
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A frame as a client sends it, masked.
+    fn frame(fin: bool, opcode: u8, payload: &[u8]) -> Vec<u8> {
+        let mask = [0x37, 0xfa, 0x21, 0x3d];
+        let mut frame = vec![(fin as u8) << 7 | opcode];
+        match payload.len() {
+            len @ ..126 => frame.push(0x80 | len as u8),
+            len => {
+                frame.push(0x80 | 126);
+                frame.extend((len as u16).to_be_bytes());
+            }
+        }
+        frame.extend(mask);
+        frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+        frame
+    }
+
+    #[test]
+    fn test_sha1() {
+        let hex = |digest: [u8; 20]| digest.map(|b| format!("{b:02x}")).concat();
+        assert_eq!(hex(sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(
+            hex(sha1(b"abc")),
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+        // Long enough to need a second block just for the padding.
+        let two_blocks = b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq";
+        assert_eq!(
+            hex(sha1(two_blocks)),
+            "84983e441c3bd26ebaae4aa1f95129e5e54670f1"
+        );
+    }
+
+    #[test]
+    fn test_base64() {
+        let cases = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for (bytes, encoded) in cases {
+            assert_eq!(base64(bytes.as_bytes()), encoded);
+        }
+    }
+
+    #[test]
+    fn test_accept() {
+        // The example in RFC 6455, section 1.3.
+        let mut response = vec![];
+        accept(&mut response, "dGhlIHNhbXBsZSBub25jZQ==").unwrap();
+        let response = String::from_utf8(response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 101 "));
+        assert!(response.contains("\r\nSec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+    }
+
+    #[test]
+    fn test_read() {
+        let long = "x".repeat(300);
+        let frames = [
+            frame(true, TEXT, b"step 1"),
+            frame(false, TEXT, b"view 0 "),
+            frame(true, PING, b"hi"),
+            frame(true, PONG, b""),
+            frame(true, CONTINUATION, long.as_bytes()),
+            frame(true, BINARY, &[1, 2, 3]),
+            frame(true, CLOSE, &[]),
+        ]
+        .concat();
+        let mut reader = Reader::new(&frames[..]);
+        assert!(matches!(reader.read().unwrap(), Message::Text(text) if text == "step 1"));
+        assert!(matches!(reader.read().unwrap(), Message::Ping(bytes) if bytes == b"hi"));
+        let joined = format!("view 0 {long}");
+        assert!(matches!(reader.read().unwrap(), Message::Text(text) if text == joined));
+        assert!(matches!(reader.read().unwrap(), Message::Binary));
+        assert!(matches!(reader.read().unwrap(), Message::Close));
+        assert!(reader.read().is_err());
+        // Clients must mask, and continue only what they started.
+        let mut unmasked = frame(true, TEXT, b"a");
+        unmasked[1] &= 0x7f;
+        assert!(Reader::new(&unmasked[..]).read().is_err());
+        let stray = frame(true, CONTINUATION, b"a");
+        assert!(Reader::new(&stray[..]).read().is_err());
+    }
+
+    #[test]
+    fn test_write() {
+        // Lengths, and how long the head is with them in it.
+        for (len, head) in [(0, 2), (125, 2), (126, 4), (0xffff, 4), (0x10000, 10)] {
+            let payload = vec![7; len];
+            let mut written = vec![];
+            write(&mut written, TEXT, &payload).unwrap();
+            assert_eq!(written.len(), head + len);
+            assert_eq!(written[0], 0x80 | TEXT);
+            assert_eq!(written[head..], payload);
+        }
+    }
+}
@@ -0,0 +1,59 @@
+/* The C API of the hashlife crate, built with `cargo build --features ffi`.
+ * Written by hand, so change it along with src/ffi.rs. */
+
+#ifndef HASHLIFE_H
+#define HASHLIFE_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+/* A pattern and the generation it's at. Functions given a null handle do
+ * nothing, and those that panic return false, NULL or 0 rather than unwind. */
+typedef struct HashLife HashLife;
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* An empty pattern under B3/S23. */
+HashLife *hl_new(void);
+
+/* Reads a pattern from NUL-terminated text in any format the crate reads,
+ * RLE included. Returns NULL if it can't be read. */
+HashLife *hl_from_rle(const char *text);
+
+void hl_free(HashLife *hl);
+
+/* Returns false if the cell couldn't be brought alive, being too far from
+ * the origin for the pattern to reach or there being no room for it. Cells
+ * that far out are always dead, so killing one does nothing. */
+bool hl_set_cell(HashLife *hl, intptr_t y, intptr_t x, bool alive);
+
+bool hl_get_cell(const HashLife *hl, intptr_t y, intptr_t x);
+
+/* Steps `steps` generations. Returns false, having stepped only some of
+ * them, if the pattern grew too big or the generation would overflow. */
+bool hl_step(HashLife *hl, uint64_t steps);
+
+uint64_t hl_population(const HashLife *hl);
+
+uint64_t hl_generation(const HashLife *hl);
+
+/* Draws black cells on white into `pixels`, `height` rows of `width` pixels
+ * with four bytes of sRGB and alpha to each. `zoom` is in pixels per cell,
+ * and (`center_y`, `center_x`) the cell drawn in the middle. Returns false
+ * if nothing was drawn. */
+bool hl_render_rgba(const HashLife *hl,
+                    uint8_t *pixels,
+                    size_t height,
+                    size_t width,
+                    double center_y,
+                    double center_x,
+                    double zoom);
+
+#ifdef __cplusplus
+} /* extern "C" */
+#endif
+
+#endif /* HASHLIFE_H */
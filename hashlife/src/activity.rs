@@ -0,0 +1,90 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::{HashLife, fx::FxHashMap, universe::TreeRef};
+
+/// The cells that changed in each of the last few steps, found by comparing
+/// the root before each step with the one after.
+#[derive(Clone, Debug)]
+pub(crate) struct Activity {
+    window: usize,
+    before: (TreeRef, usize),
+    /// Oldest first.
+    changes: VecDeque<Vec<(isize, isize)>>,
+}
+
+impl Activity {
+    /// The root the next step is compared against.
+    pub(crate) fn root(&self) -> TreeRef {
+        self.before.0
+    }
+
+    /// Follows the nodes renumbered by garbage collection.
+    pub(crate) fn relocate(&mut self, moved: &FxHashMap<TreeRef, TreeRef>) {
+        self.before.0 = moved[&self.before.0];
+    }
+}
+
+impl HashLife {
+    /// Starts keeping track of which cells were born or died in each of the
+    /// last `window` steps, for `activity` and the render heatmap, or stops
+    /// if `window` is 0. Changes are counted against the background, so the
+    /// background flashing under B0 rules isn't activity.
+    pub fn track_activity(&mut self, window: usize) {
+        self.activity = (window > 0).then(|| Activity {
+            window,
+            before: (self.root, self.depth),
+            changes: VecDeque::new(),
+        });
+    }
+
+    pub fn is_tracking_activity(&self) -> bool {
+        self.activity.is_some()
+    }
+
+    /// In how many of the last steps the cell at `p` changed, if activity is
+    /// being tracked.
+    pub fn activity(&self, p: (isize, isize)) -> Option<usize> {
+        let activity = self.activity.as_ref()?;
+        let p = self.topology.wrap(p);
+        Some(
+            activity
+                .changes
+                .iter()
+                .filter(|cells| cells.contains(&p))
+                .count(),
+        )
+    }
+
+    /// The cells that changed in the last steps, and the fraction of the
+    /// tracked steps they changed in.
+    pub(crate) fn heat(&self) -> HashMap<(isize, isize), f32> {
+        let mut heat = HashMap::new();
+        if let Some(activity) = &self.activity {
+            for &p in activity.changes.iter().flatten() {
+                *heat.entry(p).or_default() += 1. / activity.window as f32;
+            }
+        }
+        heat
+    }
+
+    /// Records the cells that changed in the step just taken.
+    pub(crate) fn update_activity(&mut self) {
+        let Some(mut activity) = self.activity.take() else {
+            return;
+        };
+        let changed = self.changed_since(activity.before);
+        activity.before = (self.root, self.depth);
+        activity.changes.push_back(changed);
+        if activity.changes.len() > activity.window {
+            activity.changes.pop_front();
+        }
+        self.activity = Some(activity);
+    }
+
+    /// The cells whose stored state differs from that in an earlier root at
+    /// the given depth.
+    fn changed_since(&mut self, before: (TreeRef, usize)) -> Vec<(isize, isize)> {
+        let differences = self.differences(before, (self.root, self.depth));
+        differences.into_iter().map(|(p, _, _)| p).collect()
+    }
+}
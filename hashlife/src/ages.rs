@@ -0,0 +1,55 @@
+use std::collections::HashMap;
+
+use crate::HashLife;
+
+/// The generation each stored cell was born in, kept up to date by stepping.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Ages {
+    born: HashMap<(isize, isize), u64>,
+}
+
+impl HashLife {
+    /// Starts or stops keeping track of when each cell was born, for `age` and
+    /// colouring by age when rendering. Tracking costs a walk over every cell
+    /// after each step. Cells already alive count as born now.
+    pub fn track_ages(&mut self, track: bool) {
+        self.ages = track.then(Ages::default);
+        self.update_ages();
+    }
+
+    pub fn is_tracking_ages(&self) -> bool {
+        self.ages.is_some()
+    }
+
+    /// How many generations ago the cell at `p` was born, if ages are being
+    /// tracked and it's alive. Cells are dated after each step, so with steps
+    /// of more than one generation those born part way through count as born
+    /// at the end, and cells set by hand count as born now.
+    pub fn age(&self, p: (isize, isize)) -> Option<u64> {
+        let ages = self.ages.as_ref()?;
+        if !self.get(p) {
+            return None;
+        }
+        let p = self.topology.wrap(p);
+        let born = ages.born.get(&p).copied().unwrap_or(self.generation);
+        Some(self.generation - born)
+    }
+
+    /// The cells that aren't dead and the generation each was born in.
+    pub(crate) fn births(&self) -> impl Iterator<Item = ((isize, isize), u64)> + '_ {
+        let born = self.ages.as_ref().map(|ages| &ages.born);
+        self.iter_cells().map(move |p| {
+            let born = born.and_then(|born| born.get(&p)).copied();
+            (p, born.unwrap_or(self.generation))
+        })
+    }
+
+    /// Dates the cells born since the last update and forgets those that died.
+    pub(crate) fn update_ages(&mut self) {
+        if self.ages.is_none() {
+            return;
+        }
+        let born = self.births().collect();
+        self.ages = Some(Ages { born });
+    }
+}
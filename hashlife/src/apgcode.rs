@@ -0,0 +1,157 @@
+use std::collections::HashSet;
+
+use crate::{HashLife, Periodic};
+
+/// The digits of the extended Wechsler format, where each of the first 32 is
+/// a column of five cells, least significant bit at the top.
+const DIGITS: &[u8; 36] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+/// How many generations `apgcode` looks for a period in.
+const MAX_PERIOD: u64 = 1 << 10;
+
+impl HashLife {
+    /// The apgcode Catagolue names the object by: `xs` and the population for
+    /// still lifes, `xp` or `xq` and the period for oscillators and
+    /// spaceships, then `_` and the cells in the extended Wechsler format, in
+    /// whichever phase and orientation gives the shortest code, and then the
+    /// first in ASCII order. Nothing for empty patterns, or ones that don't
+    /// repeat from the start within 1024 generations.
+    pub fn apgcode(&self) -> Option<String> {
+        if self.is_empty() {
+            return None;
+        }
+        let Periodic {
+            period,
+            displacement,
+        } = self.detect_period(MAX_PERIOD)?;
+        let mut hl = self.clone();
+        hl.step_by(period);
+        if hl.clone().move_to_origin().0 != self.clone().move_to_origin().0 {
+            return None;
+        }
+        let prefix = match (period, displacement) {
+            (1, _) => format!("xs{}", self.population()),
+            (_, (0, 0)) => format!("xp{period}"),
+            _ => format!("xq{period}"),
+        };
+        let mut best: Option<String> = None;
+        for _ in 0..period {
+            let cells: Vec<_> = hl.iter_cells().collect();
+            for orientation in 0..8 {
+                let code = wechsler(&orient(&cells, orientation));
+                if best
+                    .as_ref()
+                    .is_none_or(|best| (code.len(), &code) < (best.len(), best))
+                {
+                    best = Some(code);
+                }
+            }
+            hl.step(0);
+        }
+        Some(format!("{prefix}_{}", best?))
+    }
+
+    /// The object an apgcode names, under B3/S23, in the phase and
+    /// orientation the code is for, with its bounding box at the origin.
+    pub fn from_apgcode(code: &str) -> Result<HashLife, String> {
+        let (prefix, wechsler) = code
+            .split_once('_')
+            .ok_or(format!("no _ in apgcode {code:?}"))?;
+        let number = prefix
+            .strip_prefix("xs")
+            .or(prefix.strip_prefix("xp"))
+            .or(prefix.strip_prefix("xq"));
+        if number.is_none_or(|n| n.parse::<u64>().is_err()) {
+            return Err(format!("unknown apgcode prefix {prefix:?}"));
+        }
+        let digit = |c: char| DIGITS.iter().position(|&d| d as char == c);
+        let mut cells = vec![];
+        let (mut strip, mut column) = (0, 0);
+        let mut chars = wechsler.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                'w' => column += 2,
+                'x' => column += 3,
+                'y' => {
+                    let zeros = chars.next().and_then(digit);
+                    column += 4 + zeros.ok_or(format!("bad run of zeros in {code:?}"))?;
+                }
+                'z' => (strip, column) = (strip + 1, 0),
+                c => {
+                    let bits = digit(c).filter(|&bits| bits < 32);
+                    let bits = bits.ok_or(format!("bad character {c:?} in {code:?}"))?;
+                    for row in 0..5 {
+                        if bits >> row & 1 == 1 {
+                            cells.push(((5 * strip + row) as isize, column as isize));
+                        }
+                    }
+                    column += 1;
+                }
+            }
+        }
+        Ok(HashLife::from_iter(cells))
+    }
+}
+
+/// One of the eight rotations and reflections of `cells`, moved so their
+/// bounding box starts at the origin.
+fn orient(cells: &[(isize, isize)], orientation: u8) -> Vec<(isize, isize)> {
+    let mut oriented: Vec<_> = cells
+        .iter()
+        .map(|&(y, x)| {
+            let (y, x) = if orientation & 4 != 0 { (x, y) } else { (y, x) };
+            let y = if orientation & 2 != 0 { -y } else { y };
+            let x = if orientation & 1 != 0 { -x } else { x };
+            (y, x)
+        })
+        .collect();
+    let top = oriented.iter().map(|&(y, _)| y).min().unwrap_or(0);
+    let left = oriented.iter().map(|&(_, x)| x).min().unwrap_or(0);
+    for (y, x) in &mut oriented {
+        (*y, *x) = (*y - top, *x - left);
+    }
+    oriented
+}
+
+/// Cells from the origin in the extended Wechsler format: strips five rows
+/// tall, split by `z`, each a digit to a column, with runs of empty columns
+/// shortened and those at the end of a strip left out.
+fn wechsler(cells: &[(isize, isize)]) -> String {
+    let height = cells.iter().map(|&(y, _)| y + 1).max().unwrap_or(0);
+    let width = cells.iter().map(|&(_, x)| x + 1).max().unwrap_or(0);
+    let cells: HashSet<_> = cells.iter().copied().collect();
+    let mut code = String::new();
+    for strip in 0..(height + 4) / 5 {
+        if strip > 0 {
+            code.push('z');
+        }
+        let mut zeros = 0;
+        for x in 0..width {
+            let bits: usize = (0..5)
+                .filter(|&row| cells.contains(&(5 * strip + row, x)))
+                .map(|row| 1 << row)
+                .sum();
+            if bits == 0 {
+                zeros += 1;
+                continue;
+            }
+            while zeros > 39 {
+                code.push_str("yz");
+                zeros -= 39;
+            }
+            match zeros {
+                0 => {}
+                1 => code.push('0'),
+                2 => code.push('w'),
+                3 => code.push('x'),
+                _ => {
+                    code.push('y');
+                    code.push(DIGITS[zeros - 4] as char);
+                }
+            }
+            zeros = 0;
+            code.push(DIGITS[bits] as char);
+        }
+    }
+    code
+}
@@ -7,6 +7,8 @@ use std::{
 
 use itertools::Itertools;
 
+use crate::{ParseError, Rule};
+
 #[derive(Clone, Default, Debug, PartialEq)]
 pub struct BasicState {
     pub cells: HashSet<(isize, isize)>,
@@ -17,21 +19,75 @@ impl BasicState {
         self.cells.insert(p);
     }
 
+    #[cfg(not(feature = "rayon"))]
     pub fn step(&self) -> Self {
-        let mut counts = HashMap::new();
-        for &p1 in &self.cells {
-            for p2 in neighbours(p1) {
-                *counts.entry(p2).or_default() += 1;
-            }
+        let counts = count_neighbours(&self.cells);
+        let cells = counts
+            .into_iter()
+            .filter_map(|(p, count)| self.next(p, count));
+        Self {
+            cells: cells.collect(),
         }
-        let mut cells = HashSet::new();
-        for (p, count) in counts {
-            let alive = self.cells.contains(&p);
-            if let (2 | 3, true) | (3, false) = (count, alive) {
-                cells.insert(p);
-            }
+    }
+
+    /// Counts neighbours across chunks of the cells in parallel, merges the
+    /// counts, then applies the rule to them in parallel too.
+    #[cfg(feature = "rayon")]
+    pub fn step(&self) -> Self {
+        use rayon::prelude::*;
+
+        const CHUNK: usize = 1 << 12;
+        let cells = self.cells.iter().collect_vec();
+        let counts = cells
+            .par_chunks(CHUNK)
+            .map(|chunk| count_neighbours(chunk.iter().copied()))
+            .reduce(HashMap::new, |a, b| {
+                let (mut big, small) = if a.len() < b.len() { (b, a) } else { (a, b) };
+                for (p, count) in small {
+                    *big.entry(p).or_default() += count;
+                }
+                big
+            });
+        let cells = counts
+            .into_par_iter()
+            .filter_map(|(p, count)| self.next(p, count));
+        Self {
+            cells: cells.collect(),
         }
-        Self { cells }
+    }
+
+    /// Steps a generation of any rule a cell at a time, which is slow but
+    /// simple enough to check the other engines against. A set of live cells
+    /// can only hold two state rules without B0.
+    pub fn step_rule(&self, rule: &Rule) -> Self {
+        assert!(
+            rule.states() == 2 && rule.next_cell(0, |_, _| 0) == 0,
+            "BasicState can't step {rule}"
+        );
+        let r = rule.radius() as isize;
+        let alive = |(y, x): (isize, isize)| self.cells.contains(&(y, x)) as u8;
+        let near = self.cells.iter().flat_map(|&(y, x)| {
+            (y - r..=y + r).flat_map(move |y| (x - r..=x + r).map(move |x| (y, x)))
+        });
+        let cells = near
+            .unique()
+            .filter(|&(y, x)| rule.next_cell(alive((y, x)), |dy, dx| alive((y + dy, x + dx))) == 1);
+        Self {
+            cells: cells.collect(),
+        }
+    }
+
+    /// The cell `p` if it's alive next generation under B3/S23, given how
+    /// many neighbours it has.
+    fn next(&self, p: (isize, isize), count: u8) -> Option<(isize, isize)> {
+        let alive = self.cells.contains(&p);
+        matches!((count, alive), (2 | 3, true) | (3, false)).then_some(p)
+    }
+
+    /// The cells row by row, unlike iterating over the set, whose order
+    /// changes from run to run.
+    pub fn iter_sorted(&self) -> impl ExactSizeIterator<Item = (isize, isize)> + use<> {
+        self.cells.iter().copied().sorted()
     }
 
     pub fn normalize(mut self) -> Self {
@@ -49,6 +105,18 @@ impl BasicState {
     }
 }
 
+fn count_neighbours<'a>(
+    cells: impl IntoIterator<Item = &'a (isize, isize)>,
+) -> HashMap<(isize, isize), u8> {
+    let mut counts = HashMap::new();
+    for &p1 in cells {
+        for p2 in neighbours(p1) {
+            *counts.entry(p2).or_default() += 1;
+        }
+    }
+    counts
+}
+
 fn neighbours((y, x): (isize, isize)) -> impl Iterator<Item = (isize, isize)> {
     (-1..=1)
         .cartesian_product(-1..=1)
@@ -97,18 +165,27 @@ impl FromIterator<(isize, isize)> for BasicState {
 }
 
 impl FromStr for BasicState {
-    type Err = String;
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut state = BasicState::default();
-        for (y, line) in s.lines().enumerate() {
+        // Plaintext (.cells) comment lines start with '!' and take up no rows.
+        let lines = s.lines().enumerate();
+        let rows = lines.filter(|(_, line)| !line.starts_with('!'));
+        for (y, (i, line)) in rows.enumerate() {
             for (x, c) in line.chars().enumerate() {
                 match c {
-                    ' ' => (),
-                    'o' => {
+                    ' ' | '.' => (),
+                    'o' | 'O' | '*' => {
                         state.set_bit((y as isize, x as isize));
                     }
-                    _ => return Err(format!("Unexpected character {c}")),
+                    _ => {
+                        return Err(ParseError::UnexpectedChar {
+                            c,
+                            line: i + 1,
+                            column: x + 1,
+                        });
+                    }
                 }
             }
         }
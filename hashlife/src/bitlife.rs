@@ -0,0 +1,163 @@
+use crate::{LifeEngine, fx::FxHashMap};
+
+/// How many cells across a tile is, one per bit of a word.
+const TILE: isize = 64;
+
+/// A row per word, with the leftmost cell in the lowest bit.
+type Tile = [u64; TILE as usize];
+
+const EMPTY: Tile = [0; TILE as usize];
+
+/// Steps B3/S23 a generation at a time over square tiles of cells, counting
+/// neighbours for a whole tile at once with bitwise adders. There's nothing
+/// to cache, so for soups and other patterns that never repeat themselves it
+/// beats hashlife, as long as they stay small. Only tiles with live cells are
+/// kept.
+#[derive(Clone, Debug, Default)]
+pub struct BitLife {
+    /// By the position of their top left cell divided by `TILE`.
+    tiles: FxHashMap<(isize, isize), Box<Tile>>,
+    generation: u64,
+}
+
+impl BitLife {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// How many tiles of 64x64 cells hold live cells.
+    pub fn tile_count(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn step(&mut self) {
+        // Cells can only be born next to live ones, so only tiles next to
+        // kept ones can have any.
+        let mut near = Vec::with_capacity(self.tiles.len() * 9);
+        for &(ty, tx) in self.tiles.keys() {
+            for dy in -1..=1 {
+                near.extend((-1..=1).map(|dx| (ty + dy, tx + dx)));
+            }
+        }
+        near.sort_unstable();
+        near.dedup();
+        let tiles = near.into_iter().filter_map(|at| {
+            let tile = self.step_tile(at);
+            (tile != EMPTY).then(|| (at, Box::new(tile)))
+        });
+        self.tiles = tiles.collect();
+        self.generation += 1;
+    }
+
+    fn step_tile(&self, (ty, tx): (isize, isize)) -> Tile {
+        let tile = |dy, dx| self.tiles.get(&(ty + dy, tx + dx)).map_or(&EMPTY, |t| &**t);
+        // The rows from the one above the tile to the one below it, and the
+        // same shifted so that each cell lines up with its neighbour to the
+        // west or east.
+        let (mut west, mut mid, mut east) = ([0; 66], [0; 66], [0; 66]);
+        for r in 0..66 {
+            let (dy, row) = match r {
+                0 => (-1, 63),
+                65 => (1, 0),
+                r => (0, r - 1),
+            };
+            let (w, c, e) = (tile(dy, -1)[row], tile(dy, 0)[row], tile(dy, 1)[row]);
+            west[r] = c << 1 | w >> 63;
+            mid[r] = c;
+            east[r] = c >> 1 | e << 63;
+        }
+        // Bit-sliced counts of the neighbours modulo 8, which still tells 2
+        // and 3 apart from the rest, a plane of neighbours at a time.
+        let (mut s0, mut s1, mut s2) = (EMPTY, EMPTY, EMPTY);
+        let planes = [
+            (&west, 0),
+            (&mid, 0),
+            (&east, 0),
+            (&west, 1),
+            (&east, 1),
+            (&west, 2),
+            (&mid, 2),
+            (&east, 2),
+        ];
+        for (plane, dr) in planes {
+            let rows = &plane[dr..dr + TILE as usize];
+            for (((s0, s1), s2), &p) in s0.iter_mut().zip(&mut s1).zip(&mut s2).zip(rows) {
+                let c0 = *s0 & p;
+                *s0 ^= p;
+                let c1 = *s1 & c0;
+                *s1 ^= c0;
+                *s2 ^= c1;
+            }
+        }
+        let mut next = EMPTY;
+        for (y, row) in next.iter_mut().enumerate() {
+            *row = !s2[y] & s1[y] & (s0[y] | mid[y + 1]);
+        }
+        next
+    }
+}
+
+/// The tile holding a cell, and the cell's row and column within it.
+fn locate((y, x): (isize, isize)) -> ((isize, isize), usize, usize) {
+    let at = (y.div_euclid(TILE), x.div_euclid(TILE));
+    (at, y.rem_euclid(TILE) as usize, x.rem_euclid(TILE) as usize)
+}
+
+impl LifeEngine for BitLife {
+    fn set(&mut self, p: (isize, isize), alive: bool) {
+        let (at, row, column) = locate(p);
+        if alive {
+            let tile = self.tiles.entry(at).or_insert_with(|| Box::new(EMPTY));
+            tile[row] |= 1 << column;
+        } else if let Some(tile) = self.tiles.get_mut(&at) {
+            tile[row] &= !(1 << column);
+            if **tile == EMPTY {
+                self.tiles.remove(&at);
+            }
+        }
+    }
+
+    fn get(&self, p: (isize, isize)) -> bool {
+        let (at, row, column) = locate(p);
+        let tile = self.tiles.get(&at);
+        tile.is_some_and(|tile| tile[row] >> column & 1 == 1)
+    }
+
+    fn step_by(&mut self, steps: u64) {
+        for _ in 0..steps {
+            self.step();
+        }
+    }
+
+    fn population(&self) -> u64 {
+        let rows = self.tiles.values().flat_map(|tile| tile.iter());
+        rows.map(|row| row.count_ones() as u64).sum()
+    }
+
+    fn iter_cells(&self) -> Box<dyn Iterator<Item = (isize, isize)> + '_> {
+        Box::new(self.tiles.iter().flat_map(|(&(ty, tx), tile)| {
+            tile.iter().enumerate().flat_map(move |(row, &word)| {
+                let mut bits = word;
+                std::iter::from_fn(move || {
+                    let column = bits.trailing_zeros() as isize;
+                    bits &= bits.wrapping_sub(1);
+                    (column < TILE).then_some((ty * TILE + row as isize, tx * TILE + column))
+                })
+            })
+        }))
+    }
+}
+
+impl FromIterator<(isize, isize)> for BitLife {
+    fn from_iter<T: IntoIterator<Item = (isize, isize)>>(iter: T) -> Self {
+        let mut life = BitLife::new();
+        for p in iter {
+            life.set(p, true);
+        }
+        life
+    }
+}
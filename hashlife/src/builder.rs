@@ -0,0 +1,157 @@
+use std::ops::Range;
+
+use crate::{HashLife, Symmetry, eq::mix};
+
+/// Assembles a pattern from shapes, random fills and other patterns, setting
+/// all their cells at once when it's built.
+#[derive(Clone, Debug, Default)]
+pub struct PatternBuilder {
+    cells: Vec<(isize, isize)>,
+    /// Patterns to paste, transformed, with their origins where they go.
+    placed: Vec<(HashLife, (isize, isize))>,
+    /// The state of the random fills, so that the same calls always build the
+    /// same pattern.
+    random: u64,
+}
+
+impl PatternBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts the random fills from `seed`, instead of from 0.
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.random = mix(seed);
+        self
+    }
+
+    /// Fills the rows `ys` and columns `xs`.
+    pub fn rect(&mut self, ys: Range<isize>, xs: Range<isize>) -> &mut Self {
+        for y in ys {
+            self.cells.extend(xs.clone().map(|x| (y, x)));
+        }
+        self
+    }
+
+    /// Draws the edges of the rows `ys` and columns `xs`, a cell thick.
+    pub fn rect_outline(&mut self, ys: Range<isize>, xs: Range<isize>) -> &mut Self {
+        if ys.is_empty() || xs.is_empty() {
+            return self;
+        }
+        let (bottom, right) = (ys.end - 1, xs.end - 1);
+        self.rect(ys.start..ys.start + 1, xs.clone())
+            .rect(bottom..bottom + 1, xs.clone())
+            .rect(ys.clone(), xs.start..xs.start + 1)
+            .rect(ys, right..right + 1)
+    }
+
+    /// Draws a line of cells from `from` to `to`, both included, with no
+    /// gaps and no corners doubled up.
+    pub fn line(&mut self, from: (isize, isize), to: (isize, isize)) -> &mut Self {
+        let (dy, dx) = ((to.0 - from.0).abs(), -(to.1 - from.1).abs());
+        let (step_y, step_x) = ((to.0 - from.0).signum(), (to.1 - from.1).signum());
+        let (mut y, mut x) = from;
+        let mut error = dy + dx;
+        loop {
+            self.cells.push((y, x));
+            if (y, x) == to {
+                return self;
+            }
+            if 2 * error >= dx {
+                error += dx;
+                y += step_y;
+            }
+            if 2 * error <= dy {
+                error += dy;
+                x += step_x;
+            }
+        }
+    }
+
+    /// Draws the circle of cells `radius` from `center`.
+    pub fn circle(&mut self, center: (isize, isize), radius: isize) -> &mut Self {
+        for (y, x) in octant(radius) {
+            for (y, x) in [(y, x), (x, y)] {
+                for (sy, sx) in [(1, 1), (1, -1), (-1, 1), (-1, -1)] {
+                    self.cells.push((center.0 + sy * y, center.1 + sx * x));
+                }
+            }
+        }
+        self
+    }
+
+    /// Fills the circle of cells `radius` from `center`.
+    pub fn disc(&mut self, center: (isize, isize), radius: isize) -> &mut Self {
+        for (y, x) in octant(radius) {
+            for (y, x) in [(y, x), (x, y)] {
+                for sy in [1, -1] {
+                    let row = center.0 + sy * y;
+                    self.rect(row..row + 1, center.1 - x..center.1 + x + 1);
+                }
+            }
+        }
+        self
+    }
+
+    /// Brings each cell in the rows `ys` and columns `xs` alive with
+    /// probability `density`.
+    pub fn fill_random(&mut self, ys: Range<isize>, xs: Range<isize>, density: f64) -> &mut Self {
+        for y in ys {
+            for x in xs.clone() {
+                self.random = mix(self.random.wrapping_add(0x9e37_79b9_7f4a_7c15));
+                // The top 53 bits, as a float from 0 up to 1.
+                let sample = (self.random >> 11) as f64 / (1u64 << 53) as f64;
+                if sample < density {
+                    self.cells.push((y, x));
+                }
+            }
+        }
+        self
+    }
+
+    /// Adds the cells of `pattern`, rotated or reflected about the centre of
+    /// the universe as by `HashLife::rotate90_cw` and the like, then moved
+    /// so its origin is at `offset`. Patterns are pasted node by node, so
+    /// big ones are cheap.
+    pub fn place(
+        &mut self,
+        pattern: &HashLife,
+        offset: (isize, isize),
+        symmetry: Symmetry,
+    ) -> &mut Self {
+        let mut pattern = pattern.clone();
+        for &transform in symmetry.transforms() {
+            pattern.transform(transform);
+        }
+        self.placed.push((pattern, offset));
+        self
+    }
+
+    pub fn build(&self) -> HashLife {
+        let mut hl = HashLife::new();
+        hl.set_cells(self.cells.iter().copied());
+        for (pattern, offset) in &self.placed {
+            hl.paste(pattern, *offset);
+        }
+        hl
+    }
+}
+
+/// The cells of the circle of `radius` about the origin from straight down
+/// to 45 degrees round, as (y, x) with x <= y, by the midpoint algorithm.
+fn octant(radius: isize) -> Vec<(isize, isize)> {
+    let (mut y, mut x) = (radius, 0);
+    let mut error = 1 - radius;
+    let mut cells = vec![];
+    while x <= y {
+        cells.push((y, x));
+        x += 1;
+        if error < 0 {
+            error += 2 * x + 1;
+        } else {
+            y -= 1;
+            error += 2 * (x - y) + 1;
+        }
+    }
+    cells
+}
@@ -0,0 +1,72 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{HashLife, Symmetry};
+
+/// Common objects left behind by soups in Life, most common first.
+const OBJECTS: [(&str, &str); 14] = [
+    ("block", "oo\noo"),
+    ("blinker", "ooo"),
+    ("beehive", " oo\no  o\n oo"),
+    ("glider", " o\n  o\nooo"),
+    ("boat", "oo\no o\n o"),
+    ("loaf", " oo\no  o\n o o\n  o"),
+    ("tub", " o\no o\n o"),
+    ("pond", " oo\no  o\no  o\n oo"),
+    ("ship", "oo\no o\n oo"),
+    ("long boat", "oo\no o\n o o\n  o"),
+    ("toad", " ooo\nooo"),
+    ("barge", " o\no o\n o o\n  o"),
+    ("lightweight spaceship", " o  o\no\no   o\noooo"),
+    ("mango", " oo\no  o\n o  o\n  oo"),
+];
+
+/// The objects a pattern is made of.
+#[derive(Clone, Debug, Default)]
+pub struct Census {
+    /// How many of each known object there are.
+    pub counts: BTreeMap<&'static str, usize>,
+    /// Anything that isn't a known object, moved to the origin.
+    pub unrecognized: Vec<HashLife>,
+}
+
+impl HashLife {
+    /// Splits the pattern into objects and recognizes the common ones, in any
+    /// phase, orientation or position. Cells that touch are counted as one
+    /// object, so objects close enough to touch aren't recognized.
+    pub fn census(&self) -> Census {
+        let known = known_objects(self);
+        let mut census = Census::default();
+        for (component, _) in self.components(1) {
+            match known.get(&component.canonical_fingerprint()) {
+                Some(name) => *census.counts.entry(name).or_default() += 1,
+                None => census.unrecognized.push(component),
+            }
+        }
+        census
+    }
+}
+
+/// The fingerprints of every phase and orientation of each known object under
+/// the rule of `hl`, moved to the origin.
+fn known_objects(hl: &HashLife) -> HashMap<u64, &'static str> {
+    let mut known = HashMap::new();
+    for (name, s) in OBJECTS {
+        let mut object: HashLife = s.parse().unwrap();
+        object.set_rule(hl.rule().clone());
+        let Some(periodic) = object.detect_period(4) else {
+            continue;
+        };
+        for _ in 0..periodic.period {
+            let (root, depth) = (object.root, object.depth);
+            for symmetry in Symmetry::ALL {
+                for &transform in symmetry.transforms() {
+                    object.transform(transform);
+                }
+                known.entry(object.move_to_origin().0).or_insert(name);
+                (object.root, object.depth) = (root, depth);
+            }
+            object.step(0);
+        }
+    }
+    known
+}
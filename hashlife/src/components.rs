@@ -0,0 +1,36 @@
+use std::collections::HashSet;
+
+use crate::HashLife;
+
+impl HashLife {
+    /// Splits the pattern into clusters of cells, where cells up to `gap`
+    /// apart in each direction belong to the same cluster. A gap of 1 joins
+    /// cells that touch, including diagonally. Each cluster keeps the rule and
+    /// is moved so its bounding box starts at the origin, paired with where
+    /// it started.
+    pub fn components(&self, gap: usize) -> Vec<(HashLife, (isize, isize))> {
+        let gap = gap as isize;
+        let mut unvisited: HashSet<_> = self.iter_cells().collect();
+        let mut components = vec![];
+        while let Some(&start) = unvisited.iter().next() {
+            unvisited.remove(&start);
+            let mut stack = vec![start];
+            let mut cells = vec![];
+            while let Some((y, x)) = stack.pop() {
+                cells.push((y, x));
+                for dy in -gap..=gap {
+                    for dx in -gap..=gap {
+                        if unvisited.remove(&(y + dy, x + dx)) {
+                            stack.push((y + dy, x + dx));
+                        }
+                    }
+                }
+            }
+            let mut component = HashLife::with_rule(self.rule().clone());
+            component.set_cells(cells);
+            let (_, offset) = component.move_to_origin();
+            components.push((component, offset));
+        }
+        components
+    }
+}
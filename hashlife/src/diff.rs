@@ -0,0 +1,75 @@
+use crate::{HashLife, p3::P3, universe::TreeRef};
+
+/// How one state of a pattern differs from another.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Diff {
+    /// Cells dead before and alive after, sorted.
+    pub born: Vec<(isize, isize)>,
+    /// Cells alive before and dead after, sorted.
+    pub died: Vec<(isize, isize)>,
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.born.is_empty() && self.died.is_empty()
+    }
+}
+
+impl HashLife {
+    /// The cells born and died going from this pattern to `other`, such as a
+    /// later generation of it. Like setting and getting cells, this compares
+    /// the stored cells, which under B0 rules can be the dead ones; see
+    /// `is_inverted`. Cells changing between live states don't count.
+    pub fn diff(&self, other: &HashLife) -> Diff {
+        let mut hl = self.clone();
+        let after = hl.import_root(other);
+        let mut diff = Diff::default();
+        for (p, before, after) in hl.differences((self.root, self.depth), (after, other.depth)) {
+            match (before, after) {
+                (0, _) => diff.born.push(p),
+                (_, 0) => diff.died.push(p),
+                _ => {}
+            }
+        }
+        diff.born.sort();
+        diff.died.sort();
+        diff
+    }
+
+    /// The cells in two trees centred like the root whose states differ, and
+    /// their states in each, skipping over the nodes the two share.
+    pub(crate) fn differences(
+        &mut self,
+        (mut a, mut a_depth): (TreeRef, usize),
+        (mut b, mut b_depth): (TreeRef, usize),
+    ) -> Vec<((isize, isize), u8, u8)> {
+        while a_depth < b_depth {
+            a = self.universe.lock().expand_universe(a);
+            a_depth += 1;
+        }
+        while b_depth < a_depth {
+            b = self.universe.lock().expand_universe(b);
+            b_depth += 1;
+        }
+        let mut differences = vec![];
+        let mut stack = vec![(a, b, P3::origin(a_depth))];
+        while let Some((a, b, p)) = stack.pop() {
+            if a == b {
+                continue;
+            }
+            match p.quadrants() {
+                Some(ps) => {
+                    let universe = self.universe.lock();
+                    let subtrees = universe.subtree(a).into_iter().zip(universe.subtree(b));
+                    stack.extend(subtrees.zip(ps).map(|((a, b), p)| (a, b, p)));
+                }
+                None => {
+                    let universe = self.universe.lock();
+                    let states = (universe.state(a), universe.state(b));
+                    differences.push(((p.y, p.x), states.0, states.1));
+                }
+            }
+        }
+        differences
+    }
+}
@@ -0,0 +1,116 @@
+use std::ops::Range;
+
+use crate::{
+    HashLife, Topology,
+    fx::FxHashMap,
+    ops::{Op, Transform},
+    p3::P3,
+    universe::TreeRef,
+};
+
+impl HashLife {
+    /// Shifts every cell down by `dy` and right by `dx`.
+    pub fn translate(&mut self, dy: isize, dx: isize) {
+        if let Topology::Torus { .. } = self.topology {
+            // Cells that fall off one edge wrap round to the other.
+            let cells = self.cells();
+            let cells = cells
+                .into_iter()
+                .map(|((y, x), state)| ((y + dy, x + dx), state));
+            (self.root, self.depth) = (TreeRef::EMPTY, 0);
+            self.set_states(cells.collect());
+            return;
+        }
+        (self.root, self.depth) = self
+            .universe
+            .lock()
+            .translate(self.root, self.depth, (dy, dx));
+        self.trim();
+    }
+
+    /// Kills every cell outside the rows `ys` and columns `xs`.
+    pub fn crop(&mut self, ys: Range<isize>, xs: Range<isize>) {
+        let p = P3::origin(self.depth);
+        self.root = self.universe.lock().clip(self.root, p, (&ys, &xs), true);
+        self.trim();
+    }
+
+    /// Kills every cell inside the rows `ys` and columns `xs`.
+    pub fn clear_rect(&mut self, ys: Range<isize>, xs: Range<isize>) {
+        let p = P3::origin(self.depth);
+        self.root = self.universe.lock().clip(self.root, p, (&ys, &xs), false);
+        self.trim();
+    }
+
+    /// Rotates the universe a quarter turn clockwise, so (y, x) moves to
+    /// (x, -1 - y).
+    pub fn rotate90_cw(&mut self) {
+        self.transform(Transform::RotateCw);
+    }
+
+    /// Rotates the universe a quarter turn anticlockwise, so (y, x) moves to
+    /// (-1 - x, y).
+    pub fn rotate90_ccw(&mut self) {
+        self.transform(Transform::RotateCcw);
+    }
+
+    /// Mirrors the universe left to right, so (y, x) moves to (y, -1 - x).
+    pub fn flip_horizontal(&mut self) {
+        self.transform(Transform::FlipHorizontal);
+    }
+
+    /// Mirrors the universe top to bottom, so (y, x) moves to (-1 - y, x).
+    pub fn flip_vertical(&mut self) {
+        self.transform(Transform::FlipVertical);
+    }
+
+    /// Mirrors the universe along its main diagonal, so (y, x) moves to
+    /// (x, y).
+    pub fn transpose(&mut self) {
+        self.transform(Transform::Transpose);
+    }
+
+    pub(crate) fn transform(&mut self, transform: Transform) {
+        // A lone cell has no quadrants to move about.
+        if self.depth == 0 {
+            self.expand();
+        }
+        let mut cache = FxHashMap::default();
+        self.root = self
+            .universe
+            .lock()
+            .transform(self.root, transform, &mut cache);
+        if let Topology::Torus { .. } = self.topology {
+            // Cells that turn off one edge wrap round to the other.
+            let cells = self.cells();
+            (self.root, self.depth) = (TreeRef::EMPTY, 0);
+            self.set_states(cells);
+        }
+    }
+
+    /// Stamps another pattern into this one with its origin at `at`. Where
+    /// both have a cell that isn't dead, the pasted one wins.
+    pub fn paste(&mut self, other: &HashLife, at: (isize, isize)) {
+        if let Topology::Torus { .. } = self.topology {
+            // Cells that fall off one edge wrap round to the other.
+            let cells = other.cells().into_iter();
+            let cells = cells.map(|((y, x), state)| ((y + at.0, x + at.1), state));
+            self.set_states(cells.collect());
+            return;
+        }
+        let root = self.import_root(other);
+        let (mut root, depth) = self.universe.lock().translate(root, other.depth, at);
+        while self.depth < depth {
+            self.expand();
+        }
+        for _ in depth..self.depth {
+            root = self.universe.lock().expand_universe(root);
+        }
+        let mut cache = FxHashMap::default();
+        let depth = self.depth;
+        self.root = self
+            .universe
+            .lock()
+            .merge(root, self.root, depth, Op::Union, &mut cache);
+    }
+}
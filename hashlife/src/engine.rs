@@ -0,0 +1,77 @@
+use crate::{HashLife, basic_state::BasicState};
+
+/// What every way of running Life can do, so that callers can pick one at
+/// runtime, say as a `Box<dyn LifeEngine>`.
+pub trait LifeEngine {
+    fn set(&mut self, p: (isize, isize), alive: bool);
+
+    fn get(&self, p: (isize, isize)) -> bool;
+
+    fn step_by(&mut self, steps: u64);
+
+    fn population(&self) -> u64;
+
+    /// The live cells, in no particular order.
+    fn iter_cells(&self) -> Box<dyn Iterator<Item = (isize, isize)> + '_>;
+
+    /// How many nodes the engine's tree is made of, if it has one.
+    fn node_count(&self) -> Option<usize> {
+        None
+    }
+}
+
+impl LifeEngine for HashLife {
+    fn set(&mut self, p: (isize, isize), alive: bool) {
+        HashLife::set(self, p, alive);
+    }
+
+    fn get(&self, p: (isize, isize)) -> bool {
+        HashLife::get(self, p)
+    }
+
+    fn step_by(&mut self, steps: u64) {
+        HashLife::step_by(self, steps);
+    }
+
+    fn population(&self) -> u64 {
+        HashLife::population(self)
+    }
+
+    fn iter_cells(&self) -> Box<dyn Iterator<Item = (isize, isize)> + '_> {
+        Box::new(HashLife::iter_cells(self))
+    }
+
+    fn node_count(&self) -> Option<usize> {
+        Some(HashLife::node_count(self))
+    }
+}
+
+/// Steps one generation at a time under B3/S23, whatever the rule, so it's
+/// only fit for small patterns and for checking the others.
+impl LifeEngine for BasicState {
+    fn set(&mut self, p: (isize, isize), alive: bool) {
+        if alive {
+            self.cells.insert(p);
+        } else {
+            self.cells.remove(&p);
+        }
+    }
+
+    fn get(&self, p: (isize, isize)) -> bool {
+        self.cells.contains(&p)
+    }
+
+    fn step_by(&mut self, steps: u64) {
+        for _ in 0..steps {
+            *self = self.step();
+        }
+    }
+
+    fn population(&self) -> u64 {
+        self.cells.len() as u64
+    }
+
+    fn iter_cells(&self) -> Box<dyn Iterator<Item = (isize, isize)> + '_> {
+        Box::new(self.cells.iter().copied())
+    }
+}
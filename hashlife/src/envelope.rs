@@ -0,0 +1,140 @@
+use std::ops::Range;
+
+use crate::{HashLife, fx::FxHashMap, ops::Op, p3::MAX_DEPTH, universe::TreeRef};
+
+/// The union of the stored cells of every generation since tracking started.
+#[derive(Clone, Debug)]
+pub(crate) struct Envelope {
+    pub(crate) root: TreeRef,
+    pub(crate) depth: usize,
+    /// Unions already taken. The envelope changes little from step to step, so
+    /// most nodes are merged with the same nodes again.
+    cache: FxHashMap<(TreeRef, TreeRef), TreeRef>,
+}
+
+/// How many unions to remember before starting again.
+const CACHE_LIMIT: usize = 1 << 16;
+
+impl Envelope {
+    /// Follows the nodes renumbered by garbage collection.
+    pub(crate) fn relocate(&mut self, moved: &FxHashMap<TreeRef, TreeRef>) {
+        self.root = moved[&self.root];
+        self.cache.clear();
+    }
+}
+
+impl HashLife {
+    /// Starts or stops keeping track of every cell that's been alive since,
+    /// for `envelope` and the render overlay. Cells already alive count.
+    pub fn track_envelope(&mut self, track: bool) {
+        self.envelope = track.then(|| Envelope {
+            root: self.root,
+            depth: self.depth,
+            cache: FxHashMap::default(),
+        });
+    }
+
+    pub fn is_tracking_envelope(&self) -> bool {
+        self.envelope.is_some()
+    }
+
+    /// The cells alive in any generation since the envelope started being
+    /// tracked, as a pattern of its own. Cells are added after each step, so
+    /// any set and cleared by hand in between are missed. Under B0 rules these
+    /// are the cells that differed from the background.
+    pub fn envelope(&self) -> Option<HashLife> {
+        let envelope = self.envelope.as_ref()?;
+        Some(self.pattern_of(envelope.root, envelope.depth))
+    }
+
+    /// The cells that could change in the next `n` generations: those within
+    /// `n` times the rule's radius, across rows and columns alike, of a cell
+    /// that isn't dead, so at most the bounding box grown by as much. Signals
+    /// can't get any further in time, so the envelope after stepping `n`
+    /// generations is always inside it. `None` under B0 rules that flip the
+    /// background, which changes every cell, or if the cone would reach
+    /// beyond the coordinates cells can have.
+    ///
+    /// Neither the envelope nor the activity being tracked narrow it: the
+    /// envelope is where cells have been rather than where they can get to,
+    /// and a cell that's been still can be woken by a neighbour.
+    pub fn light_cone(&self, n: u64) -> Option<HashLife> {
+        if self.universe.lock().background_after(self.inverted, 1) != self.inverted {
+            return None;
+        }
+        let reach = n.checked_mul(self.rule().radius() as u64)?;
+        let reach = isize::try_from(reach).ok()?;
+        let limit = 1 << (MAX_DEPTH - 1);
+        let fits = |cells: Range<isize>| {
+            cells
+                .start
+                .checked_sub(reach)
+                .is_some_and(|start| start >= -limit)
+                && cells.end.checked_add(reach).is_some_and(|end| end <= limit)
+        };
+        if let Some((ys, xs)) = self.bounding_box()
+            && !(fits(ys) && fits(xs))
+        {
+            return None;
+        }
+        let mut cone = self.pattern_of(self.root, self.depth);
+        for axis in [(1, 0), (0, 1)] {
+            cone = cone.dilate(reach, axis);
+        }
+        Some(cone)
+    }
+
+    /// The stored cells of `root` as a pattern of its own, sharing this
+    /// pattern's universe and topology but none of its tracking, pins or
+    /// history.
+    fn pattern_of(&self, root: TreeRef, depth: usize) -> HashLife {
+        HashLife {
+            universe: self.universe.clone(),
+            root,
+            depth,
+            topology: self.topology,
+            ..HashLife::new()
+        }
+    }
+
+    /// Grows every cell into a line `reach` cells to either side of it along
+    /// `axis`, by doubling the line's length with each union.
+    fn dilate(self, reach: isize, (dy, dx): (isize, isize)) -> HashLife {
+        let mut dilated = self;
+        let mut len = 1;
+        while len < 2 * reach + 1 {
+            let step = len.min(2 * reach + 1 - len);
+            let mut shifted = dilated.clone();
+            shifted.translate(dy * step, dx * step);
+            dilated = dilated.union(&shifted);
+            len += step;
+        }
+        dilated.translate(-dy * reach, -dx * reach);
+        dilated
+    }
+
+    /// Adds the cells alive after the step just taken to the envelope.
+    pub(crate) fn update_envelope(&mut self) {
+        let Some(mut envelope) = self.envelope.take() else {
+            return;
+        };
+        let mut root = self.root;
+        let mut depth = self.depth;
+        while envelope.depth < depth {
+            envelope.root = self.universe.lock().expand_universe(envelope.root);
+            envelope.depth += 1;
+        }
+        while depth < envelope.depth {
+            root = self.universe.lock().expand_universe(root);
+            depth += 1;
+        }
+        if envelope.cache.len() > CACHE_LIMIT {
+            envelope.cache.clear();
+        }
+        envelope.root =
+            self.universe
+                .lock()
+                .merge(envelope.root, root, depth, Op::Union, &mut envelope.cache);
+        self.envelope = Some(envelope);
+    }
+}
@@ -1,6 +1,14 @@
-use std::{collections::HashMap, iter::zip};
+use std::{
+    collections::HashMap,
+    hash::{Hash, Hasher},
+    iter::zip,
+};
 
-use crate::{HashLife, p3::P3, universe::TreeRef};
+use crate::{
+    HashLife,
+    p3::P3,
+    universe::{TreeRef, Universe},
+};
 
 struct Context<'hl> {
     tr: TreeRef,
@@ -18,12 +26,16 @@ impl<'hl> Context<'hl> {
     }
 
     fn population(&self) -> usize {
-        self.hl.universe.population(self.tr)
+        self.hl.universe.lock().population(self.tr)
+    }
+
+    fn state(&self) -> u8 {
+        self.hl.universe.lock().state(self.tr)
     }
 
     fn divide(&self) -> [Self; 4] {
         let ps = self.p.quadrants().unwrap();
-        let subtree = self.hl.universe.subtree(self.tr);
+        let subtree = self.hl.universe.lock().subtree(self.tr);
         [0, 1, 2, 3]
             .map(|i| Self {
                 tr: subtree[i],
@@ -37,9 +49,15 @@ impl<'hl> Context<'hl> {
 impl PartialEq for HashLife {
     fn eq(&self, other: &Self) -> bool {
         // Only time we'll check the depth
-        if self.depth != other.depth {
+        if (self.depth, self.inverted, self.topology)
+            != (other.depth, other.inverted, other.topology)
+        {
             return false;
         }
+        // Nodes are unique within a universe.
+        if self.universe.ptr_eq(&other.universe) {
+            return self.root == other.root;
+        }
         let mut a_b_cache: HashMap<TreeRef, TreeRef> = HashMap::new();
         let mut b_a_cache: HashMap<TreeRef, TreeRef> = HashMap::new();
         let mut stack = vec![(Context::new(self), Context::new(other))];
@@ -48,6 +66,9 @@ impl PartialEq for HashLife {
             if a.population() != b.population() {
                 return false;
             }
+            if a.p.z == 0 && a.state() != b.state() {
+                return false;
+            }
             if a.population() == 0 || a.p.z == 0 {
                 continue;
             }
@@ -69,3 +90,75 @@ impl PartialEq for HashLife {
         true
     }
 }
+
+impl Eq for HashLife {}
+
+impl Hash for HashLife {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.canonical_fingerprint(), self.inverted, self.topology).hash(state);
+    }
+}
+
+impl HashLife {
+    /// A hash of the pattern that only depends on its cells, not on how the
+    /// universe stores them, so it can be compared between universes.
+    pub fn canonical_fingerprint(&self) -> u64 {
+        let mut cache = HashMap::new();
+        let universe = &*self.universe.lock();
+        if self.depth == 0 {
+            // As if expanded to depth 1, where the cell's in the SE quadrant.
+            let cell = fingerprint(self.root, 0, universe, &mut cache);
+            return mix_branch([0, 0, 0, cell]);
+        }
+        // Leave out any empty border, down to the depth stepping stops at.
+        let population =
+            |trs: [TreeRef; 4]| -> usize { trs.iter().map(|&tr| universe.population(tr)).sum() };
+        let (mut subtree, mut depth) = (universe.subtree(self.root), self.depth);
+        while depth > 1 {
+            let center = [3, 2, 1, 0].map(|i| universe.subtree(subtree[3 - i])[i]);
+            if population(center) != population(subtree) {
+                break;
+            }
+            (subtree, depth) = (center, depth - 1);
+        }
+        let subtree = subtree.map(|tr| fingerprint(tr, depth - 1, universe, &mut cache));
+        mix_branch(subtree)
+    }
+}
+
+fn fingerprint(
+    tr: TreeRef,
+    depth: usize,
+    universe: &Universe,
+    cache: &mut HashMap<TreeRef, u64>,
+) -> u64 {
+    if tr == TreeRef::EMPTY {
+        return 0;
+    }
+    if depth == 0 {
+        return mix(universe.state(tr) as u64);
+    }
+    if let Some(&fingerprint) = cache.get(&tr) {
+        return fingerprint;
+    }
+    let subtree = universe
+        .subtree(tr)
+        .map(|tr| fingerprint(tr, depth - 1, universe, cache));
+    let fingerprint = mix_branch(subtree);
+    cache.insert(tr, fingerprint);
+    fingerprint
+}
+
+fn mix_branch(subtree: [u64; 4]) -> u64 {
+    subtree
+        .into_iter()
+        .fold(0x9e37_79b9_7f4a_7c15, |h, child| mix(h ^ child))
+}
+
+/// The splitmix64 finaliser, which we use instead of `std`'s hashers so that
+/// fingerprints don't change between Rust versions.
+pub(crate) fn mix(mut x: u64) -> u64 {
+    x = (x ^ x >> 30).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ x >> 27).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ x >> 31
+}
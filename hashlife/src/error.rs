@@ -0,0 +1,171 @@
+use std::{fmt::Display, io};
+
+use crate::{Rule, Snapshot};
+
+/// Why a pattern couldn't be read.
+#[derive(Debug)]
+pub enum ParseError {
+    /// A character that doesn't belong, counting lines and columns from 1.
+    UnexpectedChar { c: char, line: usize, column: usize },
+    /// The header of a file is missing or malformed.
+    BadHeader(String),
+    /// A line that doesn't make sense, counting from 1.
+    BadLine { line: usize, text: String },
+    /// The pattern is bigger than `formats::MAX_SIZE` cells across.
+    TooLarge { width: u64, height: u64 },
+    /// A cell is in `state`, which the rule, with its `states`, doesn't have.
+    BadState { state: u8, states: u8 },
+    /// The pattern's rule couldn't be parsed.
+    Rule(RuleError),
+    /// The file couldn't be read.
+    Io(io::Error),
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::UnexpectedChar { c, line, column } => {
+                write!(
+                    f,
+                    "Unexpected character {c:?} at line {line}, column {column}"
+                )
+            }
+            ParseError::BadHeader(header) if header.is_empty() => write!(f, "Missing header"),
+            ParseError::BadHeader(header) => write!(f, "Bad header {header:?}"),
+            ParseError::BadLine { line, text } => write!(f, "Bad line {line}: {text:?}"),
+            ParseError::TooLarge { width, height } => {
+                write!(f, "Pattern too large at {width}x{height}")
+            }
+            ParseError::BadState { state, states } => {
+                write!(f, "State {state} is beyond the rule's {states} states")
+            }
+            ParseError::Rule(e) => write!(f, "{e}"),
+            ParseError::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseError::Rule(e) => Some(e),
+            ParseError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ParseError {
+    fn from(e: io::Error) -> Self {
+        ParseError::Io(e)
+    }
+}
+
+impl From<RuleError> for ParseError {
+    fn from(e: RuleError) -> Self {
+        ParseError::Rule(e)
+    }
+}
+
+/// Why a rule couldn't be read.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RuleError {
+    /// Not a rule in any of the notations supported.
+    Unsupported(String),
+    /// A character that doesn't belong in the birth or survival conditions.
+    UnexpectedChar(char),
+    /// B0 with dying states, which can't be stored by inverting the cells.
+    LiveBackground(String),
+    /// A rule file that couldn't be read, and why.
+    File(String),
+}
+
+impl Display for RuleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuleError::Unsupported(rule) => write!(f, "Unsupported rule {rule}"),
+            RuleError::UnexpectedChar(c) => write!(f, "Unexpected character {c}"),
+            RuleError::LiveBackground(rule) => {
+                write!(f, "B0 is only supported for two state rules, not {rule}")
+            }
+            RuleError::File(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for RuleError {}
+
+/// Why a step couldn't be taken as asked.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StepError {
+    /// The universe still held `nodes` after freeing all it could, more than
+    /// its `max_nodes`. The step was taken.
+    OutOfNodes { nodes: usize, max: usize },
+    /// The generation would pass `u64::MAX`. The step wasn't taken.
+    GenerationOverflow,
+    /// The step would need a tree deeper than coordinates can address, from
+    /// the pattern or the step being too big, or an edit was to a cell beyond
+    /// them. The step or edit wasn't made.
+    UniverseTooLarge,
+    /// The universe ran out of node indices, of which there are 2^32. The
+    /// step or edit wasn't made.
+    UniverseFull,
+    /// The generation asked for, `to`, is before the current one, `from`.
+    /// Nothing was stepped.
+    Backwards { from: u64, to: u64 },
+}
+
+impl Display for StepError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StepError::OutOfNodes { nodes, max } => {
+                write!(
+                    f,
+                    "Pattern needs {nodes} nodes, more than the limit of {max}"
+                )
+            }
+            StepError::GenerationOverflow => write!(f, "Generation count overflowed"),
+            StepError::UniverseTooLarge => {
+                write!(
+                    f,
+                    "Universe too large: cells would be beyond isize coordinates"
+                )
+            }
+            StepError::UniverseFull => {
+                write!(f, "Universe is full: can't hold more than 2^32 nodes")
+            }
+            StepError::Backwards { from, to } => {
+                write!(f, "Can't go back from generation {from} to {to}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for StepError {}
+
+/// Why patterns couldn't share a universe or go back to a snapshot.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UniverseError {
+    /// Patterns with different rules can't share the steps cached.
+    RuleMismatch { first: Box<Rule>, other: Box<Rule> },
+    /// The snapshot was taken of another pattern, or has been released.
+    UnknownSnapshot(Snapshot),
+}
+
+impl Display for UniverseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UniverseError::RuleMismatch { first, other } => {
+                write!(
+                    f,
+                    "Can't share a universe between rules {first} and {other}"
+                )
+            }
+            UniverseError::UnknownSnapshot(snapshot) => {
+                write!(f, "{snapshot:?} isn't of this pattern or was released")
+            }
+        }
+    }
+}
+
+impl std::error::Error for UniverseError {}
@@ -0,0 +1,178 @@
+//! A C API, declared in `include/hashlife.h`, for embedding the engine in
+//! programs in other languages. Patterns are behind opaque `HashLife *`
+//! handles, from `hl_new` or `hl_from_rle` and given back with `hl_free`.
+//! Functions given a null handle do nothing. Panics don't unwind into the
+//! caller: the function gives back false, null or 0 instead, and the pattern
+//! is left as it was or part way through the change.
+
+use std::{
+    ffi::{CStr, c_char},
+    panic::{self, AssertUnwindSafe},
+    ptr,
+};
+
+use crate::{
+    HashLife, formats,
+    render::{Rgba, Settings, View},
+};
+
+/// Runs `f`, or gives back `or` if it panics, as unwinding out of an
+/// `extern "C"` function aborts the process.
+fn guard<T>(or: T, f: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(or)
+}
+
+/// An empty pattern under B3/S23.
+#[unsafe(no_mangle)]
+pub extern "C" fn hl_new() -> *mut HashLife {
+    guard(ptr::null_mut(), || Box::into_raw(Box::new(HashLife::new())))
+}
+
+/// Reads a pattern from NUL-terminated text in any format `formats::load`
+/// knows, RLE included. Returns null if it can't be read.
+///
+/// # Safety
+///
+/// `text` must be null or point to a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hl_from_rle(text: *const c_char) -> *mut HashLife {
+    if text.is_null() {
+        return ptr::null_mut();
+    }
+    let text = unsafe { CStr::from_ptr(text) };
+    guard(ptr::null_mut(), || match formats::load(text.to_bytes()) {
+        Ok(pattern) => Box::into_raw(Box::new(pattern.life)),
+        Err(_) => ptr::null_mut(),
+    })
+}
+
+/// # Safety
+///
+/// `hl` must be null or a handle that hasn't been freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hl_free(hl: *mut HashLife) {
+    if !hl.is_null() {
+        let hl = unsafe { Box::from_raw(hl) };
+        guard((), || drop(hl));
+    }
+}
+
+/// Returns false if the cell couldn't be brought alive, being too far from
+/// the origin for the pattern to reach or there being no room for it. Cells
+/// that far out are always dead, so killing one does nothing.
+///
+/// # Safety
+///
+/// `hl` must be null or a handle that hasn't been freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hl_set_cell(hl: *mut HashLife, y: isize, x: isize, alive: bool) -> bool {
+    let Some(hl) = (unsafe { hl.as_mut() }) else {
+        return false;
+    };
+    guard(false, || {
+        if alive {
+            return hl.set_state_checked((y, x), 1).is_ok();
+        }
+        hl.set((y, x), false);
+        true
+    })
+}
+
+/// # Safety
+///
+/// `hl` must be null or a handle that hasn't been freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hl_get_cell(hl: *const HashLife, y: isize, x: isize) -> bool {
+    let hl = unsafe { hl.as_ref() };
+    guard(false, || hl.is_some_and(|hl| hl.get((y, x))))
+}
+
+/// Steps `steps` generations. Returns false, having stepped only some of
+/// them, if the pattern grew too big or the generation would overflow.
+///
+/// # Safety
+///
+/// `hl` must be null or a handle that hasn't been freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hl_step(hl: *mut HashLife, steps: u64) -> bool {
+    let Some(hl) = (unsafe { hl.as_mut() }) else {
+        return false;
+    };
+    guard(false, || {
+        (0..u64::BITS as usize)
+            .rev()
+            .filter(|&log2| steps >> log2 & 1 == 1)
+            .all(|log2| hl.step_checked(log2).is_ok())
+    })
+}
+
+/// # Safety
+///
+/// `hl` must be null or a handle that hasn't been freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hl_population(hl: *const HashLife) -> u64 {
+    let hl = unsafe { hl.as_ref() };
+    guard(0, || hl.map_or(0, HashLife::population))
+}
+
+/// # Safety
+///
+/// `hl` must be null or a handle that hasn't been freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hl_generation(hl: *const HashLife) -> u64 {
+    let hl = unsafe { hl.as_ref() };
+    guard(0, || hl.map_or(0, HashLife::generation))
+}
+
+/// Draws black cells on white into `pixels`, `height` rows of `width`
+/// pixels with four bytes of sRGB and alpha to each. `zoom` is in pixels per
+/// cell, and `(center_y, center_x)` the cell drawn in the middle. Returns
+/// false if nothing was drawn.
+///
+/// # Safety
+///
+/// `hl` must be null or a handle that hasn't been freed, and `pixels` must
+/// be null or point to `height * width * 4` writable bytes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hl_render_rgba(
+    hl: *const HashLife,
+    pixels: *mut u8,
+    height: usize,
+    width: usize,
+    center_y: f64,
+    center_x: f64,
+    zoom: f64,
+) -> bool {
+    let Some(hl) = (unsafe { hl.as_ref() }) else {
+        return false;
+    };
+    let len = height.checked_mul(width).and_then(|n| n.checked_mul(4));
+    let Some(len) = len.filter(|&len| len <= isize::MAX as usize) else {
+        return false;
+    };
+    if pixels.is_null() {
+        return false;
+    }
+    let pixels = unsafe { std::slice::from_raw_parts_mut(pixels, len) };
+    let settings = Settings {
+        height,
+        width,
+        cell_size: 1.,
+        dead_rgba: Rgba::WHITE,
+        alive_rgba: Rgba::BLACK,
+        row_offset: hl.rule().neighbourhood().row_offset(),
+        grid: None,
+        ages: None,
+        heatmap: None,
+        envelope: None,
+    };
+    let view = View {
+        zoom,
+        center: (center_y, center_x),
+        rotation: 0.,
+    };
+    guard(false, || {
+        hl.render_rgba8_into(settings, view, pixels);
+        true
+    })
+}
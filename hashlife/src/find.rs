@@ -0,0 +1,27 @@
+use itertools::Itertools;
+
+use crate::HashLife;
+
+impl HashLife {
+    /// Where `needle` appears, as the (dy, dx) to move it by to land on it.
+    /// Cells that are dead within the needle's bounding box have to be dead
+    /// here too, so a glider is only found in the same phase and orientation.
+    /// Candidates are lined up on the needle's first cell, and any whose box
+    /// doesn't hold the right population are ruled out by counting from the
+    /// tree.
+    pub fn find(&self, needle: &HashLife) -> impl Iterator<Item = (isize, isize)> + '_ {
+        let cells = needle.iter_cells().sorted().collect_vec();
+        let bounds = needle.bounding_box();
+        let population = needle.population();
+        self.iter_cells().filter_map(move |(y, x)| {
+            let (ys, xs) = bounds.clone()?;
+            let (y0, x0) = cells[0];
+            let (dy, dx) = (y - y0, x - x0);
+            let ys = ys.start + dy..ys.end + dy;
+            let xs = xs.start + dx..xs.end + dx;
+            let matches = self.population_in_rect(ys, xs) == population
+                && cells.iter().all(|&(y, x)| self.get((y + dy, x + dx)));
+            matches.then_some((dy, dx))
+        })
+    }
+}
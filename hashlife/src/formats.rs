@@ -0,0 +1,249 @@
+mod life;
+mod macrocell;
+mod rle;
+
+use std::{
+    io::{self, Read, Write},
+    path::Path,
+    str::FromStr,
+};
+
+use crate::{Glyphs, HashLife, ParseError};
+
+pub use macrocell::write as write_macrocell;
+pub use rle::write as write_rle;
+
+/// How many cells across a pattern file may spell out, beyond which it's more
+/// likely to be corrupt than real, and would take too long to read.
+pub const MAX_SIZE: u64 = 1 << 24;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Rle,
+    Macrocell,
+    Life105,
+    Life106,
+    Plaintext,
+}
+
+#[derive(Clone, Debug)]
+pub struct Pattern {
+    pub format: Format,
+    pub life: HashLife,
+    pub meta: PatternMeta,
+}
+
+/// Provenance carried alongside a pattern, e.g. the `#N`, `#O` and `#C` lines
+/// of an RLE file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PatternMeta {
+    pub name: Option<String>,
+    pub author: Option<String>,
+    pub comments: Vec<String>,
+    pub rule: Option<String>,
+}
+
+impl PatternMeta {
+    /// Records a `#X text` style comment line (without the '#'). Tags that
+    /// aren't a name or author are kept as plain comments.
+    fn push_tagged(&mut self, line: &str) {
+        let mut chars = line.chars();
+        let tag = chars.next();
+        let text = chars.as_str().trim().to_string();
+        match tag {
+            Some('N') => self.name = Some(text),
+            Some('O') => self.author = Some(text),
+            _ => self.comments.push(text),
+        }
+    }
+
+    /// Writes the metadata back out as `#X text` comment lines.
+    fn write_tagged(&self, out: &mut String) {
+        let tagged = [("N", &self.name), ("O", &self.author)];
+        for (tag, text) in tagged {
+            if let Some(text) = text {
+                out.push_str(&format!("#{tag} {text}\n"));
+            }
+        }
+        for comment in &self.comments {
+            out.push_str(&format!("#C {comment}\n"));
+        }
+    }
+}
+
+impl Format {
+    pub fn detect(text: &str) -> Self {
+        let text = text.trim_start();
+        if text.starts_with("[M2]") {
+            return Format::Macrocell;
+        }
+        if text.starts_with("#Life 1.05") {
+            return Format::Life105;
+        }
+        if text.starts_with("#Life 1.06") {
+            return Format::Life106;
+        }
+        // RLE files may open with '#' comments, but the first real line is
+        // always the "x = m, y = n" header.
+        let first_line = text
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with('#'));
+        match first_line {
+            Some(line) if line.starts_with('x') && line.contains('=') => Format::Rle,
+            _ => Format::Plaintext,
+        }
+    }
+
+    /// The format files ending in `.extension` are usually in.
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "rle" => Some(Format::Rle),
+            "mc" => Some(Format::Macrocell),
+            "lif" | "life" => Some(Format::Life106),
+            "cells" | "txt" => Some(Format::Plaintext),
+            _ => None,
+        }
+    }
+}
+
+pub fn load(bytes: &[u8]) -> Result<Pattern, ParseError> {
+    load_with_rules(bytes, |_| None)
+}
+
+/// Loads a pattern from a stream, such as stdin, reading it to the end.
+pub fn read(mut r: impl Read) -> Result<Pattern, ParseError> {
+    let mut bytes = vec![];
+    r.read_to_end(&mut bytes)?;
+    load(&bytes)
+}
+
+/// Loads a pattern from a file. Rules that are only named, like `WireWorld`,
+/// are looked for in a `.rule` file alongside it.
+pub fn load_file(path: impl AsRef<Path>) -> Result<Pattern, ParseError> {
+    let bytes = std::fs::read(&path)?;
+    let dir = path.as_ref().parent().unwrap_or(Path::new("."));
+    load_with_rules(&bytes, |name| {
+        std::fs::read_to_string(dir.join(format!("{name}.rule"))).ok()
+    })
+}
+
+/// The most `load_url` downloads, far more than any pattern file needs.
+#[cfg(feature = "fetch")]
+const MAX_DOWNLOAD: u64 = 64 << 20;
+
+/// Downloads a pattern, such as one of LifeWiki's from
+/// `https://conwaylife.com/patterns/`. Rules that are only named are looked
+/// for in a `.rule` file alongside it.
+#[cfg(feature = "fetch")]
+pub fn load_url(url: &str) -> Result<Pattern, ParseError> {
+    let bytes = fetch(url)?;
+    let dir = url.rsplit_once('/').map_or(url, |(dir, _)| dir);
+    load_with_rules(&bytes, |name| {
+        let rule = fetch(&format!("{dir}/{name}.rule")).ok()?;
+        String::from_utf8(rule).ok()
+    })
+}
+
+#[cfg(feature = "fetch")]
+fn fetch(url: &str) -> io::Result<Vec<u8>> {
+    let mut response = ureq::get(url).call().map_err(io::Error::other)?;
+    let body = response.body_mut().with_config().limit(MAX_DOWNLOAD);
+    body.read_to_vec().map_err(io::Error::other)
+}
+
+/// Writes a pattern to a stream in `format`. There are no writers for the
+/// Life 1.05 and 1.06 formats.
+pub fn write(
+    mut w: impl Write,
+    life: &HashLife,
+    meta: &PatternMeta,
+    format: Format,
+) -> io::Result<()> {
+    let text = match format {
+        Format::Rle => write_rle(life, meta),
+        Format::Macrocell => write_macrocell(life, meta),
+        Format::Plaintext => write_plaintext(life, meta),
+        Format::Life105 | Format::Life106 => {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("can't write {format:?} patterns"),
+            ));
+        }
+    };
+    w.write_all(text.as_bytes())
+}
+
+fn load_with_rules(
+    bytes: &[u8],
+    find_rule: impl Fn(&str) -> Option<String>,
+) -> Result<Pattern, ParseError> {
+    let text = std::str::from_utf8(bytes)
+        .map_err(|e| ParseError::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, e)))?;
+    let mut pattern = match Format::detect(text) {
+        Format::Rle => rle::parse(text),
+        Format::Macrocell => macrocell::parse(text),
+        Format::Life105 => life::parse_105(text),
+        Format::Life106 => life::parse_106(text),
+        Format::Plaintext => parse_plaintext(text),
+    }?;
+    if let Some(name) = &pattern.meta.rule {
+        let rule = match name.parse() {
+            Ok(rule) => rule,
+            Err(e) => find_rule(name)
+                .ok_or(e)
+                .and_then(|text| text.parse())
+                .map_err(ParseError::Rule)?,
+        };
+        pattern.life.set_rule(rule);
+    }
+    // The parsers check states against rules they can read, but not those
+    // from `.rule` files, which would index past the rule's tables.
+    let states = pattern.life.rule().states();
+    let highest = pattern.life.universe.lock().highest_state();
+    if highest >= states {
+        return Err(ParseError::BadState {
+            state: highest,
+            states,
+        });
+    }
+    Ok(pattern)
+}
+
+fn parse_plaintext(text: &str) -> Result<Pattern, ParseError> {
+    let mut meta = PatternMeta::default();
+    for comment in text.lines().filter_map(|line| line.strip_prefix('!')) {
+        match comment.split_once(':') {
+            Some(("Name", name)) => meta.name = Some(name.trim().to_string()),
+            Some(("Author", author)) => meta.author = Some(author.trim().to_string()),
+            _ => meta.comments.push(comment.trim().to_string()),
+        }
+    }
+    Ok(Pattern {
+        format: Format::Plaintext,
+        life: HashLife::from_str(text)?,
+        meta,
+    })
+}
+
+/// Writes the cells that aren't dead as `O`, with the name, author and
+/// comments as `!` lines.
+pub fn write_plaintext(life: &HashLife, meta: &PatternMeta) -> String {
+    let mut out = String::new();
+    if let Some(name) = &meta.name {
+        out.push_str(&format!("!Name: {name}\n"));
+    }
+    if let Some(author) = &meta.author {
+        out.push_str(&format!("!Author: {author}\n"));
+    }
+    for comment in &meta.comments {
+        out.push_str(&format!("!{comment}\n"));
+    }
+    let glyphs = Glyphs {
+        alive: 'O',
+        dead: '.',
+        rectangular: false,
+    };
+    out.push_str(&format!("{}\n", life.display_with(glyphs)));
+    out
+}
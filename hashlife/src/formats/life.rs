@@ -0,0 +1,79 @@
+use crate::{
+    HashLife, ParseError,
+    formats::{Format, Pattern, PatternMeta},
+};
+
+pub fn parse_105(text: &str) -> Result<Pattern, ParseError> {
+    let mut meta = PatternMeta::default();
+    let mut cells = vec![];
+    let (mut y, mut x0) = (0, 0);
+    for (i, raw) in text.lines().enumerate().skip(1) {
+        let line = raw.trim();
+        if let Some(block) = line.strip_prefix("#P") {
+            // "#P x y" moves the top-left corner of the next block of rows.
+            let coords = parse_coords(block).ok_or_else(|| bad_line(i, line))?;
+            (y, x0) = (coords.0, coords.1);
+        } else if line.starts_with("#N") {
+            meta.rule = Some("B3/S23".to_string());
+        } else if let Some(rule) = line.strip_prefix("#R") {
+            meta.rule = Some(rule.trim().to_string());
+        } else if let Some(comment) = line.strip_prefix('#') {
+            meta.push_tagged(comment);
+        } else if !line.is_empty() {
+            let indent = raw.chars().take_while(|c| c.is_whitespace()).count();
+            for (j, (x, c)) in (x0..).zip(line.chars()).enumerate() {
+                match c {
+                    '.' => (),
+                    '*' => cells.push((y, x)),
+                    _ => {
+                        return Err(ParseError::UnexpectedChar {
+                            c,
+                            line: i + 1,
+                            column: indent + j + 1,
+                        });
+                    }
+                }
+            }
+            y += 1;
+        }
+    }
+    Ok(Pattern {
+        format: Format::Life105,
+        life: cells.into_iter().collect::<HashLife>(),
+        meta,
+    })
+}
+
+pub fn parse_106(text: &str) -> Result<Pattern, ParseError> {
+    let mut meta = PatternMeta::default();
+    let mut cells = vec![];
+    for (i, line) in text.lines().enumerate().skip(1) {
+        let line = line.trim();
+        if let Some(comment) = line.strip_prefix('#') {
+            meta.comments.push(comment.trim().to_string());
+        } else if !line.is_empty() {
+            let p = parse_coords(line).ok_or_else(|| bad_line(i, line))?;
+            cells.push(p);
+        }
+    }
+    Ok(Pattern {
+        format: Format::Life106,
+        life: cells.into_iter().collect::<HashLife>(),
+        meta,
+    })
+}
+
+/// Line `i`, counting from 0, as an error.
+fn bad_line(i: usize, line: &str) -> ParseError {
+    ParseError::BadLine {
+        line: i + 1,
+        text: line.to_string(),
+    }
+}
+
+/// Parses an "x y" pair into (y, x).
+fn parse_coords(s: &str) -> Option<(isize, isize)> {
+    let mut fields = s.split_whitespace().map(|field| field.parse().ok());
+    let (x, y) = (fields.next()??, fields.next()??);
+    fields.next().is_none().then_some((y, x))
+}
@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+
+use crate::{
+    HashLife, ParseError, Rule,
+    formats::{Format, Pattern, PatternMeta},
+    p3::{MAX_DEPTH, P3},
+    universe::{Shared, TreeRef, Universe},
+};
+
+pub fn parse(text: &str) -> Result<Pattern, ParseError> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .skip_while(|(_, line)| line.trim().is_empty());
+    let header = lines.next().map_or("", |(_, header)| header);
+    if !header.starts_with("[M2]") {
+        return Err(ParseError::BadHeader(header.to_string()));
+    }
+    let mut meta = PatternMeta::default();
+    let mut universe = Universe::new();
+    // Node 0 is the empty tree; every other line defines the next node.
+    let mut nodes = vec![(TreeRef::EMPTY, 0)];
+    // Rules only known from a `.rule` file are checked once that's found, so
+    // until then, and until the rule is given, any state will do.
+    let mut states = 256;
+    for (i, line) in lines {
+        let line = line.trim();
+        if let Some(comment) = line.strip_prefix('#') {
+            match comment.strip_prefix('R') {
+                Some(rule) => {
+                    let rule = rule.trim();
+                    states = rule.parse().map_or(256, |rule: Rule| rule.states() as u16);
+                    meta.rule = Some(rule.to_string());
+                }
+                None => meta.push_tagged(comment),
+            }
+        } else if line.starts_with(['.', '*', '$']) {
+            nodes.push((parse_leaf(&mut universe, i, line)?, 3));
+        } else if !line.is_empty() {
+            let branch = parse_branch(&mut universe, &nodes, states, line);
+            nodes.push(branch.ok_or_else(|| ParseError::BadLine {
+                line: i + 1,
+                text: line.to_string(),
+            })?);
+        }
+    }
+    let &(root, depth) = nodes.last().unwrap();
+    Ok(Pattern {
+        format: Format::Macrocell,
+        life: HashLife {
+            universe: Shared::new(universe),
+            depth,
+            root,
+            ..HashLife::new()
+        },
+        meta,
+    })
+}
+
+/// Line `i` of the file, counting from 0, has a leaf.
+fn parse_leaf(universe: &mut Universe, i: usize, line: &str) -> Result<TreeRef, ParseError> {
+    // An 8x8 block: '.' dead, '*' alive, '$' ends a row.
+    let mut cells = vec![vec![0; 8]; 8];
+    let (mut y, mut x) = (0, 0);
+    for (j, c) in line.chars().enumerate() {
+        match c {
+            '.' => x += 1,
+            '*' if y < 8 && x < 8 => {
+                cells[y][x] = 1;
+                x += 1;
+            }
+            '$' => (y, x) = (y + 1, 0),
+            _ => {
+                return Err(ParseError::UnexpectedChar {
+                    c,
+                    line: i + 1,
+                    column: j + 1,
+                });
+            }
+        }
+    }
+    Ok(universe.build(&cells, 0, 0, 3))
+}
+
+/// A node made of those before it, or for depth 1, of states below
+/// `states`.
+fn parse_branch(
+    universe: &mut Universe,
+    nodes: &[(TreeRef, usize)],
+    states: u16,
+    line: &str,
+) -> Option<(TreeRef, usize)> {
+    let fields: Vec<usize> = line
+        .split_whitespace()
+        .map(|field| field.parse().ok())
+        .collect::<Option<_>>()?;
+    let &[depth, nw, ne, sw, se] = fields.as_slice() else {
+        return None;
+    };
+    // Deeper nodes would have cells beyond isize coordinates.
+    if !(1..=MAX_DEPTH).contains(&depth) {
+        return None;
+    }
+    let mut subtree = [TreeRef::EMPTY; 4];
+    for (i, n) in [nw, ne, sw, se].into_iter().enumerate() {
+        // Multi-state patterns use level 1 nodes whose children are states.
+        if depth == 1 {
+            let state = u8::try_from(n)
+                .ok()
+                .filter(|&state| (state as u16) < states)?;
+            subtree[i] = universe.leaf(state);
+            continue;
+        }
+        match nodes.get(n) {
+            Some(&(tr, _)) if n == 0 => subtree[i] = tr,
+            Some(&(tr, z)) if z + 1 == depth => subtree[i] = tr,
+            _ => return None,
+        }
+    }
+    Some((universe.branch(subtree), depth))
+}
+
+pub fn write(life: &HashLife, meta: &PatternMeta) -> String {
+    let mut out = "[M2] (hashlife)\n".to_string();
+    out.push_str(&format!("#R {}\n", life.rule()));
+    meta.write_tagged(&mut out);
+    // Two state leaves are 8x8 blocks, others 2x2 blocks of states.
+    let leaf_depth = if life.rule().states() > 2 { 1 } else { 3 };
+    let mut life = life.clone();
+    while life.depth < leaf_depth {
+        life.expand();
+    }
+    let universe = life.universe.lock();
+    let mut ids = HashMap::from([(TreeRef::EMPTY, 0)]);
+    // Children are written before their parents, so the root comes last.
+    let mut stack = vec![(life.root, life.depth, false)];
+    while let Some((tr, depth, children_done)) = stack.pop() {
+        if ids.contains_key(&tr) {
+            continue;
+        }
+        if depth == 1 && leaf_depth == 1 {
+            let [nw, ne, sw, se] = universe.subtree(tr).map(|tr| universe.state(tr));
+            out.push_str(&format!("1 {nw} {ne} {sw} {se}\n"));
+        } else if depth == 3 && leaf_depth == 3 {
+            out.push_str(&write_leaf(&universe, tr));
+        } else if children_done {
+            let subtree = universe.subtree(tr).map(|tr| ids[&tr]);
+            let [nw, ne, sw, se] = subtree;
+            out.push_str(&format!("{depth} {nw} {ne} {sw} {se}\n"));
+        } else {
+            stack.push((tr, depth, true));
+            let subtree = universe.subtree(tr);
+            stack.extend(subtree.into_iter().rev().map(|tr| (tr, depth - 1, false)));
+            continue;
+        }
+        ids.insert(tr, ids.len());
+    }
+    out
+}
+
+fn write_leaf(universe: &Universe, tr: TreeRef) -> String {
+    let mut leaf = String::new();
+    for y in -4..4 {
+        let row: String = (-4..4)
+            .map(|x| universe.get_node(tr, P3 { y, x, z: 3 }).unwrap())
+            .map(|tr| if universe.state(tr) != 0 { '*' } else { '.' })
+            .collect();
+        leaf.push_str(row.trim_end_matches('.'));
+        leaf.push('$');
+    }
+    leaf.trim_end_matches('$').to_string() + "$\n"
+}
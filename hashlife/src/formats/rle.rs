@@ -0,0 +1,193 @@
+use itertools::Itertools;
+
+use crate::{
+    HashLife, ParseError, Rule,
+    formats::{Format, MAX_SIZE, Pattern, PatternMeta},
+};
+
+pub fn parse(text: &str) -> Result<Pattern, ParseError> {
+    let mut meta = PatternMeta::default();
+    let mut lines = text.lines().enumerate();
+    let header = loop {
+        match lines.next().map(|(_, line)| line.trim()) {
+            // "#P" and "#R" give coordinates, which we don't keep.
+            Some(line) if line.starts_with('#') => match &line[1..] {
+                r if r.starts_with('r') => meta.rule = Some(r[1..].trim().to_string()),
+                p if p.starts_with(['P', 'R']) => (),
+                comment => meta.push_tagged(comment),
+            },
+            Some("") => (),
+            Some(line) => break line,
+            None => return Err(ParseError::BadHeader(String::new())),
+        }
+    };
+    let bad_header = || ParseError::BadHeader(header.to_string());
+    let (mut width, mut height) = (0, 0);
+    for field in header.split(',') {
+        let Some((key, value)) = field.split_once('=') else {
+            return Err(bad_header());
+        };
+        match key.trim() {
+            "x" => width = value.trim().parse().map_err(|_| bad_header())?,
+            "y" => height = value.trim().parse().map_err(|_| bad_header())?,
+            "rule" => meta.rule = Some(value.trim().to_string()),
+            _ => (),
+        }
+    }
+    check_size(width, height)?;
+    // Rules only known from a `.rule` file are checked once that's found, so
+    // until then any state will do.
+    let states = match &meta.rule {
+        Some(rule) => rule.parse().map_or(256, |rule: Rule| rule.states() as u16),
+        None => Rule::default().states() as u16,
+    };
+    let mut cells = vec![];
+    let (mut y, mut x, mut run, mut prefix) = (0, 0, 0, 0);
+    'lines: for (i, line) in lines {
+        for (j, c) in line.chars().enumerate() {
+            match c {
+                '0'..='9' => {
+                    run = run * 10 + c.to_digit(10).unwrap() as isize;
+                    // Runs beyond any real pattern would take forever to fill.
+                    check_size(x as u64 + run as u64, y as u64 + 1)?;
+                    continue;
+                }
+                // States above 24 are written as a prefix 'p'..='y' and a letter.
+                'p'..='y' => {
+                    prefix = (c as u8 - b'o') * 24;
+                    continue;
+                }
+                'b' | '.' => x += run.max(1),
+                '$' => (y, x) = (y + run.max(1), 0),
+                '!' => break 'lines,
+                'o' | 'A'..='X' => {
+                    let state = match c {
+                        'o' => Some(1),
+                        c => prefix.checked_add(c as u8 - b'A' + 1),
+                    };
+                    let Some(state) = state.filter(|&state| (state as u16) < states) else {
+                        return Err(ParseError::UnexpectedChar {
+                            c,
+                            line: i + 1,
+                            column: j + 1,
+                        });
+                    };
+                    for _ in 0..run.max(1) {
+                        cells.push(((y, x), state));
+                        x += 1;
+                    }
+                }
+                c if c.is_whitespace() => continue,
+                _ => {
+                    return Err(ParseError::UnexpectedChar {
+                        c,
+                        line: i + 1,
+                        column: j + 1,
+                    });
+                }
+            }
+            check_size(x as u64, y as u64 + 1)?;
+            (run, prefix) = (0, 0);
+        }
+    }
+    // Center the pattern on the origin, as plaintext patterns are.
+    let (ys, xs) = bounds(cells.iter().map(|&(p, _)| p));
+    let (dy, dx) = (
+        (ys.0 + ys.1 + 1).div_euclid(2),
+        (xs.0 + xs.1 + 1).div_euclid(2),
+    );
+    let mut life = HashLife::new();
+    life.set_states(
+        cells
+            .into_iter()
+            .map(|((y, x), state)| ((y - dy, x - dx), state))
+            .collect(),
+    );
+    Ok(Pattern {
+        format: Format::Rle,
+        life,
+        meta,
+    })
+}
+
+fn check_size(width: u64, height: u64) -> Result<(), ParseError> {
+    if width.max(height) > MAX_SIZE {
+        return Err(ParseError::TooLarge { width, height });
+    }
+    Ok(())
+}
+
+/// The inclusive (min, max) of the ys and xs, or ((0, -1), (0, -1)) if there
+/// are no cells.
+fn bounds(cells: impl Iterator<Item = (isize, isize)> + Clone) -> ((isize, isize), (isize, isize)) {
+    match (
+        cells.clone().map(|(y, _)| y).minmax().into_option(),
+        cells.map(|(_, x)| x).minmax().into_option(),
+    ) {
+        (Some(ys), Some(xs)) => (ys, xs),
+        _ => ((0, -1), (0, -1)),
+    }
+}
+
+pub fn write(life: &HashLife, meta: &PatternMeta) -> String {
+    let mut out = String::new();
+    meta.write_tagged(&mut out);
+    let cells = life.clone().into_iter().sorted().collect_vec();
+    let (ys, xs) = bounds(cells.iter().copied());
+    let rule = life.rule();
+    out.push_str(&format!(
+        "x = {}, y = {}, rule = {rule}\n",
+        xs.1 - xs.0 + 1,
+        ys.1 - ys.0 + 1
+    ));
+    // Two state rules use 'b' and 'o', others '.' and letters.
+    let multi_state = rule.states() > 2;
+    let tag = |state: u8| match (state, multi_state) {
+        (0, false) => "b".to_string(),
+        (0, true) => ".".to_string(),
+        (_, false) => "o".to_string(),
+        (state, true) => {
+            let (prefix, letter) = ((state - 1) / 24, (state - 1) % 24);
+            let prefix = (prefix > 0).then(|| (b'o' + prefix) as char);
+            prefix
+                .into_iter()
+                .chain([(b'A' + letter) as char])
+                .collect()
+        }
+    };
+    // Runs of (count, tag), with dead cells at the end of a row left implicit.
+    let mut runs: Vec<(isize, String)> = vec![];
+    let mut push = |count: isize, tag: String| match runs.last_mut() {
+        _ if count == 0 => (),
+        Some((n, last)) if *last == tag => *n += count,
+        _ => runs.push((count, tag)),
+    };
+    let (mut y, mut x) = (ys.0, xs.0);
+    for (cy, cx) in cells {
+        if cy > y {
+            push(cy - y, "$".to_string());
+            (y, x) = (cy, xs.0);
+        }
+        push(cx - x, tag(0));
+        push(1, tag(life.get_state((cy, cx))));
+        x = cx + 1;
+    }
+    push(1, "!".to_string());
+    // Keep lines to the conventional 70 characters.
+    let mut line = String::new();
+    for (count, tag) in runs {
+        let run = match count {
+            1 => tag.to_string(),
+            n => format!("{n}{tag}"),
+        };
+        if line.len() + run.len() > 70 {
+            out.push_str(&line);
+            out.push('\n');
+            line.clear();
+        }
+        line.push_str(&run);
+    }
+    out.push_str(&line);
+    out.push('\n');
+    out
+}
@@ -0,0 +1,103 @@
+use std::{
+    collections::HashMap,
+    hash::{BuildHasherDefault, Hasher},
+};
+
+/// A map hashed with `FxHasher`.
+pub(crate) type FxHashMap<K, V> = HashMap<K, V, BuildHasherDefault<FxHasher>>;
+
+/// The hasher rustc uses for its own tables: a rotate, xor and multiply per
+/// word. The keys of the node tables are a few words each, and hashing them
+/// with the default SipHash is most of the cost of interning a node. Unlike
+/// SipHash, nothing stops keys being chosen to collide.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct FxHasher {
+    hash: u64,
+}
+
+const SEED: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+
+impl FxHasher {
+    fn add(&mut self, word: u64) {
+        self.hash = (self.hash.rotate_left(5) ^ word).wrapping_mul(SEED);
+    }
+}
+
+impl Hasher for FxHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for chunk in bytes.chunks(8) {
+            let mut word = [0; 8];
+            word[..chunk.len()].copy_from_slice(chunk);
+            self.add(u64::from_le_bytes(word));
+        }
+    }
+
+    fn write_u8(&mut self, n: u8) {
+        self.add(n as u64);
+    }
+
+    fn write_u16(&mut self, n: u16) {
+        self.add(n as u64);
+    }
+
+    fn write_u32(&mut self, n: u32) {
+        self.add(n as u64);
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.add(n);
+    }
+
+    fn write_usize(&mut self, n: usize) {
+        self.add(n as u64);
+    }
+
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::hash::{BuildHasher, Hash};
+
+    use super::*;
+
+    #[test]
+    fn test_maps_agree() {
+        // Keys like the node tables': a few words each, many of them close.
+        let keys = (0..20_000u32).map(|k| (k % 97, (k / 97) as u8, k % 3 == 0));
+        let mut fx = FxHashMap::default();
+        let mut std = HashMap::new();
+        for (k, key) in keys.enumerate() {
+            if k % 5 == 4 {
+                assert_eq!(fx.remove(&key), std.remove(&key));
+            } else {
+                assert_eq!(fx.insert(key, k), std.insert(key, k));
+            }
+        }
+        assert_eq!(fx.len(), std.len());
+        for (key, value) in &std {
+            assert_eq!(fx.get(key), Some(value));
+        }
+    }
+
+    #[test]
+    fn test_hashes_are_stable() {
+        let hash = |key: &dyn Fn(&mut FxHasher)| {
+            let mut hasher = BuildHasherDefault::<FxHasher>::default().build_hasher();
+            key(&mut hasher);
+            hasher.finish()
+        };
+        let words = |h: &mut FxHasher| (1u32, 2u8, [3u64, 4]).hash(h);
+        assert_eq!(hash(&words), hash(&words));
+        // Bytes are hashed a word at a time, whatever their length.
+        let bytes = |h: &mut FxHasher| h.write(b"0123456789");
+        let padded = |h: &mut FxHasher| {
+            h.write_u64(u64::from_le_bytes(*b"01234567"));
+            h.write_u64(u64::from_le_bytes(*b"89\0\0\0\0\0\0"));
+        };
+        assert_eq!(hash(&bytes), hash(&padded));
+        assert_ne!(hash(&words), hash(&bytes));
+    }
+}
@@ -0,0 +1,203 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{
+    HashLife, Topology, UniverseError,
+    fx::FxHashMap,
+    universe::{TreeRef, UniverseConfig, UniverseStats},
+};
+
+/// A state of the pattern to go back to, which garbage collection keeps.
+/// Taking one only remembers the root, as nodes never change.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Snapshot(u64);
+
+/// Numbers snapshots across all patterns, so that a snapshot of one can't
+/// restore another.
+static SNAPSHOTS: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Clone, Debug)]
+pub(crate) struct Pinned {
+    root: TreeRef,
+    depth: usize,
+    inverted: bool,
+    topology: Topology,
+    generation: u64,
+}
+
+impl HashLife {
+    /// Frees the nodes no longer in use, which stepping and editing leave
+    /// behind without bound. Nodes are kept for the pattern, snapshots and
+    /// anything being tracked, and so are the steps cached for them. A pattern
+    /// sharing its universe moves to one of its own, as the others may still
+    /// use the nodes it frees.
+    pub fn collect_garbage(&mut self) {
+        let roots = self.roots();
+        let moved = self.universe.collect_garbage(roots);
+        self.relocate(&moved);
+    }
+
+    /// Moves the patterns into one universe, so that they store the nodes
+    /// they have in common once, compare quickly and share the steps cached.
+    /// They keep sharing it as they step and change, until garbage collection
+    /// or a new rule gives one a universe of its own. The patterns must all
+    /// have the same rule.
+    pub fn share_universe(patterns: &mut [HashLife]) -> Result<(), UniverseError> {
+        let Some((first, rest)) = patterns.split_first_mut() else {
+            return Ok(());
+        };
+        if let Some(other) = rest.iter().find(|other| other.rule() != first.rule()) {
+            return Err(UniverseError::RuleMismatch {
+                first: Box::new(first.rule().clone()),
+                other: Box::new(other.rule().clone()),
+            });
+        }
+        for other in rest.iter_mut() {
+            if other.universe.ptr_eq(&first.universe) {
+                continue;
+            }
+            let moved = {
+                let (mut universe, from) = first.universe.lock_with(&other.universe);
+                universe.import_all(&from, other.roots())
+            };
+            other.relocate(&moved);
+            other.universe = first.universe.clone();
+        }
+        Ok(())
+    }
+
+    /// Another pattern's root in this one's universe, which is just its root
+    /// if they share it.
+    pub(crate) fn import_root(&mut self, other: &HashLife) -> TreeRef {
+        if self.universe.ptr_eq(&other.universe) {
+            return other.root;
+        }
+        let (mut universe, from) = self.universe.lock_with(&other.universe);
+        universe.import(&from, other.root)
+    }
+
+    /// The nodes in use by the pattern and anything it's tracking.
+    fn roots(&self) -> Vec<TreeRef> {
+        let mut roots = vec![self.root];
+        roots.extend(self.pins.values().map(|pinned| pinned.root));
+        roots.extend(self.activity.iter().map(|activity| activity.root()));
+        roots.extend(self.envelope.iter().map(|envelope| envelope.root));
+        roots
+    }
+
+    fn relocate(&mut self, moved: &FxHashMap<TreeRef, TreeRef>) {
+        self.root = moved[&self.root];
+        for pinned in self.pins.values_mut() {
+            pinned.root = moved[&pinned.root];
+        }
+        if let Some(activity) = &mut self.activity {
+            activity.relocate(moved);
+        }
+        if let Some(envelope) = &mut self.envelope {
+            envelope.relocate(moved);
+        }
+    }
+
+    pub fn universe_config(&self) -> UniverseConfig {
+        self.universe.lock().config()
+    }
+
+    /// Limits how much memory stepping uses, which is checked after each
+    /// step.
+    pub fn set_universe_config(&mut self, config: UniverseConfig) {
+        self.universe.lock().set_config(config);
+        self.enforce_limits();
+    }
+
+    /// Forgets cached steps and frees nodes until the universe is within its
+    /// limits, or as close as it can get. Cached steps go before nodes, then
+    /// the nodes only they used.
+    pub(crate) fn enforce_limits(&mut self) {
+        let config = self.universe.lock().config();
+        if let Some(max) = config.max_cache_entries {
+            self.universe.lock().evict_cache(max);
+        }
+        let Some(max) = config.max_nodes else {
+            return;
+        };
+        if self.node_count() <= max {
+            return;
+        }
+        self.collect_garbage();
+        // Leave room to grow, so as not to collect again straight away.
+        while self.node_count() > max / 2 && self.universe.lock().cache_len() > 0 {
+            let keep = self.universe.lock().cache_len() / 2;
+            self.universe.lock().evict_cache(keep);
+            self.collect_garbage();
+        }
+    }
+
+    /// How big the universe is and how well stepping is being cached, to
+    /// help pick step sizes and limits.
+    pub fn stats(&self) -> UniverseStats {
+        self.universe.lock().stats()
+    }
+
+    /// How many nodes the universe holds, including any no longer in use.
+    pub fn node_count(&self) -> usize {
+        self.universe.lock().node_count()
+    }
+
+    /// Remembers the pattern as it is now, to `restore` later. Copies of the
+    /// pattern made after can restore it too.
+    pub fn snapshot(&mut self) -> Snapshot {
+        let snapshot = Snapshot(SNAPSHOTS.fetch_add(1, Ordering::Relaxed));
+        let pinned = Pinned {
+            root: self.root,
+            depth: self.depth,
+            inverted: self.inverted,
+            topology: self.topology,
+            generation: self.generation,
+        };
+        self.pins.insert(snapshot.clone(), pinned);
+        snapshot
+    }
+
+    /// Whether the pattern is as it was at a snapshot, which must be of it.
+    pub(crate) fn is_at(&self, snapshot: &Snapshot) -> bool {
+        let pinned = &self.pins[snapshot];
+        (
+            pinned.root,
+            pinned.depth,
+            pinned.inverted,
+            pinned.topology,
+            pinned.generation,
+        ) == (
+            self.root,
+            self.depth,
+            self.inverted,
+            self.topology,
+            self.generation,
+        )
+    }
+
+    pub(crate) fn generation_of(&self, snapshot: &Snapshot) -> u64 {
+        self.pins[snapshot].generation
+    }
+
+    /// Lets garbage collection free a snapshot's state.
+    pub fn release(&mut self, snapshot: &Snapshot) {
+        self.pins.remove(snapshot);
+    }
+
+    /// Goes back to a snapshot's state, which can be restored again. Ages are
+    /// reset, as if the cells had been set by hand.
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), UniverseError> {
+        let Some(pinned) = self.pins.get(snapshot) else {
+            return Err(UniverseError::UnknownSnapshot(snapshot.clone()));
+        };
+        self.root = pinned.root;
+        self.depth = pinned.depth;
+        self.inverted = pinned.inverted;
+        self.topology = pinned.topology;
+        self.generation = pinned.generation;
+        if self.is_tracking_ages() {
+            self.track_ages(true);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,188 @@
+use std::collections::VecDeque;
+
+use crate::{HashLife, gc::Snapshot};
+
+/// When to keep states of the pattern to undo to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HistoryConfig {
+    /// How many states to keep to undo to, forgetting the oldest first.
+    pub max_depth: usize,
+    /// Whether to keep the state before each batch of edits. Edits made
+    /// between checkpoints are undone together.
+    pub edits: bool,
+    /// How many generations of stepping to undo at a time, if stepping is
+    /// to be undone at all.
+    pub generations: Option<u64>,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            max_depth: 100,
+            edits: true,
+            generations: None,
+        }
+    }
+}
+
+/// The states of a pattern to undo and redo to, kept as snapshots.
+#[derive(Clone, Debug)]
+pub struct History {
+    config: HistoryConfig,
+    /// Oldest first.
+    undo: VecDeque<Snapshot>,
+    /// Next to redo last.
+    redo: Vec<Snapshot>,
+    /// The state at the last checkpoint, which undoing goes back past.
+    current: Snapshot,
+    /// The state after the last step, if it wasn't kept, in case it's edited.
+    stepped: Option<Snapshot>,
+}
+
+impl History {
+    pub fn config(&self) -> HistoryConfig {
+        self.config
+    }
+
+    /// How many states there are to undo to.
+    pub fn undo_len(&self) -> usize {
+        self.undo.len()
+    }
+
+    /// How many states there are to redo to.
+    pub fn redo_len(&self) -> usize {
+        self.redo.len()
+    }
+}
+
+impl HashLife {
+    /// Starts or stops keeping states to `undo` and `redo` to. Starting
+    /// forgets any states kept before.
+    pub fn track_history(&mut self, config: Option<HistoryConfig>) {
+        if let Some(history) = self.history.take() {
+            let snapshots = history.undo.iter().chain(&history.redo);
+            for snapshot in snapshots.chain(&history.stepped).chain([&history.current]) {
+                self.release(snapshot);
+            }
+        }
+        self.history = config.map(|config| History {
+            config,
+            undo: VecDeque::new(),
+            redo: vec![],
+            current: self.snapshot(),
+            stepped: None,
+        });
+    }
+
+    pub fn history(&self) -> Option<&History> {
+        self.history.as_ref()
+    }
+
+    /// Keeps the pattern as it is now to undo to, if it's changed since the
+    /// last checkpoint, and forgets what could be redone. Editors call this
+    /// after each change the user makes. If edits are kept, stepping calls it
+    /// for changes made since the last step.
+    pub fn checkpoint(&mut self) {
+        let Some(history) = &mut self.history else {
+            return;
+        };
+        // Edits since the last step go back to the state after it.
+        if let Some(stepped) = history.stepped.take() {
+            if self.is_at(&stepped) {
+                self.history.as_mut().unwrap().stepped = Some(stepped);
+            } else {
+                self.keep(stepped);
+            }
+        }
+        let history = self.history.as_ref().unwrap();
+        if !self.is_at(&history.current) {
+            let snapshot = self.snapshot();
+            self.keep(snapshot);
+        }
+    }
+
+    /// Goes back to the state before the last checkpoint, returning whether
+    /// there was one. Changes since the last checkpoint are kept first, to
+    /// redo.
+    pub fn undo(&mut self) -> bool {
+        self.checkpoint();
+        let Some(history) = &mut self.history else {
+            return false;
+        };
+        let Some(before) = history.undo.pop_back() else {
+            return false;
+        };
+        let after = std::mem::replace(&mut history.current, before.clone());
+        history.redo.push(after);
+        self.restore(&before).unwrap();
+        true
+    }
+
+    /// Goes forward to the state last undone, returning whether there was one.
+    /// Changes made since undoing it forget it instead.
+    pub fn redo(&mut self) -> bool {
+        self.checkpoint();
+        let Some(history) = &mut self.history else {
+            return false;
+        };
+        let Some(after) = history.redo.pop() else {
+            return false;
+        };
+        let before = std::mem::replace(&mut history.current, after.clone());
+        history.undo.push_back(before);
+        self.restore(&after).unwrap();
+        true
+    }
+
+    /// Keeps the edits made since the last step, before the next one.
+    pub(crate) fn checkpoint_edits(&mut self) {
+        let Some(history) = &self.history else {
+            return;
+        };
+        let unedited = history
+            .stepped
+            .as_ref()
+            .is_some_and(|stepped| self.is_at(stepped));
+        if history.config.edits && !unedited {
+            self.checkpoint();
+        }
+    }
+
+    /// Keeps the state after a step if enough generations have passed since
+    /// the last checkpoint, or otherwise in case it's edited.
+    pub(crate) fn update_history(&mut self) {
+        let Some(history) = &mut self.history else {
+            return;
+        };
+        if let Some(stepped) = history.stepped.take() {
+            self.release(&stepped);
+        }
+        let history = self.history.as_ref().unwrap();
+        let config = history.config;
+        let generations = self
+            .generation
+            .abs_diff(self.generation_of(&history.current));
+        if config.generations.is_some_and(|every| generations >= every) {
+            self.checkpoint();
+        } else if config.edits {
+            let stepped = self.snapshot();
+            self.history.as_mut().unwrap().stepped = Some(stepped);
+        }
+    }
+
+    /// Makes a snapshot the latest state to undo to, forgetting the oldest
+    /// ones past the limit and everything that could be redone.
+    fn keep(&mut self, snapshot: Snapshot) {
+        let history = self.history.as_mut().unwrap();
+        let before = std::mem::replace(&mut history.current, snapshot);
+        history.undo.push_back(before);
+        let mut forgotten = std::mem::take(&mut history.redo);
+        forgotten.extend(history.stepped.take());
+        while history.undo.len() > history.config.max_depth {
+            forgotten.extend(history.undo.pop_front());
+        }
+        for snapshot in forgotten {
+            self.release(&snapshot);
+        }
+    }
+}
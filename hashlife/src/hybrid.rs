@@ -0,0 +1,197 @@
+use crate::{BitLife, HashLife, LifeEngine, Rule, Topology};
+
+/// How many generations hashlife steps before its hit rate is first looked
+/// at, doubling each time it's good enough.
+const FIRST_CHUNK: u64 = 16;
+
+/// When `Hybrid` steps densely rather than with hashlife.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HybridConfig {
+    /// The fraction of nodes hashlife must find already stepped to keep
+    /// using it.
+    pub min_hit_rate: f64,
+    /// How many generations to step densely before trying hashlife again.
+    pub dense_generations: u64,
+    /// The most cells that dense stepping can cover, in tiles of 64x64.
+    /// Patterns bigger than this stay with hashlife, however chaotic.
+    pub max_dense_cells: usize,
+}
+
+impl Default for HybridConfig {
+    fn default() -> Self {
+        Self {
+            min_hit_rate: 0.5,
+            dense_generations: 256,
+            max_dense_cells: 1 << 22,
+        }
+    }
+}
+
+/// An engine that steps with hashlife while it's finding the steps it needs
+/// cached, and otherwise with `BitLife`, which is faster for chaotic soups. It tries hashlife again every so often, in case
+/// the pattern has settled down. Only B3/S23 on a plane is stepped densely.
+#[derive(Clone, Debug)]
+pub struct Hybrid {
+    /// The pattern while it's stepped with hashlife, and its universe while
+    /// it isn't.
+    hl: HashLife,
+    dense: Option<BitLife>,
+    config: HybridConfig,
+    /// How many generations to step with hashlife before checking its hit
+    /// rate again.
+    chunk: u64,
+    /// How many generations are left to step densely.
+    dense_left: u64,
+}
+
+impl Hybrid {
+    pub fn new(hl: HashLife) -> Self {
+        Self::with_config(hl, HybridConfig::default())
+    }
+
+    pub fn with_config(mut hl: HashLife, config: HybridConfig) -> Self {
+        // Dense steps happen outside the tree, so they couldn't be undone.
+        hl.track_history(None);
+        hl.track_rewind(0, 0);
+        Self {
+            hl,
+            dense: None,
+            config,
+            chunk: FIRST_CHUNK,
+            dense_left: 0,
+        }
+    }
+
+    pub fn config(&self) -> HybridConfig {
+        self.config
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.hl.generation()
+    }
+
+    /// Whether the pattern is being stepped densely at the moment.
+    pub fn is_dense(&self) -> bool {
+        self.dense.is_some()
+    }
+
+    /// The pattern as it is now.
+    pub fn pattern(&self) -> HashLife {
+        let mut hybrid = self.clone();
+        hybrid.make_tree();
+        hybrid.hl
+    }
+
+    /// Steps with hashlife for a chunk of the `steps`, then steps densely
+    /// from then on if it found too few of the nodes it needed cached.
+    /// Returns how many generations were stepped.
+    fn step_tree(&mut self, steps: u64) -> u64 {
+        let steps = steps.min(self.chunk);
+        let before = self.hl.stats();
+        self.hl.step_by(steps);
+        let after = self.hl.stats();
+        let hits = after.cache_hits - before.cache_hits;
+        let misses = after.cache_misses - before.cache_misses;
+        let hit_rate = hits as f64 / (hits + misses).max(1) as f64;
+        if hit_rate >= self.config.min_hit_rate {
+            self.chunk = self.chunk.saturating_mul(2);
+        } else {
+            self.chunk = FIRST_CHUNK;
+            self.make_dense();
+        }
+        steps
+    }
+
+    /// Steps densely for up to `steps` generations, going back to hashlife
+    /// when it's time to try it again or the pattern grows too big. Returns
+    /// how many generations were stepped.
+    fn step_dense(&mut self, steps: u64) -> u64 {
+        let steps = steps.min(self.dense_left);
+        let max_tiles = self.config.max_dense_cells / (64 * 64);
+        let dense = self.dense.as_mut().unwrap();
+        for stepped in 0..steps {
+            if dense.tile_count() > max_tiles {
+                self.dense_left = 0;
+                self.make_tree();
+                return stepped;
+            }
+            dense.step();
+            self.hl.generation += 1;
+        }
+        self.dense_left -= steps;
+        if self.dense_left == 0 {
+            self.make_tree();
+        }
+        steps
+    }
+
+    /// Moves the pattern into a `BitLife`, if it can be stepped there.
+    fn make_dense(&mut self) {
+        let plain_life = *self.hl.rule() == Rule::life()
+            && self.hl.topology() == Topology::Plane
+            && !self.hl.is_inverted();
+        let Some((ys, xs)) = self.hl.bounding_box() else {
+            return;
+        };
+        if !plain_life || ys.len().saturating_mul(xs.len()) > self.config.max_dense_cells {
+            return;
+        }
+        self.dense = Some(self.hl.iter_cells().collect());
+        self.dense_left = self.config.dense_generations;
+    }
+
+    /// Moves the pattern back into the tree, keeping the universe and the
+    /// steps cached in it.
+    fn make_tree(&mut self) {
+        let Some(dense) = self.dense.take() else {
+            return;
+        };
+        self.hl.crop(0..0, 0..0);
+        self.hl.set_cells(dense.iter_cells());
+    }
+}
+
+impl LifeEngine for Hybrid {
+    fn set(&mut self, p: (isize, isize), alive: bool) {
+        match &mut self.dense {
+            Some(dense) => dense.set(p, alive),
+            None => self.hl.set(p, alive),
+        }
+    }
+
+    fn get(&self, p: (isize, isize)) -> bool {
+        match &self.dense {
+            Some(dense) => dense.get(p),
+            None => self.hl.get(p),
+        }
+    }
+
+    fn step_by(&mut self, mut steps: u64) {
+        while steps > 0 {
+            let stepped = match self.dense {
+                Some(_) => self.step_dense(steps),
+                None => self.step_tree(steps),
+            };
+            steps -= stepped;
+        }
+    }
+
+    fn population(&self) -> u64 {
+        match &self.dense {
+            Some(dense) => dense.population(),
+            None => self.hl.population(),
+        }
+    }
+
+    fn iter_cells(&self) -> Box<dyn Iterator<Item = (isize, isize)> + '_> {
+        match &self.dense {
+            Some(dense) => dense.iter_cells(),
+            None => Box::new(self.hl.iter_cells()),
+        }
+    }
+
+    /// Including the nodes kept from before stepping densely.
+    fn node_count(&self) -> Option<usize> {
+        Some(self.hl.node_count())
+    }
+}
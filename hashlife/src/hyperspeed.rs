@@ -0,0 +1,74 @@
+use crate::HashLife;
+
+/// Picks step sizes as it goes, doubling them while the pattern changes
+/// steadily and halving them when something happens, so that long runs get
+/// fast without missing the interesting parts.
+#[derive(Clone, Debug)]
+pub struct Hyperspeed {
+    /// How many steady steps to take before doubling the step size.
+    pub patience: usize,
+    /// How far the population can stray from the trend of the last steps, as
+    /// a fraction of it, before the step size is halved.
+    pub tolerance: f64,
+    pub max_log2_steps: usize,
+    log2_steps: usize,
+    /// The populations after the steps taken at the current size, newest
+    /// last.
+    populations: Vec<u64>,
+    steady: usize,
+}
+
+impl Default for Hyperspeed {
+    fn default() -> Self {
+        Self {
+            patience: 4,
+            tolerance: 0.5,
+            max_log2_steps: 64,
+            log2_steps: 0,
+            populations: vec![],
+            steady: 0,
+        }
+    }
+}
+
+impl Hyperspeed {
+    /// The size of the next step.
+    pub fn log2_steps(&self) -> usize {
+        self.log2_steps
+    }
+
+    /// Takes a step and picks the size of the next one.
+    pub fn step(&mut self, hl: &mut HashLife) {
+        hl.step(self.log2_steps);
+        self.observe(hl);
+    }
+
+    /// Picks the size of the next step from the pattern after a step of the
+    /// current size, for when stepping some other way.
+    pub fn observe(&mut self, hl: &HashLife) {
+        let population = hl.population();
+        if let [.., before, last] = self.populations[..] {
+            // Carry on in a straight line from the last two steps.
+            let expected = 2. * last as f64 - before as f64;
+            let error = (population as f64 - expected).abs();
+            if error > self.tolerance * last.max(1) as f64 {
+                self.resize(self.log2_steps.saturating_sub(1));
+                return;
+            }
+            self.steady += 1;
+        }
+        self.populations.push(population);
+        if self.populations.len() > 2 {
+            self.populations.remove(0);
+        }
+        if self.steady >= self.patience && self.log2_steps < self.max_log2_steps {
+            self.resize(self.log2_steps + 1);
+        }
+    }
+
+    fn resize(&mut self, log2_steps: usize) {
+        self.log2_steps = log2_steps;
+        self.populations.clear();
+        self.steady = 0;
+    }
+}
@@ -0,0 +1,84 @@
+//! Reading PNG and BMP images as patterns, dark pixels alive, for seeding
+//! the universe with logos and screenshots.
+
+mod bmp;
+mod inflate;
+mod png;
+
+use crate::HashLife;
+
+/// The most pixels an image may have, so a small file claiming to be huge
+/// can't take all the memory.
+const MAX_PIXELS: usize = 1 << 28;
+
+/// An image's pixels as luminances, row by row from the top, from 0 for black
+/// to 1 for white.
+struct Image {
+    height: usize,
+    width: usize,
+    luminances: Vec<f32>,
+}
+
+impl Image {
+    fn new(height: usize, width: usize) -> Result<Self, String> {
+        if height
+            .checked_mul(width)
+            .is_none_or(|pixels| pixels > MAX_PIXELS)
+        {
+            return Err(format!("Image too large at {width}x{height}"));
+        }
+        Ok(Self {
+            height,
+            width,
+            luminances: vec![1.; height * width],
+        })
+    }
+
+    /// Sets a pixel from its sRGB components, from 0 to 1, over white as far
+    /// as `alpha` lets it show.
+    fn set(&mut self, y: usize, x: usize, [r, g, b]: [f32; 3], alpha: f32) {
+        let luma = 0.299 * r + 0.587 * g + 0.114 * b;
+        self.luminances[y * self.width + x] = luma * alpha + (1. - alpha);
+    }
+}
+
+impl HashLife {
+    /// Cells for the pixels of a PNG or BMP image whose luminance, from 0 for
+    /// black to 1 for white, is below `threshold`, with the top left pixel at
+    /// (0, 0). Transparent pixels count as white.
+    pub fn from_image(bytes: &[u8], threshold: f32) -> Result<HashLife, String> {
+        Self::from_image_scaled(bytes, threshold, 1)
+    }
+
+    /// Like `from_image`, with a cell to each `scale` by `scale` block of
+    /// pixels, alive if their mean luminance is below `threshold`.
+    pub fn from_image_scaled(
+        bytes: &[u8],
+        threshold: f32,
+        scale: usize,
+    ) -> Result<HashLife, String> {
+        if scale == 0 {
+            return Err("Can't scale an image by 0".to_string());
+        }
+        let image = if bytes.starts_with(png::SIGNATURE) {
+            png::decode(bytes)?
+        } else if bytes.starts_with(b"BM") {
+            bmp::decode(bytes)?
+        } else {
+            return Err("Not a PNG or BMP image".to_string());
+        };
+        let (height, width) = (image.height.div_ceil(scale), image.width.div_ceil(scale));
+        let mut sums = vec![(0., 0); height * width];
+        for (i, &luminance) in image.luminances.iter().enumerate() {
+            let (y, x) = (i / image.width / scale, i % image.width / scale);
+            let (sum, count) = &mut sums[y * width + x];
+            (*sum, *count) = (*sum + luminance, *count + 1);
+        }
+        Ok(sums
+            .iter()
+            .enumerate()
+            .filter(|&(_, &(sum, count))| sum / (count as f32) < threshold)
+            .map(|(i, _)| ((i / width) as isize, (i % width) as isize))
+            .collect())
+    }
+}
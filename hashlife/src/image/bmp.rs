@@ -0,0 +1,107 @@
+//! Decoding uncompressed BMPs, palettes and bit fields included.
+
+use super::Image;
+
+/// No compression, and colours given by bit masks.
+const RGB: u32 = 0;
+const BITFIELDS: u32 = 3;
+const ALPHA_BITFIELDS: u32 = 6;
+
+pub fn decode(bytes: &[u8]) -> Result<Image, String> {
+    let u16_at = |i: usize| {
+        bytes
+            .get(i..i + 2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+    };
+    let u32_at = |i: usize| {
+        let b = bytes.get(i..i + 4)?;
+        Some(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    };
+    let truncated = || "Truncated BMP".to_string();
+    let pixels_start = u32_at(10).ok_or_else(truncated)? as usize;
+    let header_len = u32_at(14).ok_or_else(truncated)? as usize;
+    if header_len < 40 {
+        return Err("Old BMP headers aren't supported".to_string());
+    }
+    let width = u32_at(18).ok_or_else(truncated)? as i32;
+    let height = u32_at(22).ok_or_else(truncated)? as i32;
+    let depth = u16_at(28).ok_or_else(truncated)?;
+    let compression = u32_at(30).ok_or_else(truncated)?;
+    // Rows go from the bottom up, unless the height is negative.
+    let bottom_up = height > 0;
+    let (height, width) = (
+        height.unsigned_abs() as usize,
+        width.unsigned_abs() as usize,
+    );
+    let mut image = Image::new(height, width)?;
+
+    let masks = match (compression, depth) {
+        (RGB, 16) => [0x7c00, 0x03e0, 0x001f, 0],
+        (RGB, 24 | 32) => [0xff_0000, 0x00_ff00, 0x00_00ff, 0],
+        (RGB, _) => [0; 4],
+        (BITFIELDS | ALPHA_BITFIELDS, 16 | 32) => {
+            // Right after the 40 bytes of the oldest header, in the header
+            // if it's longer.
+            let mask = |i: usize| u32_at(54 + 4 * i).ok_or_else(truncated);
+            let alpha = match header_len >= 56 || compression == ALPHA_BITFIELDS {
+                true => mask(3)?,
+                false => 0,
+            };
+            [mask(0)?, mask(1)?, mask(2)?, alpha]
+        }
+        _ => return Err(format!("BMP compression {compression} isn't supported")),
+    };
+    let palette = match depth {
+        1 | 4 | 8 => {
+            let colours = match u32_at(46).ok_or_else(truncated)? {
+                0 => 1 << depth,
+                colours => colours as usize,
+            };
+            let start = 14 + header_len;
+            let palette = bytes.get(start..start + 4 * colours.min(256));
+            palette.ok_or_else(truncated)?
+        }
+        16 | 24 | 32 => &[],
+        _ => return Err(format!("BMP bit depth {depth} isn't supported")),
+    };
+
+    let row_len = (width * depth as usize).div_ceil(32) * 4;
+    for y in 0..height {
+        let start = pixels_start + y * row_len;
+        let row = bytes.get(start..start + row_len).ok_or_else(truncated)?;
+        let y = if bottom_up { height - 1 - y } else { y };
+        for x in 0..width {
+            let (rgb, alpha) = match depth {
+                1 | 4 | 8 => {
+                    let bit = x * depth as usize;
+                    let shift = 8 - depth as usize - bit % 8;
+                    let i = (row[bit / 8] >> shift) as usize & ((1 << depth) - 1);
+                    let bgr = palette
+                        .get(4 * i..4 * i + 3)
+                        .ok_or(format!("BMP colour {i} not in the palette"))?;
+                    let rgb = [bgr[2], bgr[1], bgr[0]].map(|c| c as f32 / 255.);
+                    (rgb, 1.)
+                }
+                _ => {
+                    let bytes = depth as usize / 8;
+                    let mut value = [0; 4];
+                    value[..bytes].copy_from_slice(&row[x * bytes..(x + 1) * bytes]);
+                    let value = u32::from_le_bytes(value);
+                    let [r, g, b, a] = masks.map(|mask| field(value, mask));
+                    ([r, g, b], if masks[3] == 0 { 1. } else { a })
+                }
+            };
+            image.set(y, x, rgb, alpha);
+        }
+    }
+    Ok(image)
+}
+
+/// The bits of `value` under `mask`, from 0 to 1.
+fn field(value: u32, mask: u32) -> f32 {
+    if mask == 0 {
+        return 0.;
+    }
+    let shift = mask.trailing_zeros();
+    ((value & mask) >> shift) as f32 / (mask >> shift) as f32
+}
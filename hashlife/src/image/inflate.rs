@@ -0,0 +1,221 @@
+//! Decompressing zlib streams, as PNGs hold their pixels in.
+
+/// The base lengths of the length symbols from 257, and how many extra bits
+/// each adds.
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+/// The same for distances.
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// The order the lengths of the code length code come in.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+const MAX_BITS: usize = 15;
+
+/// Decompresses a zlib stream, failing if it would come to more than `max`
+/// bytes. The checksum isn't checked.
+pub fn zlib(data: &[u8], max: usize) -> Result<Vec<u8>, String> {
+    let [cmf, flags, ..] = *data else {
+        return Err("Truncated zlib stream".to_string());
+    };
+    if cmf & 0x0f != 8 || !(cmf as u16 * 256 + flags as u16).is_multiple_of(31) || flags & 0x20 != 0
+    {
+        return Err("Bad zlib header".to_string());
+    }
+    inflate(&data[2..], max)
+}
+
+/// Decompresses raw deflate data.
+fn inflate(data: &[u8], max: usize) -> Result<Vec<u8>, String> {
+    let mut bits = Bits { data, position: 0 };
+    let mut out = vec![];
+    loop {
+        let last = bits.take(1)? == 1;
+        match bits.take(2)? {
+            0 => {
+                bits.position = bits.position.div_ceil(8) * 8;
+                let len = bits.take(16)? as usize;
+                if bits.take(16)? as usize != !len & 0xffff {
+                    return Err("Bad stored block length".to_string());
+                }
+                let start = bits.position / 8;
+                let block = data.get(start..start + len).ok_or(TRUNCATED)?;
+                if out.len() + len > max {
+                    return Err(TOO_LONG.to_string());
+                }
+                out.extend(block);
+                bits.position += 8 * len;
+            }
+            1 => {
+                let mut lengths = [8; 288];
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                let literals = Huffman::new(&lengths);
+                let distances = Huffman::new(&[5; 30]);
+                block(&mut bits, &literals, &distances, &mut out, max)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(&mut bits)?;
+                block(&mut bits, &literals, &distances, &mut out, max)?;
+            }
+            _ => return Err("Bad deflate block type".to_string()),
+        }
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+const TRUNCATED: &str = "Truncated deflate stream";
+const TOO_LONG: &str = "Deflate stream decompresses to too much";
+
+/// Reads the literal and length, and distance, codes of a dynamic block.
+fn dynamic_codes(bits: &mut Bits) -> Result<(Huffman, Huffman), String> {
+    let literals = bits.take(5)? as usize + 257;
+    let distances = bits.take(5)? as usize + 1;
+    let code_lengths = bits.take(4)? as usize + 4;
+    let mut lengths = [0; 19];
+    for &i in &CODE_LENGTH_ORDER[..code_lengths] {
+        lengths[i] = bits.take(3)? as u8;
+    }
+    let code = Huffman::new(&lengths);
+    let mut lengths = Vec::with_capacity(literals + distances);
+    while lengths.len() < literals + distances {
+        let (length, repeat) = match code.decode(bits)? {
+            symbol @ 0..16 => (symbol as u8, 1),
+            16 => {
+                let previous = lengths.last().ok_or("Nothing to repeat")?;
+                (*previous, 3 + bits.take(2)?)
+            }
+            17 => (0, 3 + bits.take(3)?),
+            _ => (0, 11 + bits.take(7)?),
+        };
+        lengths.extend((0..repeat).map(|_| length));
+    }
+    if lengths.len() > literals + distances {
+        return Err("Code lengths run past the end".to_string());
+    }
+    let (literal_lengths, distance_lengths) = lengths.split_at(literals);
+    Ok((
+        Huffman::new(literal_lengths),
+        Huffman::new(distance_lengths),
+    ))
+}
+
+/// Decodes a compressed block's literals and back references.
+fn block(
+    bits: &mut Bits,
+    literals: &Huffman,
+    distances: &Huffman,
+    out: &mut Vec<u8>,
+    max: usize,
+) -> Result<(), String> {
+    loop {
+        let symbol = literals.decode(bits)? as usize;
+        match symbol {
+            0..256 => {
+                if out.len() == max {
+                    return Err(TOO_LONG.to_string());
+                }
+                out.push(symbol as u8);
+            }
+            256 => return Ok(()),
+            _ => {
+                let i = symbol - 257;
+                let base = *LENGTH_BASE.get(i).ok_or("Bad length symbol")?;
+                let len = base as usize + bits.take(LENGTH_EXTRA[i])? as usize;
+                let i = distances.decode(bits)? as usize;
+                let base = *DISTANCE_BASE.get(i).ok_or("Bad distance symbol")?;
+                let distance = base as usize + bits.take(DISTANCE_EXTRA[i])? as usize;
+                if distance > out.len() {
+                    return Err("Distance too far back".to_string());
+                }
+                if out.len() + len > max {
+                    return Err(TOO_LONG.to_string());
+                }
+                // The copy may overlap what it adds, so a byte at a time.
+                let start = out.len() - distance;
+                for i in start..start + len {
+                    out.push(out[i]);
+                }
+            }
+        }
+    }
+}
+
+/// Reads bits, least significant first.
+struct Bits<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl Bits<'_> {
+    fn take(&mut self, n: u8) -> Result<u32, String> {
+        let mut value = 0;
+        for i in 0..n {
+            let byte = self.data.get(self.position / 8).ok_or(TRUNCATED)?;
+            value |= ((byte >> (self.position % 8)) as u32 & 1) << i;
+            self.position += 1;
+        }
+        Ok(value)
+    }
+}
+
+/// A canonical Huffman code, as how many codes there are of each length and
+/// the symbols in the order of their codes.
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    /// The code with the given length for each symbol, where 0 means the
+    /// symbol is unused.
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0; MAX_BITS + 1];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0; MAX_BITS + 2];
+        for length in 1..=MAX_BITS {
+            offsets[length + 1] = offsets[length] + counts[length] as usize;
+        }
+        let mut symbols = vec![0; offsets[MAX_BITS + 1]];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize]] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+        Self { counts, symbols }
+    }
+
+    /// Reads a code a bit at a time, the first code of each length being
+    /// the one after the last of the length before, doubled.
+    fn decode(&self, bits: &mut Bits) -> Result<u16, String> {
+        let (mut code, mut first, mut index) = (0, 0, 0);
+        for &count in &self.counts[1..] {
+            code |= bits.take(1)? as usize;
+            if code < first + count as usize {
+                return Ok(self.symbols[index + code - first]);
+            }
+            index += count as usize;
+            first = (first + count as usize) << 1;
+            code <<= 1;
+        }
+        Err("Bad Huffman code".to_string())
+    }
+}
@@ -0,0 +1,255 @@
+//! Decoding PNGs of any colour type, bit depth and interlacing.
+
+use super::{Image, inflate};
+
+pub const SIGNATURE: &[u8; 8] = b"\x89PNG\r\n\x1a\n";
+
+/// Where each pass of Adam7 interlacing starts, and its steps down and
+/// across.
+const ADAM7: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (0, 4, 8, 8),
+    (4, 0, 8, 4),
+    (0, 2, 4, 4),
+    (2, 0, 4, 2),
+    (0, 1, 2, 2),
+    (1, 0, 2, 1),
+];
+
+struct Header {
+    height: usize,
+    width: usize,
+    depth: u8,
+    colour_type: u8,
+    interlaced: bool,
+}
+
+impl Header {
+    fn parse(data: &[u8]) -> Result<Self, String> {
+        let &[
+            w0,
+            w1,
+            w2,
+            w3,
+            h0,
+            h1,
+            h2,
+            h3,
+            depth,
+            colour_type,
+            0,
+            0,
+            interlace,
+        ] = data
+        else {
+            return Err("Bad PNG header".to_string());
+        };
+        let depths: &[u8] = match colour_type {
+            0 => &[1, 2, 4, 8, 16],
+            3 => &[1, 2, 4, 8],
+            2 | 4 | 6 => &[8, 16],
+            _ => return Err(format!("Bad PNG colour type {colour_type}")),
+        };
+        if !depths.contains(&depth) || interlace > 1 {
+            return Err("Bad PNG header".to_string());
+        }
+        Ok(Self {
+            height: u32::from_be_bytes([h0, h1, h2, h3]) as usize,
+            width: u32::from_be_bytes([w0, w1, w2, w3]) as usize,
+            depth,
+            colour_type,
+            interlaced: interlace == 1,
+        })
+    }
+
+    fn channels(&self) -> usize {
+        match self.colour_type {
+            2 => 3,
+            4 => 2,
+            6 => 4,
+            _ => 1,
+        }
+    }
+
+    /// The bytes in a row `width` pixels wide, not counting its filter.
+    fn row_len(&self, width: usize) -> usize {
+        (width * self.channels() * self.depth as usize).div_ceil(8)
+    }
+
+    /// Each pass's first pixel, steps and size.
+    fn passes(&self) -> Vec<(usize, usize, usize, usize, usize, usize)> {
+        let passes: &[_] = if self.interlaced {
+            &ADAM7
+        } else {
+            &[(0, 0, 1, 1)]
+        };
+        passes
+            .iter()
+            .map(|&(y0, x0, dy, dx)| {
+                let height = self.height.saturating_sub(y0).div_ceil(dy);
+                let width = self.width.saturating_sub(x0).div_ceil(dx);
+                (y0, x0, dy, dx, height, width)
+            })
+            .filter(|&(.., height, width)| height > 0 && width > 0)
+            .collect()
+    }
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Image, String> {
+    let mut header = None;
+    let mut palette: &[u8] = &[];
+    let mut transparency: &[u8] = &[];
+    let mut data = vec![];
+    let mut rest = &bytes[SIGNATURE.len()..];
+    loop {
+        let truncated = || "Truncated PNG".to_string();
+        let len = rest.get(..4).ok_or_else(truncated)?;
+        let len = u32::from_be_bytes(len.try_into().unwrap()) as usize;
+        let kind = rest.get(4..8).ok_or_else(truncated)?;
+        let chunk = rest.get(8..8 + len).ok_or_else(truncated)?;
+        match kind {
+            b"IHDR" => header = Some(Header::parse(chunk)?),
+            b"PLTE" => palette = chunk,
+            b"tRNS" => transparency = chunk,
+            b"IDAT" => data.extend(chunk),
+            b"IEND" => break,
+            _ => {}
+        }
+        // Skipping the checksum.
+        rest = rest.get(12 + len..).ok_or_else(truncated)?;
+    }
+    let header = header.ok_or("PNG without a header")?;
+    let mut image = Image::new(header.height, header.width)?;
+    let passes = header.passes();
+    let len = passes
+        .iter()
+        .map(|&(.., height, width)| height * (1 + header.row_len(width)))
+        .sum();
+    let data = inflate::zlib(&data, len)?;
+    if data.len() < len {
+        return Err("Truncated PNG image data".to_string());
+    }
+    let bytes_per_pixel = (header.channels() * header.depth as usize).div_ceil(8);
+    let mut rest = &data[..];
+    for (y0, x0, dy, dx, height, width) in passes {
+        let row_len = header.row_len(width);
+        let mut previous = vec![0; row_len];
+        for y in 0..height {
+            let (filter, row) = (rest[0], &rest[1..1 + row_len]);
+            rest = &rest[1 + row_len..];
+            let row = unfilter(filter, row, &previous, bytes_per_pixel)?;
+            for x in 0..width {
+                let (rgb, alpha) = pixel(&header, &row, x, palette, transparency)?;
+                image.set(y0 + y * dy, x0 + x * dx, rgb, alpha);
+            }
+            previous = row;
+        }
+    }
+    Ok(image)
+}
+
+/// Undoes the filter a row was encoded with, given the row above.
+fn unfilter(
+    filter: u8,
+    row: &[u8],
+    above: &[u8],
+    bytes_per_pixel: usize,
+) -> Result<Vec<u8>, String> {
+    let mut out: Vec<u8> = Vec::with_capacity(row.len());
+    for (i, (&byte, &up)) in row.iter().zip(above).enumerate() {
+        let left = if i >= bytes_per_pixel {
+            out[i - bytes_per_pixel]
+        } else {
+            0
+        };
+        let up_left = if i >= bytes_per_pixel {
+            above[i - bytes_per_pixel]
+        } else {
+            0
+        };
+        let predicted = match filter {
+            0 => 0,
+            1 => left,
+            2 => up,
+            3 => ((left as u16 + up as u16) / 2) as u8,
+            4 => paeth(left, up, up_left),
+            _ => return Err(format!("Bad PNG filter {filter}")),
+        };
+        out.push(byte.wrapping_add(predicted));
+    }
+    Ok(out)
+}
+
+/// Whichever of the neighbours is closest to left + up - up_left.
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate = left as i16 + up as i16 - up_left as i16;
+    let distance = |byte: u8| (estimate - byte as i16).abs();
+    if distance(left) <= distance(up) && distance(left) <= distance(up_left) {
+        left
+    } else if distance(up) <= distance(up_left) {
+        up
+    } else {
+        up_left
+    }
+}
+
+/// The `i`th sample of a row, and the largest value it could have.
+fn sample(row: &[u8], i: usize, depth: u8) -> (u16, f32) {
+    let value = match depth {
+        16 => u16::from_be_bytes([row[2 * i], row[2 * i + 1]]),
+        8 => row[i] as u16,
+        _ => {
+            let bit = i * depth as usize;
+            let shift = 8 - depth as usize - bit % 8;
+            (row[bit / 8] >> shift) as u16 & ((1 << depth) - 1)
+        }
+    };
+    (value, ((1u32 << depth) - 1) as f32)
+}
+
+/// The sRGB components, from 0 to 1, and alpha of pixel `x` of a row.
+fn pixel(
+    header: &Header,
+    row: &[u8],
+    x: usize,
+    palette: &[u8],
+    transparency: &[u8],
+) -> Result<([f32; 3], f32), String> {
+    let channels = header.channels();
+    let mut samples = [(0, 0.); 4];
+    for (c, slot) in samples[..channels].iter_mut().enumerate() {
+        *slot = sample(row, x * channels + c, header.depth);
+    }
+    let samples = &samples[..channels];
+    let value = |(value, max): (u16, f32)| value as f32 / max;
+    // A colour in the transparency chunk, as 16 bit samples.
+    let transparent = |colour: &[(u16, f32)]| {
+        transparency.len() == 2 * colour.len()
+            && colour
+                .iter()
+                .zip(transparency.chunks(2))
+                .all(|(&(value, _), t)| value == u16::from_be_bytes([t[0], t[1]]))
+    };
+    let opaque = |colour: &[(u16, f32)]| if transparent(colour) { 0. } else { 1. };
+    Ok(match header.colour_type {
+        0 => ([value(samples[0]); 3], opaque(samples)),
+        2 => (
+            [value(samples[0]), value(samples[1]), value(samples[2])],
+            opaque(samples),
+        ),
+        3 => {
+            let i = samples[0].0 as usize;
+            let rgb = palette
+                .get(3 * i..3 * i + 3)
+                .ok_or(format!("PNG colour {i} not in the palette"))?;
+            let alpha = transparency.get(i).copied().unwrap_or(255);
+            let rgb = [rgb[0], rgb[1], rgb[2]].map(|c| c as f32 / 255.);
+            (rgb, alpha as f32 / 255.)
+        }
+        4 => ([value(samples[0]); 3], value(samples[1])),
+        _ => (
+            [value(samples[0]), value(samples[1]), value(samples[2])],
+            value(samples[3]),
+        ),
+    })
+}
@@ -0,0 +1,96 @@
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use crate::{HashLife, p3::P3, universe::TreeRef};
+
+/// The cells of a pattern that aren't dead, in order of row and then
+/// column, found as they're needed.
+#[derive(Clone)]
+pub struct Cells<'a> {
+    life: &'a HashLife,
+    /// Nodes still to visit, the one with the first top left cell on top. A
+    /// node's top left cell comes before every other cell in it, so cells come
+    /// off in order.
+    pending: BinaryHeap<Pending>,
+    remaining: usize,
+}
+
+#[derive(Clone, Copy)]
+struct Pending {
+    top_left: (isize, isize),
+    tr: TreeRef,
+    p: P3,
+}
+
+impl<'a> Cells<'a> {
+    pub(crate) fn new(life: &'a HashLife) -> Self {
+        let p = P3::origin(life.depth);
+        Self {
+            life,
+            pending: BinaryHeap::from([Pending::new(life.root, p)]),
+            remaining: life.universe.lock().population(life.root),
+        }
+    }
+}
+
+impl Pending {
+    fn new(tr: TreeRef, p: P3) -> Self {
+        let (ys, xs) = p.bounds();
+        Self {
+            top_left: (ys.start, xs.start),
+            tr,
+            p,
+        }
+    }
+}
+
+impl Iterator for Cells<'_> {
+    type Item = (isize, isize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let universe = self.life.universe.lock();
+        while let Some(Pending { tr, p, .. }) = self.pending.pop() {
+            if universe.population(tr) == 0 {
+                continue;
+            }
+            match p.quadrants() {
+                Some(ps) => {
+                    let children = universe.subtree(tr).into_iter().zip(ps);
+                    self.pending
+                        .extend(children.map(|(tr, p)| Pending::new(tr, p)));
+                }
+                None => {
+                    self.remaining -= 1;
+                    return Some((p.y, p.x));
+                }
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl ExactSizeIterator for Cells<'_> {}
+
+impl PartialEq for Pending {
+    fn eq(&self, other: &Self) -> bool {
+        self.top_left == other.top_left
+    }
+}
+
+impl Eq for Pending {}
+
+impl PartialOrd for Pending {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Pending {
+    /// Reversed, so the heap gives the first node first.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.top_left.cmp(&self.top_left)
+    }
+}
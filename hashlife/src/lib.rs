@@ -1,108 +1,700 @@
+mod activity;
+mod ages;
+mod apgcode;
 mod basic_state;
+mod bitlife;
+mod builder;
+mod census;
+mod components;
+mod diff;
+mod edit;
+mod engine;
+mod envelope;
 mod eq;
+mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod find;
+pub mod formats;
+mod fx;
+mod gc;
+mod history;
+mod hybrid;
+mod hyperspeed;
+mod image;
+mod iter;
+mod metafy;
+mod ops;
 mod p3;
+pub mod patterns;
+mod period;
+mod raster;
 pub mod render;
+mod rewind;
+mod rule;
+mod search;
+mod snapshot;
+mod spacetime;
+mod stabilize;
+mod symmetry;
+mod text;
+mod timeline;
+mod topology;
 mod universe;
+#[cfg(feature = "wasm-bindgen")]
+pub mod wasm;
 
 #[cfg(test)]
 mod tests;
 
-use std::{fmt::Display, str::FromStr};
+use std::{
+    cell::Cell,
+    collections::BTreeMap,
+    fmt::Display,
+    ops::{ControlFlow, Range},
+    str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+use itertools::Itertools;
+
+pub use basic_state::BasicState;
+pub use bitlife::BitLife;
+pub use builder::PatternBuilder;
+pub use census::Census;
+pub use diff::Diff;
+pub use engine::LifeEngine;
+pub use error::{ParseError, RuleError, StepError, UniverseError};
+pub use gc::Snapshot;
+pub use history::{History, HistoryConfig};
+pub use hybrid::{Hybrid, HybridConfig};
+pub use hyperspeed::Hyperspeed;
+pub use iter::Cells;
+pub use period::Periodic;
+pub use raster::{BitGrid, DisplayRect, Glyphs};
+pub use rule::{Neighbourhood, Rule};
+pub use search::{Finding, Haul, Search};
+pub use spacetime::SpaceTime;
+pub use stabilize::{Limits, Stabilized};
+pub use symmetry::{Symmetry, SymmetryGroup};
+pub use timeline::{Branch, Timeline};
+pub use topology::Topology;
+pub use universe::{UniverseConfig, UniverseStats};
 
 use crate::{
-    basic_state::BasicState,
-    p3::P3,
-    universe::{TreeRef, Universe},
+    p3::{MAX_DEPTH, P3},
+    universe::{Shared, TreeRef, Universe},
 };
 
+/// How many times a step checks whether to stop before giving other patterns
+/// sharing its universe a turn.
+const POLLS_PER_TURN: u32 = 16;
+
 #[derive(Clone, Debug)]
 pub struct HashLife {
-    universe: Universe,
+    universe: Shared,
     depth: usize,
     root: TreeRef,
+    /// Whether the background is alive, in which case every cell is stored
+    /// inverted.
+    inverted: bool,
+    topology: Topology,
+    /// How many generations have been stepped since construction.
+    generation: u64,
+    /// When each cell was born, if that's being tracked.
+    ages: Option<ages::Ages>,
+    /// Which cells changed recently, if that's being tracked.
+    activity: Option<activity::Activity>,
+    /// Every cell alive since tracking started, if that's being tracked.
+    envelope: Option<envelope::Envelope>,
+    /// States kept through garbage collection to go back to.
+    pins: BTreeMap<gc::Snapshot, gc::Pinned>,
+    /// States to undo and redo to, if they're being kept.
+    history: Option<history::History>,
+    /// States to step back from, if they're being kept.
+    rewind: Option<rewind::Rewind>,
 }
 
 impl HashLife {
     pub fn new() -> Self {
         Self {
-            universe: Universe::new(),
+            universe: Shared::new(Universe::new()),
             root: TreeRef::EMPTY,
             depth: 0,
+            inverted: false,
+            topology: Topology::Plane,
+            generation: 0,
+            ages: None,
+            activity: None,
+            envelope: None,
+            pins: BTreeMap::new(),
+            history: None,
+            rewind: None,
         }
     }
 
+    pub fn with_rule(rule: Rule) -> Self {
+        Self {
+            universe: Shared::new(Universe::with_rule(rule)),
+            ..Self::new()
+        }
+    }
+
+    pub fn rule(&self) -> &Rule {
+        self.universe.rule()
+    }
+
+    pub fn set_rule(&mut self, rule: Rule) {
+        self.universe.set_rule(rule);
+    }
+
     pub fn step(&mut self, log2_steps: usize) {
-        let superspeed_depth = log2_steps + 2;
-        while self.depth < superspeed_depth - 1 {
+        if let Err(e) = self.step_or_fail(log2_steps, &|| false) {
+            panic!("{e}");
+        }
+    }
+
+    /// Like `step`, but fails if the generation would overflow, if the step
+    /// would outgrow the coordinates or the room for nodes, or if the universe
+    /// can't be kept within its `max_nodes` after stepping. Only the last is
+    /// found out after the step, which is kept rather than thrown away.
+    pub fn step_checked(&mut self, log2_steps: usize) -> Result<(), StepError> {
+        self.step_checked_unless(log2_steps, &|| false).map(|_| ())
+    }
+
+    /// Like `step_checked`, but giving up if `stop` returns true part way, as
+    /// `step_unless` does. Returns whether the step was taken.
+    pub fn step_checked_unless(
+        &mut self,
+        log2_steps: usize,
+        stop: &dyn Fn() -> bool,
+    ) -> Result<bool, StepError> {
+        let steps = 1u64.checked_shl(log2_steps as u32);
+        if steps.is_none_or(|steps| self.generation.checked_add(steps).is_none()) {
+            return Err(StepError::GenerationOverflow);
+        }
+        // Stepping a plane expands the tree to the step's depth and by two.
+        let superspeed_depth = log2_steps + self.universe.lock().base_depth();
+        if self.topology == Topology::Plane && self.depth.max(superspeed_depth) + 2 > MAX_DEPTH {
+            return Err(StepError::UniverseTooLarge);
+        }
+        if !self.step_or_fail(log2_steps, stop)? {
+            return Ok(false);
+        }
+        match self.universe_config().max_nodes {
+            Some(max) if self.node_count() > max => Err(StepError::OutOfNodes {
+                nodes: self.node_count(),
+                max,
+            }),
+            _ => Ok(true),
+        }
+    }
+
+    /// Takes a step unless `stop` returns true part way, in which case the
+    /// pattern is left as it was. Returns whether the step was taken. Like
+    /// `step_with`, the work towards a step that's cut short is kept, so
+    /// trying again with a deadline each time, say once a frame, gets there.
+    pub fn step_unless(&mut self, log2_steps: usize, stop: &dyn Fn() -> bool) -> bool {
+        self.step_or_fail(log2_steps, stop) == Ok(true)
+    }
+
+    /// Like `step_unless`, but failing rather than stepping if the universe
+    /// runs out of room for nodes.
+    fn step_or_fail(
+        &mut self,
+        log2_steps: usize,
+        stop: &dyn Fn() -> bool,
+    ) -> Result<bool, StepError> {
+        self.checkpoint_edits();
+        self.rewind_edits();
+        let before = (
+            self.root,
+            self.depth,
+            self.inverted,
+            self.topology,
+            self.generation,
+        );
+        self.universe.lock().take_full();
+        let stepped = match self.topology {
+            Topology::Plane => self.step_plane(log2_steps, stop),
+            Topology::Torus { w, h } => self.step_torus(log2_steps, w, h, stop),
+        };
+        let full = self.universe.lock().take_full();
+        if !stepped || full {
+            (
+                self.root,
+                self.depth,
+                self.inverted,
+                self.topology,
+                self.generation,
+            ) = before;
+            return if full {
+                Err(StepError::UniverseFull)
+            } else {
+                Ok(false)
+            };
+        }
+        self.update_ages();
+        self.update_activity();
+        self.update_envelope();
+        self.update_history();
+        self.update_rewind();
+        self.enforce_limits();
+        Ok(true)
+    }
+
+    fn step_plane(&mut self, log2_steps: usize, stop: &dyn Fn() -> bool) -> bool {
+        let superspeed_depth = log2_steps + self.universe.lock().base_depth();
+        // Leave room for the pattern to grow at the speed of light, which rules
+        // loaded from files can easily reach.
+        while self.depth < superspeed_depth {
             self.expand();
         }
         // We can only step if all the border nodes in the 4x4 square are empty.
-        let center = self.universe.reframe(self.root, P3::origin(2), 1);
-        if self.universe.population(center) != self.universe.population(self.root) {
+        let mut universe = self.universe.lock();
+        let center = universe.reframe(self.root, P3::origin(2), 1);
+        let fits = universe.population(center) == universe.population(self.root);
+        drop(universe);
+        if !fits {
             self.expand();
         }
         self.expand();
-        self.root = self.universe.step(self.root, self.depth, superspeed_depth);
+        let Some(next) = self.step_root(superspeed_depth, stop) else {
+            return false;
+        };
+        self.root = next;
+        self.inverted = self
+            .universe
+            .lock()
+            .background_after(self.inverted, 1 << log2_steps);
         self.depth -= 1;
+        self.trim();
+        let steps = 1u64.checked_shl(log2_steps as u32).unwrap_or(u64::MAX);
+        self.generation = self.generation.saturating_add(steps);
+        true
     }
 
-    pub fn set_bit(&mut self, (y, x): (isize, isize)) {
-        while !P3::new(y, x, self.depth).within_tree() {
-            self.expand();
+    /// Steps the root, which must have room around it to step, unless `stop`
+    /// returns true part way or the universe fills up. Other patterns sharing
+    /// the universe, say one being drawn while this one steps on another
+    /// thread, get a turn at it every so often.
+    fn step_root(&mut self, superspeed_depth: usize, stop: &dyn Fn() -> bool) -> Option<TreeRef> {
+        let shared = self.universe.is_shared();
+        loop {
+            let (stopped, polls) = (Cell::new(false), Cell::new(0));
+            let pause = || {
+                stopped.set(stop());
+                polls.set(polls.get() + 1);
+                stopped.get() || shared && polls.get() >= POLLS_PER_TURN
+            };
+            let mut universe = self.universe.lock();
+            let next = universe.step(
+                self.root,
+                self.depth,
+                superspeed_depth,
+                self.inverted,
+                &pause,
+            );
+            if next.is_some() || stopped.get() || universe.is_full() {
+                return next;
+            }
+            // The steps of the nodes done so far are cached, so picking up
+            // again from the root soon gets back to where it paused.
+            drop(universe);
+            std::thread::yield_now();
+        }
+    }
+
+    /// Steps forward exactly `steps` generations, one power of two at a time.
+    pub fn step_by(&mut self, steps: u64) {
+        for log2_steps in 0..u64::BITS as usize {
+            if steps >> log2_steps & 1 == 1 {
+                self.step(log2_steps);
+            }
         }
+    }
+
+    /// Like `step_by`, but calling `on_generation` after every `interval`
+    /// generations and at the end, say to show progress, with the chance to
+    /// stop early. Returns how many generations were stepped. Smaller
+    /// intervals mean smaller steps, so they make long runs slower.
+    pub fn step_by_observed(
+        &mut self,
+        steps: u64,
+        interval: u64,
+        mut on_generation: impl FnMut(&HashLife) -> ControlFlow<()>,
+    ) -> u64 {
+        let interval = interval.max(1);
+        let mut stepped = 0;
+        while stepped < steps {
+            let chunk = interval.min(steps - stepped);
+            self.step_by(chunk);
+            stepped += chunk;
+            if on_generation(self).is_break() {
+                break;
+            }
+        }
+        stepped
+    }
+
+    /// Like `step_by`, but giving up once `budget` has passed. See
+    /// `step_with`.
+    pub fn step_for(&mut self, steps: u64, budget: Duration) -> u64 {
+        let deadline = Instant::now() + budget;
+        self.step_until(steps, &|| Instant::now() >= deadline)
+    }
+
+    /// Like `step_by`, but giving up once `cancel` is set, say from another
+    /// thread. Big steps are checked on as they go, so this is quick even in
+    /// the middle of one. The step that was cut short is undone, though the
+    /// work towards it is kept and makes trying again faster. Returns how many
+    /// generations were stepped.
+    pub fn step_with(&mut self, steps: u64, cancel: &AtomicBool) -> u64 {
+        self.step_until(steps, &|| cancel.load(Ordering::Relaxed))
+    }
+
+    /// Like `step`, but giving up if `cancel` is set part way, leaving the
+    /// pattern as it was. Returns whether the step was taken.
+    pub fn try_step(&mut self, log2_steps: usize, cancel: &AtomicBool) -> bool {
+        self.step_unless(log2_steps, &|| cancel.load(Ordering::Relaxed))
+    }
+
+    fn step_until(&mut self, steps: u64, stop: &dyn Fn() -> bool) -> u64 {
+        let mut stepped = 0;
+        for log2_steps in 0..u64::BITS as usize {
+            if steps >> log2_steps & 1 == 1 {
+                if !self.step_unless(log2_steps, stop) {
+                    break;
+                }
+                stepped += 1 << log2_steps;
+            }
+        }
+        stepped
+    }
+
+    /// The number of cells that aren't dead. If the universe is inverted
+    /// these are the dead cells instead.
+    pub fn population(&self) -> u64 {
+        self.universe.lock().population(self.root) as u64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.population() == 0
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Steps forward to the given generation, which can't be in the past.
+    pub fn advance_to(&mut self, generation: u64) -> Result<(), StepError> {
+        let Some(steps) = generation.checked_sub(self.generation) else {
+            return Err(StepError::Backwards {
+                from: self.generation,
+                to: generation,
+            });
+        };
+        self.step_by(steps);
+        Ok(())
+    }
+
+    /// Whether the infinite background is currently alive. Rules with B0 bring
+    /// every empty cell alive, so the universe then holds the complement of
+    /// the pattern: cells set, read or iterated over are the dead ones. With
+    /// S8 the background stays alive, and without it it strobes every
+    /// generation.
+    pub fn is_inverted(&self) -> bool {
+        self.inverted
+    }
+
+    pub fn set_bit(&mut self, p: (isize, isize)) {
+        self.set_state(p, 1);
+    }
+
+    pub fn set(&mut self, p: (isize, isize), alive: bool) {
+        if alive {
+            return self.set_bit(p);
+        }
+        let (y, x) = self.topology.wrap(p);
         let p = P3::new(y, x, self.depth);
-        self.root = self.universe.set_bit(self.root, p);
+        // Cells outside the tree are already dead.
+        if p.within_tree() {
+            self.root = self.universe.lock().clear_bit(self.root, p);
+            self.trim();
+        }
+    }
+
+    /// The cells that aren't dead, row by row, found as they're needed by
+    /// walking the tree rather than all up front.
+    pub fn iter_cells(&self) -> Cells<'_> {
+        Cells::new(self)
+    }
+
+    /// The cells that aren't dead within the rows `ys` and columns `xs`,
+    /// skipping over any nodes outside them.
+    pub fn cells_in_rect(
+        &self,
+        ys: Range<isize>,
+        xs: Range<isize>,
+    ) -> impl Iterator<Item = (isize, isize)> + '_ {
+        let overlaps = |a: &Range<isize>, b: &Range<isize>| a.start < b.end && b.start < a.end;
+        let keep = move |p: P3| {
+            let (node_ys, node_xs) = p.bounds();
+            overlaps(&node_ys, &ys) && overlaps(&node_xs, &xs)
+        };
+        self.iter_states_where(keep).map(|(p, _)| p)
+    }
+
+    /// The number of cells that aren't dead within the rows `ys` and columns
+    /// `xs`. Nodes entirely inside the rect are counted without descending.
+    pub fn population_in_rect(&self, ys: Range<isize>, xs: Range<isize>) -> u64 {
+        let universe = self.universe.lock();
+        let mut population = 0;
+        let mut stack = vec![(self.root, P3::origin(self.depth))];
+        while let Some((tr, p)) = stack.pop() {
+            if universe.population(tr) == 0 {
+                continue;
+            }
+            let (node_ys, node_xs) = p.bounds();
+            let clip = |a: &Range<isize>, b: &Range<isize>| a.start.max(b.start)..a.end.min(b.end);
+            let (clipped_ys, clipped_xs) = (clip(&node_ys, &ys), clip(&node_xs, &xs));
+            if (&clipped_ys, &clipped_xs) == (&node_ys, &node_xs) {
+                population += universe.population(tr) as u64;
+            } else if !clipped_ys.is_empty() && !clipped_xs.is_empty() {
+                let ps = p.quadrants().unwrap();
+                stack.extend(universe.subtree(tr).into_iter().zip(ps));
+            }
+        }
+        population
+    }
+
+    /// The rows and columns spanned by the cells that aren't dead, or `None` if
+    /// there aren't any.
+    pub fn bounding_box(&self) -> Option<(Range<isize>, Range<isize>)> {
+        let top = self.extreme(|(ys, _)| ys.start)?;
+        let bottom = -self.extreme(|(ys, _)| -ys.end)?;
+        let left = self.extreme(|(_, xs)| xs.start)?;
+        let right = -self.extreme(|(_, xs)| -xs.end)?;
+        Some((top..bottom, left..right))
+    }
+
+    pub fn get(&self, p: (isize, isize)) -> bool {
+        self.get_state(p) != 0
+    }
+
+    /// Kills a cell in any state, or brings a dead one alive.
+    pub fn toggle(&mut self, p: (isize, isize)) {
+        self.set(p, !self.get(p));
+    }
+
+    /// Brings many cells alive at once, which is much faster than setting them
+    /// one at a time.
+    pub fn set_cells(&mut self, cells: impl IntoIterator<Item = (isize, isize)>) {
+        if let Err(e) = self.set_cells_checked(cells) {
+            panic!("{e}");
+        }
+    }
+
+    /// Like `set_cells`, but fails rather than panics if a cell is beyond the
+    /// coordinates a tree can address or the universe runs out of room for
+    /// nodes, leaving the pattern as it was.
+    pub fn set_cells_checked(
+        &mut self,
+        cells: impl IntoIterator<Item = (isize, isize)>,
+    ) -> Result<(), StepError> {
+        let cells: Vec<_> = cells.into_iter().map(|p| (p, 1)).collect();
+        if !cells.iter().all(|&(p, _)| self.addressable(p)) {
+            return Err(StepError::UniverseTooLarge);
+        }
+        self.edit_checked(|hl| hl.set_states(cells))
+    }
+
+    pub fn set_state(&mut self, p: (isize, isize), state: u8) {
+        if let Err(e) = self.set_state_checked(p, state) {
+            panic!("{e}");
+        }
+    }
+
+    /// Like `set_state`, but fails rather than panics if the cell is beyond
+    /// the coordinates a tree can address or the universe runs out of room
+    /// for nodes, leaving the pattern as it was.
+    pub fn set_state_checked(&mut self, p: (isize, isize), state: u8) -> Result<(), StepError> {
+        if !self.addressable(p) {
+            return Err(StepError::UniverseTooLarge);
+        }
+        self.edit_checked(|hl| {
+            let (y, x) = hl.topology.wrap(p);
+            while !P3::new(y, x, hl.depth).within_tree() {
+                hl.expand();
+            }
+            let p = P3::new(y, x, hl.depth);
+            hl.root = hl.universe.lock().set_state(hl.root, p, state);
+            if state == 0 {
+                hl.trim();
+            }
+        })
+    }
+
+    pub fn get_state(&self, p: (isize, isize)) -> u8 {
+        let (y, x) = self.topology.wrap(p);
+        let p = P3::new(y, x, self.depth);
+        let universe = self.universe.lock();
+        match universe.get_node(self.root, p) {
+            Some(tr) => universe.state(tr),
+            None => 0,
+        }
     }
 }
 
 impl HashLife {
     fn expand(&mut self) {
-        self.root = self.universe.expand_universe(self.root);
+        assert!(
+            self.depth < MAX_DEPTH,
+            "Universe too large: cells would be beyond isize coordinates"
+        );
+        self.root = self.universe.lock().expand_universe(self.root);
         self.depth += 1;
     }
-}
 
-impl IntoIterator for HashLife {
-    type Item = (isize, isize);
+    /// Whether a cell is within the coordinates the deepest tree can address.
+    fn addressable(&self, p: (isize, isize)) -> bool {
+        let (y, x) = self.topology.wrap(p);
+        P3::new(y, x, MAX_DEPTH).within_tree()
+    }
 
-    type IntoIter = std::vec::IntoIter<(isize, isize)>;
+    /// Makes an edit, undoing it if the universe ran out of room for the nodes
+    /// it needed.
+    fn edit_checked(&mut self, edit: impl FnOnce(&mut Self)) -> Result<(), StepError> {
+        self.universe.lock().take_full();
+        let before = (self.root, self.depth);
+        edit(self);
+        if self.universe.lock().take_full() {
+            (self.root, self.depth) = before;
+            return Err(StepError::UniverseFull);
+        }
+        Ok(())
+    }
 
-    fn into_iter(self) -> Self::IntoIter {
-        let mut cells = Vec::with_capacity(self.universe.population(self.root));
+    fn set_states(&mut self, mut cells: Vec<((isize, isize), u8)>) {
+        for (p, _) in &mut cells {
+            *p = self.topology.wrap(*p);
+        }
+        let (min, max) = cells.iter().fold(
+            ((0, 0), (0, 0)),
+            |((min_y, min_x), (max_y, max_x)), &((y, x), _)| {
+                ((min_y.min(y), min_x.min(x)), (max_y.max(y), max_x.max(x)))
+            },
+        );
+        while ![min, max]
+            .iter()
+            .all(|&(y, x)| P3::new(y, x, self.depth).within_tree())
+        {
+            self.expand();
+        }
+        let p = P3::origin(self.depth);
+        self.root = self.universe.lock().set_states(self.root, p, &mut cells);
+    }
+
+    /// The stored cells that aren't dead, and their states.
+    fn cells(&self) -> Vec<((isize, isize), u8)> {
+        let mut cells = Vec::with_capacity(self.universe.lock().population(self.root));
+        cells.extend(self.iter_states());
+        cells
+    }
+
+    fn iter_states(&self) -> impl Iterator<Item = ((isize, isize), u8)> + '_ {
+        self.iter_states_where(|_| true)
+    }
+
+    /// Like `iter_states`, but skipping nodes for which `keep` is false.
+    fn iter_states_where<'a>(
+        &'a self,
+        keep: impl Fn(P3) -> bool + 'a,
+    ) -> impl Iterator<Item = ((isize, isize), u8)> + 'a {
+        let mut stack = vec![(self.root, P3::origin(self.depth))];
+        std::iter::from_fn(move || {
+            while let Some((tr, p)) = stack.pop() {
+                let universe = self.universe.lock();
+                if universe.population(tr) == 0 || !keep(p) {
+                    continue;
+                }
+                match p.quadrants() {
+                    Some(ps) => stack.extend(universe.subtree(tr).into_iter().zip(ps)),
+                    None => return Some(((p.y, p.x), universe.state(tr))),
+                }
+            }
+            None
+        })
+    }
+
+    /// The smallest `bound` of any cell that isn't dead, where `bound` of a
+    /// node's bounds is at most that of any cell inside it.
+    fn extreme(&self, bound: impl Fn((Range<isize>, Range<isize>)) -> isize) -> Option<isize> {
+        let universe = self.universe.lock();
+        let mut best = None;
         let mut stack = vec![(self.root, P3::origin(self.depth))];
         while let Some((tr, p)) = stack.pop() {
-            if self.universe.population(tr) == 0 {
+            let b = bound(p.bounds());
+            if universe.population(tr) == 0 || best.is_some_and(|best| b >= best) {
                 continue;
             }
-            if let Some(ps) = p.quadrants() {
-                stack.extend(self.universe.subtree(tr).into_iter().zip(ps));
-            } else if self.universe.alive(tr) {
-                cells.push((p.y, p.x));
+            match p.quadrants() {
+                Some(ps) => {
+                    let subtree = universe.subtree(tr).into_iter().zip(ps);
+                    // Look at the most promising quadrant first.
+                    stack.extend(subtree.sorted_by_key(|&(_, p)| -bound(p.bounds())));
+                }
+                None => best = Some(b),
+            }
+        }
+        best
+    }
+
+    /// Drops the empty border that stepping and clearing cells leave, so the
+    /// depth only depends on the pattern and not on how it got there. Steps
+    /// and edits that kill cells do this already.
+    pub fn trim(&mut self) {
+        let mut universe = self.universe.lock();
+        while self.depth > 1 {
+            let center = universe.reframe(self.root, P3::origin(2), 1);
+            if universe.population(center) != universe.population(self.root) {
+                break;
             }
+            self.root = center;
+            self.depth -= 1;
         }
+    }
+}
+
+impl IntoIterator for HashLife {
+    type Item = (isize, isize);
+
+    type IntoIter = std::vec::IntoIter<(isize, isize)>;
+
+    /// Row by row, like `iter_cells`.
+    fn into_iter(self) -> Self::IntoIter {
+        let cells: Vec<_> = self.iter_cells().collect();
         cells.into_iter()
     }
 }
 
 impl Display for HashLife {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let cells = self.clone().into_iter().collect();
-        BasicState { cells }.fmt(f)
+        self.display_with(Glyphs::default()).fmt(f)
     }
 }
 
 impl FromIterator<(isize, isize)> for HashLife {
     fn from_iter<T: IntoIterator<Item = (isize, isize)>>(iter: T) -> Self {
         let mut hl = HashLife::new();
-        for p in iter {
-            hl.set_bit(p);
-        }
+        hl.set_cells(iter);
         hl
     }
 }
 
 impl FromStr for HashLife {
-    type Err = String;
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         BasicState::from_str(s).map(|s| s.into_iter().collect())
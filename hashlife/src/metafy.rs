@@ -0,0 +1,139 @@
+use std::ops::Range;
+
+use crate::{
+    HashLife,
+    fx::FxHashMap,
+    p3::{MAX_DEPTH, P3},
+    universe::{TreeRef, Universe},
+};
+
+impl HashLife {
+    /// Blows the pattern up by `scale`, a power of two, drawing each cell
+    /// that isn't dead as the cells of `on`, and each dead cell within the
+    /// bounding box as the cells of `off`, like the OTCA metapixel's unit
+    /// cells. The unit cells must fit in the rows and columns from 0 up to
+    /// `scale`; cell (y, x) becomes the one with its top left at
+    /// (y * scale, x * scale). The result runs under `on`'s rule.
+    ///
+    /// Each node of the pattern is swapped for one `scale` times bigger, so
+    /// the result shares structure as the pattern does, and takes as long to
+    /// make as the pattern has nodes, whatever its size.
+    pub fn metafy(&self, on: &HashLife, off: &HashLife, scale: u64) -> Result<HashLife, String> {
+        if !scale.is_power_of_two() {
+            return Err(format!("Scale {scale} isn't a power of two"));
+        }
+        if self.inverted || on.inverted || off.inverted {
+            return Err("Can't metafy patterns on a live background".to_string());
+        }
+        let log2_scale = scale.trailing_zeros() as usize;
+        let mut hl = HashLife::with_rule(on.rule().clone());
+        let Some((ys, xs)) = self.bounding_box() else {
+            return Ok(hl);
+        };
+        let (on, off) = (
+            unit_cell(&mut hl, on, log2_scale)?,
+            unit_cell(&mut hl, off, log2_scale)?,
+        );
+        // A root one cell across is the cell at (0, 0), so it needs another
+        // level to put that cell where bigger roots would have it.
+        let mut root = hl.import_root(self);
+        let mut depth = self.depth;
+        if depth == 0 {
+            root = hl.universe.lock().expand_universe(root);
+            depth = 1;
+        }
+        if depth + log2_scale > MAX_DEPTH {
+            return Err("Metafied pattern would be too large".to_string());
+        }
+        let mut metafier = Metafier {
+            on,
+            offs: vec![off],
+            bounds: (ys, xs),
+            cache: FxHashMap::default(),
+        };
+        let p = P3::origin(depth);
+        hl.root = metafier.substitute(&mut hl.universe.lock(), root, p);
+        hl.depth = depth + log2_scale;
+        hl.trim();
+        Ok(hl)
+    }
+}
+
+/// The node `2^log2_scale` cells across with its top left at (0, 0) in
+/// `unit`, in `hl`'s universe, if that holds all of `unit`'s cells.
+fn unit_cell(hl: &mut HashLife, unit: &HashLife, log2_scale: usize) -> Result<TreeRef, String> {
+    let mut root = hl.import_root(unit);
+    let mut depth = unit.depth;
+    // Until the south east quadrant, with its top left at (0, 0), is as big.
+    while depth <= log2_scale {
+        root = hl.universe.lock().expand_universe(root);
+        depth += 1;
+    }
+    let universe = hl.universe.lock();
+    let mut node = universe.subtree(root)[3];
+    for _ in log2_scale + 1..depth {
+        node = universe.subtree(node)[0];
+    }
+    if universe.population(node) != universe.population(root) {
+        let scale = 1u64 << log2_scale;
+        return Err(format!("Unit cell doesn't fit in {scale}x{scale}"));
+    }
+    Ok(node)
+}
+
+struct Metafier {
+    on: TreeRef,
+    /// All off, at each depth from that of the unit cells up, as far as
+    /// they've been needed.
+    offs: Vec<TreeRef>,
+    /// The bounding box of the pattern, the only dead cells to swap.
+    bounds: (Range<isize>, Range<isize>),
+    /// Keyed by the node, its depth, and the rows and columns of it inside
+    /// the bounding box, relative to its center.
+    cache: FxHashMap<(TreeRef, usize, Range<isize>, Range<isize>), TreeRef>,
+}
+
+impl Metafier {
+    /// Swaps the cells of the node at `p` for unit cells, leaving out the
+    /// dead ones outside the bounding box.
+    fn substitute(&mut self, universe: &mut Universe, tr: TreeRef, p: P3) -> TreeRef {
+        let (node_ys, node_xs) = p.bounds();
+        let inside = |node: Range<isize>, bounds: &Range<isize>, center: isize| {
+            node.start.max(bounds.start) - center..node.end.min(bounds.end) - center
+        };
+        let ys = inside(node_ys.clone(), &self.bounds.0, p.y);
+        let xs = inside(node_xs.clone(), &self.bounds.1, p.x);
+        if ys.is_empty() || xs.is_empty() {
+            return TreeRef::EMPTY;
+        }
+        if p.z == 0 {
+            return if tr == TreeRef::EMPTY {
+                self.offs[0]
+            } else {
+                self.on
+            };
+        }
+        if tr == TreeRef::EMPTY && ys.len() == node_ys.len() && xs.len() == node_xs.len() {
+            return self.off(universe, p.z);
+        }
+        let key = (tr, p.z, ys, xs);
+        if let Some(&tr) = self.cache.get(&key) {
+            return tr;
+        }
+        let ps = p.quadrants().unwrap();
+        let subtree = universe.subtree(tr);
+        let subtree = [0, 1, 2, 3].map(|i| self.substitute(universe, subtree[i], ps[i]));
+        let metafied = universe.branch(subtree);
+        self.cache.insert(key, metafied);
+        metafied
+    }
+
+    /// All off, `2^depth` unit cells across.
+    fn off(&mut self, universe: &mut Universe, depth: usize) -> TreeRef {
+        while self.offs.len() <= depth {
+            let off = *self.offs.last().unwrap();
+            self.offs.push(universe.branch([off; 4]));
+        }
+        self.offs[depth]
+    }
+}
@@ -0,0 +1,103 @@
+use crate::{HashLife, fx::FxHashMap, universe::TreeRef};
+
+/// A way of combining the cells of two patterns.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum Op {
+    Union,
+    Intersect,
+    Xor,
+    Subtract,
+}
+
+impl Op {
+    /// The state of a combined cell. Cells keep their own state where only one
+    /// side is alive, or the left side's where both are.
+    pub fn apply(self, a: u8, b: u8) -> u8 {
+        match (self, a, b) {
+            (Op::Union, 0, b) => b,
+            (Op::Union, a, _) => a,
+            (Op::Intersect, a, b) if a != 0 && b != 0 => a,
+            (Op::Xor, a, 0) => a,
+            (Op::Xor, 0, b) => b,
+            (Op::Subtract, a, 0) => a,
+            _ => 0,
+        }
+    }
+
+    /// The combined node when one side is empty, without looking inside the
+    /// other.
+    pub fn with_empty(self, a: TreeRef, b: TreeRef) -> Option<TreeRef> {
+        let empty = TreeRef::EMPTY;
+        match (self, a == empty, b == empty) {
+            (_, false, false) => None,
+            (Op::Union | Op::Xor, true, _) => Some(b),
+            (Op::Union | Op::Xor | Op::Subtract, _, true) => Some(a),
+            _ => Some(empty),
+        }
+    }
+}
+
+/// A rotation or reflection about the center of the universe.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Transform {
+    FlipHorizontal,
+    FlipVertical,
+    Transpose,
+    RotateCw,
+    RotateCcw,
+}
+
+impl Transform {
+    /// Where each quadrant of a node moves to.
+    pub fn permute(self, [nw, ne, sw, se]: [TreeRef; 4]) -> [TreeRef; 4] {
+        match self {
+            Transform::FlipHorizontal => [ne, nw, se, sw],
+            Transform::FlipVertical => [sw, se, nw, ne],
+            Transform::Transpose => [nw, sw, ne, se],
+            Transform::RotateCw => [sw, nw, se, ne],
+            Transform::RotateCcw => [ne, se, nw, sw],
+        }
+    }
+}
+
+impl HashLife {
+    /// The cells alive in either pattern.
+    pub fn union(&self, other: &HashLife) -> HashLife {
+        self.combine(other, Op::Union)
+    }
+
+    /// The cells alive in both patterns.
+    pub fn intersect(&self, other: &HashLife) -> HashLife {
+        self.combine(other, Op::Intersect)
+    }
+
+    /// The cells alive in exactly one of the patterns.
+    pub fn xor(&self, other: &HashLife) -> HashLife {
+        self.combine(other, Op::Xor)
+    }
+
+    /// The cells alive in this pattern but not the other.
+    pub fn subtract(&self, other: &HashLife) -> HashLife {
+        self.combine(other, Op::Subtract)
+    }
+
+    /// Combines the stored cells of two patterns. The result keeps this
+    /// pattern's rule and topology.
+    fn combine(&self, other: &HashLife, op: Op) -> HashLife {
+        let mut out = self.clone();
+        let mut root = out.import_root(other);
+        while out.depth < other.depth {
+            out.expand();
+        }
+        for _ in other.depth..out.depth {
+            root = out.universe.lock().expand_universe(root);
+        }
+        let mut cache = FxHashMap::default();
+        out.root = out
+            .universe
+            .lock()
+            .merge(out.root, root, out.depth, op, &mut cache);
+        out.trim();
+        out
+    }
+}
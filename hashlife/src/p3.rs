@@ -1,3 +1,9 @@
+use std::ops::Range;
+
+/// The deepest tree whose coordinates all fit in an `isize`, including the
+/// `1 << z` widths worked out while descending it.
+pub const MAX_DEPTH: usize = isize::BITS as usize - 2;
+
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub struct P3 {
     pub y: isize,
@@ -29,6 +35,15 @@ impl P3 {
         (-w..w).contains(&rel_y) && (-w..w).contains(&rel_x)
     }
 
+    /// The rows and columns of the cells within the node.
+    pub fn bounds(&self) -> (Range<isize>, Range<isize>) {
+        let (lo, hi) = match self.z {
+            0 => (0, 1),
+            z => (-1 << (z - 1), 1 << (z - 1)),
+        };
+        (self.y + lo..self.y + hi, self.x + lo..self.x + hi)
+    }
+
     pub fn descend(&mut self) -> Option<usize> {
         if self.z == 0 {
             return None;
@@ -89,4 +104,21 @@ mod tests {
         assert!(P3::new(-2, -2, 2).contains(P3::new(-3, -3, 1)));
         assert!(!P3::new(-2, -2, 2).contains(P3::new(-1, -1, 1)));
     }
+
+    #[test]
+    fn test_max_depth() {
+        let w = 1 << (MAX_DEPTH - 1);
+        assert!(P3::new(-w, w - 1, MAX_DEPTH).within_tree());
+        assert!(!P3::new(-w, w, MAX_DEPTH).within_tree());
+        assert_eq!(P3::origin(MAX_DEPTH).bounds().0, -w..w);
+        let mut corner = P3::new(w - 1, -w, MAX_DEPTH);
+        while corner.descend().is_some() {}
+    }
+
+    #[test]
+    fn test_bounds() {
+        assert_eq!(P3::new(3, -1, 0).bounds(), (3..4, -1..0));
+        assert_eq!(P3::new(0, 0, 1).bounds(), (-1..1, -1..1));
+        assert_eq!(P3::new(-2, 2, 2).bounds(), (-4..0, 0..4));
+    }
 }
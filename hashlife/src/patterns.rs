@@ -0,0 +1,60 @@
+//! Well known patterns in Life, centered on the origin like patterns loaded
+//! from files.
+
+mod lexicon;
+
+use crate::{HashLife, formats};
+
+pub use lexicon::Lexicon;
+
+macro_rules! patterns {
+    ($($(#[$attr:meta])* $name:ident = $rle:literal;)*) => {
+        $(
+            $(#[$attr])*
+            pub fn $name() -> HashLife {
+                from_rle($rle)
+            }
+        )*
+
+        /// Every pattern here, by the name of its function.
+        pub const ALL: &[(&str, fn() -> HashLife)] = &[$((stringify!($name), $name)),*];
+    };
+}
+
+patterns! {
+    block = "2o$2o!";
+    beehive = "b2o$o2bo$b2o!";
+    loaf = "b2o$o2bo$bobo$2bo!";
+    boat = "2o$obo$bo!";
+    blinker = "3o!";
+    toad = "b3o$3o!";
+    beacon = "2o$2o$2b2o$2b2o!";
+    /// The period 3 oscillator.
+    pulsar = "2b3o3b3o2$o4bobo4bo$o4bobo4bo$o4bobo4bo$2b3o3b3o2$2b3o3b3o$o4bobo4bo$\
+              o4bobo4bo$o4bobo4bo2$2b3o3b3o!";
+    /// The period 15 oscillator.
+    pentadecathlon = "2bo4bo$2ob4ob2o$2bo4bo!";
+    /// Travels diagonally down and to the right.
+    glider = "bo$2bo$3o!";
+    /// The lightweight spaceship, travelling left.
+    lwss = "bo2bo$o$o3bo$4o!";
+    /// The middleweight spaceship, travelling left.
+    mwss = "3bo$bo3bo$o$o4bo$5o!";
+    /// The heavyweight spaceship, travelling left.
+    hwss = "3b2o$bo4bo$o$o5bo$6o!";
+    /// A methuselah that stabilizes after 1103 generations.
+    r_pentomino = "b2o$2o$bo!";
+    /// A methuselah that stabilizes after 5206 generations.
+    acorn = "bo$3bo$2o2b3o!";
+    /// Dies out after 130 generations.
+    diehard = "6bo$2o$bo3b3o!";
+    /// Bill Gosper's glider gun, which fires a glider every 30 generations.
+    gosper_gun = "24bo$22bobo$12b2o6b2o12b2o$11bo3bo4b2o12b2o$2o8bo5bo3b2o$2o8bo3bob2o4bobo$\
+                  10bo5bo7bo$11bo3bo$12b2o!";
+}
+
+fn from_rle(rle: &str) -> HashLife {
+    formats::load(format!("x = 0, y = 0\n{rle}").as_bytes())
+        .unwrap()
+        .life
+}
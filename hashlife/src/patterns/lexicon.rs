@@ -0,0 +1,99 @@
+use std::{collections::BTreeMap, str::FromStr};
+
+use itertools::Itertools;
+
+use crate::{HashLife, ParseError, patterns};
+
+/// Named patterns to look up, starting with the built in ones and extended
+/// from the Life Lexicon.
+#[derive(Clone, Debug)]
+pub struct Lexicon {
+    patterns: BTreeMap<String, HashLife>,
+}
+
+impl Default for Lexicon {
+    fn default() -> Self {
+        let patterns = patterns::ALL
+            .iter()
+            .map(|(name, pattern)| (name.replace('_', " "), pattern()))
+            .collect();
+        Self { patterns }
+    }
+}
+
+impl Lexicon {
+    /// Adds the patterns from the text of the Life Lexicon, `lexicon.txt`.
+    /// Entries open with `:name:` and their diagrams are indented lines of
+    /// `.` and `*`. Only the first diagram of an entry is kept, and entries
+    /// without one are skipped. Names already here are replaced.
+    pub fn load(&mut self, text: &str) -> Result<(), ParseError> {
+        let mut name = None;
+        let mut diagram: Vec<&str> = vec![];
+        for line in text.lines().chain([""]) {
+            let row = line.trim();
+            let is_row = line.starts_with([' ', '\t'])
+                && !row.is_empty()
+                && row.chars().all(|c| matches!(c, '.' | '*'));
+            if is_row && name.is_some() {
+                diagram.push(row);
+                continue;
+            }
+            if !diagram.is_empty() {
+                let rows = diagram
+                    .iter()
+                    .map(|row| row.replace('.', " ").replace('*', "o"))
+                    .join("\n");
+                let pattern = HashLife::from_str(&rows)?;
+                self.patterns.insert(name.take().unwrap(), pattern);
+                diagram.clear();
+            }
+            if let Some((entry, _)) = line.strip_prefix(':').and_then(|l| l.split_once(':')) {
+                name = Some(entry.trim().to_string());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&HashLife> {
+        self.patterns.get(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.patterns.keys().map(String::as_str)
+    }
+
+    /// The pattern whose name best matches `query`, ignoring case, spaces and
+    /// punctuation. Exact matches win, then names starting with the query,
+    /// then ones containing it, then ones containing its letters in order.
+    /// Ties go to the shortest name.
+    pub fn find_pattern(&self, query: &str) -> Option<(&str, &HashLife)> {
+        let simplify = |s: &str| {
+            s.chars()
+                .filter(|c| c.is_alphanumeric())
+                .flat_map(char::to_lowercase)
+                .collect::<String>()
+        };
+        let query = simplify(query);
+        let rank = |name: &str| {
+            let name = simplify(name);
+            let mut letters = name.chars();
+            let rank = if name == query {
+                0
+            } else if name.starts_with(&query) {
+                1
+            } else if name.contains(&query) {
+                2
+            } else if query.chars().all(|c| letters.any(|l| l == c)) {
+                3
+            } else {
+                return None;
+            };
+            Some((rank, name.len()))
+        };
+        self.patterns
+            .iter()
+            .filter_map(|(name, pattern)| Some((rank(name)?, name, pattern)))
+            .min_by_key(|&(rank, _, _)| rank)
+            .map(|(_, name, pattern)| (name.as_str(), pattern))
+    }
+}
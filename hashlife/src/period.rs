@@ -0,0 +1,49 @@
+use std::collections::HashMap;
+
+use crate::HashLife;
+
+/// How a pattern repeats: after `period` generations it's back to the same
+/// cells, moved by `displacement` as (dy, dx).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Periodic {
+    pub period: u64,
+    pub displacement: (isize, isize),
+}
+
+impl HashLife {
+    /// Steps a copy of the pattern one generation at a time, looking for a
+    /// state it has already been in up to translation. Still lifes have period
+    /// 1 and spaceships a non-zero displacement. Patterns that take a while to
+    /// settle into a cycle are found too, so long as they do so within
+    /// `max_period` generations.
+    pub fn detect_period(&self, max_period: u64) -> Option<Periodic> {
+        let mut hl = self.clone();
+        let mut seen = HashMap::new();
+        // Where the pattern would be if it hadn't been moved back to the origin.
+        let mut position = (0, 0);
+        for generation in 0..=max_period {
+            let (fingerprint, (y, x)) = hl.move_to_origin();
+            position = (position.0 + y, position.1 + x);
+            let key = (fingerprint, hl.inverted);
+            if let Some(&(start, (y, x))) = seen.get(&key) {
+                return Some(Periodic {
+                    period: generation - start,
+                    displacement: (position.0 - y, position.1 - x),
+                });
+            }
+            seen.insert(key, (generation, position));
+            hl.step(0);
+        }
+        None
+    }
+
+    /// Moves the pattern so its bounding box starts at the origin, returning
+    /// its fingerprint there and where the box started.
+    pub(crate) fn move_to_origin(&mut self) -> (u64, (isize, isize)) {
+        let Some((ys, xs)) = self.bounding_box() else {
+            return (self.canonical_fingerprint(), (0, 0));
+        };
+        self.translate(-ys.start, -xs.start);
+        (self.canonical_fingerprint(), (ys.start, xs.start))
+    }
+}
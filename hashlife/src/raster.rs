@@ -0,0 +1,162 @@
+use std::{
+    fmt::{Display, Write},
+    ops::Range,
+};
+
+use crate::HashLife;
+
+/// A dense grid of cells, packed row by row into u64 words with the leftmost
+/// cell of each word in its lowest bit. Each row starts on a new word.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BitGrid {
+    height: usize,
+    width: usize,
+    words: Vec<u64>,
+}
+
+impl BitGrid {
+    pub fn new(height: usize, width: usize) -> Self {
+        Self {
+            height,
+            width,
+            words: vec![0; height * width.div_ceil(64)],
+        }
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn words_per_row(&self) -> usize {
+        self.width.div_ceil(64)
+    }
+
+    pub fn get(&self, y: usize, x: usize) -> bool {
+        self.row(y)[x / 64] >> (x % 64) & 1 == 1
+    }
+
+    pub fn set(&mut self, y: usize, x: usize, alive: bool) {
+        let i = y * self.words_per_row() + x / 64;
+        match alive {
+            true => self.words[i] |= 1 << (x % 64),
+            false => self.words[i] &= !(1 << (x % 64)),
+        }
+    }
+
+    pub fn row(&self, y: usize) -> &[u64] {
+        let n = self.words_per_row();
+        &self.words[y * n..(y + 1) * n]
+    }
+
+    pub fn words(&self) -> &[u64] {
+        &self.words
+    }
+}
+
+impl HashLife {
+    /// The cells in the rows `ys` and columns `xs` that aren't dead, as a grid
+    /// whose (0, 0) is (ys.start, xs.start).
+    pub fn rasterize(&self, ys: Range<isize>, xs: Range<isize>) -> BitGrid {
+        let (y0, x0) = (ys.start, xs.start);
+        let mut grid = BitGrid::new(ys.len(), xs.len());
+        for (y, x) in self.cells_in_rect(ys, xs) {
+            grid.set((y - y0) as usize, (x - x0) as usize, true);
+        }
+        grid
+    }
+
+    /// Shows just the rows `ys` and columns `xs`, a character per cell, for
+    /// patterns too big to print whole.
+    pub fn display_rect(&self, ys: Range<isize>, xs: Range<isize>) -> DisplayRect<'_> {
+        DisplayRect {
+            life: self,
+            ys,
+            xs,
+            glyphs: Glyphs {
+                dead: '.',
+                rectangular: true,
+                ..Glyphs::default()
+            },
+        }
+    }
+
+    /// Shows the whole pattern like `Display` does, but printed with
+    /// `glyphs`.
+    pub fn display_with(&self, glyphs: Glyphs) -> DisplayRect<'_> {
+        let (ys, xs) = self.bounding_box().unwrap_or((0..0, 0..0));
+        DisplayRect {
+            life: self,
+            ys,
+            xs,
+            glyphs,
+        }
+    }
+}
+
+/// How to print cells as text.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Glyphs {
+    /// For cells that aren't dead.
+    pub alive: char,
+    pub dead: char,
+    /// Whether to print the dead cells at the ends of rows, so they're all as
+    /// wide, as diffing tools want.
+    pub rectangular: bool,
+}
+
+impl Default for Glyphs {
+    /// As `Display` prints patterns.
+    fn default() -> Self {
+        Self {
+            alive: 'o',
+            dead: ' ',
+            rectangular: false,
+        }
+    }
+}
+
+/// A window onto a pattern to print, with a row per line.
+#[derive(Clone, Debug)]
+pub struct DisplayRect<'a> {
+    life: &'a HashLife,
+    ys: Range<isize>,
+    xs: Range<isize>,
+    glyphs: Glyphs,
+}
+
+impl DisplayRect<'_> {
+    pub fn glyphs(self, glyphs: Glyphs) -> Self {
+        Self { glyphs, ..self }
+    }
+}
+
+impl Display for DisplayRect<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Glyphs {
+            alive,
+            dead,
+            rectangular,
+        } = self.glyphs;
+        let grid = self.life.rasterize(self.ys.clone(), self.xs.clone());
+        for y in 0..grid.height() {
+            if y > 0 {
+                f.write_char('\n')?;
+            }
+            let width = match rectangular {
+                true => grid.width(),
+                false => (0..grid.width())
+                    .rev()
+                    .find(|&x| grid.get(y, x))
+                    .map_or(0, |x| x + 1),
+            };
+            for x in 0..width {
+                f.write_char(if grid.get(y, x) { alive } else { dead })?;
+            }
+        }
+        Ok(())
+    }
+}
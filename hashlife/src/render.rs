@@ -1,6 +1,109 @@
-use egui::Rgba;
+use std::{collections::HashMap, ops::Range};
 
-use crate::{HashLife, p3::P3};
+use itertools::Itertools;
+
+mod cache;
+mod png;
+pub mod terminal;
+
+use crate::{
+    HashLife, Topology,
+    p3::P3,
+    universe::{TreeRef, Universe},
+};
+
+pub use cache::RenderCache;
+pub(crate) use png::write_rgba8_png;
+pub use png::{Animation, export_apng, export_png, write_apng, write_png};
+
+/// A colour in linear space with premultiplied alpha, like `egui::Rgba`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Rgba(pub [f32; 4]);
+
+impl Rgba {
+    pub const TRANSPARENT: Rgba = Rgba([0.; 4]);
+    pub const BLACK: Rgba = Rgba([0., 0., 0., 1.]);
+    pub const WHITE: Rgba = Rgba([1.; 4]);
+
+    pub const fn from_rgb(r: f32, g: f32, b: f32) -> Self {
+        Self([r, g, b, 1.])
+    }
+
+    pub const fn from_rgba_premultiplied(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self([r, g, b, a])
+    }
+
+    pub fn r(self) -> f32 {
+        self.0[0]
+    }
+
+    pub fn g(self) -> f32 {
+        self.0[1]
+    }
+
+    pub fn b(self) -> f32 {
+        self.0[2]
+    }
+
+    pub fn a(self) -> f32 {
+        self.0[3]
+    }
+
+    /// Scales every channel, alpha included.
+    pub fn multiply(self, factor: f32) -> Self {
+        Self(self.0.map(|c| c * factor))
+    }
+
+    /// The colour `t` of the way from this one to `other`.
+    pub fn lerp(self, other: Self, t: f32) -> Self {
+        self.multiply(1. - t).add(other.multiply(t))
+    }
+
+    fn add(self, other: Self) -> Self {
+        let [r, g, b, a] = self.0;
+        let [r2, g2, b2, a2] = other.0;
+        Self([r + r2, g + g2, b + b2, a + a2])
+    }
+
+    /// Draws `on_top` over this colour.
+    pub fn blend(self, on_top: Self) -> Self {
+        self.multiply(1. - on_top.a()).add(on_top)
+    }
+
+    /// The colour as sRGB bytes without premultiplied alpha, as canvases and
+    /// most image formats expect.
+    pub fn to_srgba_unmultiplied(self) -> [u8; 4] {
+        let a = self.a();
+        let gamma = |c: f32| {
+            let c = if a > 0. { c / a } else { 0. };
+            let c = match c {
+                c if c <= 0.003_130_8 => c * 12.92,
+                c => 1.055 * c.powf(1. / 2.4) - 0.055,
+            };
+            (c.clamp(0., 1.) * 255.).round() as u8
+        };
+        [
+            gamma(self.r()),
+            gamma(self.g()),
+            gamma(self.b()),
+            (a.clamp(0., 1.) * 255.).round() as u8,
+        ]
+    }
+}
+
+#[cfg(feature = "egui")]
+impl From<Rgba> for egui::Rgba {
+    fn from(Rgba([r, g, b, a]): Rgba) -> Self {
+        egui::Rgba::from_rgba_premultiplied(r, g, b, a)
+    }
+}
+
+#[cfg(feature = "egui")]
+impl From<egui::Rgba> for Rgba {
+    fn from(rgba: egui::Rgba) -> Self {
+        Rgba([rgba.r(), rgba.g(), rgba.b(), rgba.a()])
+    }
+}
 
 #[derive(Clone, Copy)]
 pub struct Settings {
@@ -9,60 +112,582 @@ pub struct Settings {
     pub cell_size: f64,
     pub dead_rgba: Rgba,
     pub alive_rgba: Rgba,
+    /// How far right of the row above each row is drawn, in cells. See
+    /// `Neighbourhood::row_offset`.
+    pub row_offset: f64,
+    /// Lines between the cells, if any.
+    pub grid: Option<Grid>,
+    /// Colours for cells by age, used in place of `alive_rgba` when the
+    /// pattern is tracking ages and isn't inverted.
+    pub ages: Option<AgeRamp>,
+    /// Draws how often cells changed over the steps the pattern is tracking
+    /// activity for in this colour, in place of which cells are alive. Cells
+    /// that changed every step are drawn solid.
+    pub heatmap: Option<Rgba>,
+    /// Shades the cells that have been alive since the pattern started
+    /// tracking its envelope in this colour, under the live cells. Give it
+    /// some transparency to see the dead colour through it.
+    pub envelope: Option<Rgba>,
+}
+
+/// Cells fade from `newborn_rgba` to `old_rgba` as they age.
+#[derive(Clone, Copy)]
+pub struct AgeRamp {
+    pub newborn_rgba: Rgba,
+    pub old_rgba: Rgba,
+    /// Cells at least this many generations old are drawn `old_rgba`.
+    pub old_age: u64,
+}
+
+impl AgeRamp {
+    /// The colour of a cell `t` of the way to `old_age`.
+    fn at(&self, t: f32) -> Rgba {
+        self.newborn_rgba.lerp(self.old_rgba, t.clamp(0., 1.))
+    }
+}
+
+/// Lines drawn along the top and left edges of every cell, once they're big
+/// enough for the lines not to hide them.
+#[derive(Clone, Copy)]
+pub struct Grid {
+    pub rgba: Rgba,
+    /// The lines only show when cells are at least this many pixels across.
+    pub min_pixels_per_cell: f64,
 }
 
 #[derive(Clone, Copy)]
 pub struct View {
     pub zoom: f64,
     pub center: (f64, f64),
+    /// How far the universe is turned clockwise about the center, in radians.
+    /// Turned views are drawn a pixel at a time from the cell at its centre,
+    /// which is slower and doesn't blend cells when zoomed out.
+    pub rotation: f64,
+}
+
+impl View {
+    /// The point in the universe, in cells, under the point `(y, x)` on the
+    /// screen, in pixels from the top-left corner. Rounding down gives the
+    /// cell there.
+    pub fn screen_to_cell(&self, p: (f64, f64), settings: Settings) -> (f64, f64) {
+        Frame::new(settings, *self).cell_at(p)
+    }
+
+    /// Where the point `(y, x)` in the universe, in cells, is on the screen,
+    /// in pixels from the top-left corner.
+    pub fn cell_to_screen(&self, p: (f64, f64), settings: Settings) -> (f64, f64) {
+        Frame::new(settings, *self).screen_at(p)
+    }
+
+    /// The unturned view with the rows `ys` and columns `xs` centred and as
+    /// large as they'll fit on the screen, such as those of
+    /// `HashLife::bounding_box`.
+    pub fn fit((ys, xs): (Range<isize>, Range<isize>), settings: Settings) -> Self {
+        let offsets = [ys.start, ys.end].map(|y| y as f64 * settings.row_offset);
+        let (lo, hi) = (offsets[0].min(offsets[1]), offsets[0].max(offsets[1]));
+        let rows = ys.start as f64..ys.end as f64;
+        let cols = xs.start as f64 + lo..xs.end as f64 + hi;
+        let size = |cells: &Range<f64>| (cells.end - cells.start).max(1.);
+        let zoom = (settings.height as f64 / size(&rows)).min(settings.width as f64 / size(&cols));
+        let mid = |cells: Range<f64>| (cells.start + cells.end) / 2.;
+        Self {
+            zoom: zoom / settings.cell_size,
+            center: (mid(rows), mid(cols)),
+            rotation: 0.,
+        }
+    }
+}
+
+/// Where the pixels fall on the universe, in cells.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Frame {
+    height: usize,
+    width: usize,
+    pixels_per_cell: f64,
+    /// The top-left corner of the top-left pixel.
+    top: f64,
+    left: f64,
+    row_offset: f64,
+    rotation: f64,
+    /// Nodes at this level are no bigger than a pixel, so are spread over the
+    /// pixels they overlap instead of being looked into.
+    level: usize,
+}
+
+impl Frame {
+    fn new(settings: Settings, view: View) -> Self {
+        let pixels_per_cell = view.zoom * settings.cell_size;
+        let (y, x) = view.center;
+        Self {
+            height: settings.height,
+            width: settings.width,
+            pixels_per_cell,
+            top: y - settings.height as f64 / pixels_per_cell / 2.,
+            left: x - settings.width as f64 / pixels_per_cell / 2.,
+            row_offset: settings.row_offset,
+            rotation: view.rotation,
+            level: (-pixels_per_cell.log2()).floor().max(0.) as usize,
+        }
+    }
+
+    /// How far right the cells drawn in pixel row `i` are shifted.
+    fn shift(&self, i: usize) -> f64 {
+        (self.top + (i as f64 + 0.5) / self.pixels_per_cell) * self.row_offset
+    }
+
+    /// The point in the universe under the point `(i, j)` on the screen,
+    /// measured in pixels from the top-left corner.
+    fn cell_at(&self, (i, j): (f64, f64)) -> (f64, f64) {
+        let (cy, cx) = self.center();
+        let dy = (i - self.height as f64 / 2.) / self.pixels_per_cell;
+        let dx = (j - self.width as f64 / 2.) / self.pixels_per_cell;
+        let (sin, cos) = self.rotation.sin_cos();
+        let y = cy + dy * cos - dx * sin;
+        (y, cx + dx * cos + dy * sin - y * self.row_offset)
+    }
+
+    /// The inverse of `cell_at`.
+    fn screen_at(&self, (y, x): (f64, f64)) -> (f64, f64) {
+        let (cy, cx) = self.center();
+        let (dy, dx) = (y - cy, x + y * self.row_offset - cx);
+        let (sin, cos) = self.rotation.sin_cos();
+        (
+            self.height as f64 / 2. + (dy * cos + dx * sin) * self.pixels_per_cell,
+            self.width as f64 / 2. + (dx * cos - dy * sin) * self.pixels_per_cell,
+        )
+    }
+
+    fn center(&self) -> (f64, f64) {
+        let half = |n: usize| n as f64 / self.pixels_per_cell / 2.;
+        (self.top + half(self.height), self.left + half(self.width))
+    }
+
+    fn is_turned(&self) -> bool {
+        self.rotation != 0.
+    }
+
+    /// The cell under the centre of pixel (i, j).
+    fn cell_under(&self, i: usize, j: usize) -> (isize, isize) {
+        let (y, x) = self.cell_at((i as f64 + 0.5, j as f64 + 0.5));
+        (y.floor() as isize, x.floor() as isize)
+    }
+
+    /// Whether pixel (i, j) is the first in its row or column of cells.
+    fn on_grid(&self, i: usize, j: usize) -> bool {
+        let cell = |i: f64, j: f64| {
+            let (y, x) = self.cell_at((i + 0.5, j + 0.5));
+            (y.floor(), x.floor())
+        };
+        let (i, j) = (i as f64, j as f64);
+        cell(i, j) != cell(i - 1., j) || cell(i, j) != cell(i, j - 1.)
+    }
+
+    /// The rows and columns of cells in view.
+    fn extent(&self) -> (Range<f64>, Range<f64>) {
+        let rows = self.top..self.top + self.height as f64 / self.pixels_per_cell;
+        let shifts = [self.shift(0), self.shift(self.height.saturating_sub(1))];
+        let (lo, hi) = (shifts[0].min(shifts[1]), shifts[0].max(shifts[1]));
+        let cols = self.left - hi..self.left + self.width as f64 / self.pixels_per_cell - lo;
+        (rows, cols)
+    }
+
+    /// Whether any cells in the rows and columns are in view.
+    fn shows(&self, rows: &Range<f64>, cols: &Range<f64>) -> bool {
+        let (view_rows, view_cols) = self.extent();
+        let overlaps = |a: &Range<f64>, b: &Range<f64>| a.start < b.end && b.start < a.end;
+        overlaps(rows, &view_rows) && overlaps(cols, &view_cols)
+    }
+
+    /// Adds `density` to the pixels covered by the rows and columns of cells,
+    /// in proportion to how much of each pixel they cover.
+    fn splat(&self, coverage: &mut [f32], rows: &Range<f64>, cols: &Range<f64>, density: f32) {
+        // Measured in pixels from the top-left corner.
+        let ppc = self.pixels_per_cell;
+        let pixels = |a: f64, b: f64, n: usize| {
+            let clamp = |p: f64| p.clamp(0., n as f64) as usize;
+            clamp(a.floor())..clamp(b.ceil())
+        };
+        let overlap = |a: f64, b: f64, k: usize| b.min(k as f64 + 1.) - a.max(k as f64);
+        let (a, b) = ((rows.start - self.top) * ppc, (rows.end - self.top) * ppc);
+        for i in pixels(a, b, self.height) {
+            let left = self.left - self.shift(i);
+            let (c, d) = ((cols.start - left) * ppc, (cols.end - left) * ppc);
+            for j in pixels(c, d, self.width) {
+                let area = overlap(a, b, i) * overlap(c, d, j);
+                coverage[i * self.width + j] += density * area as f32;
+            }
+        }
+    }
+
+    /// The `height` rows of pixels starting at row `top`.
+    #[cfg(feature = "rayon")]
+    fn band(&self, top: usize, height: usize) -> Self {
+        Self {
+            height,
+            top: self.top + top as f64 / self.pixels_per_cell,
+            ..*self
+        }
+    }
+
+    /// Where the copies of a w x h torus in view start.
+    fn copies(&self, w: usize, h: usize) -> impl Iterator<Item = (isize, isize)> + use<> {
+        let (rows, cols) = self.extent();
+        let starts = |cells: Range<f64>, n: usize| {
+            let k = |c: f64| (c / n as f64).floor() as isize;
+            (k(cells.start)..=k(cells.end)).map(move |k| k * n as isize)
+        };
+        starts(rows, h).cartesian_product(starts(cols, w).collect_vec())
+    }
 }
 
 impl HashLife {
-    fn liveness(&self, (y, x, z): (f64, f64, f64)) -> f64 {
-        let real_z = z.round().max(0.);
-        let y = y * 2f64.powf(real_z - z);
-        let x = x * 2f64.powf(real_z - z);
-        let p = P3 {
-            y: y.round() as isize,
-            x: x.round() as isize,
-            z: real_z as usize,
+    pub fn render(&self, settings: Settings, view: View) -> Vec<Rgba> {
+        self.pixels(settings, view, None).collect()
+    }
+
+    /// Like `render`, but reusing the parts of earlier renders in `cache` that
+    /// haven't changed. Rendering the same view again, say after a step, only
+    /// redraws the tiles whose nodes are different.
+    pub fn render_cached(
+        &self,
+        settings: Settings,
+        view: View,
+        cache: &mut RenderCache,
+    ) -> Vec<Rgba> {
+        self.pixels(settings, view, Some(cache)).collect()
+    }
+
+    /// Like `render`, but as sRGB bytes, four to a pixel.
+    pub fn render_rgba8(&self, settings: Settings, view: View) -> Vec<u8> {
+        let mut bytes = vec![0; settings.height * settings.width * 4];
+        self.render_rgba8_into(settings, view, &mut bytes);
+        bytes
+    }
+
+    /// Like `render_rgba8`, but into a buffer of exactly `width * height * 4`
+    /// bytes, such as a frame buffer that's reused every frame.
+    pub fn render_rgba8_into(&self, settings: Settings, view: View, bytes: &mut [u8]) {
+        assert_eq!(bytes.len(), settings.height * settings.width * 4);
+        for (pixel, rgba) in bytes
+            .chunks_exact_mut(4)
+            .zip(self.pixels(settings, view, None))
+        {
+            pixel.copy_from_slice(&rgba.to_srgba_unmultiplied());
+        }
+    }
+
+    /// The colour of each pixel, row by row.
+    fn pixels(
+        &self,
+        settings: Settings,
+        view: View,
+        mut cache: Option<&mut RenderCache>,
+    ) -> impl Iterator<Item = Rgba> + use<> {
+        let frame = Frame::new(settings, view);
+        if let Some(cache) = &mut cache {
+            cache.follow(self);
+        }
+        let grid = settings
+            .grid
+            .filter(|grid| frame.pixels_per_cell >= grid.min_pixels_per_cell);
+        let heatmap = settings.heatmap.filter(|_| self.is_tracking_activity());
+        let (alive_rgba, liveness) = match heatmap {
+            Some(rgba) => (rgba, self.splat_cells(&frame, self.heat())),
+            None => (settings.alive_rgba, self.liveness(frame, cache)),
         };
-        let Some(tr) = self.universe.get_node(self.root, p) else {
-            return 0.;
+        let ramp = settings
+            .ages
+            .filter(|_| heatmap.is_none() && self.is_tracking_ages() && !self.inverted);
+        let ages = ramp.map(|ramp| {
+            let old_age = ramp.old_age.max(1) as f32;
+            let age = |born: u64| ((self.generation - born) as f32 / old_age).min(1.);
+            self.splat_cells(&frame, self.births().map(|(p, born)| (p, age(born))))
+        });
+        let envelope = settings.envelope.zip(self.envelope.as_ref());
+        let envelope = envelope.map(|(rgba, envelope)| {
+            let tree = (envelope.root, envelope.depth);
+            (rgba, self.coverage(frame, tree, None))
+        });
+        liveness.into_iter().enumerate().map(move |(k, alpha)| {
+            let alive = match (ramp, &ages) {
+                (Some(ramp), Some(ages)) if alpha > 0. => ramp.at(ages[k] / alpha),
+                _ => alive_rgba,
+            };
+            let dead = match &envelope {
+                Some((rgba, coverage)) => settings
+                    .dead_rgba
+                    .blend(rgba.multiply(coverage[k].clamp(0., 1.))),
+                None => settings.dead_rgba,
+            };
+            let rgba = dead.blend(alive.multiply(alpha));
+            match grid {
+                Some(grid) if frame.on_grid(k / frame.width, k % frame.width) => {
+                    rgba.blend(grid.rgba)
+                }
+                _ => rgba,
+            }
+        })
+    }
+
+    /// How alive each pixel is, from 0 to 1, row by row.
+    fn liveness(&self, frame: Frame, cache: Option<&mut RenderCache>) -> Vec<f32> {
+        let coverage = self.coverage(frame, (self.root, self.depth), cache);
+        coverage
+            .into_iter()
+            .map(|density| self.shade(density.clamp(0., 1.)))
+            .collect()
+    }
+
+    /// How much of each pixel the stored cells of a tree centred like the
+    /// root cover, row by row. Rather than looking up every pixel, the tree is
+    /// walked once down to the nodes no bigger than a pixel, skipping those
+    /// out of view or empty, and each pixel is shaded by how much of it they
+    /// cover. The cache is only for the root.
+    fn coverage(
+        &self,
+        frame: Frame,
+        tree: (TreeRef, usize),
+        cache: Option<&mut RenderCache>,
+    ) -> Vec<f32> {
+        let mut coverage = vec![0.; frame.height * frame.width];
+        let universe = &*self.universe.lock();
+        match (self.topology, cache) {
+            _ if frame.is_turned() => {
+                for (k, density) in coverage.iter_mut().enumerate() {
+                    let p = frame.cell_under(k / frame.width, k % frame.width);
+                    *density = self.density_at(universe, tree, p, frame.level);
+                }
+            }
+            (Topology::Torus { w, h }, _) if self.fits_in_pixel(&frame) => {
+                // Every pixel looks the same.
+                let density = universe.population(tree.0) as f32 / (w * h) as f32;
+                coverage.fill(density);
+            }
+            (Topology::Plane, Some(cache)) => coverage = self.repaint(universe, frame, cache),
+            (_, Some(cache)) => {
+                for offset in self.copies(&frame) {
+                    let cache = Some(&mut *cache);
+                    self.paint(universe, &frame, tree, offset, &mut coverage, cache);
+                }
+            }
+            (_, None) => self.paint_copies(universe, &frame, tree, &mut coverage),
+        }
+        coverage
+    }
+
+    /// Adds up the values of the cells covering each pixel, weighted by how
+    /// much of the pixel they cover.
+    fn splat_cells(
+        &self,
+        frame: &Frame,
+        cells: impl IntoIterator<Item = ((isize, isize), f32)>,
+    ) -> Vec<f32> {
+        let mut coverage = vec![0.; frame.height * frame.width];
+        if frame.is_turned() {
+            let cells: HashMap<_, _> = cells.into_iter().collect();
+            for (k, value) in coverage.iter_mut().enumerate() {
+                let p = frame.cell_under(k / frame.width, k % frame.width);
+                *value = cells.get(&self.topology.wrap(p)).copied().unwrap_or(0.);
+            }
+            return coverage;
+        }
+        let cells = cells.into_iter().collect_vec();
+        if let Topology::Torus { w, h } = self.topology
+            && self.fits_in_pixel(frame)
+        {
+            let total: f32 = cells.iter().map(|&(_, value)| value).sum();
+            coverage.fill(total / (w * h) as f32);
+            return coverage;
+        }
+        for (dy, dx) in self.copies(frame) {
+            for &((y, x), value) in &cells {
+                let rows = (y + dy) as f64..(y + dy + 1) as f64;
+                let cols = (x + dx) as f64..(x + dx + 1) as f64;
+                frame.splat(&mut coverage, &rows, &cols, value);
+            }
+        }
+        coverage
+    }
+
+    /// Whether the pattern is a torus small enough to fit in a pixel, so that
+    /// every pixel looks the same.
+    fn fits_in_pixel(&self, frame: &Frame) -> bool {
+        match self.topology {
+            Topology::Plane => false,
+            Topology::Torus { w, h } => 2f64.powi(frame.level as i32) >= w.min(h) as f64,
+        }
+    }
+
+    /// Where the copies of the pattern in view start, which is just the one
+    /// unless it's a torus.
+    fn copies(&self, frame: &Frame) -> Vec<(isize, isize)> {
+        match self.topology {
+            Topology::Plane => vec![(0, 0)],
+            Topology::Torus { w, h } => frame.copies(w, h).collect(),
+        }
+    }
+
+    #[cfg(not(feature = "rayon"))]
+    fn paint_copies(
+        &self,
+        universe: &Universe,
+        frame: &Frame,
+        tree: (TreeRef, usize),
+        coverage: &mut [f32],
+    ) {
+        for offset in self.copies(frame) {
+            self.paint(universe, frame, tree, offset, coverage, None);
+        }
+    }
+
+    /// Splits the pixels into bands of rows and draws them in parallel.
+    #[cfg(feature = "rayon")]
+    fn paint_copies(
+        &self,
+        universe: &Universe,
+        frame: &Frame,
+        tree: (TreeRef, usize),
+        coverage: &mut [f32],
+    ) {
+        use rayon::prelude::*;
+
+        const BAND: usize = 16;
+        if frame.width == 0 {
+            return;
+        }
+        let bands = coverage.par_chunks_mut(BAND * frame.width).enumerate();
+        bands.for_each(|(k, band)| {
+            let frame = frame.band(k * BAND, band.len() / frame.width);
+            for offset in self.copies(&frame) {
+                self.paint(universe, &frame, tree, offset, band, None);
+            }
+        });
+    }
+
+    /// Adds the tree moved by (dy, dx) to the pixels' coverage, a tile at a
+    /// time if there's a cache.
+    fn paint(
+        &self,
+        universe: &Universe,
+        frame: &Frame,
+        (root, depth): (TreeRef, usize),
+        (dy, dx): (isize, isize),
+        coverage: &mut [f32],
+        mut cache: Option<&mut RenderCache>,
+    ) {
+        let mut stack = vec![(root, P3::origin(depth))];
+        while let Some((tr, p)) = stack.pop() {
+            let (ys, xs) = p.bounds();
+            let rows = (ys.start + dy) as f64..(ys.end + dy) as f64;
+            let cols = (xs.start + dx) as f64..(xs.end + dx) as f64;
+            if universe.population(tr) == 0 || !frame.shows(&rows, &cols) {
+                continue;
+            }
+            match (p.quadrants(), &mut cache) {
+                (_, Some(cache)) if p.z == frame.level + cache::TILE_BITS => {
+                    let tile = cache.tile(self, universe, tr, p.z, frame.level);
+                    frame.splat_tile(coverage, &rows, &cols, &tile, 1.);
+                }
+                (Some(ps), _) if p.z > frame.level => {
+                    stack.extend(universe.subtree(tr).into_iter().zip(ps));
+                }
+                (_, _) => frame.splat(coverage, &rows, &cols, self.density(universe, tr, p.z)),
+            }
+        }
+    }
+
+    /// The density of the node of a tree at `level` holding the cell at `p`.
+    fn density_at(
+        &self,
+        universe: &Universe,
+        (root, depth): (TreeRef, usize),
+        p: (isize, isize),
+        level: usize,
+    ) -> f32 {
+        let (y, x) = self.topology.wrap(p);
+        let (mut tr, mut p) = (root, P3::origin(depth));
+        let contains = |p: &P3| {
+            let (ys, xs) = p.bounds();
+            ys.contains(&y) && xs.contains(&x)
         };
-        let population = self.universe.population(tr) as f64;
-        if population == 0. {
+        if !contains(&p) {
             return 0.;
         }
-        let capacity = 2f64.powf(self.depth as f64 - real_z).powf(2.);
-        population / capacity
+        // Empty nodes are left early.
+        while p.z > level && universe.population(tr) > 0 {
+            let ps = p.quadrants().unwrap();
+            let k = ps.iter().position(contains).unwrap();
+            (tr, p) = (universe.subtree(tr)[k], ps[k]);
+        }
+        match universe.population(tr) {
+            0 => 0.,
+            _ => self.density(universe, tr, p.z),
+        }
     }
 
-    pub fn render(&self, settings: Settings, view: View) -> Vec<Rgba> {
-        let mut pixels = Vec::with_capacity(settings.height * settings.width);
-        let (y, x) = view.center;
-        let mut z = self.depth as f64;
-        let mut pixels_per_cell = view.zoom * settings.cell_size;
-        if pixels_per_cell < 1.0 {
-            z += pixels_per_cell.log2();
-            pixels_per_cell = 1.0;
-        }
-        let (y, x) = (
-            y - settings.height as f64 / pixels_per_cell / 2.,
-            x - settings.width as f64 / pixels_per_cell / 2.,
-        );
-        for i in 0..settings.height {
-            for j in 0..settings.width {
-                let p = (
-                    y + i as f64 / pixels_per_cell,
-                    x + j as f64 / pixels_per_cell,
-                    z,
-                );
-                let alpha = self.liveness(p);
-                let rgba = settings.alive_rgba.multiply(alpha as f32);
-                pixels.push(settings.dead_rgba.blend(rgba));
+    /// How much of a square of 4^z cells a node fills. Single cells fade out
+    /// through the dying states of Generations rules.
+    fn density(&self, universe: &Universe, tr: TreeRef, z: usize) -> f32 {
+        if z == 0 {
+            let (state, states) = (universe.state(tr), self.rule().states());
+            return (states - state) as f32 / (states - 1) as f32;
+        }
+        (universe.population(tr) as f64 / 4f64.powi(z as i32)) as f32
+    }
+
+    /// How alive a node looks, given how much of it is stored. Inverted cells
+    /// are stored dead.
+    fn shade(&self, density: f32) -> f32 {
+        if self.inverted { 1. - density } else { density }
+    }
+}
+
+#[cfg(all(test, feature = "rayon"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bands_match_whole_frame() {
+        let soup = (0..200).map(|k| ((k * 37 % 61) - 30, (k * 53 % 67) - 33));
+        let plane = HashLife::from_iter(soup.clone());
+        let mut torus =
+            HashLife::from_iter(soup.map(|(y, x)| (y.rem_euclid(23), x.rem_euclid(19))));
+        torus.set_topology(Topology::Torus { w: 19, h: 23 });
+        // Tall enough for a ragged last band, and zoomed so that some nodes
+        // straddle the bands.
+        let settings = Settings {
+            height: 41,
+            width: 29,
+            cell_size: 1.,
+            dead_rgba: Rgba::BLACK,
+            alive_rgba: Rgba::WHITE,
+            row_offset: 0.,
+            grid: None,
+            ages: None,
+            heatmap: None,
+            envelope: None,
+        };
+        for hl in [plane, torus] {
+            for zoom in [0.3, 1., 2.5] {
+                let view = View {
+                    zoom,
+                    center: (0.5, -3.),
+                    rotation: 0.,
+                };
+                let frame = Frame::new(settings, view);
+                let tree = (hl.root, hl.depth);
+                let universe = &*hl.universe.lock();
+                let mut bands = vec![0.; frame.height * frame.width];
+                hl.paint_copies(universe, &frame, tree, &mut bands);
+                let mut whole = vec![0.; frame.height * frame.width];
+                for offset in hl.copies(&frame) {
+                    hl.paint(universe, &frame, tree, offset, &mut whole, None);
+                }
+                assert!(whole.iter().any(|&c| c > 0.));
+                for (a, b) in bands.iter().zip(&whole) {
+                    assert!((a - b).abs() < 1e-4, "{a} != {b} at zoom {zoom}");
+                }
             }
         }
-        pixels
     }
 }
@@ -0,0 +1,233 @@
+use std::{collections::HashMap, ops::Range, rc::Rc};
+
+use crate::{
+    HashLife,
+    p3::P3,
+    render::Frame,
+    universe::{TreeRef, Universe},
+};
+
+/// Tiles are the nodes this many levels above the pixel sized ones, so are
+/// 2^TILE_BITS samples across.
+pub(super) const TILE_BITS: usize = 5;
+
+/// The densities of the pixel sized nodes within a tile, row by row.
+type Tile = Rc<[f32]>;
+
+/// Tiles from earlier renders. Nodes are shared wherever the same pattern
+/// appears, so a tile drawn once is reused for every copy of it and for as
+/// long as it stays on screen, whether the view pans or the pattern steps.
+///
+/// Tiles are only good for the universe they were drawn from and its rule, so
+/// the cache should be cleared when switching to a different one.
+#[derive(Clone, Debug)]
+pub struct RenderCache {
+    tiles: HashMap<(TreeRef, usize), Tile>,
+    capacity: usize,
+    last: Option<LastRender>,
+    redrawn: usize,
+    /// How many times the universe had collected garbage when drawn from.
+    collections: u64,
+}
+
+/// What was on screen after the last render, so that if the view hasn't moved
+/// only the tiles that changed need drawing again.
+#[derive(Clone, Debug)]
+struct LastRender {
+    frame: Frame,
+    inverted: bool,
+    coverage: Vec<f32>,
+    /// The tiles in view, by the top-left cell of each.
+    placed: HashMap<(isize, isize), TreeRef>,
+}
+
+impl Default for RenderCache {
+    fn default() -> Self {
+        Self::with_capacity(4096)
+    }
+}
+
+impl RenderCache {
+    /// A cache that starts over once it holds more than `capacity` tiles.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            tiles: HashMap::new(),
+            capacity,
+            last: None,
+            redrawn: 0,
+            collections: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.tiles.clear();
+        self.last = None;
+    }
+
+    /// Starts over if garbage collection has renumbered the nodes since the
+    /// last render.
+    pub(super) fn follow(&mut self, hl: &HashLife) {
+        let collections = hl.universe.lock().collections();
+        if self.collections != collections {
+            self.clear();
+            self.collections = collections;
+        }
+    }
+
+    /// How many tiles the last render had to draw, rather than leaving them
+    /// as they were the time before.
+    pub fn redrawn(&self) -> usize {
+        self.redrawn
+    }
+
+    /// The tile for a node at depth z, drawn with nodes at depth `level` as
+    /// single samples.
+    pub(super) fn tile(
+        &mut self,
+        hl: &HashLife,
+        universe: &Universe,
+        tr: TreeRef,
+        z: usize,
+        level: usize,
+    ) -> Tile {
+        if let Some(tile) = self.tiles.get(&(tr, level)) {
+            return tile.clone();
+        }
+        if self.tiles.len() >= self.capacity {
+            self.tiles.clear();
+        }
+        let n = 1 << (z - level);
+        let mut samples = vec![0.; n * n];
+        let mut stack = vec![(tr, P3::new(0, 0, z))];
+        while let Some((tr, p)) = stack.pop() {
+            if universe.population(tr) == 0 {
+                continue;
+            }
+            match p.quadrants() {
+                Some(ps) if p.z > level => {
+                    stack.extend(universe.subtree(tr).into_iter().zip(ps));
+                }
+                _ => {
+                    // Measured in samples from the tile's top-left corner.
+                    let (ys, xs) = p.bounds();
+                    let half = (n as isize) << level >> 1;
+                    let y = ((ys.start + half) >> level) as usize;
+                    let x = ((xs.start + half) >> level) as usize;
+                    samples[y * n + x] = hl.density(universe, tr, level);
+                }
+            }
+        }
+        let tile: Tile = samples.into();
+        self.tiles.insert((tr, level), tile.clone());
+        tile
+    }
+}
+
+impl HashLife {
+    /// Draws a plane into the coverage of the last render, taking back the
+    /// tiles whose node has changed since or gone and adding the new ones.
+    /// Anything else about the render changing means starting again.
+    pub(super) fn repaint(
+        &self,
+        universe: &Universe,
+        frame: Frame,
+        cache: &mut RenderCache,
+    ) -> Vec<f32> {
+        let tile_z = frame.level + TILE_BITS;
+        let last = cache.last.take();
+        cache.redrawn = 0;
+        if self.depth <= tile_z {
+            // There aren't any tiles, and nothing to gain from them.
+            let mut coverage = vec![0.; frame.height * frame.width];
+            let root = (self.root, self.depth);
+            self.paint(universe, &frame, root, (0, 0), &mut coverage, None);
+            return coverage;
+        }
+        let mut placed = HashMap::new();
+        let mut stack = vec![(self.root, P3::origin(self.depth))];
+        while let Some((tr, p)) = stack.pop() {
+            let (ys, xs) = p.bounds();
+            let rows = ys.start as f64..ys.end as f64;
+            let cols = xs.start as f64..xs.end as f64;
+            if universe.population(tr) == 0 || !frame.shows(&rows, &cols) {
+                continue;
+            }
+            match p.quadrants() {
+                Some(ps) if p.z > tile_z => {
+                    stack.extend(universe.subtree(tr).into_iter().zip(ps));
+                }
+                _ => {
+                    placed.insert((ys.start, xs.start), tr);
+                }
+            }
+        }
+        let size = 1isize << tile_z;
+        let rect = |(y, x): (isize, isize)| {
+            let rows = y as f64..(y + size) as f64;
+            let cols = x as f64..(x + size) as f64;
+            (rows, cols)
+        };
+        let (mut coverage, before) = match last {
+            Some(last) if (last.frame, last.inverted) == (frame, self.inverted) => {
+                (last.coverage, last.placed)
+            }
+            _ => (vec![0.; frame.height * frame.width], HashMap::new()),
+        };
+        for (&at, &tr) in &before {
+            if placed.get(&at) != Some(&tr) {
+                let tile = cache.tile(self, universe, tr, tile_z, frame.level);
+                let (rows, cols) = rect(at);
+                frame.splat_tile(&mut coverage, &rows, &cols, &tile, -1.);
+            }
+        }
+        for (&at, &tr) in &placed {
+            if before.get(&at) != Some(&tr) {
+                let tile = cache.tile(self, universe, tr, tile_z, frame.level);
+                let (rows, cols) = rect(at);
+                frame.splat_tile(&mut coverage, &rows, &cols, &tile, 1.);
+                cache.redrawn += 1;
+            }
+        }
+        cache.last = Some(LastRender {
+            frame,
+            inverted: self.inverted,
+            coverage: coverage.clone(),
+            placed,
+        });
+        coverage
+    }
+}
+
+impl Frame {
+    /// Splats each sample of a tile covering the rows and columns of cells,
+    /// scaled by `sign` so that a tile can be taken back out again.
+    pub(super) fn splat_tile(
+        &self,
+        coverage: &mut [f32],
+        rows: &Range<f64>,
+        cols: &Range<f64>,
+        tile: &[f32],
+        sign: f32,
+    ) {
+        let n = tile.len().isqrt();
+        let cells_per_sample = (rows.end - rows.start) / n as f64;
+        let sample = |start: f64, k: usize| {
+            let start = start + k as f64 * cells_per_sample;
+            start..start + cells_per_sample
+        };
+        for (k, &density) in tile.iter().enumerate() {
+            if density != 0. {
+                let (rows, cols) = (sample(rows.start, k / n), sample(cols.start, k % n));
+                self.splat(coverage, &rows, &cols, sign * density);
+            }
+        }
+    }
+}
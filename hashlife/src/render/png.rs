@@ -0,0 +1,208 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use crate::{
+    HashLife,
+    render::{Settings, View},
+};
+
+const SIGNATURE: &[u8; 8] = b"\x89PNG\r\n\x1a\n";
+/// The most a stored deflate block can hold.
+const BLOCK: usize = 0xffff;
+
+/// Saves a render as a PNG file. See `write_png`.
+pub fn export_png(
+    hl: &HashLife,
+    settings: Settings,
+    view: View,
+    path: impl AsRef<Path>,
+) -> io::Result<()> {
+    let mut w = BufWriter::new(File::create(path)?);
+    write_png(hl, settings, view, &mut w)?;
+    w.flush()
+}
+
+/// Writes a render as an 8 bit RGBA PNG. The pixels are stored without
+/// compression, so there's nothing to depend on, at the cost of size.
+pub fn write_png(hl: &HashLife, settings: Settings, view: View, w: impl Write) -> io::Result<()> {
+    let pixels = hl.render_rgba8(settings, view);
+    write_rgba8_png(settings.height, settings.width, &pixels, w)
+}
+
+/// Writes sRGB bytes, four to a pixel and row by row, as a PNG.
+pub(crate) fn write_rgba8_png(
+    height: usize,
+    width: usize,
+    pixels: &[u8],
+    mut w: impl Write,
+) -> io::Result<()> {
+    let header = header(height, width)?;
+    w.write_all(SIGNATURE)?;
+    write_chunk(&mut w, b"IHDR", &header)?;
+    write_chunk(&mut w, b"IDAT", &image_data(width, pixels))?;
+    write_chunk(&mut w, b"IEND", &[])
+}
+
+/// How to step a pattern through an animation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Animation {
+    pub frames: u32,
+    /// How many generations to step between frames.
+    pub step: u64,
+    /// How long each frame shows for, in milliseconds.
+    pub delay: u16,
+    /// How many times to play the animation, or 0 to loop forever.
+    pub plays: u32,
+}
+
+impl Default for Animation {
+    fn default() -> Self {
+        Self {
+            frames: 100,
+            step: 1,
+            delay: 100,
+            plays: 0,
+        }
+    }
+}
+
+/// Saves an animation as an APNG file. See `write_apng`.
+pub fn export_apng(
+    hl: &HashLife,
+    settings: Settings,
+    view: View,
+    animation: Animation,
+    path: impl AsRef<Path>,
+) -> io::Result<()> {
+    let mut w = BufWriter::new(File::create(path)?);
+    write_apng(hl, settings, view, animation, &mut w)?;
+    w.flush()
+}
+
+/// Writes an animated PNG of the pattern stepping forward, starting from how
+/// it is now. Viewers without APNG support show the first frame.
+pub fn write_apng(
+    hl: &HashLife,
+    settings: Settings,
+    view: View,
+    animation: Animation,
+    mut w: impl Write,
+) -> io::Result<()> {
+    let header = header(settings.height, settings.width)?;
+    if animation.frames == 0 {
+        return Err(invalid("Animations need at least one frame"));
+    }
+    w.write_all(SIGNATURE)?;
+    write_chunk(&mut w, b"IHDR", &header)?;
+    let mut control = vec![];
+    control.extend(animation.frames.to_be_bytes());
+    control.extend(animation.plays.to_be_bytes());
+    write_chunk(&mut w, b"acTL", &control)?;
+    // Frame controls and frame data share one sequence.
+    let mut sequence = 0u32;
+    let mut hl = hl.clone();
+    for frame in 0..animation.frames {
+        if frame > 0 {
+            hl.step_by(animation.step);
+        }
+        let mut control = vec![];
+        control.extend(sequence.to_be_bytes());
+        // The size, then the offset of the frame within the image.
+        control.extend(&header[..8]);
+        control.extend([0; 8]);
+        control.extend(animation.delay.to_be_bytes());
+        control.extend(1000u16.to_be_bytes());
+        // Frames cover the whole image, so nothing needs clearing or blending.
+        control.extend([0, 0]);
+        write_chunk(&mut w, b"fcTL", &control)?;
+        sequence += 1;
+        let data = image_data(settings.width, &hl.render_rgba8(settings, view));
+        if frame == 0 {
+            write_chunk(&mut w, b"IDAT", &data)?;
+        } else {
+            let data = [&sequence.to_be_bytes()[..], &data].concat();
+            write_chunk(&mut w, b"fdAT", &data)?;
+            sequence += 1;
+        }
+    }
+    write_chunk(&mut w, b"IEND", &[])
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+/// The IHDR chunk for an RGBA image.
+fn header(height: usize, width: usize) -> io::Result<Vec<u8>> {
+    if width == 0 || height == 0 {
+        return Err(invalid("PNGs can't be empty"));
+    }
+    let width = u32::try_from(width).map_err(|_| invalid("Image too wide"))?;
+    let height = u32::try_from(height).map_err(|_| invalid("Image too tall"))?;
+    let mut header = vec![];
+    header.extend(width.to_be_bytes());
+    header.extend(height.to_be_bytes());
+    // 8 bits per channel, RGBA, and no interlacing.
+    header.extend([8, 6, 0, 0, 0]);
+    Ok(header)
+}
+
+/// RGBA pixels as PNG image data.
+fn image_data(width: usize, pixels: &[u8]) -> Vec<u8> {
+    // Every row starts with the filter it's encoded with, here none.
+    let mut rows = Vec::with_capacity(pixels.len() + pixels.len() / (width * 4));
+    for row in pixels.chunks(width * 4) {
+        rows.push(0);
+        rows.extend(row);
+    }
+    zlib_stored(&rows)
+}
+
+fn write_chunk(w: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(data.len()).map_err(|_| invalid("Image too big for a PNG"))?;
+    w.write_all(&len.to_be_bytes())?;
+    w.write_all(kind)?;
+    w.write_all(data)?;
+    w.write_all(&crc32(kind.iter().chain(data)).to_be_bytes())
+}
+
+/// A zlib stream of uncompressed deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    // Deflate with a 32K window, and a check that makes the header a multiple
+    // of 31.
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(BLOCK).peekable();
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+        out.push(last as u8);
+        out.extend(len.to_le_bytes());
+        out.extend((!len).to_le_bytes());
+        out.extend(block);
+    }
+    out.extend(adler32(data).to_be_bytes());
+    out
+}
+
+fn crc32<'a>(bytes: impl IntoIterator<Item = &'a u8>) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in bytes {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    b << 16 | a
+}
@@ -0,0 +1,154 @@
+use std::fmt::Write;
+
+use itertools::Itertools;
+
+use crate::{
+    HashLife, LifeEngine,
+    render::{Frame, Rgba, Settings, View},
+};
+
+/// The characters to draw with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Glyphs {
+    /// `█`, a dot to a character.
+    Blocks,
+    /// `▀`, `▄` and `█`, two dots tall and one wide.
+    HalfBlocks,
+    /// Braille patterns, four dots tall and two wide.
+    Braille,
+}
+
+impl Glyphs {
+    /// How many dots tall and wide each character is.
+    pub fn dots(self) -> (usize, usize) {
+        match self {
+            Glyphs::Blocks => (1, 1),
+            Glyphs::HalfBlocks => (2, 1),
+            Glyphs::Braille => (4, 2),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Style {
+    pub glyphs: Glyphs,
+    /// Whether to colour the characters with 24 bit ANSI escapes, shading
+    /// them like `render` would. Without colour, dots are drawn where the
+    /// cells under them are at least half alive.
+    pub truecolor: bool,
+}
+
+/// Draws the view as `settings.height` lines of `settings.width` characters,
+/// where `settings.cell_size` is in dots rather than pixels.
+pub fn render(hl: &HashLife, settings: Settings, view: View, style: Style) -> String {
+    let (dy, dx) = style.glyphs.dots();
+    let dots = Settings {
+        height: settings.height * dy,
+        width: settings.width * dx,
+        ..settings
+    };
+    let lit = hl.liveness(Frame::new(dots, view), None);
+    let lit = lit.into_iter().map(|alpha| alpha >= 0.5).collect_vec();
+    let colours = match style.truecolor {
+        true => hl.pixels(dots, view, None).collect(),
+        false => vec![],
+    };
+    let mut text = String::new();
+    for i in 0..settings.height {
+        for j in 0..settings.width {
+            // The dots under the character, row by row.
+            let under = (0..dy)
+                .flat_map(|y| (0..dx).map(move |x| (i * dy + y) * dots.width + j * dx + x))
+                .collect_vec();
+            let glyph = glyph(style.glyphs, &under.iter().map(|&k| lit[k]).collect_vec());
+            if !style.truecolor {
+                text.push(glyph);
+                continue;
+            }
+            let mean = |under: &[usize]| {
+                let sum = under
+                    .iter()
+                    .fold(Rgba::TRANSPARENT, |sum, &k| sum.add(colours[k]));
+                sum.multiply(1. / under.len() as f32)
+            };
+            let (fg, bg, glyph) = match style.glyphs {
+                // Both halves are coloured in, so only one glyph is needed.
+                Glyphs::HalfBlocks => (colours[under[0]], colours[under[1]], '▀'),
+                Glyphs::Blocks | Glyphs::Braille => {
+                    let on = under.iter().copied().filter(|&k| lit[k]).collect_vec();
+                    let fg = if on.is_empty() {
+                        mean(&under)
+                    } else {
+                        mean(&on)
+                    };
+                    (fg, settings.dead_rgba, glyph)
+                }
+            };
+            write_colours(&mut text, fg, bg);
+            text.push(glyph);
+        }
+        if style.truecolor {
+            text.push_str("\x1b[0m");
+        }
+        if i + 1 < settings.height {
+            text.push('\n');
+        }
+    }
+    text
+}
+
+/// Draws `rows` lines of `columns` characters from the cell `top_left` on,
+/// a dot to a cell. Unlike `render` it draws any engine, but can't zoom out
+/// or colour the cells.
+pub fn render_engine(
+    engine: &dyn LifeEngine,
+    (top, left): (isize, isize),
+    rows: usize,
+    columns: usize,
+    glyphs: Glyphs,
+) -> String {
+    let (dy, dx) = glyphs.dots();
+    let mut text = String::new();
+    for i in 0..rows {
+        for j in 0..columns {
+            let lit = (0..dy)
+                .flat_map(|y| (0..dx).map(move |x| (i * dy + y, j * dx + x)))
+                .map(|(y, x)| engine.get((top + y as isize, left + x as isize)))
+                .collect_vec();
+            text.push(glyph(glyphs, &lit));
+        }
+        if i + 1 < rows {
+            text.push('\n');
+        }
+    }
+    text
+}
+
+/// The character with the given dots lit, row by row.
+fn glyph(glyphs: Glyphs, lit: &[bool]) -> char {
+    match glyphs {
+        Glyphs::Blocks => match lit[0] {
+            false => ' ',
+            true => '█',
+        },
+        Glyphs::HalfBlocks => match (lit[0], lit[1]) {
+            (false, false) => ' ',
+            (true, false) => '▀',
+            (false, true) => '▄',
+            (true, true) => '█',
+        },
+        Glyphs::Braille => {
+            // Braille numbers its dots down the left column then the right,
+            // with the bottom row added last.
+            const BITS: [u32; 8] = [0x01, 0x08, 0x02, 0x10, 0x04, 0x20, 0x40, 0x80];
+            let bits = lit.iter().zip(BITS).filter(|&(&on, _)| on);
+            char::from_u32(0x2800 + bits.map(|(_, bit)| bit).sum::<u32>()).unwrap()
+        }
+    }
+}
+
+fn write_colours(text: &mut String, fg: Rgba, bg: Rgba) {
+    let [r, g, b, _] = fg.to_srgba_unmultiplied();
+    let [r2, g2, b2, _] = bg.to_srgba_unmultiplied();
+    write!(text, "\x1b[38;2;{r};{g};{b}m\x1b[48;2;{r2};{g2};{b2}m").unwrap();
+}
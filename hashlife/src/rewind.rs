@@ -0,0 +1,112 @@
+use std::collections::VecDeque;
+
+use crate::{HashLife, gc::Snapshot};
+
+/// States kept every so many generations while stepping, to step back from.
+#[derive(Clone, Debug)]
+pub(crate) struct Rewind {
+    interval: u64,
+    max_checkpoints: usize,
+    /// Oldest first.
+    checkpoints: VecDeque<Snapshot>,
+    /// The state after the last step, to notice edits made since.
+    stepped: Snapshot,
+}
+
+impl HashLife {
+    /// Starts keeping the state every `interval` generations of stepping, up
+    /// to `max_checkpoints` of them, for `step_back`, or stops if `interval`
+    /// is 0. The state now is the first.
+    pub fn track_rewind(&mut self, interval: u64, max_checkpoints: usize) {
+        if let Some(rewind) = self.rewind.take() {
+            for snapshot in rewind.checkpoints.iter().chain([&rewind.stepped]) {
+                self.release(snapshot);
+            }
+        }
+        if interval == 0 {
+            return;
+        }
+        self.rewind = Some(Rewind {
+            interval,
+            max_checkpoints: max_checkpoints.max(1),
+            checkpoints: VecDeque::from([self.snapshot()]),
+            stepped: self.snapshot(),
+        });
+    }
+
+    pub fn is_tracking_rewind(&self) -> bool {
+        self.rewind.is_some()
+    }
+
+    /// How many generations `step_back` can go back, if rewind is being
+    /// tracked.
+    pub fn rewind_limit(&self) -> Option<u64> {
+        let rewind = self.rewind.as_ref()?;
+        if !self.is_at(&rewind.stepped) {
+            return Some(0);
+        }
+        let oldest = rewind.checkpoints.front().unwrap();
+        Some(self.generation - self.generation_of(oldest))
+    }
+
+    /// Goes back `steps` generations, by stepping forward again from the last
+    /// checkpoint before then. Editing the pattern starts the checkpoints
+    /// again, as what came before can't be stepped to it.
+    pub fn step_back(&mut self, steps: u64) -> Result<(), String> {
+        if self.rewind.is_none() {
+            return Err("Rewind isn't being tracked".to_string());
+        }
+        self.rewind_edits();
+        let limit = self.rewind_limit().unwrap();
+        if steps > limit {
+            return Err(format!(
+                "Can only step back {limit} generations, not {steps}"
+            ));
+        }
+        let target = self.generation - steps;
+        // Replaying shouldn't look like an edit, or keep checkpoints again.
+        let mut rewind = self.rewind.take().unwrap();
+        while self.generation_of(rewind.checkpoints.back().unwrap()) > target {
+            let checkpoint = rewind.checkpoints.pop_back().unwrap();
+            self.release(&checkpoint);
+        }
+        let checkpoint = rewind.checkpoints.back().unwrap();
+        self.restore(checkpoint).unwrap();
+        self.step_by(target - self.generation);
+        let stepped = std::mem::replace(&mut rewind.stepped, self.snapshot());
+        self.release(&stepped);
+        self.rewind = Some(rewind);
+        Ok(())
+    }
+
+    /// Starts the checkpoints again from the state before a step, if the
+    /// pattern has been edited since the last one.
+    pub(crate) fn rewind_edits(&mut self) {
+        let Some(rewind) = &self.rewind else {
+            return;
+        };
+        if !self.is_at(&rewind.stepped) {
+            let (interval, max_checkpoints) = (rewind.interval, rewind.max_checkpoints);
+            self.track_rewind(interval, max_checkpoints);
+        }
+    }
+
+    /// Keeps the state after a step if it's been long enough since the last
+    /// checkpoint, forgetting the oldest past the limit.
+    pub(crate) fn update_rewind(&mut self) {
+        let Some(mut rewind) = self.rewind.take() else {
+            return;
+        };
+        let last = self.generation_of(rewind.checkpoints.back().unwrap());
+        if self.generation.saturating_sub(last) >= rewind.interval {
+            rewind.checkpoints.push_back(self.snapshot());
+        }
+        while rewind.checkpoints.len() > rewind.max_checkpoints {
+            let oldest = rewind.checkpoints.pop_front().unwrap();
+            self.release(&oldest);
+        }
+        let stepped = std::mem::replace(&mut rewind.stepped, self.snapshot());
+        self.release(&stepped);
+        self.rewind = Some(rewind);
+    }
+}
@@ -0,0 +1,576 @@
+mod table;
+
+use std::{
+    fmt::{Debug, Display, Write},
+    ops::RangeInclusive,
+    str::FromStr,
+};
+
+use table::RuleFile;
+
+use crate::RuleError;
+
+/// Hensel's letters for each neighbour count up to 4, with a representative
+/// neighbourhood for each. Neighbourhoods are 3x3 masks where bit `3 * y + x`
+/// is the cell at row y, column x (so the center is bit 4). Counts above 4 use
+/// the complement of the letter for `8 - count`.
+const LETTERS: [&[(char, u16)]; 5] = [
+    &[],
+    &[('c', 1), ('e', 2)],
+    &[
+        ('c', 5),
+        ('e', 10),
+        ('a', 3),
+        ('i', 40),
+        ('k', 33),
+        ('n', 68),
+    ],
+    &[
+        ('c', 69),
+        ('e', 42),
+        ('a', 11),
+        ('i', 7),
+        ('k', 98),
+        ('n', 13),
+        ('j', 14),
+        ('q', 70),
+        ('r', 41),
+        ('y', 97),
+    ],
+    &[
+        ('c', 325),
+        ('e', 170),
+        ('a', 15),
+        ('i', 45),
+        ('k', 99),
+        ('n', 71),
+        ('j', 106),
+        ('q', 102),
+        ('r', 43),
+        ('y', 101),
+        ('t', 105),
+        ('w', 78),
+        ('z', 108),
+    ],
+];
+
+const CENTER: u16 = 1 << 4;
+const NEIGHBOURS: u16 = 0b111_101_111;
+const VON_NEUMANN: u16 = 0b010_101_010;
+/// Hexagonal grids are drawn on a square grid by leaving out the NE and SW
+/// neighbours, so each row sits half a cell to the left of the one above.
+const HEXAGONAL: u16 = 0b110_101_011;
+
+/// A rule for a two state or Generations cellular automaton.
+///
+/// Outer-totalistic and isotropic non-totalistic rules, e.g. `B3/S23` or
+/// `B2-a/S12`, are stored as a transition table over 3x3 neighbourhoods.
+/// Larger than Life rules like `R2,C2,S5-8,B7-8,NM` count neighbours over a
+/// wider radius. Arbitrary transition functions can be loaded from the
+/// `@TABLE` or `@TREE` of a Golly `.rule` file.
+///
+/// Totalistic rules can also count just the von Neumann or hexagonal neighbours
+/// by ending in `V` or `H`, e.g. `B2/S34H`.
+///
+/// Generations rules like `B2/S/C3` add dying states: a live cell that doesn't
+/// survive counts up through states 2.. until it wraps back round to dead.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Rule {
+    kind: Kind,
+    states: u8,
+}
+
+#[derive(Clone, PartialEq, Eq)]
+enum Kind {
+    Isotropic {
+        birth: Conditions,
+        survival: Conditions,
+        neighbourhood: Neighbourhood,
+        table: Box<[bool; 512]>,
+    },
+    LargerThanLife(LargerThanLife),
+    File(Box<RuleFile>),
+}
+
+/// Which nearby cells count as a cell's neighbours.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Neighbourhood {
+    Moore,
+    VonNeumann,
+    Hexagonal,
+}
+
+impl Neighbourhood {
+    /// How far right of the row above each row should be drawn, in cells, for
+    /// neighbours to sit evenly around a cell.
+    pub fn row_offset(self) -> f64 {
+        match self {
+            Neighbourhood::Hexagonal => -0.5,
+            _ => 0.,
+        }
+    }
+
+    /// The neighbours within a 3x3 neighbourhood laid out as in `LETTERS`.
+    fn mask(self) -> u16 {
+        match self {
+            Neighbourhood::Moore => NEIGHBOURS,
+            Neighbourhood::VonNeumann => VON_NEUMANN,
+            Neighbourhood::Hexagonal => HEXAGONAL,
+        }
+    }
+
+    /// Whether the cell offset by (dy, dx) is a neighbour within radius r.
+    fn contains(self, dy: isize, dx: isize, r: isize) -> bool {
+        match self {
+            Neighbourhood::Moore => dy.abs() <= r && dx.abs() <= r,
+            Neighbourhood::VonNeumann => dy.abs() + dx.abs() <= r,
+            Neighbourhood::Hexagonal => dy.abs().max(dx.abs()).max((dx - dy).abs()) <= r,
+        }
+    }
+
+    fn suffix(self) -> &'static str {
+        match self {
+            Neighbourhood::Moore => "",
+            Neighbourhood::VonNeumann => "V",
+            Neighbourhood::Hexagonal => "H",
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq)]
+struct LargerThanLife {
+    radius: usize,
+    middle: bool,
+    neighbourhood: Neighbourhood,
+    birth: RangeInclusive<usize>,
+    survival: RangeInclusive<usize>,
+}
+
+impl Rule {
+    pub fn life() -> Self {
+        "B3/S23".parse().unwrap()
+    }
+
+    /// The number of cell states, including dead. This is 2 unless the rule is
+    /// a Generations rule.
+    pub fn states(&self) -> u8 {
+        self.states
+    }
+
+    /// How far away a cell's neighbours can be.
+    pub fn radius(&self) -> usize {
+        match &self.kind {
+            Kind::Isotropic { .. } | Kind::File(_) => 1,
+            Kind::LargerThanLife(ltl) => ltl.radius,
+        }
+    }
+
+    pub fn neighbourhood(&self) -> Neighbourhood {
+        match &self.kind {
+            Kind::Isotropic { neighbourhood, .. } => *neighbourhood,
+            Kind::LargerThanLife(ltl) => ltl.neighbourhood,
+            Kind::File(_) => Neighbourhood::Moore,
+        }
+    }
+
+    /// Whether a cell's next state only depends on its own state and which of
+    /// its neighbours are live (state 1), as `next` assumes.
+    pub fn uses_live_neighbourhood(&self) -> bool {
+        !matches!(self.kind, Kind::File(_))
+    }
+
+    /// The rule in a form `from_str` accepts. This is the same as `to_string`
+    /// except for rules loaded from files, which are only displayed by name.
+    pub fn definition(&self) -> String {
+        match &self.kind {
+            Kind::File(file) => file.source.clone(),
+            _ => self.to_string(),
+        }
+    }
+
+    /// The next state of a cell, given the live (state 1) cells of its 3x3
+    /// neighbourhood laid out as in `LETTERS`. Only valid for rules with
+    /// radius 1 that use the live neighbourhood.
+    pub fn next(&self, state: u8, neighbourhood: u16) -> u8 {
+        let neighbourhood = match state {
+            0 => neighbourhood & !CENTER,
+            _ => neighbourhood | CENTER,
+        };
+        match &self.kind {
+            Kind::Isotropic { table, .. } => self.decay(state, table[neighbourhood as usize]),
+            _ => self.next_cell(state, |dy, dx| {
+                (neighbourhood >> (3 * (dy + 1) + dx + 1) & 1) as u8
+            }),
+        }
+    }
+
+    /// The next state of a cell, where `neighbour(dy, dx)` is the state of the
+    /// cell offset by (dy, dx), for offsets up to the radius.
+    pub fn next_cell(&self, state: u8, neighbour: impl Fn(isize, isize) -> u8) -> u8 {
+        let alive = |dy, dx| neighbour(dy, dx) == 1;
+        let ltl = match &self.kind {
+            Kind::Isotropic { .. } => {
+                let neighbourhood = (0..9).fold(0, |n, i| {
+                    let (dy, dx) = (i / 3 - 1, i % 3 - 1);
+                    n | (alive(dy, dx) as u16) << i
+                });
+                return self.next(state, neighbourhood);
+            }
+            Kind::File(file) => return file.next_cell(state, neighbour),
+            Kind::LargerThanLife(ltl) => ltl,
+        };
+        let r = ltl.radius as isize;
+        let mut count = 0;
+        for dy in -r..=r {
+            for dx in -r..=r {
+                let in_range = ltl.neighbourhood.contains(dy, dx, r);
+                let counted = in_range && (ltl.middle || (dy, dx) != (0, 0));
+                count += (counted && alive(dy, dx)) as usize;
+            }
+        }
+        let stays_alive = match state {
+            0 => ltl.birth.contains(&count),
+            _ => ltl.survival.contains(&count),
+        };
+        self.decay(state, stays_alive)
+    }
+
+    fn decay(&self, state: u8, alive: bool) -> u8 {
+        match (state, alive) {
+            (0, born) => born as u8,
+            (1, true) => 1,
+            (state, _) => (state + 1) % self.states,
+        }
+    }
+
+    fn isotropic(
+        birth: Conditions,
+        survival: Conditions,
+        states: u8,
+        neighbourhood: Neighbourhood,
+    ) -> Self {
+        let mut table = Box::new([false; 512]);
+        for (cells, next) in table.iter_mut().enumerate() {
+            // Only Moore rules can use letters, so the others just count.
+            let (count, letter) = match neighbourhood {
+                Neighbourhood::Moore => classify(cells as u16),
+                _ => (
+                    (cells as u16 & neighbourhood.mask()).count_ones() as usize,
+                    0,
+                ),
+            };
+            let conditions = match cells as u16 & CENTER {
+                0 => &birth,
+                _ => &survival,
+            };
+            *next = conditions.0[count] & 1 << letter != 0;
+        }
+        let kind = Kind::Isotropic {
+            birth,
+            survival,
+            neighbourhood,
+            table,
+        };
+        Self { kind, states }
+    }
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        Self::life()
+    }
+}
+
+impl FromStr for Rule {
+    type Err = RuleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rule = parse(s)?;
+        // A live background is stored by inverting every cell, which only
+        // makes sense with two states.
+        if rule.states > 2 && rule.next_cell(0, |_, _| 0) != 0 {
+            return Err(RuleError::LiveBackground(s.to_string()));
+        }
+        Ok(rule)
+    }
+}
+
+fn parse(s: &str) -> Result<Rule, RuleError> {
+    let unsupported = || RuleError::Unsupported(s.to_string());
+    if s.contains("@RULE") {
+        let file = RuleFile::parse(s).map_err(RuleError::File)?;
+        let states = file.states;
+        let kind = Kind::File(Box::new(file));
+        return Ok(Rule { kind, states });
+    }
+    if s.trim().starts_with(['R', 'r']) && s.contains(',') {
+        return parse_larger_than_life(s.trim()).ok_or_else(unsupported);
+    }
+    let (s, neighbourhood) = match s.trim() {
+        s if s.ends_with(['V', 'v']) => (&s[..s.len() - 1], Neighbourhood::VonNeumann),
+        s if s.ends_with(['H', 'h']) => (&s[..s.len() - 1], Neighbourhood::Hexagonal),
+        s => (s, Neighbourhood::Moore),
+    };
+    let parts: Vec<&str> = s.split('/').collect();
+    let tagged = |tag: char| {
+        let mut tagged = parts
+            .iter()
+            .filter_map(|p| p.strip_prefix([tag, tag.to_ascii_lowercase()]));
+        tagged.next()
+    };
+    let (birth, survival, states) = match (tagged('B'), tagged('S'), tagged('C')) {
+        (Some(b), Some(s), c) if parts.len() == 2 + c.is_some() as usize => (b, s, c),
+        // The older "S/B" and "S/B/C" notations, e.g. "23/3" and "/2/3".
+        (None, None, None) => match parts.as_slice() {
+            [s, b] => (*b, *s, None),
+            [s, b, c] => (*b, *s, Some(*c)),
+            _ => return Err(unsupported()),
+        },
+        _ => return Err(unsupported()),
+    };
+    let states = match states.map(str::parse) {
+        None => 2,
+        Some(Ok(states @ 2..)) => states,
+        Some(_) => return Err(unsupported()),
+    };
+    let (birth, survival): (Conditions, Conditions) = (birth.parse()?, survival.parse()?);
+    if neighbourhood != Neighbourhood::Moore {
+        let size = neighbourhood.mask().count_ones() as usize;
+        for conditions in [&birth, &survival] {
+            if !conditions.is_totalistic() || conditions.0[size + 1..].iter().any(|&c| c != 0) {
+                return Err(unsupported());
+            }
+        }
+    }
+    Ok(Rule::isotropic(birth, survival, states, neighbourhood))
+}
+
+/// Parses Larger than Life rules in either Golly's `R5,C0,M1,S34..58,B34..45,NM`
+/// form or the HROT style `R2,C2,S5-8,B7-8,NM`.
+fn parse_larger_than_life(s: &str) -> Option<Rule> {
+    let mut ltl = LargerThanLife {
+        radius: 1,
+        middle: false,
+        neighbourhood: Neighbourhood::Moore,
+        birth: 3..=3,
+        survival: 2..=3,
+    };
+    let mut states = 2;
+    let range = |s: &str| {
+        let (lo, hi) = s
+            .split_once("..")
+            .or_else(|| s.split_once('-'))
+            .unwrap_or((s, s));
+        Some(lo.parse().ok()?..=hi.parse().ok()?)
+    };
+    for field in s.split(',') {
+        let (tag, value) = field.split_at_checked(1)?;
+        match tag.to_ascii_uppercase().as_str() {
+            "R" => ltl.radius = value.parse().ok().filter(|&r| r >= 1)?,
+            // C0 and C1 are both plain two state rules.
+            "C" => states = value.parse::<u8>().ok()?.max(2),
+            "M" => ltl.middle = value == "1",
+            "S" => ltl.survival = range(value)?,
+            "B" => ltl.birth = range(value)?,
+            "N" => {
+                ltl.neighbourhood = match value {
+                    "M" => Neighbourhood::Moore,
+                    "N" => Neighbourhood::VonNeumann,
+                    "H" => Neighbourhood::Hexagonal,
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        }
+    }
+    let kind = Kind::LargerThanLife(ltl);
+    Some(Rule { kind, states })
+}
+
+impl Display for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            Kind::Isotropic {
+                birth,
+                survival,
+                neighbourhood,
+                ..
+            } => {
+                write!(f, "B{birth}/S{survival}")?;
+                if self.states > 2 {
+                    write!(f, "/C{}", self.states)?;
+                }
+                f.write_str(neighbourhood.suffix())
+            }
+            Kind::LargerThanLife(ltl) => {
+                let states = if self.states > 2 { self.states } else { 0 };
+                let neighbourhood = match ltl.neighbourhood {
+                    Neighbourhood::Moore => 'M',
+                    Neighbourhood::VonNeumann => 'N',
+                    Neighbourhood::Hexagonal => 'H',
+                };
+                write!(
+                    f,
+                    "R{},C{states},M{},S{}..{},B{}..{},N{neighbourhood}",
+                    ltl.radius,
+                    ltl.middle as u8,
+                    ltl.survival.start(),
+                    ltl.survival.end(),
+                    ltl.birth.start(),
+                    ltl.birth.end(),
+                )
+            }
+            Kind::File(file) => f.write_str(&file.name),
+        }
+    }
+}
+
+impl Debug for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Rule").field(&self.to_string()).finish()
+    }
+}
+
+/// For each neighbour count, a bitmask over that count's letters in `LETTERS`.
+/// Counts 0 and 8 only have the one (unlettered) neighbourhood, bit 0.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Conditions([u16; 9]);
+
+fn letters(count: usize) -> &'static [(char, u16)] {
+    LETTERS[count.min(8 - count)]
+}
+
+fn all_letters(count: usize) -> u16 {
+    (1 << letters(count).len().max(1)) - 1
+}
+
+impl Conditions {
+    /// Whether the conditions only depend on the neighbour count.
+    fn is_totalistic(&self) -> bool {
+        let mut counts = self.0.iter().enumerate();
+        counts.all(|(count, &mask)| mask == 0 || mask == all_letters(count))
+    }
+}
+
+impl FromStr for Conditions {
+    type Err = RuleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut conditions = [0; 9];
+        // The current count, and whether we've seen a letter or '-' for it.
+        let mut count = None;
+        let mut negate = None;
+        for c in s.chars() {
+            match (c, count, negate) {
+                ('0'..='8', _, _) => {
+                    let n = c.to_digit(10).unwrap() as usize;
+                    conditions[n] = all_letters(n);
+                    (count, negate) = (Some(n), None);
+                }
+                ('-', Some(_), None) => negate = Some(true),
+                (c, Some(n), _) => {
+                    let Some(i) = letters(n).iter().position(|&(l, _)| l == c) else {
+                        return Err(RuleError::UnexpectedChar(c));
+                    };
+                    if negate.is_none() {
+                        // Letters without a '-' pick out just those neighbourhoods.
+                        (conditions[n], negate) = (0, Some(false));
+                    }
+                    match negate {
+                        Some(true) => conditions[n] &= !(1 << i),
+                        _ => conditions[n] |= 1 << i,
+                    }
+                }
+                _ => return Err(RuleError::UnexpectedChar(c)),
+            }
+        }
+        Ok(Conditions(conditions))
+    }
+}
+
+impl Display for Conditions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (count, &mask) in self.0.iter().enumerate() {
+            if mask == 0 {
+                continue;
+            }
+            write!(f, "{count}")?;
+            if mask == all_letters(count) {
+                continue;
+            }
+            let chosen = |mask: u16| {
+                let letters = letters(count).iter().enumerate();
+                letters
+                    .filter(move |(i, _)| mask & 1 << i != 0)
+                    .map(|(_, &(l, _))| l)
+            };
+            let excluded = all_letters(count) & !mask;
+            if excluded.count_ones() < mask.count_ones() {
+                f.write_char('-')?;
+                chosen(excluded).try_for_each(|l| f.write_char(l))?;
+            } else {
+                chosen(mask).try_for_each(|l| f.write_char(l))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The neighbour count and index of the Hensel letter of a neighbourhood.
+fn classify(neighbourhood: u16) -> (usize, usize) {
+    let neighbours = neighbourhood & NEIGHBOURS;
+    let count = neighbours.count_ones() as usize;
+    // Above 4 the letters name the dead neighbours instead.
+    let flip = if count > 4 { NEIGHBOURS } else { 0 };
+    let letter = letters(count)
+        .iter()
+        .position(|&(_, rep)| symmetries(rep ^ flip).contains(&neighbours))
+        .unwrap_or_default();
+    (count, letter)
+}
+
+/// All rotations and reflections of a 3x3 neighbourhood.
+fn symmetries(neighbourhood: u16) -> [u16; 8] {
+    let mut out = [0; 8];
+    out[0] = neighbourhood;
+    out[4] = transform(neighbourhood, |y, x| (y, 2 - x));
+    for i in [1, 2, 3, 5, 6, 7] {
+        out[i] = transform(out[i - 1], |y, x| (x, 2 - y));
+    }
+    out
+}
+
+fn transform(neighbourhood: u16, f: fn(usize, usize) -> (usize, usize)) -> u16 {
+    let mut out = 0;
+    for (y, x) in (0..3).flat_map(|y| (0..3).map(move |x| (y, x))) {
+        if neighbourhood & 1 << (3 * y + x) != 0 {
+            let (y, x) = f(y, x);
+            out |= 1 << (3 * y + x);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_letters_cover_neighbourhoods() {
+        // Each count's letters should split its neighbourhoods into distinct
+        // symmetry classes that between them cover every neighbourhood.
+        let mut seen = [0; 9];
+        for neighbourhood in 0..256u16 {
+            let neighbours = neighbourhood & 0b1111 | (neighbourhood & 0b11110000) << 1;
+            let (count, letter) = classify(neighbours);
+            let flip = if count > 4 { NEIGHBOURS } else { 0 };
+            if let Some(&(_, rep)) = letters(count).get(letter) {
+                assert!(symmetries(rep ^ flip).contains(&neighbours));
+            }
+            seen[count] |= 1 << letter;
+        }
+        for (count, seen) in seen.into_iter().enumerate() {
+            assert_eq!(seen, all_letters(count));
+        }
+    }
+}
@@ -0,0 +1,326 @@
+use std::collections::HashMap;
+
+/// A rule loaded from a Golly `.rule` file, using either its `@TABLE` or its
+/// `@TREE` section for the transition function.
+#[derive(Clone, PartialEq, Eq)]
+pub struct RuleFile {
+    pub name: String,
+    pub source: String,
+    pub states: u8,
+    transition: Transition,
+}
+
+#[derive(Clone, PartialEq, Eq)]
+enum Transition {
+    Table(Table),
+    Tree(Tree),
+}
+
+/// Offsets of the Moore neighbours in `@TABLE` order, clockwise from north.
+const MOORE: [(isize, isize); 8] = [
+    (-1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+    (1, 0),
+    (1, -1),
+    (0, -1),
+    (-1, -1),
+];
+const VON_NEUMANN: [(isize, isize); 4] = [(-1, 0), (0, 1), (1, 0), (0, -1)];
+
+#[derive(Clone, PartialEq, Eq)]
+struct Table {
+    neighbours: &'static [(isize, isize)],
+    /// Rearrangements of the neighbours that a transition also applies to.
+    symmetries: Vec<Vec<usize>>,
+    permute: bool,
+    vars: Vec<Vec<u8>>,
+    /// The center, neighbours and then the next state.
+    transitions: Vec<Vec<Term>>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Term {
+    State(u8),
+    Var(usize),
+}
+
+#[derive(Clone, PartialEq, Eq)]
+struct Tree {
+    neighbours: &'static [(isize, isize)],
+    /// Each node maps a cell's state to the next node, or at level 1 to the
+    /// next state. The root is the last node.
+    nodes: Vec<Vec<usize>>,
+}
+
+impl RuleFile {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut name = None;
+        let mut sections: HashMap<&str, Vec<&str>> = HashMap::new();
+        let mut section = "";
+        for line in source.lines() {
+            let line = line.split('#').next().unwrap().trim();
+            if let Some(header) = line.strip_prefix('@') {
+                let (tag, rest) = header.split_once(' ').unwrap_or((header, ""));
+                if tag == "RULE" {
+                    name = Some(rest.trim().to_string());
+                }
+                section = tag;
+            } else if !line.is_empty() {
+                sections.entry(section).or_default().push(line);
+            }
+        }
+        let name = name.ok_or("Missing @RULE")?;
+        let (states, transition) = match (sections.get("TABLE"), sections.get("TREE")) {
+            (Some(table), _) => parse_table(table)?,
+            (None, Some(tree)) => parse_tree(tree)?,
+            (None, None) => return Err(format!("Rule {name} has no @TABLE or @TREE")),
+        };
+        Ok(RuleFile {
+            name,
+            source: source.to_string(),
+            states,
+            transition,
+        })
+    }
+
+    pub fn next_cell(&self, state: u8, neighbour: impl Fn(isize, isize) -> u8) -> u8 {
+        match &self.transition {
+            Transition::Table(table) => table.next_cell(state, neighbour),
+            Transition::Tree(tree) => tree.next_cell(state, neighbour),
+        }
+    }
+}
+
+impl Table {
+    fn next_cell(&self, state: u8, neighbour: impl Fn(isize, isize) -> u8) -> u8 {
+        let cells: Vec<u8> = self
+            .neighbours
+            .iter()
+            .map(|&(dy, dx)| neighbour(dy, dx))
+            .collect();
+        // The first matching transition wins, and if none match the cell
+        // stays as it is.
+        for transition in &self.transitions {
+            let (center, rest) = transition.split_first().unwrap();
+            let (output, terms) = rest.split_last().unwrap();
+            let mut bound = vec![None; self.vars.len()];
+            if !self.matches(*center, state, &mut bound) {
+                continue;
+            }
+            let bound = match self.permute {
+                true => self
+                    .matches_permuted(terms, &cells, &mut bound)
+                    .then_some(bound),
+                false => self.symmetries.iter().find_map(|symmetry| {
+                    let mut bound = bound.clone();
+                    let mut pairs = terms.iter().zip(symmetry);
+                    pairs
+                        .all(|(&term, &i)| self.matches(term, cells[i], &mut bound))
+                        .then_some(bound)
+                }),
+            };
+            if let Some(bound) = bound {
+                return match *output {
+                    Term::State(next) => next,
+                    Term::Var(v) => bound[v].unwrap_or(state),
+                };
+            }
+        }
+        state
+    }
+
+    fn matches(&self, term: Term, state: u8, bound: &mut [Option<u8>]) -> bool {
+        match term {
+            Term::State(s) => s == state,
+            Term::Var(v) => match bound[v] {
+                Some(s) => s == state,
+                None if self.vars[v].contains(&state) => {
+                    bound[v] = Some(state);
+                    true
+                }
+                None => false,
+            },
+        }
+    }
+
+    /// Matches the neighbour terms against the cells in any order.
+    fn matches_permuted(&self, terms: &[Term], cells: &[u8], bound: &mut Vec<Option<u8>>) -> bool {
+        let Some((&term, terms)) = terms.split_first() else {
+            return true;
+        };
+        for i in 0..cells.len() {
+            let mut attempt = bound.clone();
+            if self.matches(term, cells[i], &mut attempt) {
+                let rest = [&cells[..i], &cells[i + 1..]].concat();
+                if self.matches_permuted(terms, &rest, &mut attempt) {
+                    *bound = attempt;
+                    return true;
+                }
+            }
+        }
+        false
+    }
+}
+
+impl Tree {
+    fn next_cell(&self, state: u8, neighbour: impl Fn(isize, isize) -> u8) -> u8 {
+        let mut node = self.nodes.len() - 1;
+        for &(dy, dx) in self.neighbours {
+            node = self.nodes[node][neighbour(dy, dx) as usize];
+        }
+        self.nodes[node][state as usize] as u8
+    }
+}
+
+fn parse_table(lines: &[&str]) -> Result<(u8, Transition), String> {
+    let mut states = None;
+    let mut neighbours: &[(isize, isize)] = &MOORE;
+    let mut symmetry = "none";
+    let mut var_names: HashMap<&str, usize> = HashMap::new();
+    let mut vars: Vec<Vec<u8>> = vec![];
+    let mut transitions = vec![];
+    let bad_line = |line: &str| format!("Bad @TABLE line {line}");
+    for &line in lines {
+        if let Some((key, value)) = line.split_once(':') {
+            let value = value.trim();
+            match key.trim() {
+                "n_states" => states = Some(value.parse().map_err(|_| bad_line(line))?),
+                "neighborhood" => {
+                    neighbours = match value {
+                        "Moore" => &MOORE,
+                        "vonNeumann" => &VON_NEUMANN,
+                        _ => return Err(format!("Unsupported neighborhood {value}")),
+                    }
+                }
+                "symmetries" => symmetry = value,
+                _ => return Err(bad_line(line)),
+            }
+            continue;
+        }
+        let term = |token: &str, var_names: &HashMap<&str, usize>| match token.parse() {
+            Ok(s) => Some(Term::State(s)),
+            Err(_) => var_names.get(token).map(|&v| Term::Var(v)),
+        };
+        if let Some(var) = line.strip_prefix("var ") {
+            let (name, values) = var.split_once('=').ok_or_else(|| bad_line(line))?;
+            let values = values.trim().trim_start_matches('{').trim_end_matches('}');
+            let mut states = vec![];
+            for token in values.split(',').map(str::trim) {
+                match term(token, &var_names) {
+                    Some(Term::State(s)) => states.push(s),
+                    Some(Term::Var(v)) => states.extend(vars[v].iter().copied()),
+                    None => return Err(bad_line(line)),
+                }
+            }
+            var_names.insert(name.trim(), vars.len());
+            vars.push(states);
+            continue;
+        }
+        // Transitions are comma separated, or single digits run together.
+        let tokens: Vec<String> = match line.contains(',') {
+            true => line.split(',').map(|t| t.trim().to_string()).collect(),
+            false => line.chars().map(String::from).collect(),
+        };
+        let terms: Option<Vec<Term>> = tokens.iter().map(|t| term(t, &var_names)).collect();
+        match terms {
+            Some(terms) if terms.len() == neighbours.len() + 2 => transitions.push(terms),
+            _ => return Err(bad_line(line)),
+        }
+    }
+    let n = neighbours.len();
+    let rotations = |step: usize| {
+        (0..n)
+            .step_by(step)
+            .map(move |r| (0..n).map(move |i| (i + r) % n))
+    };
+    let reflect = |i: usize| (n - i) % n;
+    let symmetries: Vec<Vec<usize>> = match symmetry {
+        "none" | "permute" => vec![(0..n).collect()],
+        "rotate4" => rotations(n / 4).map(Iterator::collect).collect(),
+        "rotate8" if n == 8 => rotations(1).map(Iterator::collect).collect(),
+        "reflect_horizontal" => vec![(0..n).collect(), (0..n).map(reflect).collect()],
+        "rotate4reflect" | "rotate8reflect" => {
+            let step = if symmetry == "rotate4reflect" {
+                n / 4
+            } else {
+                1
+            };
+            let rotations = rotations(step).map(Iterator::collect::<Vec<_>>);
+            rotations
+                .flat_map(|r| [r.clone(), r.into_iter().map(reflect).collect()])
+                .collect()
+        }
+        _ => return Err(format!("Unsupported symmetries {symmetry}")),
+    };
+    let states = states.ok_or("Missing n_states")?;
+    let table = Table {
+        neighbours,
+        symmetries,
+        permute: symmetry == "permute",
+        vars,
+        transitions,
+    };
+    Ok((states, Transition::Table(table)))
+}
+
+fn parse_tree(lines: &[&str]) -> Result<(u8, Transition), String> {
+    let mut states = None;
+    let mut neighbours: &[(isize, isize)] = &[];
+    let mut nodes = vec![];
+    let bad_line = |line: &str| format!("Bad @TREE line {line}");
+    for &line in lines {
+        if let Some((key, value)) = line.split_once('=') {
+            let value: usize = value.trim().parse().map_err(|_| bad_line(line))?;
+            match key.trim() {
+                "num_states" => states = u8::try_from(value).ok(),
+                // Golly looks neighbours up in the order nw, ne, sw, se, n, w,
+                // e, s for Moore, or n, w, e, s for von Neumann.
+                "num_neighbors" if value == 8 => {
+                    neighbours = &[
+                        (-1, -1),
+                        (-1, 1),
+                        (1, -1),
+                        (1, 1),
+                        (-1, 0),
+                        (0, -1),
+                        (0, 1),
+                        (1, 0),
+                    ]
+                }
+                "num_neighbors" if value == 4 => neighbours = &[(-1, 0), (0, -1), (0, 1), (1, 0)],
+                "num_nodes" => (),
+                _ => return Err(bad_line(line)),
+            }
+            continue;
+        }
+        let fields: Vec<usize> = line
+            .split_whitespace()
+            .map(|field| field.parse().map_err(|_| bad_line(line)))
+            .collect::<Result<_, _>>()?;
+        nodes.push(fields);
+    }
+    let states = states.ok_or("Missing num_states")? as usize;
+    // Check each node has a child per state, and each child is a lower node
+    // (or at level 1, a state).
+    let mut levels = vec![];
+    for node in &nodes {
+        let (&level, children) = node.split_first().ok_or("Empty @TREE node")?;
+        let valid = children.len() == states
+            && children.iter().all(|&child| match level {
+                1 => child < states,
+                _ => levels.get(child) == Some(&(level - 1)),
+            });
+        if !valid {
+            return Err(format!("Bad @TREE node {node:?}"));
+        }
+        levels.push(level);
+    }
+    if levels.last() != Some(&(neighbours.len() + 1)) {
+        return Err("@TREE root has the wrong level".to_string());
+    }
+    let nodes = nodes.into_iter().map(|node| node[1..].to_vec()).collect();
+    let tree = Tree { neighbours, nodes };
+    Ok((states as u8, Transition::Tree(tree)))
+}
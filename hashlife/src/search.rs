@@ -0,0 +1,228 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    thread,
+};
+
+use crate::{BitLife, HashLife, Limits, eq::mix};
+
+/// What to search and how.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Search {
+    /// How many soups to run, numbered from 0.
+    pub soups: u64,
+    pub threads: usize,
+    /// Picks the cells of every soup, so a search can be repeated.
+    pub seed: u64,
+    /// The width and height of each soup.
+    pub size: usize,
+    pub limits: Limits,
+    /// Soups taking at least this long to stabilize are reported.
+    pub methuselah: u64,
+}
+
+impl Default for Search {
+    fn default() -> Self {
+        Self {
+            soups: 1000,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            seed: 0,
+            size: 16,
+            limits: Limits::default(),
+            methuselah: 5000,
+        }
+    }
+}
+
+/// Something interesting about a soup, which can be rebuilt from its number
+/// with `soup`.
+#[derive(Clone, Debug)]
+pub enum Finding {
+    Methuselah {
+        soup: u64,
+        generation: u64,
+    },
+    /// An object left behind that the census doesn't know, at the origin.
+    Unrecognized {
+        soup: u64,
+        object: Box<HashLife>,
+    },
+    /// A soup that didn't stabilize within the limits.
+    Unstable {
+        soup: u64,
+    },
+}
+
+/// What a search left behind, by apgcode.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Haul {
+    pub counts: BTreeMap<String, u64>,
+    /// The first few soups each object turned up in, by number.
+    pub samples: BTreeMap<String, Vec<u64>>,
+}
+
+/// How many soups `Haul` keeps for each object.
+const SAMPLES: usize = 10;
+
+impl Haul {
+    fn add(&mut self, apgcode: String, count: u64, soups: impl IntoIterator<Item = u64>) {
+        let samples = self.samples.entry(apgcode.clone()).or_default();
+        samples.extend(soups);
+        samples.sort_unstable();
+        samples.truncate(SAMPLES);
+        *self.counts.entry(apgcode).or_default() += count;
+    }
+
+    /// The haul of `search` laid out as Catagolue takes submissions, most
+    /// common objects first. The `@MD5` line of a real submission is left
+    /// out, since it needs a key from Catagolue, and Catagolue only accepts
+    /// soups it can make again from the seed, which these aren't.
+    pub fn submission(&self, search: &Search) -> String {
+        let mut counts: Vec<_> = self.counts.iter().collect();
+        counts.sort_by(|(a, m), (b, n)| n.cmp(m).then(a.cmp(b)));
+        let mut out = format!(
+            "@VERSION hashlife-{}\n@ROOT {}\n@RULE b3s23\n@SYMMETRY C1\n\
+             @NUM_SOUPS {}\n@NUM_OBJECTS {}\n\n@CENSUS TABLE\n",
+            env!("CARGO_PKG_VERSION"),
+            search.seed,
+            search.soups,
+            self.counts.values().sum::<u64>(),
+        );
+        for (apgcode, count) in &counts {
+            writeln!(out, "{apgcode} {count}").unwrap();
+        }
+        out.push_str("\n@SAMPLE_SOUPIDS\n");
+        for (apgcode, _) in counts {
+            let soups = self.samples[apgcode].iter().map(u64::to_string);
+            writeln!(out, "{apgcode} {}", soups.collect::<Vec<_>>().join(" ")).unwrap();
+        }
+        out
+    }
+}
+
+impl Search {
+    /// The randomly filled square for the given soup number, where each cell
+    /// is alive with probability one half.
+    pub fn soup(&self, soup: u64) -> HashLife {
+        let mut hl = HashLife::new();
+        hl.set_cells(self.soup_cells(soup));
+        hl
+    }
+
+    fn soup_cells(&self, soup: u64) -> Vec<(isize, isize)> {
+        let mut state = mix(self.seed ^ mix(soup));
+        let mut bits = 0;
+        let mut cells = vec![];
+        for y in 0..self.size as isize {
+            for x in 0..self.size as isize {
+                if bits == 0 {
+                    state = mix(state.wrapping_add(0x9e37_79b9_7f4a_7c15));
+                    bits = 64;
+                }
+                if state >> (64 - bits) & 1 == 1 {
+                    cells.push((y, x));
+                }
+                bits -= 1;
+            }
+        }
+        cells
+    }
+
+    /// Runs every soup to stabilization across `threads` threads and adds up
+    /// the census of what they leave behind. Findings are passed to
+    /// `on_finding` as they turn up, from whichever thread found them.
+    pub fn run(&self, on_finding: impl Fn(Finding) + Sync) -> BTreeMap<&'static str, usize> {
+        self.run_with(
+            |soup, counts| self.run_soup(soup, counts, &on_finding),
+            |total, counts| {
+                for (name, count) in counts {
+                    *total.entry(name).or_default() += count;
+                }
+            },
+        )
+    }
+
+    /// Runs every soup to stabilization like `run`, and counts the objects
+    /// left behind by apgcode, as Catagolue does. Unstable soups, and objects
+    /// that don't repeat, aren't counted.
+    pub fn haul(&self) -> Haul {
+        self.run_with(
+            |soup, haul: &mut Haul| {
+                let life = BitLife::from_iter(self.soup_cells(soup));
+                let Ok(stabilized) = life.stabilize(self.limits) else {
+                    return;
+                };
+                for (object, _) in stabilized.state.components(1) {
+                    if let Some(apgcode) = object.apgcode() {
+                        haul.add(apgcode, 1, [soup]);
+                    }
+                }
+            },
+            |total, haul| {
+                for (apgcode, count) in haul.counts {
+                    let samples = haul.samples.get(&apgcode).cloned().unwrap_or_default();
+                    total.add(apgcode, count, samples);
+                }
+            },
+        )
+    }
+
+    /// Shares the soups out between threads, each of which runs its soups
+    /// into a `T` of its own, and merges what they ran into.
+    fn run_with<T: Default + Send>(
+        &self,
+        run_soup: impl Fn(u64, &mut T) + Sync,
+        merge: impl Fn(&mut T, T) + Sync,
+    ) -> T {
+        let next = AtomicU64::new(0);
+        let total = Mutex::new(T::default());
+        thread::scope(|scope| {
+            for _ in 0..self.threads.max(1) {
+                scope.spawn(|| {
+                    let mut part = T::default();
+                    loop {
+                        let soup = next.fetch_add(1, Ordering::Relaxed);
+                        if soup >= self.soups {
+                            break;
+                        }
+                        run_soup(soup, &mut part);
+                    }
+                    merge(&mut total.lock().unwrap(), part);
+                });
+            }
+        });
+        total.into_inner().unwrap()
+    }
+
+    fn run_soup(
+        &self,
+        soup: u64,
+        counts: &mut BTreeMap<&'static str, usize>,
+        on_finding: &impl Fn(Finding),
+    ) {
+        // Soups are chaotic until they settle, so hashlife would find few of
+        // its steps cached.
+        let life = BitLife::from_iter(self.soup_cells(soup));
+        let Ok(stabilized) = life.stabilize(self.limits) else {
+            return on_finding(Finding::Unstable { soup });
+        };
+        if stabilized.generation >= self.methuselah {
+            on_finding(Finding::Methuselah {
+                soup,
+                generation: stabilized.generation,
+            });
+        }
+        let census = stabilized.state.census();
+        for (name, count) in census.counts {
+            *counts.entry(name).or_default() += count;
+        }
+        for object in census.unrecognized {
+            let object = Box::new(object);
+            on_finding(Finding::Unrecognized { soup, object });
+        }
+    }
+}
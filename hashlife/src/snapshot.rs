@@ -0,0 +1,191 @@
+use std::{
+    collections::HashMap,
+    io::{self, Read, Write},
+};
+
+use crate::{
+    HashLife, Rule, RuleError, Topology,
+    p3::MAX_DEPTH,
+    universe::{Shared, TreeRef, Universe},
+};
+
+const MAGIC: &[u8; 4] = b"HLSN";
+const VERSION: u8 = 1;
+
+/// The widest torus, whose cells still fit in a tree no deeper than
+/// `MAX_DEPTH`.
+const MAX_WIDTH: u64 = 1 << (MAX_DEPTH - 1);
+
+const ALIVE: u64 = 0;
+const BRANCH: u64 = 1;
+/// A cell in a state other than 1, followed by the state.
+const STATE: u64 = 2;
+
+impl HashLife {
+    /// Writes the reachable node table as a compact binary snapshot. Nodes are
+    /// written children first, so the root is the last node in the table.
+    pub fn write_snapshot(&self, mut w: impl Write) -> io::Result<()> {
+        let mut ids = HashMap::from([(TreeRef::EMPTY, 0)]);
+        let mut nodes = vec![];
+        let mut stack = vec![(self.root, self.depth, false)];
+        while let Some((tr, depth, children_done)) = stack.pop() {
+            if ids.contains_key(&tr) {
+                continue;
+            }
+            if depth == 0 {
+                let state = self.universe.lock().state(tr);
+                nodes.push(match state {
+                    1 => vec![ALIVE],
+                    state => vec![STATE, state as u64],
+                });
+            } else if children_done {
+                let subtree = self.universe.lock().subtree(tr).map(|tr| ids[&tr]);
+                nodes.push([BRANCH].into_iter().chain(subtree).collect());
+            } else {
+                stack.push((tr, depth, true));
+                let subtree = self.universe.lock().subtree(tr);
+                stack.extend(subtree.into_iter().rev().map(|tr| (tr, depth - 1, false)));
+                continue;
+            }
+            ids.insert(tr, ids.len() as u64);
+        }
+        w.write_all(MAGIC)?;
+        w.write_all(&[VERSION])?;
+        let rule = self.rule().definition();
+        write_varint(&mut w, rule.len() as u64)?;
+        w.write_all(rule.as_bytes())?;
+        w.write_all(&[self.inverted as u8])?;
+        // A plane is written as a 0 x 0 torus.
+        let (width, height) = match self.topology {
+            Topology::Plane => (0, 0),
+            Topology::Torus { w, h } => (w, h),
+        };
+        write_varint(&mut w, width as u64)?;
+        write_varint(&mut w, height as u64)?;
+        write_varint(&mut w, self.generation)?;
+        write_varint(&mut w, self.depth as u64)?;
+        write_varint(&mut w, nodes.len() as u64)?;
+        for node in nodes {
+            for n in node {
+                write_varint(&mut w, n)?;
+            }
+        }
+        w.flush()
+    }
+
+    pub fn read_snapshot(mut r: impl Read) -> io::Result<Self> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("Not a hashlife snapshot"));
+        }
+        let mut version = [0];
+        r.read_exact(&mut version)?;
+        if version[0] != VERSION {
+            return Err(invalid("Unsupported snapshot version"));
+        }
+        let len = read_varint(&mut r)?;
+        // Read as far as the file goes rather than trusting the length up
+        // front, so a corrupt one can't ask for any amount of memory.
+        let mut rule = vec![];
+        (&mut r).take(len).read_to_end(&mut rule)?;
+        if rule.len() as u64 != len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let rule = String::from_utf8(rule).map_err(|_| invalid("Bad rule"))?;
+        let rule: Rule = rule
+            .parse()
+            .map_err(|e: RuleError| invalid(&e.to_string()))?;
+        let mut inverted = [0];
+        r.read_exact(&mut inverted)?;
+        if inverted[0] > 1 {
+            return Err(invalid("Bad background"));
+        }
+        let topology = match (read_varint(&mut r)?, read_varint(&mut r)?) {
+            (0, 0) => Topology::Plane,
+            (w @ 1..=MAX_WIDTH, h @ 1..=MAX_WIDTH) => Topology::Torus {
+                w: w as usize,
+                h: h as usize,
+            },
+            _ => return Err(invalid("Bad torus size")),
+        };
+        let generation = read_varint(&mut r)?;
+        let depth = read_varint(&mut r)?;
+        if depth > MAX_DEPTH as u64 {
+            return Err(invalid("Snapshot too deep"));
+        }
+        let depth = depth as usize;
+        let len = read_varint(&mut r)?;
+        let states = rule.states();
+        let mut universe = Universe::with_rule(rule);
+        // Each node and its depth, which the empty node doesn't have.
+        let mut trs = vec![(TreeRef::EMPTY, None)];
+        for _ in 0..len {
+            let node = match read_varint(&mut r)? {
+                ALIVE => (universe.leaf(1), Some(0)),
+                STATE => match u8::try_from(read_varint(&mut r)?) {
+                    Ok(state) if (1..states).contains(&state) => (universe.leaf(state), Some(0)),
+                    _ => return Err(invalid("Bad cell state")),
+                },
+                BRANCH => {
+                    let mut subtree = [TreeRef::EMPTY; 4];
+                    let mut depth = None;
+                    for tr in &mut subtree {
+                        let id = read_varint(&mut r)?;
+                        let &(child, child_depth) = usize::try_from(id)
+                            .ok()
+                            .and_then(|id| trs.get(id))
+                            .ok_or_else(|| invalid("Dangling node"))?;
+                        // A node's children are all one level down from it.
+                        if child_depth.is_some() && depth.is_some() && child_depth != depth {
+                            return Err(invalid("Children at different depths"));
+                        }
+                        *tr = child;
+                        depth = depth.or(child_depth);
+                    }
+                    (universe.branch(subtree), depth.map(|depth| depth + 1))
+                }
+                _ => return Err(invalid("Unknown node tag")),
+            };
+            trs.push(node);
+        }
+        let &(root, root_depth) = trs.last().unwrap();
+        if root_depth.is_some_and(|root_depth| root_depth != depth) {
+            return Err(invalid("Root at the wrong depth"));
+        }
+        Ok(HashLife {
+            universe: Shared::new(universe),
+            depth,
+            root,
+            inverted: inverted[0] == 1,
+            topology,
+            generation,
+            ..HashLife::new()
+        })
+    }
+}
+
+fn write_varint(w: &mut impl Write, mut n: u64) -> io::Result<()> {
+    while n >= 0x80 {
+        w.write_all(&[n as u8 | 0x80])?;
+        n >>= 7;
+    }
+    w.write_all(&[n as u8])
+}
+
+fn read_varint(r: &mut impl Read) -> io::Result<u64> {
+    let mut n = 0;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0];
+        r.read_exact(&mut byte)?;
+        n |= ((byte[0] & 0x7f) as u64) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(n);
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "Varint too long",
+    ))
+}
@@ -0,0 +1,94 @@
+use std::{
+    io::{self, Write},
+    ops::Range,
+};
+
+use crate::{BitGrid, HashLife, render::Rgba};
+
+/// A slice of a pattern recorded every generation, for seeing how it changes
+/// over time, such as signals travelling down a wire.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpaceTime {
+    rows: Range<isize>,
+    cols: Range<isize>,
+    /// Which columns of the slice had any cells alive, generation by
+    /// generation.
+    history: Vec<Vec<bool>>,
+}
+
+impl SpaceTime {
+    /// Records the rows `rows` of the columns `cols`. A column counts as alive
+    /// if any cell in it is, so the slice is usually a single row.
+    pub fn new(rows: Range<isize>, cols: Range<isize>) -> Self {
+        Self {
+            rows,
+            cols,
+            history: vec![],
+        }
+    }
+
+    /// Adds the slice of `hl` as the next generation.
+    pub fn record(&mut self, hl: &HashLife) {
+        let mut slice = vec![false; self.cols.len()];
+        for (_, x) in hl.cells_in_rect(self.rows.clone(), self.cols.clone()) {
+            slice[(x - self.cols.start) as usize] = true;
+        }
+        self.history.push(slice);
+    }
+
+    /// How many generations have been recorded.
+    pub fn len(&self) -> usize {
+        self.history.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.history.is_empty()
+    }
+
+    /// The diagram with time going down a row at a time, and the columns of
+    /// the slice across.
+    pub fn to_grid(&self) -> BitGrid {
+        let mut grid = BitGrid::new(self.history.len(), self.cols.len());
+        for (t, slice) in self.history.iter().enumerate() {
+            for (x, &alive) in slice.iter().enumerate() {
+                grid.set(t, x, alive);
+            }
+        }
+        grid
+    }
+
+    /// Writes the diagram as a PNG, a pixel per cell.
+    pub fn write_png(&self, dead_rgba: Rgba, alive_rgba: Rgba, w: impl Write) -> io::Result<()> {
+        let (dead, alive) = (
+            dead_rgba.to_srgba_unmultiplied(),
+            alive_rgba.to_srgba_unmultiplied(),
+        );
+        let pixels = self
+            .history
+            .iter()
+            .flatten()
+            .flat_map(|&cell| if cell { alive } else { dead })
+            .collect::<Vec<_>>();
+        crate::render::write_rgba8_png(self.history.len(), self.cols.len(), &pixels, w)
+    }
+}
+
+impl HashLife {
+    /// Records the rows `rows` of the columns `cols` for this generation and
+    /// each of the next `generations`, without stepping this pattern.
+    pub fn space_time(
+        &self,
+        rows: Range<isize>,
+        cols: Range<isize>,
+        generations: u64,
+    ) -> SpaceTime {
+        let mut space_time = SpaceTime::new(rows, cols);
+        let mut hl = self.clone();
+        space_time.record(&hl);
+        for _ in 0..generations {
+            hl.step(0);
+            space_time.record(&hl);
+        }
+        space_time
+    }
+}
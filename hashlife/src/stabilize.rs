@@ -0,0 +1,97 @@
+use crate::{BitLife, HashLife, LifeEngine};
+
+/// How long `stabilize` looks for a soup to settle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Limits {
+    /// Gives up after this many generations.
+    pub max_generations: u64,
+    /// Only looks for cycles up to this long.
+    pub max_period: u64,
+    /// How many times a cycle must repeat before it's believed.
+    pub repeats: u64,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_generations: 100_000,
+            max_period: 60,
+            repeats: 8,
+        }
+    }
+}
+
+/// A pattern that has settled into a cycle.
+#[derive(Clone, Debug)]
+pub struct Stabilized {
+    /// The first generation of the cycle.
+    pub generation: u64,
+    pub period: u64,
+    /// The pattern once the cycle had been confirmed.
+    pub state: HashLife,
+}
+
+impl HashLife {
+    /// Runs a copy of the pattern until its population goes round the same
+    /// cycle `limits.repeats` times. Escaping gliders and spaceships don't
+    /// change the population, so they don't stop it from settling.
+    pub fn stabilize(&self, limits: Limits) -> Result<Stabilized, String> {
+        let mut hl = self.clone();
+        let start = hl.generation();
+        let (first, period) = settle(&mut hl, limits)?;
+        Ok(Stabilized {
+            generation: start + first,
+            period,
+            state: hl,
+        })
+    }
+}
+
+impl BitLife {
+    /// Like `HashLife::stabilize`, but stepping densely, which is faster for
+    /// small soups under B3/S23.
+    pub fn stabilize(&self, limits: Limits) -> Result<Stabilized, String> {
+        let mut life = self.clone();
+        let (first, period) = settle(&mut life, limits)?;
+        let mut state: HashLife = life.iter_cells().collect();
+        state.generation = life.generation();
+        Ok(Stabilized {
+            generation: self.generation() + first,
+            period,
+            state,
+        })
+    }
+}
+
+/// Steps a pattern until its population settles into a cycle, returning how
+/// many generations in the cycle began, and its period.
+fn settle(life: &mut impl LifeEngine, limits: Limits) -> Result<(u64, u64), String> {
+    let mut populations = vec![life.population()];
+    while (populations.len() as u64) <= limits.max_generations {
+        life.step_by(1);
+        populations.push(life.population());
+        let n = populations.len() as u64;
+        let repeats = |period| {
+            (n - period * limits.repeats..n)
+                .all(|i| populations[i as usize] == populations[(i - period) as usize])
+        };
+        let Some(period) = (1..=limits.max_period)
+            .take_while(|&period| period * (limits.repeats + 1) <= n)
+            .find(|&period| repeats(period))
+        else {
+            continue;
+        };
+        // Wind back to where the cycle began.
+        let mut first = n - period * (limits.repeats + 1);
+        while first > 0
+            && populations[first as usize - 1] == populations[(first + period) as usize - 1]
+        {
+            first -= 1;
+        }
+        return Ok((first, period));
+    }
+    Err(format!(
+        "Didn't stabilize within {} generations",
+        limits.max_generations
+    ))
+}
@@ -0,0 +1,84 @@
+use crate::{HashLife, ops::Transform};
+
+/// One of the eight rotations and reflections of a square.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Symmetry {
+    Identity,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    FlipHorizontal,
+    FlipVertical,
+    /// Reflection in the diagonal from top left to bottom right.
+    Transpose,
+    /// Reflection in the diagonal from top right to bottom left.
+    AntiTranspose,
+}
+
+impl Symmetry {
+    pub const ALL: [Symmetry; 8] = [
+        Symmetry::Identity,
+        Symmetry::Rotate90,
+        Symmetry::Rotate180,
+        Symmetry::Rotate270,
+        Symmetry::FlipHorizontal,
+        Symmetry::FlipVertical,
+        Symmetry::Transpose,
+        Symmetry::AntiTranspose,
+    ];
+
+    pub(crate) fn transforms(self) -> &'static [Transform] {
+        use Transform::*;
+        match self {
+            Symmetry::Identity => &[],
+            Symmetry::Rotate90 => &[RotateCw],
+            Symmetry::Rotate180 => &[RotateCw, RotateCw],
+            Symmetry::Rotate270 => &[RotateCcw],
+            Symmetry::FlipHorizontal => &[FlipHorizontal],
+            Symmetry::FlipVertical => &[FlipVertical],
+            Symmetry::Transpose => &[Transpose],
+            Symmetry::AntiTranspose => &[Transpose, RotateCw, RotateCw],
+        }
+    }
+}
+
+/// The symmetries a pattern has, which always include the identity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SymmetryGroup(u8);
+
+impl SymmetryGroup {
+    pub fn contains(self, symmetry: Symmetry) -> bool {
+        self.0 & 1 << symmetry as u8 != 0
+    }
+
+    pub fn iter(self) -> impl Iterator<Item = Symmetry> {
+        Symmetry::ALL.into_iter().filter(move |&s| self.contains(s))
+    }
+
+    /// The number of symmetries in the group, from 1 for an asymmetric
+    /// pattern to 8 for one with every symmetry of the square.
+    pub fn order(self) -> usize {
+        self.0.count_ones() as usize
+    }
+}
+
+impl HashLife {
+    /// Which rotations and reflections leave the pattern the same, up to
+    /// translation.
+    pub fn symmetries(&self) -> SymmetryGroup {
+        let mut hl = self.clone();
+        let (root, depth) = (hl.root, hl.depth);
+        let mut fingerprint = |transforms: &[Transform]| {
+            (hl.root, hl.depth) = (root, depth);
+            for &transform in transforms {
+                hl.transform(transform);
+            }
+            hl.move_to_origin().0
+        };
+        let original = fingerprint(&[]);
+        let symmetries = Symmetry::ALL
+            .into_iter()
+            .filter(|s| fingerprint(s.transforms()) == original);
+        SymmetryGroup(symmetries.fold(0, |group, s| group | 1 << s as u8))
+    }
+}
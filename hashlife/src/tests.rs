@@ -1,6 +1,15 @@
-use crate::{HashLife, basic_state::BasicState, p3::P3};
+use crate::{
+    BitLife, Finding, Glyphs, HashLife, HistoryConfig, Hybrid, HybridConfig, Hyperspeed,
+    LifeEngine, Limits, ParseError, Periodic, Rule, RuleError, Search, StepError, Symmetry,
+    Timeline, Topology, UniverseConfig, UniverseError, basic_state::BasicState, p3::P3,
+};
 use itertools::Itertools;
-use std::str::FromStr;
+use std::{
+    ops::ControlFlow,
+    str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
 
 const GLIDER: [&'static str; 6] = [
     "
@@ -99,11 +108,107 @@ mod basic_state {
             assert_eq!(a, b);
         }
     }
+
+    #[test]
+    fn test_plaintext() {
+        // LifeWiki .cells files use '!' comments, '.' for dead and 'O' for alive.
+        let plaintext = BasicState::from_str(
+            "!Name: Glider
+!
+.O.
+..O
+OOO",
+        )
+        .unwrap();
+        assert_eq!(plaintext, BasicState::from_str(" o\n  o\nooo").unwrap());
+        assert_eq!(plaintext, BasicState::from_str(" *\n  *\n***").unwrap());
+    }
+
+    #[test]
+    fn test_step_many_cells() {
+        // Enough cells to be counted in several chunks in parallel.
+        let mut seed = 3u64;
+        let cells = (0..120 * 120).filter_map(|k| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+            (seed >> 63 == 1).then_some((k / 120, k % 120))
+        });
+        let mut state = BasicState::from_iter(cells.collect_vec());
+        let mut hl = HashLife::from_iter(state.clone());
+        for _ in 0..4 {
+            state = state.step();
+            hl.step(0);
+            assert_eq!(state.cells.len() as u64, hl.population());
+            assert!(state.cells.iter().all(|&p| hl.get(p)));
+        }
+    }
+
+    #[test]
+    fn test_step_rule() {
+        let mut state = BasicState::from_str(L3_CROSS).unwrap();
+        let mut hl = HashLife::with_rule(Rule::from_str("B36/S23").unwrap());
+        hl.set_cells(state.clone());
+        for _ in 0..8 {
+            assert_eq!(state.step_rule(&Rule::life()), state.step());
+            state = state.step_rule(hl.rule());
+            hl.step(0);
+            assert_eq!(state.cells, hl.iter_cells().collect());
+        }
+    }
+
+    #[test]
+    fn test_engines_agree() {
+        let mut engines: [Box<dyn LifeEngine>; 2] =
+            [Box::new(BasicState::default()), Box::new(HashLife::new())];
+        for engine in &mut engines {
+            for p in crate::patterns::r_pentomino().iter_cells() {
+                engine.set(p, true);
+            }
+            engine.set((0, 0), false);
+            engine.set((0, 0), true);
+            assert!(engine.get((0, 0)) && !engine.get((5, 5)));
+            engine.step_by(100);
+        }
+        let cells = engines
+            .each_ref()
+            .map(|e| e.iter_cells().sorted().collect_vec());
+        assert_eq!(cells[0], cells[1]);
+        assert_eq!(engines[0].population(), engines[1].population());
+        assert_eq!(engines[0].population(), cells[0].len() as u64);
+    }
+
+    #[test]
+    fn test_iter_sorted() {
+        let state = BasicState::from_str(L3_CROSS).unwrap().step();
+        let cells = state.iter_sorted().collect_vec();
+        assert_eq!(cells, state.cells.iter().copied().sorted().collect_vec());
+        assert_eq!(state.iter_sorted().len(), state.cells.len());
+    }
 }
 
 mod hash_life {
     use super::*;
 
+    #[test]
+    fn test_iter_cells_ordered() {
+        let mut hl = crate::patterns::r_pentomino();
+        hl.step_by(200);
+        hl.set((-1000, 1000), true);
+        let cells = hl.iter_cells().collect_vec();
+        assert!(cells.is_sorted());
+        assert_eq!(cells.len() as u64, hl.population());
+        assert_eq!(hl.iter_cells().len(), cells.len());
+        let mut iter = hl.iter_cells();
+        iter.next();
+        assert_eq!(iter.len(), cells.len() - 1);
+        assert_eq!(hl.clone().into_iter().collect_vec(), cells);
+        assert_eq!(
+            HashLife::from_iter(cells.clone())
+                .iter_cells()
+                .collect_vec(),
+            cells
+        );
+    }
+
     #[test]
     fn test_single() {
         let a = HashLife::from_str("o").unwrap();
@@ -120,13 +225,13 @@ mod hash_life {
 
     #[test]
     fn test_reframe() {
-        let HashLife {
-            mut universe, root, ..
-        } = HashLife::from_str(L3_CROSS).unwrap();
+        let HashLife { universe, root, .. } = HashLife::from_str(L3_CROSS).unwrap();
+        let root = universe.lock().reframe(root, P3 { y: 1, x: 1, z: 3 }, 2);
         let hl = HashLife {
-            root: universe.reframe(root, P3 { y: 1, x: 1, z: 3 }, 2),
+            root,
             universe,
             depth: 2,
+            ..HashLife::new()
         };
         let expected = dedent(
             "
@@ -170,14 +275,3073 @@ mod hash_life {
         assert_eq!(a, b);
     }
 
+    #[test]
+    fn test_step_by() {
+        let mut a = HashLife::from_str(GLIDER[0]).unwrap();
+        let mut b = a.clone();
+        a.step_by(13);
+        for _ in 0..13 {
+            b.step(0);
+        }
+        assert_eq!(a, b);
+        a.step_by(96);
+        b.step(5);
+        b.step(6);
+        assert_eq!(a, b);
+        a.step_by(0);
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_advance_to() {
+        let mut a = HashLife::from_str(GLIDER[0]).unwrap();
+        let mut b = a.clone();
+        assert_eq!(a.generation(), 0);
+        a.step(3);
+        a.step(0);
+        assert_eq!(a.generation(), 9);
+        a.advance_to(1000).unwrap();
+        assert_eq!(a.generation(), 1000);
+        b.step_by(1000);
+        assert_eq!(a, b);
+        assert_eq!(
+            a.advance_to(999),
+            Err(StepError::Backwards {
+                from: 1000,
+                to: 999
+            })
+        );
+        a.advance_to(1000).unwrap();
+        assert_eq!(a, b);
+    }
+
     #[test]
     fn test_glider_pop() {
         // Test population is maintained over many steps.
         let mut a = HashLife::from_str(GLIDER[0]).unwrap();
-        let pop1 = a.universe.population(a.root);
+        let pop1 = a.universe.lock().population(a.root);
         a.step(50);
         a.step(50);
-        let pop2 = a.universe.population(a.root);
+        let pop2 = a.universe.lock().population(a.root);
         assert_eq!(pop1, pop2);
+        assert_eq!(a.population(), 10);
+    }
+
+    #[test]
+    fn test_set_get_toggle() {
+        let mut hl = HashLife::new();
+        hl.set((2, 3), true);
+        assert!(hl.get((2, 3)));
+        assert!(!hl.get((3, 2)));
+        hl.toggle((3, 2));
+        hl.toggle((2, 3));
+        assert_eq!(hl.clone().into_iter().collect_vec(), [(3, 2)]);
+        hl.set((3, 2), false);
+        hl.set((100, 100), false);
+        assert!(hl.is_empty());
+    }
+
+    #[test]
+    fn test_set_cells() {
+        let cells = (0..2000)
+            .map(|i: isize| ((i * 37) % 101 - 50, (i * i) % 203 - 100))
+            .collect_vec();
+        let mut a = HashLife::new();
+        for &p in &cells {
+            a.set_bit(p);
+        }
+        let mut b = HashLife::new();
+        b.set_bit((1000, 1000));
+        b.set_cells(cells.iter().copied());
+        b.set((1000, 1000), false);
+        assert_eq!(
+            a.clone().into_iter().sorted().collect_vec(),
+            b.clone().into_iter().sorted().collect_vec()
+        );
+        assert_eq!(a.population(), cells.iter().unique().count() as u64);
+    }
+
+    #[test]
+    fn test_iter_cells() {
+        let mut hl = HashLife::from_str(GLIDER[0]).unwrap();
+        hl.step(4);
+        assert_eq!(
+            hl.iter_cells().sorted().collect_vec(),
+            hl.clone().into_iter().sorted().collect_vec()
+        );
+        assert_eq!(hl.iter_cells().count(), 10);
+        assert_eq!(HashLife::new().iter_cells().next(), None);
+    }
+
+    #[test]
+    fn test_cells_in_rect() {
+        let hl = HashLife::from_str(GLIDER[0]).unwrap();
+        let (ys, xs) = (-1..1, -6..0);
+        let expected = hl
+            .iter_cells()
+            .filter(|(y, x)| ys.contains(y) && xs.contains(x))
+            .sorted()
+            .collect_vec();
+        assert_eq!(expected.len(), 4);
+        assert_eq!(hl.cells_in_rect(ys, xs).sorted().collect_vec(), expected);
+        assert_eq!(hl.cells_in_rect(100..200, 0..10).count(), 0);
+    }
+
+    #[test]
+    fn test_rasterize() {
+        let hl = HashLife::from_str(GLIDER[0]).unwrap();
+        let grid = hl.rasterize(-2..2, -10..90);
+        assert_eq!(
+            (grid.height(), grid.width(), grid.words_per_row()),
+            (4, 100, 2)
+        );
+        for y in -2..2 {
+            for x in -10..90 {
+                assert_eq!(
+                    grid.get((y + 2) as usize, (x + 10) as usize),
+                    hl.get((y, x))
+                );
+            }
+        }
+        assert_eq!(
+            grid.words().iter().map(|w| w.count_ones()).sum::<u32>(),
+            hl.cells_in_rect(-2..2, -10..90).count() as u32
+        );
+    }
+
+    #[test]
+    fn test_display_rect() {
+        let mut hl = HashLife::from_str(" o\n  o\nooo").unwrap();
+        hl.set((1 << 40, 1 << 40), true);
+        assert_eq!(hl.display_rect(-1..2, -1..2).to_string(), ".o.\n..o\nooo");
+        let glyphs = Glyphs {
+            alive: '#',
+            dead: ' ',
+            rectangular: true,
+        };
+        let window = hl.display_rect(0..2, -2..2).glyphs(glyphs);
+        assert_eq!(window.to_string(), "   #\n ###");
+        assert_eq!(hl.display_rect(5..5, 0..3).to_string(), "");
+    }
+
+    #[test]
+    fn test_glyphs() {
+        let hl = HashLife::from_str(" o\n  o\nooo\n\no").unwrap();
+        assert_eq!(hl.to_string(), " o\n  o\nooo\n\no");
+        let glyphs = Glyphs {
+            alive: '█',
+            ..Glyphs::default()
+        };
+        assert_eq!(hl.display_with(glyphs).to_string(), " █\n  █\n███\n\n█");
+        let glyphs = Glyphs {
+            alive: 'O',
+            dead: '.',
+            rectangular: true,
+        };
+        let text = hl.display_with(glyphs).to_string();
+        assert_eq!(text, ".O.\n..O\nOOO\n...\nO..");
+        assert_eq!(HashLife::new().display_with(glyphs).to_string(), "");
+    }
+
+    #[test]
+    fn test_population_in_rect() {
+        let mut hl = HashLife::from_str(GLIDER[0]).unwrap();
+        hl.step(6);
+        for (ys, xs) in [
+            (-2..2, -10..90),
+            (-100..100, -100..100),
+            (0..0, 0..5),
+            (-3..1, 2..4),
+        ] {
+            let expected = hl.cells_in_rect(ys.clone(), xs.clone()).count() as u64;
+            assert_eq!(hl.population_in_rect(ys, xs), expected);
+        }
+        assert_eq!(hl.population_in_rect(-100..100, -100..100), 10);
+    }
+
+    #[test]
+    fn test_set_ops() {
+        let mut a = HashLife::new();
+        a.set_cells([(0, 0), (0, 1), (1, 0)]);
+        let mut b = HashLife::new();
+        b.set_cells([(0, 0), (1, 1), (-40, 30)]);
+        let cells = |hl: HashLife| hl.into_iter().sorted().collect_vec();
+        let (a_cells, b_cells) = (cells(a.clone()), cells(b.clone()));
+        let both = a_cells
+            .iter()
+            .filter(|p| b_cells.contains(p))
+            .copied()
+            .collect_vec();
+        let only_a = a_cells
+            .iter()
+            .filter(|p| !b_cells.contains(p))
+            .copied()
+            .collect_vec();
+        let only_b = b_cells
+            .iter()
+            .filter(|p| !a_cells.contains(p))
+            .copied()
+            .collect_vec();
+        assert_eq!(both.len(), 1);
+        assert_eq!(cells(a.intersect(&b)), both);
+        assert_eq!(cells(a.subtract(&b)), only_a);
+        assert_eq!(cells(b.subtract(&a)), only_b);
+        let xor = only_a.iter().chain(&only_b).copied().sorted().collect_vec();
+        assert_eq!(cells(a.xor(&b)), xor);
+        let union = xor.iter().chain(&both).copied().sorted().collect_vec();
+        assert_eq!(cells(a.union(&b)), union);
+        assert_eq!(cells(b.union(&a)), union);
+    }
+
+    #[test]
+    fn test_paste() {
+        let glider = HashLife::from_str(GLIDER[0]).unwrap();
+        let glider_cells = glider.iter_cells().collect_vec();
+        for at in [(0, 0), (1, -1), (3, 5), (-17, 64), (1000, -3000)] {
+            let mut hl = HashLife::new();
+            hl.set_cells([(0, 0), (5, 5)]);
+            hl.paste(&glider, at);
+            let expected = glider_cells
+                .iter()
+                .map(|&(y, x)| (y + at.0, x + at.1))
+                .chain([(0, 0), (5, 5)])
+                .unique()
+                .sorted()
+                .collect_vec();
+            assert_eq!(hl.iter_cells().sorted().collect_vec(), expected, "{at:?}");
+        }
+        // Pasted across the edge of a torus, a blinker keeps blinking.
+        let blinker = HashLife::from_iter([(0, 0), (0, 1), (0, 2)]);
+        let mut torus = HashLife::new();
+        torus.set_topology(Topology::Torus { w: 8, h: 8 });
+        torus.paste(&blinker, (0, 7));
+        assert_eq!(torus.iter_cells().collect_vec(), [(0, 0), (0, 1), (0, 7)]);
+        torus.step(0);
+        assert_eq!(torus.iter_cells().collect_vec(), [(0, 0), (1, 0), (7, 0)]);
+    }
+
+    #[test]
+    fn test_stamp_text() {
+        let mut hl = HashLife::new();
+        hl.stamp_text("Hi\n-", (10, -3));
+        let expected = "\
+            o...o...o.....\n\
+            o...o.........\n\
+            o...o..oo.....\n\
+            ooooo...o.....\n\
+            o...o...o.....\n\
+            o...o...o.....\n\
+            o...o..ooo....\n\
+            ..............\n\
+            ..............\n\
+            ..............\n\
+            ..............\n\
+            ooooo.........";
+        assert_eq!(hl.display_rect(10..22, -3..11).to_string(), expected);
+        let population = hl.population();
+        hl.stamp_text(" ", (0, 0));
+        hl.stamp_text("\u{e9}", (0, 100));
+        let mut question = HashLife::new();
+        question.stamp_text("?", (0, 100));
+        assert_eq!(hl.population(), population + question.population());
+    }
+
+    #[test]
+    fn test_pattern_builder() {
+        use crate::{PatternBuilder, Symmetry};
+
+        let shapes = PatternBuilder::new()
+            .rect_outline(0..3, 0..4)
+            .line((4, 0), (5, 3))
+            .circle((9, 2), 2)
+            .build();
+        let disc = PatternBuilder::new().disc((0, 0), 2).build();
+        assert_eq!(disc.population(), 21);
+        assert_eq!(
+            disc.display_rect(-2..3, -2..3).to_string(),
+            ".ooo.\nooooo\nooooo\nooooo\n.ooo."
+        );
+        assert_eq!(
+            shapes.display_rect(0..6, 0..4).to_string(),
+            "oooo\no..o\noooo\n....\noo..\n..oo"
+        );
+        let circle = shapes.display_rect(7..12, 0..5).to_string();
+        assert_eq!(circle, ".ooo.\no...o\no...o\no...o\n.ooo.");
+
+        let random = |seed| {
+            let mut builder = PatternBuilder::new();
+            builder.seed(seed).fill_random(0..100, 0..100, 0.25);
+            builder.build()
+        };
+        assert_eq!(random(1), random(1));
+        assert_ne!(random(1), random(2));
+        assert!((2300..2700).contains(&random(1).population()));
+        let full = PatternBuilder::new().fill_random(0..10, 0..10, 1.).build();
+        assert_eq!(full.population(), 100);
+
+        let glider = HashLife::from_str(GLIDER[0]).unwrap();
+        let mut rotated = glider.clone();
+        rotated.rotate90_cw();
+        rotated.translate(1000, -7);
+        let placed = PatternBuilder::new()
+            .rect(0..1, 0..1)
+            .place(&glider, (1000, -7), Symmetry::Rotate90)
+            .build();
+        assert_eq!(
+            placed.iter_cells().sorted().collect_vec(),
+            rotated.iter_cells().chain([(0, 0)]).sorted().collect_vec()
+        );
+    }
+
+    #[test]
+    fn test_metafy() {
+        let glider = HashLife::from_str(GLIDER[0]).unwrap();
+        let on = HashLife::from_iter([(0, 0), (1, 1), (2, 2), (3, 3)]);
+        let off = HashLife::from_iter([(0, 3)]);
+        let meta = glider.metafy(&on, &off, 4).unwrap();
+        let (ys, xs) = glider.bounding_box().unwrap();
+        let expected = ys
+            .cartesian_product(xs)
+            .flat_map(|(y, x)| {
+                let unit = if glider.get((y, x)) { &on } else { &off };
+                unit.iter_cells()
+                    .map(move |(dy, dx)| (4 * y + dy, 4 * x + dx))
+            })
+            .sorted()
+            .collect_vec();
+        assert_eq!(meta.iter_cells().sorted().collect_vec(), expected);
+
+        // Far apart cells share the nodes of the unit cells between them.
+        let single = HashLife::from_iter([(0, 0)]);
+        let mut far = HashLife::from_iter([(0, 0), (1 << 30, 0)]);
+        let meta = far.metafy(&on, &off, 1 << 10).unwrap();
+        assert_eq!(meta.population(), 2 * 4 + ((1 << 30) - 1));
+        assert!(meta.universe.lock().node_count() < 1000);
+        assert_eq!(single.metafy(&on, &off, 4).unwrap(), on);
+
+        assert_eq!(
+            HashLife::new().metafy(&on, &off, 4).unwrap().population(),
+            0
+        );
+        assert!(glider.metafy(&on, &off, 3).is_err());
+        assert!(glider.metafy(&on, &off, 2).is_err());
+        far.translate(-1, 0);
+        assert!(glider.metafy(&on, &far, 1 << 40).is_err());
+    }
+
+    #[test]
+    fn test_transforms() {
+        let hl = HashLife::from_str(GLIDER[0]).unwrap();
+        let cells = hl.iter_cells().collect_vec();
+        type Edit = fn(&mut HashLife);
+        type Map = fn((isize, isize)) -> (isize, isize);
+        let transforms: [(Edit, Map); 5] = [
+            (HashLife::rotate90_cw, |(y, x)| (x, -1 - y)),
+            (HashLife::rotate90_ccw, |(y, x)| (-1 - x, y)),
+            (HashLife::flip_horizontal, |(y, x)| (y, -1 - x)),
+            (HashLife::flip_vertical, |(y, x)| (-1 - y, x)),
+            (HashLife::transpose, |(y, x)| (x, y)),
+        ];
+        for (edit, f) in transforms {
+            let mut transformed = hl.clone();
+            edit(&mut transformed);
+            let expected = cells.iter().map(|&p| f(p)).sorted().collect_vec();
+            assert_eq!(transformed.iter_cells().sorted().collect_vec(), expected);
+        }
+        let mut spun = hl.clone();
+        for _ in 0..4 {
+            spun.rotate90_cw();
+        }
+        assert_eq!(spun, hl);
+        for (edit, f) in transforms {
+            let mut cell = HashLife::from_iter([(0, 0)]);
+            edit(&mut cell);
+            assert_eq!(cell.iter_cells().collect_vec(), [f((0, 0))]);
+        }
+        // Turned across the edge of a torus, a blinker keeps blinking.
+        let mut torus = HashLife::new();
+        torus.set_topology(Topology::Torus { w: 8, h: 8 });
+        torus.set_cells([(1, 1), (1, 2), (1, 3)]);
+        torus.rotate90_cw();
+        assert_eq!(torus.iter_cells().collect_vec(), [(1, 6), (2, 6), (3, 6)]);
+        torus.step(0);
+        assert_eq!(torus.iter_cells().collect_vec(), [(2, 5), (2, 6), (2, 7)]);
+    }
+
+    #[test]
+    fn test_translate() {
+        let hl = HashLife::from_str(GLIDER[0]).unwrap();
+        let cells = hl.iter_cells().collect_vec();
+        for (dy, dx) in [(0, 0), (1, 0), (-3, 7), (64, -64), (12345, 678)] {
+            let mut moved = hl.clone();
+            moved.translate(dy, dx);
+            let expected = cells
+                .iter()
+                .map(|&(y, x)| (y + dy, x + dx))
+                .sorted()
+                .collect_vec();
+            assert_eq!(moved.iter_cells().sorted().collect_vec(), expected);
+            moved.translate(-dy, -dx);
+            assert_eq!(moved, hl);
+        }
+        let mut torus = HashLife::new();
+        torus.set_topology(Topology::Torus { w: 4, h: 4 });
+        torus.set_bit((3, 0));
+        torus.translate(1, -1);
+        assert_eq!(torus.iter_cells().collect_vec(), [(0, 3)]);
+    }
+
+    #[test]
+    fn test_crop_and_clear_rect() {
+        let mut hl = HashLife::from_str(GLIDER[0]).unwrap();
+        hl.step(5);
+        let cells = hl.iter_cells().collect_vec();
+        for (ys, xs) in [
+            (-2..2, -10..0),
+            (-100..100, -100..100),
+            (0..0, 0..5),
+            (-3..1, 2..4),
+        ] {
+            let inside = |&(y, x): &(isize, isize)| ys.contains(&y) && xs.contains(&x);
+            let mut cropped = hl.clone();
+            cropped.crop(ys.clone(), xs.clone());
+            let expected = cells.iter().copied().filter(inside).sorted().collect_vec();
+            assert_eq!(cropped.iter_cells().sorted().collect_vec(), expected);
+            let mut cleared = hl.clone();
+            cleared.clear_rect(ys.clone(), xs.clone());
+            let expected = cells
+                .iter()
+                .copied()
+                .filter(|p| !inside(p))
+                .sorted()
+                .collect_vec();
+            assert_eq!(cleared.iter_cells().sorted().collect_vec(), expected);
+        }
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        use std::collections::HashSet;
+        let a = HashLife::from_str(GLIDER[0]).unwrap();
+        // The same cells built up differently, in a different universe.
+        let mut b = HashLife::new();
+        b.set_cells(a.iter_cells().collect_vec().into_iter().rev());
+        for _ in 0..8 {
+            b.expand();
+        }
+        assert_ne!(a.depth, b.depth);
+        assert_eq!(a.canonical_fingerprint(), b.canonical_fingerprint());
+        let mut c = a.clone();
+        c.step(0);
+        assert_ne!(a.canonical_fingerprint(), c.canonical_fingerprint());
+        let mut single = HashLife::new();
+        single.set_bit((0, 0));
+        let mut deep = single.clone();
+        for _ in 0..5 {
+            deep.expand();
+        }
+        assert_eq!(single.canonical_fingerprint(), deep.canonical_fingerprint());
+        let set: HashSet<HashLife> = [a.clone(), a.clone(), c].into_iter().collect();
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn test_bounding_box() {
+        assert_eq!(HashLife::new().bounding_box(), None);
+        let mut hl = HashLife::new();
+        hl.set_cells([(-3, 5), (7, -2), (0, 0)]);
+        assert_eq!(hl.bounding_box(), Some((-3..8, -2..6)));
+    }
+
+    #[test]
+    fn test_symmetries() {
+        let order = |s: &str| HashLife::from_str(s).unwrap().symmetries().order();
+        // Block, blinker, beehive, glider and R-pentomino.
+        assert_eq!(order("oo\noo"), 8);
+        assert_eq!(order("ooo"), 4);
+        assert_eq!(order(" oo\no  o\n oo"), 4);
+        assert_eq!(order(" o\n  o\nooo"), 1);
+        assert_eq!(order(" oo\noo\n o"), 1);
+        let tromino = HashLife::from_str("oo\n o").unwrap().symmetries();
+        assert!(tromino.contains(Symmetry::AntiTranspose));
+        assert!(!tromino.contains(Symmetry::Transpose));
+        assert_eq!(
+            tromino.iter().collect_vec(),
+            [Symmetry::Identity, Symmetry::AntiTranspose]
+        );
+    }
+
+    #[test]
+    fn test_detect_period() {
+        let period = |s: &str| HashLife::from_str(s).unwrap().detect_period(100);
+        let periodic = |period, displacement| {
+            Some(Periodic {
+                period,
+                displacement,
+            })
+        };
+        assert_eq!(period(""), periodic(1, (0, 0)));
+        assert_eq!(period("oo\noo"), periodic(1, (0, 0)));
+        assert_eq!(period("ooo"), periodic(2, (0, 0)));
+        assert_eq!(period(" o\n  o\nooo"), periodic(4, (1, 1)));
+        // The lightweight spaceship.
+        assert_eq!(period(" o  o\no\no   o\noooo"), periodic(4, (0, -2)));
+        // An R-pentomino takes over a thousand generations to settle.
+        assert_eq!(period(" oo\noo\n o"), None);
+        // A pre-block, which settles after a generation.
+        assert_eq!(period("oo\no"), periodic(1, (0, 0)));
+    }
+
+    #[test]
+    fn test_stabilize() {
+        let stabilize = |s: &str| HashLife::from_str(s).unwrap().stabilize(Limits::default());
+        let r_pentomino = stabilize(" oo\noo\n o").unwrap();
+        assert_eq!((r_pentomino.generation, r_pentomino.period), (1103, 1));
+        assert_eq!(r_pentomino.state.population(), 116);
+        let blinker = stabilize("ooo").unwrap();
+        assert_eq!((blinker.generation, blinker.period), (0, 1));
+        let limits = Limits {
+            max_generations: 100,
+            ..Limits::default()
+        };
+        let r_pentomino = HashLife::from_str(" oo\noo\n o").unwrap();
+        assert!(r_pentomino.stabilize(limits).is_err());
+    }
+
+    #[test]
+    fn test_components() {
+        let hl = HashLife::from_str(
+            "
+            oo  o
+            oo   o
+                   o
+            o      o",
+        )
+        .unwrap();
+        let sizes = |gap| {
+            let components = hl.components(gap);
+            for (component, (y, x)) in &components {
+                let mut moved = component.clone();
+                moved.translate(*y, *x);
+                assert_eq!(moved.intersect(&hl).population(), component.population());
+            }
+            components
+                .iter()
+                .map(|(component, _)| component.population())
+                .sorted()
+                .collect_vec()
+        };
+        assert_eq!(sizes(1), [1, 2, 2, 4]);
+        assert_eq!(sizes(2), [4, 5]);
+        assert_eq!(sizes(3), [9]);
+        assert!(HashLife::new().components(1).is_empty());
+    }
+
+    #[test]
+    fn test_find() {
+        let hl = HashLife::from_str(
+            "
+            oo     oo    o
+            oo     oo     o
+                        ooo
+              oo
+              oo  o       o
+                   o     o
+                 ooo     ooo",
+        )
+        .unwrap();
+        let found = |needle: &str| {
+            let (ys, xs) = hl.bounding_box().unwrap();
+            let needle = HashLife::from_str(needle).unwrap();
+            let (ny, nx) = needle
+                .bounding_box()
+                .map(|(ys, xs)| (ys.start, xs.start))
+                .unwrap();
+            hl.find(&needle)
+                .map(|(y, x)| (y + ny - ys.start, x + nx - xs.start))
+                .sorted()
+                .collect_vec()
+        };
+        assert_eq!(found("oo\noo"), [(0, 0), (0, 7), (3, 2)]);
+        // The glider in a different orientation doesn't count.
+        assert_eq!(found(" o\n  o\nooo"), [(0, 12), (4, 5)]);
+        assert_eq!(found("oo\noo\noo"), []);
+        assert_eq!(hl.find(&HashLife::new()).count(), 0);
+    }
+
+    #[test]
+    fn test_census() {
+        let hl = HashLife::from_str(
+            "
+            oo    ooo
+            oo
+                           o
+             o            o o
+            o o      oo   o o
+             o      o o    o
+                     o
+
+            ooo      o
+            o  o      o
+             oo     ooo",
+        )
+        .unwrap();
+        let census = hl.census();
+        let counts = census.counts.into_iter().collect_vec();
+        assert_eq!(
+            counts,
+            [
+                ("beehive", 1),
+                ("blinker", 1),
+                ("block", 1),
+                ("boat", 1),
+                ("glider", 1),
+                ("tub", 1),
+            ]
+        );
+        assert_eq!(census.unrecognized.len(), 1);
+        assert_eq!(census.unrecognized[0].population(), 7);
+        assert_eq!(census.unrecognized[0].bounding_box(), Some((0..3, 0..4)));
+    }
+
+    #[test]
+    fn test_search() {
+        let search = |threads| Search {
+            soups: 4,
+            threads,
+            seed: 1,
+            size: 6,
+            limits: Limits {
+                max_generations: 1000,
+                ..Limits::default()
+            },
+            methuselah: 200,
+        };
+        assert_eq!(search(1).soup(3), search(2).soup(3));
+        assert_ne!(search(1).soup(3), search(1).soup(4));
+        assert_eq!(
+            search(1).soup(3).population_in_rect(0..8, 0..8),
+            search(1).soup(3).population()
+        );
+        let findings = std::sync::Mutex::new(vec![]);
+        let counts = search(3).run(|finding| {
+            let soup = match finding {
+                Finding::Methuselah { soup, .. } => soup,
+                Finding::Unrecognized { soup, .. } => soup,
+                Finding::Unstable { soup } => soup,
+            };
+            findings.lock().unwrap().push(soup);
+        });
+        assert!(counts.values().sum::<usize>() > 0);
+        assert_eq!(search(1).run(|_| {}), counts);
+        assert!(findings.into_inner().unwrap().iter().all(|&soup| soup < 4));
+    }
+
+    #[test]
+    fn test_apgcode() {
+        let apgcode = |s: &str| HashLife::from_str(s).unwrap().apgcode();
+        assert_eq!(apgcode("oo\noo").as_deref(), Some("xs4_33"));
+        assert_eq!(apgcode("ooo").as_deref(), Some("xp2_7"));
+        assert_eq!(apgcode(" o\n  o\nooo").as_deref(), Some("xq4_153"));
+        assert_eq!(apgcode(" oo\no  o\n oo").as_deref(), Some("xs6_696"));
+        assert_eq!(apgcode(" ooo\nooo").as_deref(), Some("xp2_7e"));
+        assert_eq!(
+            apgcode(" o  o\no\no   o\noooo").as_deref(),
+            Some("xq4_6frc")
+        );
+        assert_eq!(
+            crate::patterns::pulsar().apgcode().as_deref(),
+            Some("xp3_co9nas0san9oczgoldlo0oldlogz1047210127401")
+        );
+        // Far apart objects share a code, with runs of 39 zeros and the rest.
+        let mut blocks = HashLife::from_str("oo\noo").unwrap();
+        blocks.paste(&HashLife::from_str("oo\noo").unwrap(), (0, 50));
+        assert_eq!(blocks.apgcode().as_deref(), Some("xs8_33yzy533"));
+        // Repeats, but only once it's died out.
+        assert_eq!(apgcode("oo"), None);
+        assert_eq!(HashLife::new().apgcode(), None);
+
+        for code in ["xs4_33", "xq4_153", "xp2_7e", "xs8_33yzy533", "xq4_6frc"] {
+            let hl = HashLife::from_apgcode(code).unwrap();
+            assert_eq!(hl.apgcode().as_deref(), Some(code));
+        }
+        let glider = HashLife::from_apgcode("xq4_153").unwrap();
+        assert_eq!(glider.bounding_box(), Some((0..3, 0..3)));
+        assert_eq!(glider.population(), 5);
+        assert!(HashLife::from_apgcode("xs4").is_err());
+        assert!(HashLife::from_apgcode("yl4_33").is_err());
+        assert!(HashLife::from_apgcode("xs4_3!").is_err());
+    }
+
+    #[test]
+    fn test_haul() {
+        let search = Search {
+            soups: 8,
+            threads: 3,
+            seed: 1,
+            size: 6,
+            limits: Limits {
+                max_generations: 1000,
+                ..Limits::default()
+            },
+            ..Search::default()
+        };
+        let haul = search.haul();
+        assert_eq!(
+            Search {
+                threads: 1,
+                ..search
+            }
+            .haul(),
+            haul
+        );
+        assert!(haul.counts["xs4_33"] > 0);
+        assert!(
+            haul.samples
+                .values()
+                .all(|soups| soups.is_sorted() && soups.len() <= 10)
+        );
+        let submission = haul.submission(&search);
+        assert!(submission.contains("@RULE b3s23\n"));
+        assert!(submission.contains("@NUM_SOUPS 8\n"));
+        let table = submission.split("@CENSUS TABLE\n").nth(1).unwrap();
+        let first = table.lines().next().unwrap();
+        let most = haul.counts.values().max().unwrap();
+        assert!(first.ends_with(&format!(" {most}")));
+        assert!(submission.contains(&format!(
+            "\n@SAMPLE_SOUPIDS\n{} ",
+            first.split(' ').next().unwrap()
+        )));
+    }
+
+    #[test]
+    fn test_is_empty() {
+        let mut hl = HashLife::new();
+        assert!(hl.is_empty());
+        hl.set_bit((3, -4));
+        assert!(!hl.is_empty());
+        hl.step(0);
+        assert!(hl.is_empty());
+        assert_eq!(hl.population(), 0);
+    }
+
+    #[test]
+    fn test_ages() {
+        let mut hl = HashLife::from_iter([(0, 0), (0, 1), (0, 2)]);
+        assert_eq!(hl.age((0, 0)), None);
+        hl.track_ages(true);
+        assert!(hl.is_tracking_ages());
+        assert_eq!(hl.age((0, 1)), Some(0));
+        hl.step(0);
+        // The middle of the blinker survives, the ends are born.
+        assert_eq!(hl.age((0, 1)), Some(1));
+        assert_eq!(hl.age((-1, 1)), Some(0));
+        assert_eq!(hl.age((0, 0)), None);
+        hl.set_bit((10, 10));
+        assert_eq!(hl.age((10, 10)), Some(0));
+        // Cells are only dated at the end of each step, so the ends look like
+        // they survived.
+        hl.step(1);
+        assert_eq!(hl.age((0, 1)), Some(3));
+        assert_eq!(hl.age((-1, 1)), Some(2));
+        assert_eq!(hl.age((10, 10)), None);
+        hl.set_topology(Topology::Torus { w: 8, h: 8 });
+        hl.step(0);
+        assert_eq!(hl.age((0, 1)), Some(4));
+        assert_eq!(hl.age((8, 9)), Some(4));
+        hl.track_ages(false);
+        assert_eq!(hl.age((0, 1)), None);
+    }
+
+    #[test]
+    fn test_activity() {
+        let mut hl = HashLife::from_iter([(0, 0), (0, 1), (0, 2), (10, 10), (10, 11)]);
+        assert_eq!(hl.activity((0, 0)), None);
+        hl.set_cells([(11, 10), (11, 11)]);
+        hl.track_activity(2);
+        assert!(hl.is_tracking_activity());
+        assert_eq!(hl.activity((0, 0)), Some(0));
+        hl.step(0);
+        assert_eq!(hl.activity((0, 0)), Some(1));
+        assert_eq!(hl.activity((-1, 1)), Some(1));
+        assert_eq!(hl.activity((0, 1)), Some(0));
+        assert_eq!(hl.activity((10, 10)), Some(0));
+        hl.step(0);
+        hl.step(0);
+        assert_eq!(hl.activity((0, 0)), Some(2));
+        assert_eq!(hl.activity((10, 10)), Some(0));
+        hl.track_activity(0);
+        assert_eq!(hl.activity((0, 0)), None);
+    }
+
+    #[test]
+    fn test_step_by_observed() {
+        let mut hl = HashLife::from_iter([(0, 1), (1, 2), (2, 0), (2, 1), (2, 2)]);
+        let mut seen = vec![];
+        let stepped = hl.step_by_observed(20, 4, |hl| {
+            seen.push((hl.generation(), hl.population()));
+            ControlFlow::Continue(())
+        });
+        assert_eq!(stepped, 20);
+        assert_eq!(seen, [(4, 5), (8, 5), (12, 5), (16, 5), (20, 5)]);
+        // Stopping early.
+        let stepped = hl.step_by_observed(100, 4, |hl| match hl.generation() {
+            28 => ControlFlow::Break(()),
+            _ => ControlFlow::Continue(()),
+        });
+        assert_eq!(stepped, 8);
+        assert_eq!(hl.generation(), 28);
+    }
+
+    #[test]
+    fn test_step_with() {
+        // A soup big enough to take a while to step.
+        let mut seed = 1u64;
+        let cells = (0..10_000).filter_map(|k| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+            (seed >> 63 == 1).then_some((k / 100, k % 100))
+        });
+        let mut hl = HashLife::from_iter(cells.collect_vec());
+        let before = hl.clone();
+        let cancel = AtomicBool::new(true);
+        assert_eq!(hl.step_with(1 << 10, &cancel), 0);
+        assert!(!hl.try_step(10, &cancel));
+        assert_eq!(hl.step_checked_unless(10, &|| true), Ok(false));
+        assert_eq!(hl.generation(), 0);
+        assert!(hl == before);
+        cancel.store(false, Ordering::Relaxed);
+        assert_eq!(hl.step_with(32, &cancel), 32);
+        assert_eq!(hl.step_checked_unless(5, &|| false), Ok(true));
+        assert_eq!(hl.step_for(64, Duration::ZERO), 0);
+        assert_eq!(hl.generation(), 64);
+        let mut expected = before.clone();
+        expected.step_by(64);
+        assert!(hl == expected);
+    }
+
+    #[test]
+    fn test_try_step() {
+        let mut seed = 7u64;
+        let cells = (0..10_000).filter_map(|k| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+            (seed >> 63 == 1).then_some((k / 100, k % 100))
+        });
+        let cells = cells.collect_vec();
+        let before = HashLife::from_iter(cells.iter().copied());
+        // In a universe of its own, so its steps aren't cached for `before`.
+        let mut expected = HashLife::from_iter(cells);
+        expected.step(8);
+        // Cut short part way rather than before starting.
+        let mut hl = before.clone();
+        let polls = std::cell::Cell::new(0);
+        let stop = || {
+            polls.set(polls.get() + 1);
+            polls.get() > 1
+        };
+        assert!(!hl.step_unless(8, &stop));
+        assert_eq!(polls.get(), 2);
+        assert!(hl == before);
+        // Finished on another thread, as the app does.
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let cancel = AtomicBool::new(false);
+            let stepped = hl.try_step(8, &cancel);
+            tx.send(stepped.then_some(hl)).unwrap();
+        });
+        let hl = rx.recv().unwrap().unwrap();
+        assert_eq!(hl.generation(), 256);
+        assert!(hl == expected);
+    }
+
+    #[test]
+    fn test_hyperspeed() {
+        let mut hl = HashLife::from_iter([(0, 0), (0, 1), (1, 0), (1, 1)]);
+        let mut hyperspeed = Hyperspeed::default();
+        hyperspeed.max_log2_steps = 2;
+        // Two steps to see the trend, then four steady ones.
+        for _ in 0..6 {
+            assert_eq!(hyperspeed.log2_steps(), 0);
+            hyperspeed.step(&mut hl);
+        }
+        assert_eq!(hyperspeed.log2_steps(), 1);
+        for _ in 0..12 {
+            hyperspeed.step(&mut hl);
+        }
+        assert_eq!(hyperspeed.log2_steps(), 2);
+        assert_eq!(hl.generation(), 6 + 2 * 6 + 4 * 6);
+        // Lots of cells appearing slows it down.
+        hl.set_cells((0..10).map(|k| (10 * k, 20)));
+        hyperspeed.observe(&hl);
+        assert_eq!(hyperspeed.log2_steps(), 1);
+    }
+
+    #[test]
+    fn test_collect_garbage() {
+        let glider = [(0, 1), (1, 2), (2, 0), (2, 1), (2, 2)];
+        let mut hl = HashLife::from_iter(glider);
+        hl.track_activity(2);
+        hl.track_envelope(true);
+        let start = hl.snapshot();
+        let dropped = hl.snapshot();
+        hl.release(&dropped);
+        for _ in 0..100 {
+            hl.step(0);
+        }
+        let before = hl.node_count();
+        hl.collect_garbage();
+        assert!(hl.node_count() < before / 2);
+        let mut expected = HashLife::from_iter(glider);
+        expected.step_by(100);
+        assert!(hl == expected);
+        // Stepping on uses what's left of the cache and the trackers.
+        hl.step(0);
+        expected.step(0);
+        assert!(hl == expected);
+        assert_eq!(hl.envelope().unwrap().population(), 108);
+        assert_eq!(hl.activity((27, 27)), Some(1));
+        hl.restore(&start).unwrap();
+        assert!(hl == HashLife::from_iter(glider));
+        assert_eq!(hl.generation(), 0);
+        assert_eq!(
+            hl.restore(&dropped),
+            Err(UniverseError::UnknownSnapshot(dropped))
+        );
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let glider = HashLife::from_str(GLIDER[0]).unwrap();
+        let mut hl = glider.clone();
+        let start = hl.snapshot();
+        hl.step_by(10);
+        let ten = hl.snapshot();
+        let mut expected = hl.clone();
+        // Go back and take another branch, then come back to the first.
+        hl.restore(&start).unwrap();
+        assert!(hl == glider);
+        hl.set_cells([(10, 10)]);
+        hl.step_by(5);
+        hl.collect_garbage();
+        hl.restore(&ten).unwrap();
+        assert_eq!(hl.generation(), 10);
+        assert!(hl == expected);
+        // Snapshots only restore the pattern they're of, and its copies.
+        assert!(expected.restore(&start).is_ok());
+        assert!(HashLife::new().restore(&start).is_err());
+    }
+
+    #[test]
+    fn test_history() {
+        let mut hl = HashLife::from_str(GLIDER[0]).unwrap();
+        let start = hl.clone();
+        assert!(!hl.undo());
+        hl.track_history(Some(HistoryConfig::default()));
+        hl.set_cells([(10, 10)]);
+        hl.checkpoint();
+        let edited = hl.clone();
+        hl.step_by(4);
+        let stepped = hl.clone();
+        // Edits since the last step are kept before the next one, and undo
+        // back to the state after the step.
+        hl.set_cells([(-10, -10)]);
+        hl.step(0);
+        assert_eq!(hl.history().unwrap().undo_len(), 3);
+        let mut edited_after_step = stepped.clone();
+        edited_after_step.set_cells([(-10, -10)]);
+        assert!(hl.undo() && hl == edited_after_step);
+        assert!(hl.undo() && hl == stepped);
+        assert!(hl.undo() && hl == edited);
+        assert!(hl.undo() && hl == start);
+        assert!(!hl.undo());
+        assert!(hl.redo() && hl == edited);
+        // Editing forgets what could be redone.
+        hl.set_cells([(5, 5)]);
+        assert!(!hl.redo());
+        assert!(hl.undo() && hl == edited);
+    }
+
+    #[test]
+    fn test_timeline() {
+        let gun = crate::patterns::gosper_gun();
+        let mut timeline = Timeline::new(gun.clone());
+        let root = timeline.root();
+        let eater = timeline.fork(root, |hl| hl.set_cells([(40, 40), (40, 41)]));
+        let nudged = timeline.fork(root, |hl| hl.toggle((0, 0)));
+        timeline.step_by(eater, 64);
+        let misses = timeline.pattern(eater).stats().cache_misses;
+        timeline.step_by(nudged, 64);
+        let misses = timeline.pattern(nudged).stats().cache_misses - misses;
+        // The branches share a universe, so the gun's steps are reused.
+        let mut alone = gun.clone();
+        alone.toggle((0, 0));
+        alone.step_by(64);
+        assert!(misses < alone.stats().cache_misses);
+        for (branch, edit) in [(eater, vec![(40, 40), (40, 41)]), (nudged, vec![(0, 0)])] {
+            let mut expected = gun.clone();
+            for p in edit {
+                expected.toggle(p);
+            }
+            expected.step_by(64);
+            assert!(timeline.pattern(branch) == expected);
+            assert_eq!(timeline.pattern(branch).generation(), 64);
+        }
+        assert!(timeline.pattern(root) == gun);
+        let diff = timeline.diff(eater, nudged);
+        let expected = timeline.pattern(eater).diff(&timeline.pattern(nudged));
+        assert!(!diff.is_empty() && diff == expected);
+        let deeper = timeline.fork(nudged, |hl| hl.step(0));
+        assert_eq!(timeline.children(root), vec![eater, nudged]);
+        assert_eq!(timeline.parent(deeper), Some(nudged));
+        timeline.prune(nudged);
+        assert_eq!(timeline.branches().collect_vec(), vec![root, eater]);
+    }
+
+    #[test]
+    fn test_step_back() {
+        let gun = crate::patterns::gosper_gun();
+        let at = |generation| {
+            let mut hl = gun.clone();
+            hl.step_by(generation);
+            hl
+        };
+        let mut hl = gun.clone();
+        assert!(hl.step_back(1).is_err());
+        hl.track_rewind(16, 4);
+        for _ in 0..100 {
+            hl.step(0);
+        }
+        // Checkpoints are kept at 48, 64, 80 and 96.
+        hl.step_back(10).unwrap();
+        assert_eq!(hl.generation(), 90);
+        assert!(hl == at(90));
+        assert_eq!(hl.rewind_limit(), Some(42));
+        assert!(hl.step_back(43).is_err());
+        hl.step_back(42).unwrap();
+        assert!(hl == at(48));
+        // Edits can't be stepped back past.
+        hl.toggle((0, 0));
+        let edited = hl.clone();
+        assert_eq!(hl.rewind_limit(), Some(0));
+        hl.step(1);
+        hl.step(0);
+        assert_eq!(hl.rewind_limit(), Some(3));
+        hl.step_back(3).unwrap();
+        assert!(hl == edited);
+        hl.track_rewind(0, 0);
+        assert_eq!(hl.rewind_limit(), None);
+    }
+
+    #[test]
+    fn test_trim() {
+        let small = HashLife::from_iter([(0, 0), (1, 1)]);
+        let mut hl = small.clone();
+        hl.set_cells([(1000, -1000)]);
+        assert!(hl.depth > 10);
+        // Killing the far cell any way leaves the same tree as never having
+        // set it.
+        let mut killed = hl.clone();
+        killed.set((1000, -1000), false);
+        let mut cleared = hl.clone();
+        cleared.clear_rect(500..2000, -2000..-500);
+        let mut cropped = hl.clone();
+        cropped.crop(-10..10, -10..10);
+        let subtracted = hl.subtract(&HashLife::from_iter([(1000, -1000)]));
+        for hl in [killed, cleared, cropped, subtracted] {
+            assert_eq!(hl.depth, small.depth);
+            assert!(hl == small);
+        }
+        hl.trim();
+        assert!(hl.depth > 10);
+    }
+
+    #[test]
+    fn test_history_generations() {
+        let mut hl = HashLife::from_str(GLIDER[0]).unwrap();
+        let config = HistoryConfig {
+            max_depth: 3,
+            edits: false,
+            generations: Some(8),
+        };
+        hl.track_history(Some(config));
+        for _ in 0..20 {
+            hl.step(1);
+        }
+        // Every fourth step is kept, up to the limit.
+        let history = hl.history().unwrap();
+        assert_eq!((history.undo_len(), history.redo_len()), (3, 0));
+        assert_eq!(hl.generation(), 40);
+        assert!(hl.undo());
+        assert_eq!(hl.generation(), 32);
+        assert!(hl.undo() && hl.undo());
+        assert_eq!(hl.generation(), 16);
+        assert!(!hl.undo());
+        hl.collect_garbage();
+        assert!(hl.redo() && hl.redo());
+        let mut expected = HashLife::from_str(GLIDER[0]).unwrap();
+        expected.step_by(32);
+        assert!(hl == expected);
+        hl.track_history(None);
+        assert!(hl.history().is_none() && !hl.redo());
+    }
+
+    #[test]
+    fn test_universe_config() {
+        let gun = crate::patterns::gosper_gun();
+        let mut expected = gun.clone();
+        expected.step_by(1 << 8);
+        let mut hl = gun.clone();
+        let config = UniverseConfig {
+            max_nodes: Some(2000),
+            max_cache_entries: Some(500),
+        };
+        hl.set_universe_config(config);
+        assert_eq!(hl.universe_config(), config);
+        for _ in 0..1 << 8 {
+            hl.step(0);
+            assert!(hl.universe.lock().cache_len() <= 500);
+        }
+        assert!(hl.node_count() <= 2000);
+        assert!(hl == expected);
+        // Live nodes are never dropped, however low the limit.
+        hl.set_universe_config(UniverseConfig {
+            max_nodes: Some(1),
+            max_cache_entries: Some(0),
+        });
+        assert_eq!(hl.universe.lock().cache_len(), 0);
+        assert!(hl == expected);
+        hl.step(0);
+        expected.step(0);
+        assert!(hl == expected);
+    }
+
+    #[test]
+    fn test_step_checked() {
+        let mut hl = crate::patterns::gosper_gun();
+        assert_eq!(hl.step_checked(4), Ok(()));
+        hl.set_universe_config(UniverseConfig {
+            max_nodes: Some(1),
+            max_cache_entries: None,
+        });
+        let Err(StepError::OutOfNodes { nodes, max: 1 }) = hl.step_checked(0) else {
+            panic!("Stepped within one node");
+        };
+        // The step stands, and it's up to the caller what to do about the
+        // universe being over its limit.
+        assert_eq!(nodes, hl.node_count());
+        assert_eq!(hl.generation(), 17);
+        let mut expected = crate::patterns::gosper_gun();
+        expected.step_by(17);
+        assert!(hl == expected);
+        assert_eq!(hl.step_checked(64), Err(StepError::GenerationOverflow));
+        hl.generation = u64::MAX - 1;
+        assert_eq!(hl.step_checked(1), Err(StepError::GenerationOverflow));
+        assert_eq!(hl.generation(), u64::MAX - 1);
+    }
+
+    #[test]
+    fn test_universe_full() {
+        let mut hl = crate::patterns::gosper_gun();
+        let before = hl.clone();
+        let nodes = hl.node_count();
+        hl.universe.lock().set_capacity(nodes);
+        assert_eq!(hl.step_checked(4), Err(StepError::UniverseFull));
+        assert!(!hl.step_unless(4, &|| false));
+        assert_eq!(
+            hl.set_state_checked((100, 100), 1),
+            Err(StepError::UniverseFull)
+        );
+        assert_eq!(
+            hl.set_cells_checked([(-100, 0)]),
+            Err(StepError::UniverseFull)
+        );
+        assert_eq!(hl.generation(), 0);
+        assert!(hl == before);
+        // Setting a cell that's already alive needs no new nodes.
+        let (p, _) = hl.cells()[0];
+        assert_eq!(hl.set_state_checked(p, 1), Ok(()));
+        // The steps that were cut short didn't leave anything wrong behind.
+        hl.universe.lock().set_capacity(usize::MAX);
+        assert_eq!(hl.step_checked(4), Ok(()));
+        let mut expected = crate::patterns::gosper_gun();
+        expected.step(4);
+        assert!(hl == expected);
+    }
+
+    #[test]
+    fn test_universe_too_large() {
+        let mut hl = HashLife::from_str(GLIDER[0]).unwrap();
+        assert_eq!(hl.step_checked(60), Err(StepError::UniverseTooLarge));
+        assert_eq!(hl.generation(), 0);
+        assert_eq!(hl.step_checked(40), Ok(()));
+        assert_eq!(hl.population(), 10);
+        let mut far = HashLife::new();
+        far.set((1 << 60, 0), true);
+        assert_eq!(far.step_checked(0), Err(StepError::UniverseTooLarge));
+        // Edits beyond the coordinates fail rather than panic, and change
+        // nothing.
+        let before = far.clone();
+        for p in [(1 << 61, 0), (0, -(1 << 61) - 1), (isize::MIN, isize::MAX)] {
+            assert_eq!(
+                far.set_state_checked(p, 1),
+                Err(StepError::UniverseTooLarge)
+            );
+            assert_eq!(
+                far.set_cells_checked([(0, 0), p]),
+                Err(StepError::UniverseTooLarge)
+            );
+        }
+        assert!(far == before);
+        assert_eq!(
+            far.set_state_checked((-(1 << 61), (1 << 61) - 1), 1),
+            Ok(())
+        );
+        assert_eq!(far.population(), 2);
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut hl = crate::patterns::gosper_gun();
+        let stats = hl.stats();
+        assert_eq!(
+            (stats.cache_entries, stats.cache_hits, stats.cache_misses),
+            (0, 0, 0)
+        );
+        assert_eq!(stats.nodes, hl.node_count());
+        assert_eq!(stats.interned, stats.nodes - 1);
+        for _ in 0..60 {
+            hl.step(0);
+        }
+        let stats = hl.stats();
+        assert!(stats.cache_hits > 0);
+        // The same node can be missed twice before it's worked out.
+        assert!(stats.cache_entries as u64 <= stats.cache_misses);
+        assert!(stats.bytes > stats.nodes * 8);
+        // Most of the gun has been stepped before.
+        hl.step(0);
+        let after = hl.stats();
+        assert!(after.cache_hits > stats.cache_hits);
+    }
+
+    #[test]
+    fn test_mixed_step_sizes() {
+        // Steps of different sizes share the cache, so each must find only
+        // the results of its own size, such as for an oscillator coming back
+        // to a phase stepped before.
+        let mut seed = 7u64;
+        let cells = (0..400).filter_map(|k| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+            (seed >> 63 == 1).then_some((k / 20, k % 20))
+        });
+        let soup = HashLife::from_iter(cells.collect_vec());
+        let blinker = HashLife::from_iter([(0, 0), (0, 1), (0, 2)]);
+        for mut a in [blinker, soup] {
+            let mut b = a.clone();
+            for log2_steps in [0, 0, 1, 0, 2, 3, 1, 0] {
+                a.step(log2_steps);
+                for _ in 0..1 << log2_steps {
+                    b.step(0);
+                }
+                assert!(a == b, "{log2_steps}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_bitlife() {
+        let mut seed = 5u64;
+        let cells = (0..48 * 48).filter_map(|k| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+            (seed >> 63 == 1).then_some((k / 48 - 24, k % 48 - 40))
+        });
+        let cells = cells.collect_vec();
+        let mut expected = HashLife::from_iter(cells.clone());
+        let mut life = BitLife::from_iter(cells);
+        assert_eq!(life.population(), expected.population());
+        for steps in [1, 1, 2, 60, 100] {
+            life.step_by(steps);
+            expected.step_by(steps);
+            let cells = life.iter_cells().sorted().collect_vec();
+            assert_eq!(cells, expected.iter_cells().sorted().collect_vec());
+            assert_eq!(life.population(), expected.population());
+        }
+        assert_eq!(life.generation(), 164);
+        let p = life.iter_cells().next().unwrap();
+        assert!(life.get(p));
+        life.set(p, false);
+        assert!(!life.get(p) && !life.get((1 << 40, -1 << 40)));
+        // Soups stabilize the same either way.
+        let search = Search {
+            size: 8,
+            ..Search::default()
+        };
+        for soup in 0..2 {
+            let soup = search.soup(soup);
+            let a = soup.stabilize(Limits::default()).unwrap();
+            let b = BitLife::from_iter(soup.iter_cells())
+                .stabilize(Limits::default())
+                .unwrap();
+            assert_eq!((a.generation, a.period), (b.generation, b.period));
+            assert!(a.state == b.state);
+            assert_eq!(a.state.generation(), b.state.generation());
+        }
+    }
+
+    #[test]
+    fn test_hybrid() {
+        let mut seed = 11u64;
+        let cells = (0..64 * 64).filter_map(|k| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+            (seed >> 63 == 1).then_some((k / 64, k % 64))
+        });
+        let mut expected = HashLife::from_iter(cells.collect_vec());
+        let mut hybrid = Hybrid::with_config(
+            expected.clone(),
+            HybridConfig {
+                dense_generations: 100,
+                ..HybridConfig::default()
+            },
+        );
+        let mut was_dense = false;
+        for steps in [1, 15, 40, 100, 3, 141] {
+            hybrid.step_by(steps);
+            expected.step_by(steps);
+            was_dense |= hybrid.is_dense();
+            assert_eq!(hybrid.generation(), expected.generation());
+            assert_eq!(hybrid.population(), expected.population());
+            assert!(hybrid.get((-1, -1)) == expected.get((-1, -1)));
+            assert!(hybrid.pattern() == expected);
+        }
+        assert!(was_dense);
+        // Cells set outside the grid go back into the tree.
+        hybrid.set((1000, 1000), true);
+        expected.set((1000, 1000), true);
+        assert!(hybrid.pattern() == expected);
+        let cells = hybrid.iter_cells().sorted().collect_vec();
+        assert_eq!(cells, expected.iter_cells().sorted().collect_vec());
+        // A regular pattern stays in the tree.
+        let mut gun = Hybrid::new(crate::patterns::gosper_gun());
+        for _ in 0..20 {
+            gun.step_by(30);
+            assert!(!gun.is_dense());
+        }
+    }
+
+    #[test]
+    fn test_share_universe() {
+        let gun = crate::patterns::gosper_gun();
+        let mut later = gun.clone();
+        later.step(4);
+        let snapshot = later.snapshot();
+        later.step(2);
+        let glider = HashLife::from_str(GLIDER[0]).unwrap();
+        let mut patterns = [gun.clone(), later.clone(), glider.clone()];
+        HashLife::share_universe(&mut patterns).unwrap();
+        assert!(patterns.iter().map(|hl| hl.node_count()).all_equal());
+        assert!(patterns[0] == gun && patterns[1] == later && patterns[2] == glider);
+        assert!(patterns[0] != patterns[1]);
+        // Stepping one leaves the others as they were.
+        let [gun_copy, later_copy, _] = &mut patterns;
+        later_copy.step(0);
+        later.step(0);
+        assert!(*gun_copy == gun && *later_copy == later);
+        later_copy.restore(&snapshot).unwrap();
+        later.restore(&snapshot).unwrap();
+        assert!(*later_copy == later);
+        let highlife = Rule::from_str("B36/S23").unwrap();
+        let b36 = HashLife::with_rule(highlife.clone());
+        assert_eq!(
+            HashLife::share_universe(&mut [gun, b36]),
+            Err(UniverseError::RuleMismatch {
+                first: Box::new(Rule::life()),
+                other: Box::new(highlife)
+            })
+        );
+    }
+
+    #[test]
+    fn test_clones_share_universe() {
+        let gun = crate::patterns::gosper_gun();
+        let mut later = gun.clone();
+        later.step(6);
+        // The nodes the copy made are in the gun's universe too, and so are
+        // its steps, which the gun reuses.
+        assert_eq!(gun.node_count(), later.node_count());
+        let mut caught_up = gun.clone();
+        let misses = caught_up.stats().cache_misses;
+        caught_up.step(6);
+        assert_eq!(caught_up.stats().cache_misses, misses);
+        assert!(caught_up == later);
+        // Stepping on another thread while the others read the universe.
+        let (mut stepping, population) = (later.clone(), later.population());
+        let handle = std::thread::spawn(move || {
+            stepping.step_by(100);
+            stepping
+        });
+        for _ in 0..100 {
+            assert_eq!(later.iter_cells().count() as u64, population);
+        }
+        let stepping = handle.join().unwrap();
+        assert_eq!(stepping.generation(), 164);
+        assert_eq!(stepping.node_count(), gun.node_count());
+        // Garbage collection moves the pattern to a universe of its own.
+        caught_up.collect_garbage();
+        assert!(caught_up.node_count() < gun.node_count());
+        later.step(4);
+        assert!(caught_up.node_count() < gun.node_count());
+        assert_eq!(later.node_count(), gun.node_count());
+        assert!(caught_up != later);
+    }
+
+    #[test]
+    fn test_diff() {
+        let blinker = HashLife::from_iter([(0, 0), (0, 1), (0, 2)]);
+        assert!(blinker.diff(&blinker).is_empty());
+        let mut stepped = blinker.clone();
+        stepped.step(0);
+        let diff = blinker.diff(&stepped);
+        assert_eq!(diff.born, [(-1, 1), (1, 1)]);
+        assert_eq!(diff.died, [(0, 0), (0, 2)]);
+        // Patterns built apart, and of different sizes, compare cell by cell.
+        let other = HashLife::from_iter([(0, 1), (100, -100)]);
+        let diff = blinker.diff(&other);
+        assert_eq!(diff.born, [(100, -100)]);
+        assert_eq!(diff.died, [(0, 0), (0, 2)]);
+        assert_eq!(other.diff(&blinker).born, diff.died);
+    }
+
+    #[test]
+    fn test_envelope() {
+        let mut hl = HashLife::from_iter([(0, 0), (0, 1), (0, 2)]);
+        assert!(hl.envelope().is_none());
+        hl.track_envelope(true);
+        assert!(hl.is_tracking_envelope());
+        hl.step(0);
+        let plus = [(-1, 1), (0, 0), (0, 1), (0, 2), (1, 1)];
+        let envelope = hl.envelope().unwrap();
+        assert_eq!(envelope.iter_cells().sorted().collect_vec(), plus);
+        assert!(!envelope.is_tracking_envelope());
+        for _ in 0..5 {
+            hl.step(0);
+        }
+        // A glider far away grows the envelope along its path, from after the
+        // first step.
+        hl.set_cells([(100, 101), (101, 102), (102, 100), (102, 101), (102, 102)]);
+        for _ in 0..4 {
+            hl.step(0);
+        }
+        let envelope = hl.envelope().unwrap();
+        assert_eq!(envelope.population(), 5 + 10);
+        assert!(envelope.get((103, 103)));
+        assert!(!envelope.get((100, 101)));
+        hl.track_envelope(false);
+        assert!(hl.envelope().is_none());
+    }
+
+    #[test]
+    fn test_light_cone() {
+        let single = HashLife::from_iter([(0, 0)]);
+        let cone = single.light_cone(2).unwrap();
+        assert_eq!(cone.population(), 25);
+        assert_eq!(cone.bounding_box(), Some((-2..3, -2..3)));
+        let cone = single.light_cone(0).unwrap();
+        assert_eq!(cone.iter_cells().collect_vec(), [(0, 0)]);
+
+        // Two far apart cells have two cones, not their bounding box grown.
+        let apart = HashLife::from_iter([(0, 0), (0, 1000)]);
+        assert_eq!(apart.light_cone(3).unwrap().population(), 2 * 49);
+
+        // Whatever a glider and block get up to stays inside.
+        let mut hl = HashLife::from_str(GLIDER[0]).unwrap();
+        hl.set_cells([(10, 3), (10, 4), (11, 3), (11, 4)]);
+        let cone = hl.light_cone(16).unwrap();
+        hl.track_envelope(true);
+        for _ in 0..16 {
+            hl.step(0);
+        }
+        let envelope = hl.envelope().unwrap();
+        assert_eq!(envelope.subtract(&cone).population(), 0);
+        assert!(cone.population() > envelope.population());
+
+        let mut ltl = HashLife::with_rule(Rule::from_str("R2,C0,M0,S2..4,B3..3,NH").unwrap());
+        ltl.set_bit((0, 0));
+        assert_eq!(ltl.light_cone(1).unwrap().population(), 25);
+        assert_eq!(HashLife::new().light_cone(5).unwrap().population(), 0);
+        let mut flashing = HashLife::with_rule(Rule::from_str("B0/S").unwrap());
+        flashing.set_bit((0, 0));
+        assert!(flashing.light_cone(1).is_none());
+        assert!(single.light_cone(u64::MAX).is_none());
+    }
+
+    #[test]
+    fn test_light_cone_tracking() {
+        let mut hl = HashLife::from_iter([(0, 1), (1, 2), (2, 0), (2, 1), (2, 2)]);
+        hl.track_envelope(true);
+        hl.track_activity(4);
+        let snapshot = hl.snapshot();
+        for _ in 0..4 {
+            hl.step(0);
+        }
+        let cone = hl.light_cone(1).unwrap();
+        // A pattern of its own, with none of the tracking or pins.
+        assert!(!cone.is_tracking_envelope());
+        assert!(!cone.is_tracking_activity());
+        assert_eq!(cone.generation(), 0);
+        assert!(matches!(
+            cone.clone().restore(&snapshot),
+            Err(UniverseError::UnknownSnapshot(_))
+        ));
+        // Grown from where the glider is now, not narrowed to where it's been.
+        let untracked = HashLife::from_iter(hl.iter_cells());
+        assert_eq!(
+            cone.iter_cells().sorted().collect_vec(),
+            untracked
+                .light_cone(1)
+                .unwrap()
+                .iter_cells()
+                .sorted()
+                .collect_vec()
+        );
+        assert!(cone.subtract(&hl.envelope().unwrap()).population() > 0);
+    }
+}
+
+mod rule {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Rule::from_str("B3/S23").unwrap(), Rule::life());
+        assert_eq!(Rule::from_str("23/3").unwrap(), Rule::life());
+        assert_eq!(Rule::from_str("s23/b3").unwrap(), Rule::life());
+        assert_eq!(Rule::from_str("B3aceijknqry/S23").unwrap(), Rule::life());
+        assert!(Rule::from_str("B9/S23").is_err());
+        assert_eq!(
+            Rule::from_str("B3x/S23"),
+            Err(RuleError::UnexpectedChar('x'))
+        );
+        assert_eq!(
+            Rule::from_str("B3/S23/X"),
+            Err(RuleError::Unsupported("B3/S23/X".into()))
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        for rule in ["B3/S23", "B2-a/S12", "B2ce3ai/S", "B35678/S5-c678", "B0/S8"] {
+            assert_eq!(Rule::from_str(rule).unwrap().to_string(), rule);
+        }
+        assert_eq!(
+            Rule::from_str("B2cekain/S1ce").unwrap().to_string(),
+            "B2/S1"
+        );
+    }
+
+    #[test]
+    fn test_hensel() {
+        // Seeds births above and below a domino, but 2a forbids both.
+        let mut hl = HashLife::with_rule("B2/S".parse().unwrap());
+        hl.set_bit((0, 0));
+        hl.set_bit((0, 1));
+        hl.step(0);
+        assert_eq!(hl.to_string(), "oo\n\noo");
+        let mut hl = HashLife::with_rule("B2-a/S".parse().unwrap());
+        hl.set_bit((0, 0));
+        hl.set_bit((0, 1));
+        hl.step(0);
+        assert_eq!(hl.to_string(), "");
+    }
+
+    #[test]
+    fn test_generations() {
+        assert_eq!(Rule::from_str("/2/3").unwrap().to_string(), "B2/S/C3");
+        assert_eq!(Rule::from_str("B2/S/C3").unwrap().states(), 3);
+        assert!(Rule::from_str("B2/S/C1").is_err());
+        // Brian's Brain: a live domino fades to dying, while new cells are
+        // born either side of it.
+        let mut hl = HashLife::with_rule("B2/S/C3".parse().unwrap());
+        hl.set_bit((0, 0));
+        hl.set_bit((1, 0));
+        hl.step(0);
+        assert_eq!(hl.get_state((0, 0)), 2);
+        assert_eq!(hl.get_state((1, 0)), 2);
+        assert_eq!(hl.get_state((0, -1)), 1);
+        assert_eq!(hl.get_state((1, 1)), 1);
+        hl.step(0);
+        assert_eq!(hl.get_state((0, 0)), 0);
+        assert_eq!(hl.get_state((0, -1)), 2);
+    }
+
+    #[test]
+    fn test_generations_rle() {
+        let rle = "x = 2, y = 2, rule = B2/S/C3\nAB$.A!";
+        let pattern = crate::formats::load(rle.as_bytes()).unwrap();
+        assert_eq!(pattern.life.rule().states(), 3);
+        assert_eq!(pattern.life.get_state((-1, -1)), 1);
+        assert_eq!(pattern.life.get_state((-1, 0)), 2);
+        let written = crate::formats::write_rle(&pattern.life, &pattern.meta);
+        assert_eq!(written, format!("{rle}\n"));
+        let mc = crate::formats::write_macrocell(&pattern.life, &pattern.meta);
+        let reloaded = crate::formats::load(mc.as_bytes()).unwrap();
+        assert_eq!(reloaded.life, pattern.life);
+    }
+
+    #[test]
+    fn test_neighbourhoods() {
+        for rule in ["B2/S34H", "B1/S012V", "B2/S/C3H"] {
+            assert_eq!(Rule::from_str(rule).unwrap().to_string(), rule);
+        }
+        assert!(Rule::from_str("B2a/S34H").is_err());
+        assert!(Rule::from_str("B7/SH").is_err());
+        assert!(Rule::from_str("B5/SV").is_err());
+        // Hexagonal neighbours leave out the NE and SW corners, so a domino
+        // only has two neighbours in common on the NW-SE diagonal.
+        let mut hl = HashLife::with_rule("B2/S34H".parse().unwrap());
+        hl.set_bit((0, 0));
+        hl.set_bit((0, 1));
+        hl.step(0);
+        assert_eq!(hl.into_iter().sorted().collect_vec(), [(-1, 0), (1, 1)]);
+        let mut hl = HashLife::with_rule("B1/SV".parse().unwrap());
+        hl.set_bit((0, 0));
+        hl.step(0);
+        assert_eq!(hl.to_string(), " o\no o\n o");
+        let rule = Rule::from_str("R2,C0,M0,S2..4,B3..3,NH").unwrap();
+        assert_eq!(Rule::from_str(&rule.to_string()).unwrap(), rule);
+        let mut cells = BasicState::from_str(L3_CROSS)
+            .unwrap()
+            .into_iter()
+            .sorted()
+            .collect_vec();
+        let mut hl = HashLife::with_rule(rule.clone());
+        for &p in &cells {
+            hl.set_bit(p);
+        }
+        for _ in 0..4 {
+            cells = naive_step(&rule, &cells);
+            hl.step(0);
+            assert_eq!(hl.clone().into_iter().sorted().collect_vec(), cells);
+        }
+    }
+
+    #[test]
+    fn test_b0() {
+        assert_eq!(
+            Rule::from_str("B0/S/C3"),
+            Err(RuleError::LiveBackground("B0/S/C3".into()))
+        );
+        // Without S8 the background strobes, so a lone cell alternates with a
+        // hole the shape of its neighbourhood.
+        let mut hl = HashLife::with_rule("B0/S".parse().unwrap());
+        hl.set_bit((0, 0));
+        hl.step(0);
+        assert!(hl.is_inverted());
+        assert_eq!(hl.to_string(), "ooo\nooo\nooo");
+        hl.step(0);
+        assert!(!hl.is_inverted());
+        assert_eq!(hl.to_string(), "o");
+        // Big steps should match small ones whatever the background.
+        for rule in ["B0/S", "B0/S8", "B013/S0125", "B02/S3478"] {
+            let mut a = HashLife::from_str(L3_CROSS).unwrap();
+            a.set_rule(rule.parse().unwrap());
+            let mut b = a.clone();
+            a.step(3);
+            for _ in 0..8 {
+                b.step(0);
+            }
+            assert_eq!(a, b, "{rule}");
+            assert_eq!(a.is_inverted(), rule.contains('8'));
+        }
+    }
+
+    /// Steps a set of cells forward one generation cell by cell.
+    fn naive_step(rule: &Rule, cells: &[(isize, isize)]) -> Vec<(isize, isize)> {
+        let state = BasicState::from_iter(cells.iter().copied());
+        state.step_rule(rule).into_iter().sorted().collect()
+    }
+
+    #[test]
+    fn test_bitboard_steps() {
+        // Radius 1 rules step 8x8 nodes as bitboards, one generation at a time
+        // or two at once.
+        let mut seed = 7u64;
+        let soup = (0..144)
+            .filter(|_| {
+                seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+                seed >> 62 == 1
+            })
+            .collect_vec();
+        for rule in ["B3/S23", "B36/S23", "B2-a/S12", "B2/S34H", "B1/S1V"] {
+            let rule = Rule::from_str(rule).unwrap();
+            let mut cells = soup.iter().map(|&k| (k / 12, k % 12)).collect_vec();
+            let mut hl = HashLife::with_rule(rule.clone());
+            hl.set_cells(cells.iter().copied());
+            let mut superspeed = hl.clone();
+            for _ in 0..4 {
+                cells = naive_step(&rule, &cells);
+                hl.step(0);
+                assert_eq!(hl.iter_cells().sorted().collect_vec(), cells, "{rule}");
+            }
+            superspeed.step(1);
+            superspeed.step(1);
+            assert!(superspeed == hl, "{rule}");
+        }
+    }
+
+    #[test]
+    fn test_larger_than_life() {
+        let rule = Rule::from_str("R2,C2,S5-8,B7-8,NM").unwrap();
+        assert_eq!(rule.to_string(), "R2,C0,M0,S5..8,B7..8,NM");
+        assert_eq!(Rule::from_str(&rule.to_string()).unwrap(), rule);
+        assert!(Rule::from_str("R0,C0,M0,S5..8,B7..8,NM").is_err());
+        // Life written as a Larger than Life rule.
+        let life = Rule::from_str("R1,C0,M0,S2..3,B3..3,NM").unwrap();
+        let mut a = HashLife::from_str(GLIDER[0]).unwrap();
+        let mut b = HashLife::with_rule(life);
+        for p in a.clone() {
+            b.set_bit(p);
+        }
+        a.step(5);
+        b.step(5);
+        assert_eq!(a.to_string(), b.to_string());
+    }
+
+    #[test]
+    fn test_larger_than_life_steps() {
+        for rule in ["R2,C0,M1,S4..9,B6..9,NM", "R3,C0,M0,S3..7,B4..6,NN"] {
+            let rule = Rule::from_str(rule).unwrap();
+            let mut cells = BasicState::from_str(L3_CROSS)
+                .unwrap()
+                .into_iter()
+                .sorted()
+                .collect_vec();
+            let mut hl = HashLife::with_rule(rule.clone());
+            for &p in &cells {
+                hl.set_bit(p);
+            }
+            let mut superspeed = hl.clone();
+            for _ in 0..4 {
+                cells = naive_step(&rule, &cells);
+                hl.step(0);
+                assert_eq!(hl.clone().into_iter().sorted().collect_vec(), cells);
+            }
+            superspeed.step(2);
+            assert_eq!(superspeed.into_iter().sorted().collect_vec(), cells);
+        }
+    }
+
+    const WIREWORLD: &str = "@RULE WireWorld
+@TABLE
+n_states:4
+neighborhood:Moore
+symmetries:permute
+var a={0,1,2,3}
+var b={0,1,2,3}
+var c={0,1,2,3}
+var d={0,1,2,3}
+var e={0,1,2,3}
+var f={0,1,2,3}
+var g={0,1,2,3}
+var h={0,1,2,3}
+var i={0,2,3}
+var j={0,2,3}
+var k={0,2,3}
+var l={0,2,3}
+var m={0,2,3}
+var n={0,2,3}
+var o={0,2,3}
+1,a,b,c,d,e,f,g,h,2
+2,a,b,c,d,e,f,g,h,3
+3,1,i,j,k,l,m,n,o,1
+3,1,1,i,j,k,l,m,n,1
+";
+
+    #[test]
+    fn test_table() {
+        let rule = Rule::from_str(WIREWORLD).unwrap();
+        assert_eq!(rule.to_string(), "WireWorld");
+        assert_eq!(rule.states(), 4);
+        // An electron travelling down a wire.
+        let mut hl = HashLife::with_rule(rule);
+        for x in -2..30 {
+            hl.set_state((0, x), 3);
+        }
+        hl.set_state((0, -1), 2);
+        hl.set_state((0, 0), 1);
+        let mut slow = hl.clone();
+        hl.step(3);
+        for _ in 0..8 {
+            slow.step(0);
+        }
+        assert_eq!(hl, slow);
+        assert_eq!(hl.get_state((0, 7)), 2);
+        assert_eq!(hl.get_state((0, 8)), 1);
+        assert_eq!(hl.get_state((0, 9)), 3);
+    }
+
+    #[test]
+    fn test_tree() {
+        // Cells copy their northern neighbour, so everything moves south.
+        let rule = Rule::from_str(
+            "@RULE South
+@TREE
+num_states=2
+num_neighbors=4
+num_nodes=9
+1 0 0
+1 1 1
+2 0 0
+2 1 1
+3 2 2
+3 3 3
+4 4 4
+4 5 5
+5 6 7
+",
+        )
+        .unwrap();
+        let mut hl = HashLife::with_rule(rule);
+        hl.set_bit((0, 0));
+        hl.set_bit((0, 1));
+        hl.step(3);
+        assert_eq!(
+            hl.clone().into_iter().sorted().collect_vec(),
+            [(8, 0), (8, 1)]
+        );
+        assert!(
+            Rule::from_str("@RULE Broken\n@TREE\nnum_states=2\nnum_neighbors=4\n1 0 0\n").is_err()
+        );
+    }
+
+    #[test]
+    fn test_load_rule() {
+        let pattern = crate::formats::load(b"x = 2, y = 1, rule = B2/S\n2o!").unwrap();
+        assert_eq!(pattern.life.rule().to_string(), "B2/S");
+        assert!(crate::formats::load(b"x = 2, y = 1, rule = Bogus\n2o!").is_err());
+    }
+
+    #[test]
+    fn test_load_rule_file() {
+        let dir = std::env::temp_dir().join(format!("hashlife-rules-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("WireWorld.rule"), WIREWORLD).unwrap();
+        let load = |name: &str, text: &str| {
+            let path = dir.join(name);
+            std::fs::write(&path, text).unwrap();
+            crate::formats::load_file(path)
+        };
+        let pattern = load("wire.mc", "[M2]\n#R WireWorld\n1 0 1 2 3\n").unwrap();
+        assert_eq!(pattern.life.rule().states(), 4);
+        assert_eq!(pattern.life.get_state((0, 0)), 3);
+        // States past the rule's are only found once its file is.
+        for (name, text) in [
+            ("past.mc", "[M2]\n#R WireWorld\n1 0 1 2 4\n"),
+            ("past.rle", "x = 1, y = 1, rule = WireWorld\nD!"),
+        ] {
+            assert!(matches!(
+                load(name, text).unwrap_err(),
+                ParseError::BadState {
+                    state: 4,
+                    states: 4
+                }
+            ));
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
+
+mod formats {
+    use super::*;
+    use crate::formats::{self, Format, PatternMeta};
+
+    fn cells(life: HashLife) -> BasicState {
+        life.into_iter().collect::<BasicState>().normalize()
+    }
+
+    fn glider() -> BasicState {
+        BasicState::from_str(" o\n  o\nooo").unwrap()
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(
+            Format::detect("#N Glider\nx = 3, y = 3\nbo$2bo$3o!"),
+            Format::Rle
+        );
+        assert_eq!(Format::detect("[M2] (golly 2.0)\n"), Format::Macrocell);
+        assert_eq!(Format::detect("#Life 1.05\n"), Format::Life105);
+        assert_eq!(Format::detect("#Life 1.06\n"), Format::Life106);
+        assert_eq!(
+            Format::detect("!Name: Glider\n.O.\n..O\nOOO"),
+            Format::Plaintext
+        );
+    }
+
+    #[test]
+    fn test_rle() {
+        let pattern =
+            formats::load(b"#C A glider\nx = 3, y = 3, rule = B3/S23\nbo$2bo$3o!").unwrap();
+        assert_eq!(pattern.format, Format::Rle);
+        assert_eq!(pattern.meta.comments, ["A glider"]);
+        assert_eq!(pattern.meta.rule.as_deref(), Some("B3/S23"));
+        assert_eq!(cells(pattern.life), glider());
+    }
+
+    #[test]
+    fn test_macrocell() {
+        let pattern =
+            formats::load(b"[M2] (golly 2.0)\n#R B3/S23\n$$$$$..*$...*$.***$\n4 0 0 0 1\n")
+                .unwrap();
+        assert_eq!(pattern.format, Format::Macrocell);
+        assert_eq!(pattern.life.depth, 4);
+        assert_eq!(cells(pattern.life), glider());
+    }
+
+    #[test]
+    fn test_life() {
+        let pattern =
+            formats::load(b"#Life 1.05\n#D A glider\n#N\n#P -1 -1\n.*.\n..*\n***\n").unwrap();
+        assert_eq!(pattern.meta.comments, ["A glider"]);
+        assert_eq!(cells(pattern.life), glider());
+        let pattern = formats::load(b"#Life 1.06\n0 -1\n1 0\n-1 1\n0 1\n1 1\n").unwrap();
+        assert_eq!(cells(pattern.life), glider());
+    }
+
+    fn meta() -> PatternMeta {
+        PatternMeta {
+            name: Some("Glider".to_string()),
+            author: Some("Richard K. Guy".to_string()),
+            comments: vec!["The smallest spaceship.".to_string()],
+            rule: Some("B3/S23".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_rle_round_trip() {
+        let rle = formats::write_rle(&HashLife::from_str(" o\n  o\nooo").unwrap(), &meta());
+        assert_eq!(
+            rle,
+            "#N Glider\n#O Richard K. Guy\n#C The smallest spaceship.\n\
+             x = 3, y = 3, rule = B3/S23\nbo$2bo$3o!\n"
+        );
+        let pattern = formats::load(rle.as_bytes()).unwrap();
+        assert_eq!(pattern.meta, meta());
+        assert_eq!(cells(pattern.life), glider());
+    }
+
+    #[test]
+    fn test_macrocell_round_trip() {
+        let mc = formats::write_macrocell(&HashLife::from_str(L3_CROSS).unwrap(), &meta());
+        let pattern = formats::load(mc.as_bytes()).unwrap();
+        assert_eq!(pattern.format, Format::Macrocell);
+        assert_eq!(pattern.meta, meta());
+        assert_eq!(pattern.life.to_string(), dedent(L3_CROSS));
+    }
+
+    #[test]
+    fn test_streams() {
+        let life = HashLife::from_str(L3_CROSS).unwrap();
+        for format in [Format::Rle, Format::Macrocell, Format::Plaintext] {
+            let mut bytes = vec![];
+            formats::write(&mut bytes, &life, &meta(), format).unwrap();
+            let pattern = formats::read(bytes.as_slice()).unwrap();
+            assert_eq!(pattern.format, format);
+            assert_eq!(pattern.meta.name, meta().name);
+            assert_eq!(pattern.meta.comments, meta().comments);
+            assert_eq!(pattern.life.to_string(), dedent(L3_CROSS));
+        }
+        let error = formats::write(vec![], &life, &meta(), Format::Life106).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::Unsupported);
+        assert_eq!(Format::from_extension("MC"), Some(Format::Macrocell));
+        assert_eq!(Format::from_extension("png"), None);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |bytes: &[u8]| formats::load(bytes).unwrap_err();
+        assert!(matches!(
+            HashLife::from_str("!Name: x\n o\no?").unwrap_err(),
+            ParseError::UnexpectedChar {
+                c: '?',
+                line: 3,
+                column: 2
+            }
+        ));
+        assert!(matches!(
+            error(b"#C A glider\nx = 3, y = 3\nbo$\n 2bo$3?!"),
+            ParseError::UnexpectedChar {
+                c: '?',
+                line: 4,
+                column: 7
+            }
+        ));
+        assert!(matches!(
+            error(b"x = three, y = 3\n3o!"),
+            ParseError::BadHeader(_)
+        ));
+        assert!(matches!(
+            error(b"x = 99999999999, y = 1\no!"),
+            ParseError::TooLarge { .. }
+        ));
+        assert!(matches!(
+            error(b"x = 1, y = 1\n99999999999o!"),
+            ParseError::TooLarge { .. }
+        ));
+        assert!(matches!(
+            error(b"[M2]\n$$..*$\n4 0 0 0 2\n"),
+            ParseError::BadLine { line: 3, .. }
+        ));
+        // States past the rule's, once it's known.
+        assert!(matches!(
+            error(b"[M2]\n#R B2/S/C3\n1 0 1 2 0\n1 0 0 3 0\n"),
+            ParseError::BadLine { line: 4, .. }
+        ));
+        assert!(matches!(
+            error(b"[M2]\n1 0 0 2 0\n"),
+            ParseError::BadState {
+                state: 2,
+                states: 2
+            }
+        ));
+        // A chain of nodes each holding the last, one deeper than can be
+        // addressed.
+        let mut deep = "[M2]\n*$\n".to_string();
+        for depth in 4..=crate::p3::MAX_DEPTH + 1 {
+            deep.push_str(&format!("{depth} {} 0 0 0\n", depth - 3));
+        }
+        assert!(matches!(
+            error(deep.as_bytes()),
+            ParseError::BadLine { line, .. } if line == crate::p3::MAX_DEPTH
+        ));
+        deep.truncate(deep.trim_end().rfind('\n').unwrap() + 1);
+        assert!(
+            formats::load(deep.as_bytes())
+                .unwrap()
+                .life
+                .bounding_box()
+                .is_some()
+        );
+        assert!(matches!(
+            error(b"#Life 1.06\n0 0\n0 zero\n"),
+            ParseError::BadLine { line: 3, .. }
+        ));
+        assert!(matches!(
+            error(b"x = 2, y = 1, rule = Bogus\n2o!"),
+            ParseError::Rule(_)
+        ));
+        // Past the 255 states a cell can have, whatever the rule.
+        assert!(matches!(
+            error(b"x = 1, y = 1, rule = Bogus\nyP!"),
+            ParseError::UnexpectedChar {
+                c: 'P',
+                line: 2,
+                column: 2
+            }
+        ));
+        assert!(matches!(
+            error(b"x = 2, y = 1, rule = B2/S/C3\nAC!"),
+            ParseError::UnexpectedChar { c: 'C', .. }
+        ));
+        assert!(matches!(
+            formats::load_file("/nonexistent/pattern.rle").unwrap_err(),
+            ParseError::Io(_)
+        ));
+    }
+}
+
+mod snapshot {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut a = HashLife::from_str(GLIDER[0]).unwrap();
+        a.step(4);
+        let mut bytes = vec![];
+        a.write_snapshot(&mut bytes).unwrap();
+        let mut b = HashLife::read_snapshot(bytes.as_slice()).unwrap();
+        assert_eq!(a, b);
+        assert_eq!(b.generation(), 16);
+        a.step(3);
+        b.step(3);
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_keeps_rule() {
+        let mut a = HashLife::with_rule("B36/S23".parse().unwrap());
+        a.set_bit((0, 0));
+        let mut bytes = vec![];
+        a.write_snapshot(&mut bytes).unwrap();
+        let b = HashLife::read_snapshot(bytes.as_slice()).unwrap();
+        assert_eq!(b.rule(), a.rule());
+    }
+
+    #[test]
+    fn test_rejects_garbage() {
+        assert!(HashLife::read_snapshot(&b"HLSN"[..]).is_err());
+        assert!(HashLife::read_snapshot(&b"nope\x01\x00\x00"[..]).is_err());
+        let mut bytes = vec![];
+        HashLife::from_str(L3_CROSS)
+            .unwrap()
+            .write_snapshot(&mut bytes)
+            .unwrap();
+        bytes.pop();
+        assert!(HashLife::read_snapshot(bytes.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_rejects_corrupt() {
+        use std::io::ErrorKind;
+        let error = |bytes: &[u8]| HashLife::read_snapshot(bytes).unwrap_err().kind();
+        // A rule claiming to be far longer than the file.
+        let long_rule = b"HLSN\x01\xff\xff\xff\xff\xff\xff\xff\xff\x7fB3/S23";
+        assert_eq!(error(long_rule), ErrorKind::UnexpectedEof);
+        // Life on a plane at generation 0, then the depth and nodes.
+        let snapshot = |nodes: &[u8]| [&b"HLSN\x01\x06B3/S23\x00\x00\x00\x00"[..], nodes].concat();
+        assert!(
+            HashLife::read_snapshot(&snapshot(b"\x01\x02\x00\x01\x01\x00\x00\x00")[..]).is_ok()
+        );
+        // Deeper than coordinates can address.
+        assert_eq!(error(&snapshot(b"\x3f\x00")), ErrorKind::InvalidData);
+        // A branch over a cell and another branch.
+        let mixed = b"\x02\x03\x00\x01\x01\x01\x01\x01\x01\x02\x01\x00\x00";
+        assert_eq!(error(&snapshot(mixed)), ErrorKind::InvalidData);
+        // A root at depth 1 said to be at depth 2.
+        let shallow = b"\x02\x02\x00\x01\x01\x00\x00\x00";
+        assert_eq!(error(&snapshot(shallow)), ErrorKind::InvalidData);
+        // A state Life doesn't have.
+        assert_eq!(
+            error(&snapshot(b"\x00\x01\x02\x05")),
+            ErrorKind::InvalidData
+        );
+    }
+}
+
+mod topology {
+    use super::*;
+
+    #[test]
+    fn test_wrap() {
+        let mut hl = HashLife::new();
+        hl.set_topology(Topology::Torus { w: 8, h: 6 });
+        hl.set_bit((-1, 9));
+        assert_eq!(hl.get_state((5, 1)), 1);
+        assert_eq!(hl.get_state((11, -7)), 1);
+        assert_eq!(hl.into_iter().collect_vec(), [(5, 1)]);
+    }
+
+    #[test]
+    fn test_blinker_across_edge() {
+        let mut hl = HashLife::new();
+        hl.set_topology(Topology::Torus { w: 8, h: 8 });
+        for x in [-1, 0, 1] {
+            hl.set_bit((0, x));
+        }
+        hl.step(0);
+        assert_eq!(
+            hl.into_iter().sorted().collect_vec(),
+            [(0, 0), (1, 0), (7, 0)]
+        );
+    }
+
+    #[test]
+    fn test_glider_laps() {
+        // A glider crosses an 8x8 torus in 32 generations, however it's
+        // stepped.
+        let mut a = HashLife::from_str(" o\n  o\nooo").unwrap();
+        a.set_topology(Topology::Torus { w: 8, h: 8 });
+        let start = a.clone().into_iter().sorted().collect_vec();
+        let mut b = a.clone();
+        a.step(5);
+        assert_eq!(a.generation(), 32);
+        for _ in 0..32 {
+            b.step(0);
+        }
+        assert_eq!(a.clone().into_iter().sorted().collect_vec(), start);
+        assert_eq!(b.into_iter().sorted().collect_vec(), start);
+        a.step(4);
+        assert_ne!(a.into_iter().sorted().collect_vec(), start);
+    }
+
+    #[test]
+    fn test_matches_tiled_plane() {
+        // A torus behaves like a plane tiled with copies of it, away from the
+        // edges of the tiling, whatever the background does.
+        let (w, h) = (10isize, 6isize);
+        let soup = (0..h)
+            .cartesian_product(0..w)
+            .filter(|&(y, x)| (y * 7 + x * 3 + y * x) % 5 < 2)
+            .collect_vec();
+        for rule in ["B3/S23", "B36/S23", "B013/S0125", "B0/S8"] {
+            let mut torus = HashLife::with_rule(rule.parse().unwrap());
+            torus.set_topology(Topology::Torus {
+                w: w as usize,
+                h: h as usize,
+            });
+            torus.set_cells(soup.iter().copied());
+            let mut plane = HashLife::with_rule(rule.parse().unwrap());
+            let copies = (-4..4).cartesian_product(-4..4);
+            plane.set_cells(
+                copies
+                    .flat_map(|(ky, kx)| soup.iter().map(move |&(y, x)| (y + ky * h, x + kx * w))),
+            );
+            for log2_steps in [0, 3, 0] {
+                torus.step(log2_steps);
+                plane.step(log2_steps);
+                let alive = |p: &(isize, isize)| plane.get(*p) != plane.is_inverted();
+                let expected = (0..h).cartesian_product(0..w).filter(alive).collect_vec();
+                let cells = torus.clone().into_iter().sorted().collect_vec();
+                assert_eq!(cells, expected, "{rule}");
+                assert!(!torus.is_inverted());
+            }
+        }
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let mut a = HashLife::from_str(GLIDER[0]).unwrap();
+        a.set_topology(Topology::Torus { w: 10, h: 12 });
+        let mut bytes = vec![];
+        a.write_snapshot(&mut bytes).unwrap();
+        let b = HashLife::read_snapshot(bytes.as_slice()).unwrap();
+        assert_eq!(b.topology(), a.topology());
+        assert_eq!(b.generation(), a.generation());
+        assert_eq!(a, b);
+    }
+}
+
+mod patterns {
+    use super::*;
+    use crate::patterns;
+
+    #[test]
+    fn test_periods() {
+        let period = |hl: HashLife| {
+            let periodic = hl.detect_period(30).unwrap();
+            (periodic.period, periodic.displacement)
+        };
+        assert_eq!(period(patterns::block()), (1, (0, 0)));
+        assert_eq!(period(patterns::beacon()), (2, (0, 0)));
+        assert_eq!(period(patterns::pulsar()), (3, (0, 0)));
+        assert_eq!(period(patterns::pentadecathlon()), (15, (0, 0)));
+        assert_eq!(period(patterns::glider()), (4, (1, 1)));
+        assert_eq!(period(patterns::lwss()), (4, (0, -2)));
+        assert_eq!(period(patterns::mwss()), (4, (0, -2)));
+        assert_eq!(period(patterns::hwss()), (4, (0, -2)));
+    }
+
+    #[test]
+    fn test_methuselahs() {
+        let mut diehard = patterns::diehard();
+        diehard.step_by(129);
+        assert!(!diehard.is_empty());
+        diehard.step(0);
+        assert!(diehard.is_empty());
+        let mut gun = patterns::gosper_gun();
+        assert_eq!(gun.population(), 36);
+        gun.step_by(120);
+        assert_eq!(gun.population(), 36 + 4 * 5);
+    }
+
+    #[test]
+    fn test_all() {
+        assert_eq!(patterns::ALL.len(), 17);
+        let (name, glider) = patterns::ALL
+            .iter()
+            .find(|(name, _)| *name == "glider")
+            .unwrap();
+        assert_eq!(*name, "glider");
+        assert_eq!(glider(), patterns::glider());
+    }
+    #[test]
+    fn test_lexicon() {
+        let text = "
+LIFE LEXICON
+
+:B-heptomino: (stabilizes at time 148) This is a very common pattern.
+	*.**
+	***.
+	.*..
+
+:block: (p1) The most common still life.
+	**
+	**
+Also see {beehive}.
+
+:Gosper glider gun: The first known gun.
+	........................*...........
+	......................*.*...........
+	............**......**............**
+	...........*...*....**............**
+	**........*.....*...**..............
+	**........*...*.**....*.*...........
+	..........*.....*.......*...........
+	...........*...*....................
+	............**......................
+
+:grin: The following common parent of the {block}.
+	*..*
+	.**.
+
+:gun: A stationary pattern that emits spaceships forever.
+";
+        let mut lexicon = patterns::Lexicon::default();
+        lexicon.load(text).unwrap();
+        assert_eq!(lexicon.get("B-heptomino").unwrap().population(), 7);
+        assert_eq!(lexicon.get("block"), Some(&patterns::block()));
+        assert_eq!(lexicon.get("grin").unwrap().population(), 4);
+        assert_eq!(lexicon.get("gun"), None);
+        assert_eq!(lexicon.get("glider"), Some(&patterns::glider()));
+        let (name, gun) = lexicon.find_pattern("gosper").unwrap();
+        assert_eq!((name, gun), ("gosper gun", &patterns::gosper_gun()));
+        assert_eq!(
+            lexicon.get("Gosper glider gun").unwrap().population(),
+            patterns::gosper_gun().population()
+        );
+        assert_eq!(
+            lexicon.find_pattern("GOSPER GLIDER-GUN").unwrap().0,
+            "Gosper glider gun"
+        );
+        assert_eq!(lexicon.find_pattern("bhept").unwrap().0, "B-heptomino");
+        assert_eq!(lexicon.find_pattern("r pent").unwrap().0, "r pentomino");
+        assert_eq!(lexicon.find_pattern("zzz"), None);
+    }
+}
+
+mod render {
+    use std::f64::consts::{FRAC_PI_2, PI};
+
+    use super::*;
+    use crate::render::{AgeRamp, Animation, Grid, RenderCache, Rgba, Settings, View};
+
+    #[test]
+    fn test_rgba() {
+        let grey = Rgba::WHITE.multiply(0.5);
+        assert_eq!(grey, Rgba::from_rgba_premultiplied(0.5, 0.5, 0.5, 0.5));
+        assert_eq!(
+            Rgba::BLACK.lerp(Rgba::WHITE, 0.5),
+            Rgba::from_rgb(0.5, 0.5, 0.5)
+        );
+        // Opaque colours hide what's under them, transparent ones nothing.
+        assert_eq!(Rgba::WHITE.blend(Rgba::BLACK), Rgba::BLACK);
+        assert_eq!(Rgba::BLACK.blend(Rgba::TRANSPARENT), Rgba::BLACK);
+        assert_eq!(Rgba::BLACK.blend(grey), Rgba::from_rgb(0.5, 0.5, 0.5));
+        #[cfg(feature = "egui")]
+        for rgba in [grey, Rgba::from_rgba_premultiplied(0.1, 0.2, 0.3, 0.4)] {
+            let egui = egui::Rgba::from(rgba);
+            assert_eq!(
+                egui,
+                egui::Rgba::from_rgba_premultiplied(rgba.r(), rgba.g(), rgba.b(), rgba.a())
+            );
+            assert_eq!(Rgba::from(egui), rgba);
+        }
+    }
+
+    #[test]
+    fn test_rgba8() {
+        assert_eq!(Rgba::WHITE.to_srgba_unmultiplied(), [255; 4]);
+        assert_eq!(Rgba::BLACK.to_srgba_unmultiplied(), [0, 0, 0, 255]);
+        assert_eq!(
+            Rgba::from_rgb(0.5, 0., 1.).to_srgba_unmultiplied(),
+            [188, 0, 255, 255]
+        );
+        assert_eq!(Rgba::TRANSPARENT.to_srgba_unmultiplied(), [0; 4]);
+        let hl = HashLife::from_str("oo\noo").unwrap();
+        let settings = Settings {
+            height: 3,
+            width: 5,
+            cell_size: 1.,
+            dead_rgba: Rgba::WHITE,
+            alive_rgba: Rgba::BLACK,
+            row_offset: 0.,
+            grid: None,
+            ages: None,
+            heatmap: None,
+            envelope: None,
+        };
+        let view = View {
+            zoom: 1.,
+            center: (0., 0.),
+            rotation: 0.,
+        };
+        let bytes = hl.render_rgba8(settings, view);
+        let pixels = hl.render(settings, view);
+        assert_eq!(bytes.len(), 3 * 5 * 4);
+        let expected = pixels.iter().flat_map(|rgba| rgba.to_srgba_unmultiplied());
+        assert_eq!(bytes, expected.collect_vec());
+        assert!(bytes.chunks(4).any(|pixel| pixel == [0, 0, 0, 255]));
+        assert!(bytes.chunks(4).any(|pixel| pixel == [255; 4]));
+        let mut buffer = vec![1; 3 * 5 * 4];
+        hl.render_rgba8_into(settings, view, &mut buffer);
+        assert_eq!(buffer, bytes);
+    }
+
+    /// Renders black on white, as a grid of 'o's for black, '.'s for white
+    /// and '?'s for anything in between.
+    fn draw(
+        hl: &HashLife,
+        (height, width): (usize, usize),
+        zoom: f64,
+        center: (f64, f64),
+    ) -> String {
+        draw_with_grid(hl, (height, width), zoom, center, None)
+    }
+
+    fn draw_with_grid(
+        hl: &HashLife,
+        size: (usize, usize),
+        zoom: f64,
+        center: (f64, f64),
+        grid: Option<Grid>,
+    ) -> String {
+        let settings = Settings {
+            grid,
+            ..black_on_white(size)
+        };
+        let view = View {
+            zoom,
+            center,
+            rotation: 0.,
+        };
+        draw_view(hl, settings, view)
+    }
+
+    fn draw_view(hl: &HashLife, settings: Settings, view: View) -> String {
+        let shade = |rgba: &Rgba| match rgba.r() {
+            0. => 'o',
+            1. => '.',
+            _ => '?',
+        };
+        let pixels = hl.render(settings, view);
+        let mut rows = pixels
+            .chunks(settings.width)
+            .map(|row| row.iter().map(shade).collect::<String>());
+        rows.join("\n")
+    }
+
+    fn black_on_white((height, width): (usize, usize)) -> Settings {
+        Settings {
+            height,
+            width,
+            cell_size: 1.,
+            dead_rgba: Rgba::WHITE,
+            alive_rgba: Rgba::BLACK,
+            row_offset: 0.,
+            grid: None,
+            ages: None,
+            heatmap: None,
+            envelope: None,
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let block = HashLife::from_iter([(0, 0), (0, 1), (1, 0), (1, 1)]);
+        assert_eq!(draw(&block, (4, 4), 1., (1., 1.)), "....\n.oo.\n.oo.\n....");
+        assert_eq!(draw(&block, (4, 4), 2., (1., 1.)), "oooo\noooo\noooo\noooo");
+        assert_eq!(draw(&block, (2, 2), 0.5, (0., 0.)), "..\n.o");
+        // Pixels are shaded by how much of them is covered, so are only fully
+        // alive or dead when lined up with the cells.
+        assert_eq!(draw(&block, (2, 2), 0.5, (1., 1.)), "??\n??");
+        let diagonal = HashLife::from_iter([(0, 0), (1, 1)]);
+        assert_eq!(draw(&diagonal, (2, 2), 0.5, (0., 0.)), "..\n.?");
+        // Too small to see at all, but still drawn as a pixel.
+        assert_eq!(draw(&block, (1, 1), 1e-3, (0., 0.)), "?");
+        assert_eq!(draw(&HashLife::new(), (1, 2), 1., (0., 0.)), "..");
+    }
+
+    #[test]
+    fn test_grid() {
+        let grid = Some(Grid {
+            rgba: Rgba::from_rgb(0.5, 0.5, 0.5),
+            min_pixels_per_cell: 4.,
+        });
+        let hl = HashLife::from_iter([(0, 0)]);
+        let drawn = draw_with_grid(&hl, (8, 8), 4., (0.5, 0.5), grid);
+        let expected = [
+            "..?...?.", "..?...?.", "????????", "..?ooo?.", "..?ooo?.", "..?ooo?.", "????????",
+            "..?...?.",
+        ];
+        assert_eq!(drawn, expected.join("\n"));
+        let drawn = draw_with_grid(&hl, (4, 4), 2., (0.5, 0.5), grid);
+        assert_eq!(drawn, draw(&hl, (4, 4), 2., (0.5, 0.5)));
+    }
+
+    #[test]
+    fn test_render_torus() {
+        let mut hl = HashLife::from_iter([(0, 0)]);
+        hl.set_topology(Topology::Torus { w: 3, h: 2 });
+        let drawn = draw(&hl, (4, 6), 1., (2., 3.));
+        assert_eq!(drawn, "o..o..\n......\no..o..\n......");
+        hl.set_topology(Topology::Torus { w: 2, h: 2 });
+        assert_eq!(draw(&hl, (2, 2), 1e-3, (0., 0.)), "??\n??");
+    }
+
+    #[test]
+    fn test_render_inverted() {
+        let mut hl = HashLife::with_rule("B0123478/S34678".parse().unwrap());
+        hl.set_cells([(0, 0)]);
+        hl.step(0);
+        assert!(hl.is_inverted());
+        let drawn = draw(&hl, (5, 5), 1., (0.5, 0.5));
+        assert_eq!(drawn.matches('.').count(), hl.population() as usize);
+    }
+
+    #[test]
+    fn test_render_ages() {
+        let mut hl = HashLife::from_iter([(0, 0), (0, 1), (1, 0), (1, 1), (5, 5)]);
+        let ramp = AgeRamp {
+            newborn_rgba: Rgba::from_rgb(1., 0., 0.),
+            old_rgba: Rgba::from_rgb(0., 0., 1.),
+            old_age: 4,
+        };
+        let settings = Settings {
+            height: 2,
+            width: 2,
+            cell_size: 1.,
+            dead_rgba: Rgba::WHITE,
+            alive_rgba: Rgba::BLACK,
+            row_offset: 0.,
+            grid: None,
+            ages: Some(ramp),
+            heatmap: None,
+            envelope: None,
+        };
+        let view = |zoom| View {
+            zoom,
+            center: (0., 0.),
+            rotation: 0.,
+        };
+        // Ages aren't drawn unless they're tracked.
+        assert_eq!(hl.render(settings, view(1.))[3], Rgba::BLACK);
+        hl.track_ages(true);
+        hl.step(1);
+        hl.set_cells([(-1, -1), (3, 3)]);
+        let pixels = hl.render(settings, view(1.));
+        // The block is halfway to old, and the cell set by hand newborn.
+        assert_eq!(pixels[0], Rgba::from_rgb(1., 0., 0.));
+        assert_eq!(pixels[3], Rgba::from_rgb(0.5, 0., 0.5));
+        // A pixel covering both averages their ages, weighted by coverage.
+        let pixel = hl.render(settings, view(0.25))[3];
+        let expected = Rgba::WHITE.blend(Rgba::from_rgb(0.6, 0., 0.4).multiply(5. / 16.));
+        assert!(same_pixels(&[pixel], &[expected]));
+    }
+
+    #[test]
+    fn test_render_heatmap() {
+        let mut hl = HashLife::from_iter([(0, 0), (0, 1), (0, 2)]);
+        hl.track_activity(2);
+        hl.step(0);
+        let settings = Settings {
+            height: 3,
+            width: 3,
+            cell_size: 1.,
+            dead_rgba: Rgba::WHITE,
+            alive_rgba: Rgba::BLACK,
+            row_offset: 0.,
+            grid: None,
+            ages: None,
+            heatmap: Some(Rgba::BLACK),
+            envelope: None,
+        };
+        let view = View {
+            zoom: 1.,
+            center: (0.5, 1.5),
+            rotation: 0.,
+        };
+        let grey = Rgba::from_rgb(0.5, 0.5, 0.5);
+        let (w, g) = (Rgba::WHITE, grey);
+        assert_eq!(hl.render(settings, view), [w, g, w, g, w, g, w, g, w]);
+        hl.step(0);
+        let pixels = hl.render(settings, view);
+        assert_eq!(pixels[1], Rgba::BLACK);
+        assert_eq!(pixels[4], Rgba::WHITE);
+    }
+
+    #[test]
+    fn test_render_envelope() {
+        let mut hl = HashLife::from_iter([(0, 0), (0, 1), (0, 2)]);
+        let grey = Rgba::from_rgb(0.5, 0.5, 0.5);
+        let settings = Settings {
+            envelope: Some(Rgba::BLACK.multiply(0.5)),
+            ..black_on_white((3, 3))
+        };
+        let view = View {
+            zoom: 1.,
+            center: (0.5, 1.5),
+            rotation: 0.,
+        };
+        // Nothing is drawn until the envelope is tracked.
+        assert_eq!(draw_view(&hl, settings, view), "...\nooo\n...");
+        hl.track_envelope(true);
+        hl.step(0);
+        let (w, g, b) = (Rgba::WHITE, grey, Rgba::BLACK);
+        assert_eq!(hl.render(settings, view), [w, b, w, g, b, g, w, b, w]);
+    }
+
+    #[test]
+    fn test_view() {
+        let mut settings = Settings {
+            height: 4,
+            width: 6,
+            cell_size: 1.,
+            dead_rgba: Rgba::WHITE,
+            alive_rgba: Rgba::BLACK,
+            row_offset: 0.,
+            grid: None,
+            ages: None,
+            heatmap: None,
+            envelope: None,
+        };
+        let view = View {
+            zoom: 2.,
+            center: (1., 1.),
+            rotation: 0.,
+        };
+        assert_eq!(view.screen_to_cell((0., 0.), settings), (0., -0.5));
+        assert_eq!(view.screen_to_cell((3., 5.), settings), (1.5, 2.));
+        assert_eq!(view.cell_to_screen((1.5, 2.), settings), (3., 5.));
+        settings.row_offset = 0.5;
+        for p in [(0., 0.), (3., 5.), (-7.25, 100.)] {
+            let (y, x) = view.cell_to_screen(view.screen_to_cell(p, settings), settings);
+            assert!((y - p.0).abs() < 1e-9 && (x - p.1).abs() < 1e-9);
+        }
+        // Cells to the right on lower rows.
+        let (_, x) = view.cell_to_screen((2., 0.), settings);
+        assert_eq!(view.cell_to_screen((0., 0.), settings).1 + 2., x);
+
+        settings.row_offset = 0.;
+        (settings.height, settings.width) = (10, 20);
+        let view = View::fit((0..4, -2..6), settings);
+        assert_eq!((view.zoom, view.center), (2.5, (2., 2.)));
+        assert_eq!(view.cell_to_screen((0., -2.), settings), (0., 0.));
+        assert_eq!(view.cell_to_screen((4., 6.), settings), (10., 20.));
+        settings.row_offset = -0.5;
+        let view = View::fit((0..4, -2..6), settings);
+        for corner in [(0., -2.), (0., 6.), (4., -2.), (4., 6.)] {
+            let (y, x) = view.cell_to_screen(corner, settings);
+            assert!((-1e-9..=10. + 1e-9).contains(&y) && (-1e-9..=20. + 1e-9).contains(&x));
+        }
+    }
+
+    #[test]
+    fn test_render_rotated() {
+        let hl = HashLife::from_iter([(0, 0), (0, 1), (0, 2), (1, 2)]);
+        let settings = black_on_white((5, 5));
+        let view = |rotation| View {
+            zoom: 1.,
+            center: (0.5, 1.5),
+            rotation,
+        };
+        let turned = [".....", "..o..", "..o..", ".oo..", "....."];
+        assert_eq!(draw_view(&hl, settings, view(FRAC_PI_2)), turned.join("\n"));
+        let upside_down = [".....", ".o...", ".ooo.", ".....", "....."];
+        assert_eq!(draw_view(&hl, settings, view(PI)), upside_down.join("\n"));
+        assert_eq!(
+            draw_view(&hl, settings, view(2. * PI)),
+            draw_view(&hl, settings, view(0.))
+        );
+        let p = (1.25, -3.5);
+        let (y, x) = view(1.).cell_to_screen(view(1.).screen_to_cell(p, settings), settings);
+        assert!((y - p.0).abs() < 1e-9 && (x - p.1).abs() < 1e-9);
+        // Zoomed out, pixels take the density of the node under their centre.
+        let block = HashLife::from_iter([(0, 0), (0, 1), (1, 0), (1, 1)]);
+        let view = View {
+            zoom: 0.5,
+            center: (0., 0.),
+            rotation: PI,
+        };
+        assert_eq!(draw_view(&block, black_on_white((2, 2)), view), "o.\n..");
+    }
+
+    /// The kind and data of each chunk of a PNG.
+    fn png_chunks(png: &[u8]) -> Vec<(&[u8], &[u8])> {
+        let mut chunks = vec![];
+        let mut rest = &png[8..];
+        while !rest.is_empty() {
+            let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            chunks.push((&rest[4..8], &rest[8..8 + len]));
+            rest = &rest[12 + len..];
+        }
+        chunks
+    }
+
+    /// Reads a zlib stream of stored deflate blocks, each with a header and
+    /// length.
+    fn inflate_stored(zlib: &[u8]) -> Vec<u8> {
+        let mut stream = &zlib[2..];
+        let mut data = vec![];
+        loop {
+            let len = u16::from_le_bytes([stream[1], stream[2]]) as usize;
+            data.extend(&stream[5..5 + len]);
+            if stream[0] == 1 {
+                return data;
+            }
+            stream = &stream[5 + len..];
+        }
+    }
+
+    #[test]
+    fn test_png() {
+        let hl = HashLife::from_iter([(0, 0), (1, 1), (2, 2)]);
+        let settings = black_on_white((300, 200));
+        let view = View {
+            zoom: 37.,
+            center: (1.5, 1.5),
+            rotation: 0.,
+        };
+        let mut png = vec![];
+        crate::render::write_png(&hl, settings, view, &mut png).unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        let chunks = png_chunks(&png);
+        let kinds = chunks.iter().map(|&(kind, _)| kind).collect_vec();
+        assert_eq!(kinds, [b"IHDR", b"IDAT", b"IEND"]);
+        assert_eq!(chunks[0].1, [0, 0, 0, 200, 0, 0, 1, 44, 8, 6, 0, 0, 0]);
+        // The checksum of an IEND chunk is always the same.
+        assert_eq!(&png[png.len() - 4..], [0xae, 0x42, 0x60, 0x82]);
+        let rows = inflate_stored(chunks[1].1);
+        let pixels = hl.render_rgba8(settings, view);
+        assert_eq!(rows.len(), pixels.len() + 300);
+        for (row, expected) in rows.chunks(1 + 200 * 4).zip(pixels.chunks(200 * 4)) {
+            assert_eq!((row[0], &row[1..]), (0, expected));
+        }
+        let empty = black_on_white((0, 10));
+        assert!(crate::render::write_png(&hl, empty, view, &mut vec![]).is_err());
+    }
+
+    #[test]
+    fn test_apng() {
+        let hl = HashLife::from_iter([(0, 0), (0, 1), (0, 2)]);
+        let settings = black_on_white((3, 3));
+        let view = View {
+            zoom: 1.,
+            center: (0.5, 1.5),
+            rotation: 0.,
+        };
+        let animation = Animation {
+            frames: 3,
+            delay: 250,
+            ..Animation::default()
+        };
+        let mut png = vec![];
+        crate::render::write_apng(&hl, settings, view, animation, &mut png).unwrap();
+        let chunks = png_chunks(&png);
+        let kinds = chunks.iter().map(|&(kind, _)| kind).collect_vec();
+        let expected: [&[u8]; 9] = [
+            b"IHDR", b"acTL", b"fcTL", b"IDAT", b"fcTL", b"fdAT", b"fcTL", b"fdAT", b"IEND",
+        ];
+        assert_eq!(kinds, expected);
+        assert_eq!(chunks[1].1, [0, 0, 0, 3, 0, 0, 0, 0]);
+        let control = chunks[2].1;
+        assert_eq!(control[..12], [0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0, 3]);
+        assert_eq!(control[20..], [0, 250, 3, 232, 0, 0]);
+        let sequence = |data: &[u8]| u32::from_be_bytes(data[..4].try_into().unwrap());
+        let sequences = [2, 4, 5, 6, 7].map(|k| sequence(chunks[k].1));
+        assert_eq!(sequences, [0, 1, 2, 3, 4]);
+        let frames = [chunks[3].1, &chunks[5].1[4..], &chunks[7].1[4..]].map(inflate_stored);
+        let mut stepped = hl.clone();
+        stepped.step(0);
+        let render = |hl: &HashLife| {
+            let pixels = hl.render_rgba8(settings, view);
+            pixels
+                .chunks(12)
+                .flat_map(|row| [&[0][..], row].concat())
+                .collect_vec()
+        };
+        assert_eq!(frames, [render(&hl), render(&stepped), render(&hl)]);
+        let still = Animation {
+            frames: 0,
+            ..animation
+        };
+        assert!(crate::render::write_apng(&hl, settings, view, still, &mut vec![]).is_err());
+    }
+
+    #[test]
+    fn test_from_image() {
+        let glider = HashLife::from_iter([(0, 1), (1, 2), (2, 0), (2, 1), (2, 2)]);
+        let cells = |hl: &HashLife| hl.iter_cells().sorted().collect_vec();
+
+        // Four pixels to a cell, stored without compression.
+        let view = View {
+            zoom: 4.,
+            center: (1.5, 1.5),
+            rotation: 0.,
+        };
+        let mut png = vec![];
+        crate::render::write_png(&glider, black_on_white((12, 12)), view, &mut png).unwrap();
+        let scaled = HashLife::from_image_scaled(&png, 0.5, 4).unwrap();
+        assert_eq!(cells(&scaled), cells(&glider));
+        assert_eq!(
+            HashLife::from_image(&png, 0.5).unwrap().population(),
+            5 * 16
+        );
+        assert_eq!(HashLife::from_image(&png, 0.).unwrap().population(), 0);
+
+        // Greyscale, with the rows filtered and compressed with fixed codes.
+        let png = [
+            137, 80, 78, 71, 13, 10, 26, 10, 0, 0, 0, 13, 73, 72, 68, 82, 0, 0, 0, 4, 0, 0, 0, 3,
+            8, 0, 0, 0, 0, 145, 159, 241, 26, 0, 0, 0, 23, 73, 68, 65, 84, 120, 218, 99, 248, 207,
+            240, 255, 63, 227, 127, 6, 198, 255, 76, 140, 140, 12, 12, 0, 51, 253, 5, 2, 4, 173,
+            196, 96, 0, 0, 0, 0, 73, 69, 78, 68, 174, 66, 96, 130,
+        ];
+        assert_eq!(
+            cells(&HashLife::from_image(&png, 0.5).unwrap()),
+            cells(&glider)
+        );
+        assert!(HashLife::from_image(&png[..40], 0.5).is_err());
+
+        // 24 bit, bottom row first, each padded to four bytes.
+        let mut bmp = b"BM".to_vec();
+        bmp.extend([0; 8].iter().chain(&54u32.to_le_bytes()));
+        // The header's length, width and height, one plane of 24 bits, and
+        // no compression.
+        bmp.extend([40, 3, 3].map(u32::to_le_bytes).concat());
+        bmp.extend([1, 0, 24, 0]);
+        bmp.extend([0; 24]);
+        for row in ["ooo", "  o", " o "] {
+            for c in row.chars() {
+                bmp.extend([if c == 'o' { 0 } else { 255 }; 3]);
+            }
+            bmp.extend([0; 3]);
+        }
+        assert_eq!(
+            cells(&HashLife::from_image(&bmp, 0.5).unwrap()),
+            cells(&glider)
+        );
+
+        assert!(HashLife::from_image(b"GIF89a", 0.5).is_err());
+        assert!(HashLife::from_image_scaled(&bmp, 0.5, 0).is_err());
+    }
+
+    #[test]
+    fn test_terminal() {
+        use crate::render::terminal::{self, Glyphs, Style};
+
+        let block = HashLife::from_iter([(0, 0), (0, 1), (1, 0), (1, 1)]);
+        let view = View {
+            zoom: 1.,
+            center: (1., 1.),
+            rotation: 0.,
+        };
+        let style = |glyphs, truecolor| Style { glyphs, truecolor };
+        let half_blocks = style(Glyphs::HalfBlocks, false);
+        let drawn = terminal::render(&block, black_on_white((2, 4)), view, half_blocks);
+        assert_eq!(drawn, " ▄▄ \n ▀▀ ");
+        let braille = style(Glyphs::Braille, false);
+        let drawn = terminal::render(&block, black_on_white((1, 2)), view, braille);
+        assert_eq!(drawn, "⠰⠆");
+        let view = View {
+            center: (0., 0.5),
+            ..view
+        };
+        let colour = style(Glyphs::HalfBlocks, true);
+        let drawn = terminal::render(&block, black_on_white((1, 1)), view, colour);
+        assert_eq!(drawn, "\x1b[38;2;255;255;255m\x1b[48;2;0;0;0m▀\x1b[0m");
+        let glider = BasicState::from_iter(crate::patterns::glider().iter_cells());
+        let drawn = terminal::render_engine(&glider, (-1, -2), 3, 4, Glyphs::Blocks);
+        assert_eq!(drawn, "  █ \n   █\n ███");
+        let drawn = terminal::render_engine(&glider, (-1, -1), 2, 3, Glyphs::HalfBlocks);
+        assert_eq!(drawn, " ▀▄\n▀▀▀");
+    }
+
+    #[test]
+    fn test_space_time() {
+        let hl = HashLife::from_iter([(0, 0), (0, 1), (0, 2), (5, 3)]);
+        let space_time = hl.space_time(0..1, -1..4, 2);
+        assert_eq!(space_time.len(), 3);
+        let grid = space_time.to_grid();
+        let draw = |grid: &crate::BitGrid| {
+            let row = |t| (0..5).map(move |x| if grid.get(t, x) { 'o' } else { '.' });
+            (0..grid.height())
+                .map(|t| row(t).collect::<String>())
+                .join("\n")
+        };
+        assert_eq!(draw(&grid), ".ooo.\n..o..\n.ooo.");
+        // Slices of more than one row merge them.
+        assert_eq!(draw(&hl.space_time(0..6, -1..4, 0).to_grid()), ".oooo");
+        let mut png = vec![];
+        space_time
+            .write_png(Rgba::WHITE, Rgba::BLACK, &mut png)
+            .unwrap();
+        let chunks = png_chunks(&png);
+        assert_eq!(chunks[0].1[..8], [0, 0, 0, 5, 0, 0, 0, 3]);
+        let data = inflate_stored(chunks[1].1);
+        assert_eq!(data[1..5], [255; 4]);
+        assert_eq!(data[5..9], [0, 0, 0, 255]);
+    }
+
+    /// Whether two renders match, up to rounding from adding up coverage in a
+    /// different order.
+    fn same_pixels(a: &[Rgba], b: &[Rgba]) -> bool {
+        let close = |a: &Rgba, b: &Rgba| (0..4).all(|k| (a.0[k] - b.0[k]).abs() < 1e-4);
+        a.len() == b.len() && a.iter().zip(b).all(|(a, b)| close(a, b))
+    }
+
+    #[test]
+    fn test_render_cached() {
+        let mut hl = crate::patterns::gosper_gun();
+        hl.step_by(300);
+        let mut cache = RenderCache::default();
+        let views = [
+            (1., (0., 0.)),
+            (3.5, (10.3, -7.9)),
+            (0.3, (40., 40.)),
+            (0.01, (0., 0.)),
+        ];
+        for ((zoom, center), row_offset) in views.into_iter().cartesian_product([0., -0.5]) {
+            let settings = Settings {
+                height: 90,
+                width: 160,
+                cell_size: 1.,
+                dead_rgba: Rgba::WHITE,
+                alive_rgba: Rgba::BLACK,
+                row_offset,
+                grid: None,
+                ages: None,
+                heatmap: None,
+                envelope: None,
+            };
+            let view = View {
+                zoom,
+                center,
+                rotation: 0.,
+            };
+            let expected = hl.render(settings, view);
+            let rendered = hl.render_cached(settings, view, &mut cache);
+            assert!(same_pixels(&rendered, &expected));
+            let tiles = cache.len();
+            let rendered = hl.render_cached(settings, view, &mut cache);
+            assert!(same_pixels(&rendered, &expected));
+            assert_eq!(cache.len(), tiles);
+        }
+        assert!(!cache.is_empty());
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_render_cached_after_collecting() {
+        let mut hl = HashLife::new();
+        let empty = hl.snapshot();
+        hl.set_cells((0..64).map(|k| (k, k * k % 64)));
+        hl.restore(&empty).unwrap();
+        hl.paste(&crate::patterns::gosper_gun(), (20, 20));
+        let settings = black_on_white((64, 64));
+        let view = View {
+            zoom: 0.5,
+            center: (32., 32.),
+            rotation: 0.,
+        };
+        let mut cache = RenderCache::default();
+        hl.render_cached(settings, view, &mut cache);
+        let tiles = cache.len();
+        // The nodes the tiles are kept by move down into the garbage's place,
+        // so the cache starts over rather than mixing them up.
+        hl.collect_garbage();
+        let rendered = hl.render_cached(settings, view, &mut cache);
+        assert!(same_pixels(&rendered, &hl.render(settings, view)));
+        assert_eq!(cache.len(), tiles);
+    }
+
+    #[test]
+    fn test_render_incremental() {
+        let mut hl = HashLife::new();
+        let mut glider = crate::patterns::glider();
+        glider.translate(100, 100);
+        for at in [(0, 0), (0, 200), (200, 0), (200, 200)] {
+            hl.paste(&crate::patterns::block(), at);
+        }
+        hl.paste(&glider, (0, 0));
+        let settings = Settings {
+            height: 256,
+            width: 256,
+            cell_size: 1.,
+            dead_rgba: Rgba::WHITE,
+            alive_rgba: Rgba::BLACK,
+            row_offset: 0.,
+            grid: None,
+            ages: None,
+            heatmap: None,
+            envelope: None,
+        };
+        let view = View {
+            zoom: 1.,
+            center: (100., 100.),
+            rotation: 0.,
+        };
+        let mut cache = RenderCache::default();
+        assert!(same_pixels(
+            &hl.render_cached(settings, view, &mut cache),
+            &hl.render(settings, view)
+        ));
+        let tiles = cache.redrawn();
+        assert!(tiles >= 5);
+        // Only the glider's tile changes, until it crosses into the next one.
+        for _ in 0..40 {
+            hl.step(0);
+            assert!(same_pixels(
+                &hl.render_cached(settings, view, &mut cache),
+                &hl.render(settings, view)
+            ));
+            assert!(cache.redrawn() <= 2);
+        }
+        let view = View {
+            zoom: 1.,
+            center: (90., 100.),
+            rotation: 0.,
+        };
+        assert!(same_pixels(
+            &hl.render_cached(settings, view, &mut cache),
+            &hl.render(settings, view)
+        ));
+        assert_eq!(cache.redrawn(), tiles);
+    }
+}
+
+#[cfg(feature = "ffi")]
+mod ffi {
+    use std::ptr;
+
+    use crate::ffi::*;
+
+    #[test]
+    fn test_ffi() {
+        unsafe {
+            let hl = hl_from_rle(c"x = 3, y = 3\nbo$2bo$3o!".as_ptr());
+            assert!(!hl.is_null());
+            assert_eq!(hl_population(hl), 5);
+            assert!(hl_step(hl, 4));
+            assert_eq!(hl_generation(hl), 4);
+            assert!(hl_get_cell(hl, 1, 2));
+            assert!(hl_set_cell(hl, 1, 2, false));
+            assert!(!hl_get_cell(hl, 1, 2));
+            assert_eq!(hl_population(hl), 4);
+
+            let mut pixels = vec![0; 8 * 8 * 4];
+            assert!(hl_render_rgba(hl, pixels.as_mut_ptr(), 8, 8, 4., 4., 1.));
+            let black = pixels.chunks(4).filter(|p| p == &[0, 0, 0, 255]).count();
+            assert_eq!(black, 4);
+            // Sizes whose bytes can't be counted are turned away unread.
+            let far = usize::MAX / 2;
+            assert!(!hl_render_rgba(hl, pixels.as_mut_ptr(), far, 3, 4., 4., 1.));
+            assert!(!hl_render_rgba(hl, ptr::null_mut(), 8, 8, 4., 4., 1.));
+            // Cells too far out are refused rather than panicking.
+            assert!(!hl_set_cell(hl, isize::MAX, isize::MIN, true));
+            assert!(!hl_set_cell(hl, 1 << 61, 0, true));
+            assert!(hl_set_cell(hl, 1 << 61, 0, false));
+            assert_eq!(hl_population(hl), 4);
+            hl_free(hl);
+
+            assert!(hl_from_rle(c"not a pattern".as_ptr()).is_null());
+            assert!(hl_from_rle(ptr::null()).is_null());
+            assert!(!hl_step(ptr::null_mut(), 1));
+            assert!(!hl_set_cell(ptr::null_mut(), 0, 0, true));
+            assert_eq!(hl_population(ptr::null()), 0);
+            hl_free(ptr::null_mut());
+
+            let hl = hl_new();
+            assert!(hl_set_cell(hl, -3, 7, true));
+            assert!(hl_step(hl, 1));
+            assert_eq!(hl_population(hl), 0);
+            hl_free(hl);
+        }
     }
 }
@@ -0,0 +1,123 @@
+use crate::{Diff, HashLife, gc::Snapshot};
+
+/// A tree of states of a pattern, for exploring what happens after
+/// different changes to it. Every branch is a snapshot of one pattern, so
+/// they share a universe and the steps cached in it.
+#[derive(Clone, Debug)]
+pub struct Timeline {
+    hl: HashLife,
+    /// Indexed by `Branch`, with pruned branches gone.
+    branches: Vec<Option<Node>>,
+}
+
+/// A branch of a timeline. Methods given a pruned branch panic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Branch(usize);
+
+#[derive(Clone, Debug)]
+struct Node {
+    snapshot: Snapshot,
+    parent: Option<Branch>,
+}
+
+impl Timeline {
+    /// Starts a timeline with the pattern as it is now as its root.
+    pub fn new(mut hl: HashLife) -> Self {
+        hl.track_history(None);
+        hl.track_rewind(0, 0);
+        let snapshot = hl.snapshot();
+        Self {
+            hl,
+            branches: vec![Some(Node {
+                snapshot,
+                parent: None,
+            })],
+        }
+    }
+
+    pub fn root(&self) -> Branch {
+        Branch(0)
+    }
+
+    pub fn parent(&self, branch: Branch) -> Option<Branch> {
+        self.node(branch).parent
+    }
+
+    /// The branches forked from `branch`, oldest first.
+    pub fn children(&self, branch: Branch) -> Vec<Branch> {
+        self.branches()
+            .filter(|&child| self.parent(child) == Some(branch))
+            .collect()
+    }
+
+    /// The branches not pruned, oldest first.
+    pub fn branches(&self) -> impl Iterator<Item = Branch> + '_ {
+        let branches = self.branches.iter().enumerate();
+        branches.filter_map(|(i, node)| node.as_ref().map(|_| Branch(i)))
+    }
+
+    /// The pattern on a branch. Copies share the timeline's universe, until
+    /// garbage collection gives one of them its own.
+    pub fn pattern(&mut self, branch: Branch) -> HashLife {
+        self.checkout(branch);
+        self.hl.clone()
+    }
+
+    /// Starts a new branch from the state of `branch`, changed by `edit`.
+    pub fn fork(&mut self, branch: Branch, edit: impl FnOnce(&mut HashLife)) -> Branch {
+        self.checkout(branch);
+        edit(&mut self.hl);
+        let snapshot = self.hl.snapshot();
+        self.branches.push(Some(Node {
+            snapshot,
+            parent: Some(branch),
+        }));
+        Branch(self.branches.len() - 1)
+    }
+
+    /// Changes a branch in place, such as by stepping it.
+    pub fn update<T>(&mut self, branch: Branch, f: impl FnOnce(&mut HashLife) -> T) -> T {
+        self.checkout(branch);
+        let out = f(&mut self.hl);
+        let snapshot = self.hl.snapshot();
+        let node = self.branches[branch.0].as_mut().unwrap();
+        let before = std::mem::replace(&mut node.snapshot, snapshot);
+        self.hl.release(&before);
+        out
+    }
+
+    pub fn step(&mut self, branch: Branch, log2_steps: usize) {
+        self.update(branch, |hl| hl.step(log2_steps));
+    }
+
+    pub fn step_by(&mut self, branch: Branch, steps: u64) {
+        self.update(branch, |hl| hl.step_by(steps));
+    }
+
+    /// How the pattern on `b` differs from that on `a`.
+    pub fn diff(&mut self, a: Branch, b: Branch) -> Diff {
+        let a = self.pattern(a);
+        a.diff(&self.pattern(b))
+    }
+
+    /// Drops a branch and every branch forked from it, letting garbage
+    /// collection free what only they used.
+    pub fn prune(&mut self, branch: Branch) {
+        for child in self.children(branch) {
+            self.prune(child);
+        }
+        let node = self.branches[branch.0]
+            .take()
+            .expect("Branch already pruned");
+        self.hl.release(&node.snapshot);
+    }
+
+    fn node(&self, branch: Branch) -> &Node {
+        self.branches[branch.0].as_ref().expect("Branch was pruned")
+    }
+
+    fn checkout(&mut self, branch: Branch) {
+        let snapshot = self.node(branch).snapshot.clone();
+        self.hl.restore(&snapshot).unwrap();
+    }
+}
@@ -0,0 +1,119 @@
+use crate::{HashLife, fx::FxHashMap, ops::Op, p3::P3, universe::TreeRef};
+
+/// The shape of the universe cells live on.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Topology {
+    /// An unbounded plane.
+    #[default]
+    Plane,
+    /// A w x h rectangle of cells from (0, 0) to (h - 1, w - 1), whose edges
+    /// wrap round to the opposite side.
+    Torus { w: usize, h: usize },
+}
+
+impl Topology {
+    /// Maps a cell onto the universe.
+    pub fn wrap(self, (y, x): (isize, isize)) -> (isize, isize) {
+        match self {
+            Topology::Plane => (y, x),
+            Topology::Torus { w, h } => (y.rem_euclid(h as isize), x.rem_euclid(w as isize)),
+        }
+    }
+}
+
+impl HashLife {
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    /// Changes the topology. Cells outside a torus are wrapped back onto it.
+    pub fn set_topology(&mut self, topology: Topology) {
+        let cells = self.cells();
+        (self.root, self.depth, self.topology) = (TreeRef::EMPTY, 0, topology);
+        self.set_states(cells);
+    }
+
+    /// Steps a torus forward by surrounding it with copies of itself, stepping
+    /// that as a plane, and cutting the torus back out. The copies are pasted
+    /// and cut node by node, so stepping shares the cache like a plane does.
+    /// They only need to be as wide as the furthest a cell can affect in one
+    /// step, so big steps are broken up into steps no further than the torus
+    /// is wide.
+    pub(crate) fn step_torus(
+        &mut self,
+        log2_steps: usize,
+        w: usize,
+        h: usize,
+        stop: &dyn Fn() -> bool,
+    ) -> bool {
+        let r = self.rule().radius();
+        let log2_chunk = ((w.min(h) / r).max(1).ilog2() as usize).min(log2_steps);
+        let pad = (r << log2_chunk) as isize;
+        let (w, h) = (w as isize, h as isize);
+        for _ in 0..1usize << (log2_steps - log2_chunk) {
+            // The copies reach `pad` cells past each edge, and the background
+            // beyond them is too far away to matter.
+            let copies = |n: isize| {
+                let k = (pad + n - 1) / n;
+                -k..=k
+            };
+            let mut tiles = vec![];
+            for ky in copies(h) {
+                for kx in copies(w) {
+                    let shift = (ky * h, kx * w);
+                    tiles.push(self.universe.lock().translate(self.root, self.depth, shift));
+                }
+            }
+            let depth = tiles.iter().map(|&(_, depth)| depth).max().unwrap();
+            let mut cache = FxHashMap::default();
+            let mut root = TreeRef::EMPTY;
+            for (mut tile, tile_depth) in tiles {
+                for _ in tile_depth..depth {
+                    tile = self.universe.lock().expand_universe(tile);
+                }
+                root = self
+                    .universe
+                    .lock()
+                    .merge(root, tile, depth, Op::Union, &mut cache);
+            }
+            let padded = (&(-pad..h + pad), &(-pad..w + pad));
+            self.root = self
+                .universe
+                .lock()
+                .clip(root, P3::origin(depth), padded, true);
+            self.depth = depth;
+            self.topology = Topology::Plane;
+            if !self.step_plane(log2_chunk, stop) {
+                return false;
+            }
+            self.topology = Topology::Torus {
+                w: w as usize,
+                h: h as usize,
+            };
+            self.crop_torus(w, h);
+        }
+        true
+    }
+
+    /// Cuts the torus out of the plane it was stepped on, flipping the cells
+    /// back if they're stored inverted, as a torus has no background.
+    fn crop_torus(&mut self, w: isize, h: isize) {
+        let torus = (&(0..h), &(0..w));
+        if self.inverted {
+            while !P3::new(h - 1, w - 1, self.depth).within_tree() {
+                self.expand();
+            }
+            let full = self.universe.lock().full(self.depth);
+            let mut cache = FxHashMap::default();
+            let depth = self.depth;
+            self.root = self
+                .universe
+                .lock()
+                .merge(full, self.root, depth, Op::Xor, &mut cache);
+            self.inverted = false;
+        }
+        let p = P3::origin(self.depth);
+        self.root = self.universe.lock().clip(self.root, p, torus, true);
+        self.trim();
+    }
+}
@@ -1,18 +1,48 @@
-use std::collections::HashMap;
+use std::{
+    ops::Range,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
 
-use crate::p3::P3;
+use crate::{
+    fx::FxHashMap,
+    ops::{Op, Transform},
+    p3::P3,
+    rule::Rule,
+};
+
+/// How many nodes to step between checking whether to stop.
+const POLL_INTERVAL: u32 = 1024;
+
+/// How many nodes a universe can hold. Indices are 32 bits to keep nodes
+/// small, which is plenty, as 2^32 nodes would take hundreds of gigabytes.
+const MAX_NODES: usize = u32::MAX as usize;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
-pub struct TreeRef(usize);
+pub struct TreeRef(u32);
 
 impl TreeRef {
     pub const EMPTY: TreeRef = TreeRef(0);
+
+    /// The node at index `i`, which must be below `MAX_NODES`.
+    fn at(i: usize) -> TreeRef {
+        debug_assert!(i < MAX_NODES);
+        TreeRef(i as u32)
+    }
+
+    fn index(self) -> usize {
+        self.0 as usize
+    }
 }
 
+/// A node. Leaves are single cells, whatever the rule, since cells can have
+/// up to 255 states and everything that walks the tree goes down to them.
+/// Two state rules only pack 8x8 nodes into bitboards to step them, in
+/// `l3_gen`, so stepping is faster but nodes take as much room as ever.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 enum Tree {
     Empty,
-    Alive,
+    /// A cell in a non-dead state.
+    Cell(u8),
     Branch([TreeRef; 4]),
 }
 
@@ -21,30 +51,233 @@ impl Tree {
         match self {
             Tree::Branch(subtree) => subtree == &[TreeRef::EMPTY; 4],
             Tree::Empty => true,
-            Tree::Alive => false,
+            Tree::Cell(_) => false,
         }
     }
 }
 
 #[derive(Clone, Debug)]
 pub struct Universe {
+    rule: Rule,
+    /// The next background after a dead or alive one.
+    next_background: [bool; 2],
     nodes: Vec<Tree>,
     populations: Vec<usize>,
-    next_gen: HashMap<(TreeRef, bool), TreeRef>,
-    interned_nodes: HashMap<Tree, TreeRef>,
+    /// Keyed by the node, the log2 of how many generations it was stepped, and
+    /// whether the background it was stepped on was alive, so that steps of
+    /// different sizes share the cache. Each result also has the last step it
+    /// was used in.
+    next_gen: FxHashMap<(TreeRef, u8, bool), (TreeRef, u64)>,
+    /// Each node by its contents, so equal nodes are stored once. This is one
+    /// map rather than shards with a lock each: patterns sharing the universe
+    /// take turns through `Shared`'s lock, and nodes are numbered by their
+    /// place in `nodes`, so two threads could never intern at once anyway.
+    interned_nodes: FxHashMap<Tree, TreeRef>,
+    /// How many times garbage has been collected, renumbering the nodes.
+    collections: u64,
+    /// How many steps have been taken.
+    steps: u64,
+    config: UniverseConfig,
+    /// How often stepping a node found it cached, or had to work it out.
+    hits: u64,
+    misses: u64,
+    /// The next generation of the center 2x2 of each 4x4 bitmask laid out as
+    /// in `make_l2_bitmask`, packed nw, ne, sw, se from bit 3 down. Made on
+    /// the first step of a rule with two states and a radius of 1.
+    l2_table: Option<Arc<[u8]>>,
+    /// Which counts of live neighbours bring a dead cell alive and keep a live
+    /// one alive, a bit per count, if that's all the rule goes by. Made with
+    /// the table.
+    totalistic: Option<[u16; 2]>,
+    /// How many nodes there's room for, which is only ever less than
+    /// `MAX_NODES` in tests.
+    capacity: usize,
+    /// Whether a node was left out since `take_full`, as there was no room
+    /// for it. Steps stop as soon as it's set.
+    full: bool,
+}
+
+/// What a universe holds and how well its cache is doing.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct UniverseStats {
+    /// Nodes stored, whether in use or not.
+    pub nodes: usize,
+    /// Entries in the table used to share equal nodes.
+    pub interned: usize,
+    /// Steps of nodes cached.
+    pub cache_entries: usize,
+    /// How many times stepping a node found it cached, ever.
+    pub cache_hits: u64,
+    /// How many times stepping a node had to work it out, ever.
+    pub cache_misses: u64,
+    /// Roughly how much memory the tables take, going by their capacity.
+    pub bytes: usize,
+}
+
+/// Limits on how much a universe remembers. Stepping big patterns for a long
+/// time otherwise uses ever more memory.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct UniverseConfig {
+    /// How many nodes to hold before collecting garbage, and if that's not
+    /// enough, forgetting the steps used least recently so that the nodes
+    /// only they need can go too. Nodes in use are never dropped, so this can
+    /// be exceeded by a pattern too big for it, and also part way through a
+    /// step.
+    pub max_nodes: Option<usize>,
+    /// How many steps of nodes to remember. Once there are more, those used
+    /// least recently are forgotten.
+    pub max_cache_entries: Option<usize>,
+}
+
+/// A universe that patterns can share, storing the nodes they have in common
+/// once and caching the steps of each for the others. Copies of a pattern
+/// share its universe. Garbage collection and changing the rule would change
+/// nodes the others use, so give the pattern a universe of its own instead.
+#[derive(Clone, Debug)]
+pub(crate) struct Shared {
+    universe: Arc<Mutex<Universe>>,
+    /// The universe's rule, which only changes along with the universe, kept
+    /// out here to borrow without locking it.
+    rule: Arc<Rule>,
+}
+
+impl Shared {
+    pub(crate) fn new(universe: Universe) -> Self {
+        Self {
+            rule: Arc::new(universe.rule.clone()),
+            universe: Arc::new(Mutex::new(universe)),
+        }
+    }
+
+    pub(crate) fn ptr_eq(&self, other: &Shared) -> bool {
+        Arc::ptr_eq(&self.universe, &other.universe)
+    }
+
+    /// Whether other patterns use the universe too.
+    pub(crate) fn is_shared(&self) -> bool {
+        Arc::strong_count(&self.universe) > 1
+    }
+
+    /// The universe, once no other pattern is using it. A pattern that panicked
+    /// while using it can only have left nodes and steps behind that nothing
+    /// refers to, so the universe is still fine to use.
+    pub(crate) fn lock(&self) -> MutexGuard<'_, Universe> {
+        self.universe.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// This universe and another, which must be a different one, locked
+    /// always in the same order so that two patterns importing from each other
+    /// at once can't each wait for the other.
+    pub(crate) fn lock_with<'a>(
+        &'a self,
+        other: &'a Shared,
+    ) -> (MutexGuard<'a, Universe>, MutexGuard<'a, Universe>) {
+        debug_assert!(!self.ptr_eq(other));
+        if Arc::as_ptr(&self.universe) < Arc::as_ptr(&other.universe) {
+            let universe = self.lock();
+            (universe, other.lock())
+        } else {
+            let other = other.lock();
+            (self.lock(), other)
+        }
+    }
+
+    pub(crate) fn rule(&self) -> &Rule {
+        &self.rule
+    }
+
+    /// Frees the nodes no longer in use, as `Universe::collect_garbage` does.
+    /// Others sharing the universe may still use them, so rather than change
+    /// it, this pattern moves to a universe of its own.
+    pub(crate) fn collect_garbage(
+        &mut self,
+        roots: impl IntoIterator<Item = TreeRef>,
+    ) -> FxHashMap<TreeRef, TreeRef> {
+        let (universe, moved) = self.lock().collect_garbage(roots);
+        self.universe = Arc::new(Mutex::new(universe));
+        moved
+    }
+
+    /// Changes the rule, copying the universe first if others share it.
+    pub(crate) fn set_rule(&mut self, rule: Rule) {
+        if self.is_shared() {
+            let universe = self.lock().clone();
+            *self = Shared::new(universe);
+        }
+        self.lock().set_rule(rule.clone());
+        self.rule = Arc::new(rule);
+    }
 }
 
 impl Universe {
     pub fn new() -> Self {
+        Self::with_rule(Rule::default())
+    }
+
+    pub fn with_rule(rule: Rule) -> Self {
         Self {
+            next_background: next_background(&rule),
+            rule,
             // index 0 must be empty tree
             nodes: vec![Tree::Empty],
             populations: vec![0],
-            next_gen: HashMap::new(),
-            interned_nodes: HashMap::new(),
+            next_gen: FxHashMap::default(),
+            interned_nodes: FxHashMap::default(),
+            collections: 0,
+            steps: 0,
+            config: UniverseConfig::default(),
+            hits: 0,
+            misses: 0,
+            l2_table: None,
+            totalistic: None,
+            capacity: MAX_NODES,
+            full: false,
         }
     }
 
+    /// Whether the background is alive after `gens` generations, starting from
+    /// an alive or dead background. Only rules with B0 can bring it alive.
+    pub fn background_after(&self, alive: bool, gens: usize) -> bool {
+        let next = |alive: bool| self.next_background[alive as usize];
+        match gens {
+            0 => alive,
+            // Any map from bool to bool repeats after two steps.
+            gens if gens % 2 == 1 => next(alive),
+            _ => next(next(alive)),
+        }
+    }
+
+    /// The depth of the smallest node we can step forward a generation, which
+    /// needs a border at least as wide as the rule's radius.
+    pub fn base_depth(&self) -> usize {
+        2 + self.rule.radius().next_power_of_two().trailing_zeros() as usize
+    }
+
+    /// Whether the universe ran out of room for nodes since this was last
+    /// called. Anything built meanwhile had nodes left out.
+    pub(crate) fn take_full(&mut self) -> bool {
+        std::mem::take(&mut self.full)
+    }
+
+    /// Whether the universe has run out of room for nodes since `take_full`.
+    pub(crate) fn is_full(&self) -> bool {
+        self.full
+    }
+
+    #[cfg(test)]
+    pub(crate) fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity.min(MAX_NODES);
+    }
+
+    pub fn set_rule(&mut self, rule: Rule) {
+        // Cached steps were computed under the old rule.
+        self.next_background = next_background(&rule);
+        self.rule = rule;
+        self.next_gen.clear();
+        self.l2_table = None;
+        self.totalistic = None;
+    }
+
     pub fn get_node(&self, mut tr: TreeRef, mut p: P3) -> Option<TreeRef> {
         if !p.within_tree() {
             return None;
@@ -55,7 +288,7 @@ impl Universe {
         Some(tr)
     }
 
-    pub fn set_bit(&mut self, mut tr: TreeRef, mut p: P3) -> TreeRef {
+    pub fn set_state(&mut self, mut tr: TreeRef, mut p: P3, state: u8) -> TreeRef {
         let mut stack = vec![];
         while let Some(i) = p.descend() {
             let subtree = self.subtree(tr);
@@ -65,17 +298,246 @@ impl Universe {
         stack
             .into_iter()
             .rev()
-            .fold(self.canonicalise(Tree::Alive), |tr, (mut subtree, i)| {
+            .fold(self.leaf(state), |tr, (mut subtree, i)| {
                 subtree[i] = tr;
                 self.canonicalise(Tree::Branch(subtree))
             })
     }
 
+    /// Sets the states of many cells at once, building each node that changes
+    /// just the once. `p` is the node's position as in `P3::quadrants`, and
+    /// later cells win over earlier ones at the same position.
+    pub fn set_states(
+        &mut self,
+        tr: TreeRef,
+        p: P3,
+        cells: &mut [((isize, isize), u8)],
+    ) -> TreeRef {
+        let Some(ps) = p.quadrants() else {
+            return match cells.last() {
+                Some(&(_, state)) => self.leaf(state),
+                None => tr,
+            };
+        };
+        if cells.is_empty() {
+            return tr;
+        }
+        let quadrant =
+            |&((y, x), _): &((isize, isize), u8)| (y >= p.y) as usize * 2 + (x >= p.x) as usize;
+        cells.sort_by_key(quadrant);
+        let mut subtree = self.subtree(tr);
+        let mut rest = cells;
+        for i in 0..4 {
+            let n = rest.iter().take_while(|cell| quadrant(cell) == i).count();
+            let (cells, tail) = rest.split_at_mut(n);
+            subtree[i] = self.set_states(subtree[i], ps[i], cells);
+            rest = tail;
+        }
+        self.branch(subtree)
+    }
+
+    /// Copies a node from another universe into this one.
+    pub fn import(&mut self, other: &Universe, tr: TreeRef) -> TreeRef {
+        self.import_all(other, [tr])[&tr]
+    }
+
+    /// Copies nodes from another universe, returning where each node under
+    /// them ended up.
+    pub fn import_all(
+        &mut self,
+        other: &Universe,
+        trs: impl IntoIterator<Item = TreeRef>,
+    ) -> FxHashMap<TreeRef, TreeRef> {
+        let mut copied = FxHashMap::from_iter([(TreeRef::EMPTY, TreeRef::EMPTY)]);
+        let mut stack: Vec<_> = trs.into_iter().map(|tr| (tr, false)).collect();
+        while let Some((tr, children_done)) = stack.pop() {
+            if copied.contains_key(&tr) {
+                continue;
+            }
+            let copy = match other.nodes[tr.index()] {
+                Tree::Branch(subtree) if children_done => {
+                    self.branch(subtree.map(|tr| copied[&tr]))
+                }
+                Tree::Branch(subtree) => {
+                    stack.push((tr, true));
+                    stack.extend(subtree.map(|tr| (tr, false)));
+                    continue;
+                }
+                Tree::Cell(state) => self.leaf(state),
+                Tree::Empty => TreeRef::EMPTY,
+            };
+            copied.insert(tr, copy);
+        }
+        copied
+    }
+
+    /// Shifts a node at the given depth by (dy, dx). This returns the shifted
+    /// node and its depth, which is deep enough to hold all of it.
+    pub fn translate(
+        &mut self,
+        mut tr: TreeRef,
+        mut depth: usize,
+        (dy, dx): (isize, isize),
+    ) -> (TreeRef, usize) {
+        if tr == TreeRef::EMPTY {
+            return (tr, depth);
+        }
+        // The pattern needs to be in the center, with room to move by up to
+        // a quarter of the node.
+        loop {
+            let room = depth >= 2 && dy.unsigned_abs().max(dx.unsigned_abs()) < 1 << (depth - 2);
+            if room {
+                let center = self.reframe(tr, P3::origin(2), 1);
+                if self.population(center) == self.population(tr) {
+                    break;
+                }
+            }
+            tr = self.expand_universe(tr);
+            depth += 1;
+        }
+        let outer = self.expand_universe(tr);
+        let half = 1 << (depth - 1);
+        let (y, x) = ((half - dy) as usize, (half - dx) as usize);
+        let tr = self.window(outer, depth, y, x, &mut FxHashMap::default());
+        (tr, depth)
+    }
+
+    /// The node at the given depth whose top-left cell is (y, x) within the
+    /// twice as big `outer` node.
+    fn window(
+        &mut self,
+        outer: TreeRef,
+        depth: usize,
+        y: usize,
+        x: usize,
+        cache: &mut FxHashMap<(TreeRef, usize, usize), TreeRef>,
+    ) -> TreeRef {
+        if outer == TreeRef::EMPTY {
+            return outer;
+        }
+        // Aligned windows are just a subtree.
+        if (y, x) == (0, 0) {
+            return self.subtree(outer)[0];
+        }
+        if let Some(&tr) = cache.get(&(outer, y, x)) {
+            return tr;
+        }
+        let subtree = self.subtree(outer).map(|tr| self.subtree(tr));
+        let grid = |r: usize, c: usize| subtree[r / 2 * 2 + c / 2][r % 2 * 2 + c % 2];
+        let half = 1 << (depth - 1);
+        let subtree = [(0, 0), (0, half), (half, 0), (half, half)].map(|(dy, dx)| {
+            let (y, x) = (y + dy, x + dx);
+            let (r, c) = (y / half, x / half);
+            let quadrant = [(r, c), (r, c + 1), (r + 1, c), (r + 1, c + 1)];
+            let quadrant = self.branch(quadrant.map(|(r, c)| grid(r, c)));
+            self.window(quadrant, depth - 1, y % half, x % half, cache)
+        });
+        let tr = self.branch(subtree);
+        cache.insert((outer, y, x), tr);
+        tr
+    }
+
+    /// Rotates or reflects a node by moving its quadrants about, all the way
+    /// down. Cells have no quadrants, so a node at depth 0 stays as it is.
+    pub fn transform(
+        &mut self,
+        tr: TreeRef,
+        transform: Transform,
+        cache: &mut FxHashMap<TreeRef, TreeRef>,
+    ) -> TreeRef {
+        let Tree::Branch(subtree) = self.nodes[tr.index()] else {
+            return tr;
+        };
+        if let Some(&tr) = cache.get(&tr) {
+            return tr;
+        }
+        let subtree = subtree.map(|tr| self.transform(tr, transform, cache));
+        let transformed = self.branch(transform.permute(subtree));
+        cache.insert(tr, transformed);
+        transformed
+    }
+
+    /// Keeps just the cells inside the rows `ys` and columns `xs`, or just those
+    /// outside. `p` is the node's position as in `P3::quadrants`.
+    pub fn clip(
+        &mut self,
+        tr: TreeRef,
+        p: P3,
+        (ys, xs): (&Range<isize>, &Range<isize>),
+        inside: bool,
+    ) -> TreeRef {
+        let (node_ys, node_xs) = p.bounds();
+        let contains = |outer: &Range<isize>, inner: &Range<isize>| {
+            outer.start <= inner.start && inner.end <= outer.end
+        };
+        let disjoint = |a: &Range<isize>, b: &Range<isize>| a.end <= b.start || b.end <= a.start;
+        if tr == TreeRef::EMPTY {
+            return tr;
+        } else if contains(ys, &node_ys) && contains(xs, &node_xs) {
+            return if inside { tr } else { TreeRef::EMPTY };
+        } else if disjoint(ys, &node_ys) || disjoint(xs, &node_xs) {
+            return if inside { TreeRef::EMPTY } else { tr };
+        }
+        let ps = p.quadrants().unwrap();
+        let subtree = self.subtree(tr);
+        let subtree = [0, 1, 2, 3].map(|i| self.clip(subtree[i], ps[i], (ys, xs), inside));
+        self.branch(subtree)
+    }
+
+    /// Combines two nodes at the given depth cell by cell.
+    pub fn merge(
+        &mut self,
+        a: TreeRef,
+        b: TreeRef,
+        depth: usize,
+        op: Op,
+        cache: &mut FxHashMap<(TreeRef, TreeRef), TreeRef>,
+    ) -> TreeRef {
+        if let Some(tr) = op.with_empty(a, b) {
+            return tr;
+        }
+        if depth == 0 {
+            return self.leaf(op.apply(self.state(a), self.state(b)));
+        }
+        if let Some(&tr) = cache.get(&(a, b)) {
+            return tr;
+        }
+        let (sub_a, sub_b) = (self.subtree(a), self.subtree(b));
+        let subtree = [0, 1, 2, 3].map(|i| self.merge(sub_a[i], sub_b[i], depth - 1, op, cache));
+        let tr = self.branch(subtree);
+        cache.insert((a, b), tr);
+        tr
+    }
+
+    /// The node at the given depth with every cell alive.
+    pub fn full(&mut self, depth: usize) -> TreeRef {
+        let mut tr = self.leaf(1);
+        for _ in 0..depth {
+            tr = self.branch([tr; 4]);
+        }
+        tr
+    }
+
+    pub fn clear_bit(&mut self, tr: TreeRef, p: P3) -> TreeRef {
+        self.set_state(tr, p, 0)
+    }
+
+    pub fn leaf(&mut self, state: u8) -> TreeRef {
+        match state {
+            0 => TreeRef::EMPTY,
+            state => self.canonicalise(Tree::Cell(state)),
+        }
+    }
+
+    pub fn branch(&mut self, subtree: [TreeRef; 4]) -> TreeRef {
+        self.canonicalise(Tree::Branch(subtree))
+    }
+
     pub fn expand_universe(&mut self, tr: TreeRef) -> TreeRef {
         let empty = TreeRef::EMPTY;
-        let subtree = match self.nodes[tr.0] {
+        let subtree = match self.nodes[tr.index()] {
             Tree::Empty => return empty,
-            Tree::Alive => [empty, empty, empty, tr],
+            Tree::Cell(_) => [empty, empty, empty, tr],
             Tree::Branch([nw, ne, sw, se]) => [
                 self.canonicalise(Tree::Branch([empty, empty, empty, nw])),
                 self.canonicalise(Tree::Branch([empty, empty, ne, empty])),
@@ -86,16 +548,16 @@ impl Universe {
         self.canonicalise(Tree::Branch(subtree))
     }
 
-    pub fn alive(&self, TreeRef(i): TreeRef) -> bool {
-        match self.nodes[i] {
-            Tree::Alive => true,
-            Tree::Empty => false,
-            _ => panic!(),
+    pub fn state(&self, tr: TreeRef) -> u8 {
+        match self.nodes[tr.index()] {
+            Tree::Cell(state) => state,
+            Tree::Empty => 0,
+            Tree::Branch(_) => panic!("{tr:?} is a branch, not a cell"),
         }
     }
 
-    pub fn subtree(&self, TreeRef(i): TreeRef) -> [TreeRef; 4] {
-        match self.nodes[i] {
+    pub fn subtree(&self, tr: TreeRef) -> [TreeRef; 4] {
+        match self.nodes[tr.index()] {
             Tree::Branch(subtree) => subtree,
             Tree::Empty => [
                 TreeRef::EMPTY,
@@ -103,12 +565,143 @@ impl Universe {
                 TreeRef::EMPTY,
                 TreeRef::EMPTY,
             ],
-            _ => panic!(),
+            Tree::Cell(_) => panic!("{tr:?} is a cell, not a branch"),
         }
     }
 
-    pub fn population(&self, TreeRef(i): TreeRef) -> usize {
-        self.populations[i]
+    /// The highest state of any cell stored, reachable or not.
+    pub fn highest_state(&self) -> u8 {
+        let states = self.nodes.iter().map(|tree| match tree {
+            Tree::Cell(state) => *state,
+            _ => 0,
+        });
+        states.max().unwrap_or(0)
+    }
+
+    /// How many nodes are stored, reachable or not.
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn collections(&self) -> u64 {
+        self.collections
+    }
+
+    pub fn config(&self) -> UniverseConfig {
+        self.config
+    }
+
+    pub fn set_config(&mut self, config: UniverseConfig) {
+        self.config = config;
+    }
+
+    pub fn stats(&self) -> UniverseStats {
+        // Hash tables store a control byte per slot.
+        let slot = |entry: usize| entry + 1;
+        let interned = size_of::<(Tree, TreeRef)>();
+        let next_gen = size_of::<((TreeRef, u8, bool), (TreeRef, u64))>();
+        UniverseStats {
+            nodes: self.nodes.len(),
+            interned: self.interned_nodes.len(),
+            cache_entries: self.next_gen.len(),
+            cache_hits: self.hits,
+            cache_misses: self.misses,
+            bytes: self.nodes.capacity() * size_of::<Tree>()
+                + self.populations.capacity() * size_of::<usize>()
+                + self.interned_nodes.capacity() * slot(interned)
+                + self.next_gen.capacity() * slot(next_gen),
+        }
+    }
+
+    /// How many steps of nodes are cached.
+    pub fn cache_len(&self) -> usize {
+        self.next_gen.len()
+    }
+
+    /// Forgets all but the `keep` or so cached steps used most recently.
+    pub fn evict_cache(&mut self, keep: usize) {
+        if self.next_gen.len() <= keep {
+            return;
+        }
+        if keep == 0 {
+            self.next_gen.clear();
+            return;
+        }
+        let mut uses: Vec<_> = self.next_gen.values().map(|&(_, used)| used).collect();
+        let k = uses.len() - keep;
+        let (_, &mut cutoff, _) = uses.select_nth_unstable(k);
+        // Steps used in the same step as the cutoff stay or go together.
+        self.next_gen.retain(|_, &mut (_, used)| used > cutoff);
+    }
+
+    /// A copy of the universe without the nodes that can't be reached from
+    /// `roots` or from the cached steps of those that can, or the steps cached
+    /// for the nodes left out, and with the rest renumbered. Also returns where
+    /// each of the nodes kept moved to.
+    pub fn collect_garbage(
+        &self,
+        roots: impl IntoIterator<Item = TreeRef>,
+    ) -> (Universe, FxHashMap<TreeRef, TreeRef>) {
+        let mut results: FxHashMap<TreeRef, Vec<TreeRef>> = FxHashMap::default();
+        for (&(tr, _, _), &(next, _)) in &self.next_gen {
+            results.entry(tr).or_default().push(next);
+        }
+        let mut live = vec![false; self.nodes.len()];
+        let mut stack: Vec<_> = roots.into_iter().collect();
+        stack.push(TreeRef::EMPTY);
+        while let Some(tr) = stack.pop() {
+            let i = tr.index();
+            if live[i] {
+                continue;
+            }
+            live[i] = true;
+            if let Tree::Branch(subtree) = self.nodes[i] {
+                stack.extend(subtree);
+            }
+            if let Some(next) = results.get(&tr) {
+                stack.extend(next);
+            }
+        }
+        // Nodes are made after their children, so renumbering them in order
+        // finds the children already moved.
+        let mut moved = FxHashMap::default();
+        let (mut nodes, mut populations) = (vec![], vec![]);
+        for (i, &tree) in self.nodes.iter().enumerate() {
+            if !live[i] {
+                continue;
+            }
+            let tree = match tree {
+                Tree::Branch(subtree) => Tree::Branch(subtree.map(|tr| moved[&tr])),
+                tree => tree,
+            };
+            moved.insert(TreeRef::at(i), TreeRef::at(nodes.len()));
+            nodes.push(tree);
+            populations.push(self.populations[i]);
+        }
+        let interned_nodes = (nodes.iter().enumerate().skip(1))
+            .map(|(i, &tree)| (tree, TreeRef::at(i)))
+            .collect();
+        let next_gen = (self.next_gen.iter())
+            .filter_map(|(&(tr, log2_gens, background), &(next, used))| {
+                let key = (*moved.get(&tr)?, log2_gens, background);
+                Some((key, (moved[&next], used)))
+            })
+            .collect();
+        let universe = Universe {
+            rule: self.rule.clone(),
+            nodes,
+            populations,
+            next_gen,
+            interned_nodes,
+            collections: self.collections + 1,
+            l2_table: self.l2_table.clone(),
+            ..*self
+        };
+        (universe, moved)
+    }
+
+    pub fn population(&self, tr: TreeRef) -> usize {
+        self.populations[tr.index()]
     }
 
     pub fn reframe(&mut self, tr: TreeRef, p: P3, z: usize) -> TreeRef {
@@ -139,46 +732,99 @@ impl Universe {
         done.pop().unwrap()
     }
 
-    pub fn step(&mut self, tr: TreeRef, depth: usize, superspeed_depth: usize) -> TreeRef {
+    /// Steps the center of a node forward, where `background` is whether the
+    /// cells outside the pattern are alive. Rules with B0 bring the background
+    /// alive, so it's stored inverted: live cells are stored as dead and vice
+    /// versa. Gives up if `stop` returns true, which is checked every so often,
+    /// keeping the results found so far.
+    pub fn step(
+        &mut self,
+        tr: TreeRef,
+        depth: usize,
+        superspeed_depth: usize,
+        background: bool,
+        stop: &dyn Fn() -> bool,
+    ) -> Option<TreeRef> {
         enum State {
-            Step(TreeRef, usize),
-            Push9(TreeRef, usize),
-            Pop9Into4(usize),
+            Step(TreeRef, usize, bool),
+            Push9(TreeRef, usize, bool),
+            Pop9Into4(usize, bool),
             Pop4Into1,
-            UpdateCache((TreeRef, bool)),
+            UpdateCache((TreeRef, u8, bool)),
+        }
+        self.steps += 1;
+        if self.l2_table.is_none() && self.steps_bitboards() {
+            self.l2_table = Some(self.make_l2_table());
+            self.totalistic = self.totalistic_counts();
         }
         let mut done = vec![];
-        let mut stack = vec![State::Step(tr, depth)];
+        let mut stack = vec![State::Step(tr, depth, background)];
+        let mut polls = 0u32;
         while let Some(state) = stack.pop() {
+            polls = polls.wrapping_add(1);
+            if self.full || polls.is_multiple_of(POLL_INTERVAL) && stop() {
+                return None;
+            }
             match state {
-                State::Step(tr, depth) => {
-                    let key = (tr, depth <= superspeed_depth);
-                    if let Some(&tr) = self.next_gen.get(&key) {
-                        done.push(tr);
+                State::Step(tr, depth, background) => {
+                    // Nodes above the superspeed depth step as far as those
+                    // at it.
+                    let log2_gens = depth.min(superspeed_depth) - self.base_depth();
+                    let key = (tr, log2_gens as u8, background);
+                    if let Some((tr, used)) = self.next_gen.get_mut(&key) {
+                        *used = self.steps;
+                        done.push(*tr);
+                        self.hits += 1;
                     } else {
+                        self.misses += 1;
                         stack.push(State::UpdateCache(key));
-                        stack.push(State::Push9(tr, depth));
+                        stack.push(State::Push9(tr, depth, background));
                     }
                 }
-                State::Push9(tr, 2) => {
+                State::Push9(tr, depth, background)
+                    if depth == self.base_depth()
+                        && (depth > 2 || !self.rule.uses_live_neighbourhood()) =>
+                {
+                    done.push(self.base_gen(tr, depth, background));
+                }
+                State::Push9(tr, 3, background) if self.steps_bitboards() => {
+                    let gens = if 3 <= superspeed_depth { 2 } else { 1 };
+                    done.push(self.l3_gen(tr, gens, background));
+                }
+                State::Push9(tr, 2, background) if self.l2_table.is_some() => {
+                    let bitmask = self.make_l2_bitmask(tr);
+                    let next = self.background_after(background, 1);
+                    let next = self.l2_lookup(bitmask, background, next);
+                    let subtree = [3, 2, 1, 0].map(|i| self.leaf(next >> i & 1));
+                    done.push(self.branch(subtree));
+                }
+                State::Push9(tr, 2, background) => {
                     let bitmask = self.make_l2_bitmask(tr);
-                    done.push(self.l2_gen(bitmask));
+                    let states = [(-1, -1), (-1, 0), (0, -1), (0, 0)]
+                        .map(|(y, x)| self.get_node(tr, P3 { y, x, z: 2 }).unwrap())
+                        .map(|tr| self.state(tr));
+                    done.push(self.l2_gen(bitmask, states, background));
                 }
-                State::Push9(tr, depth) => {
+                State::Push9(tr, depth, background) => {
                     let l2_trees = [0, 1, 2, 3, 4, 5, 6, 7, 8]
                         .map(|i| (i / 3 * 2 - 2, i % 3 * 2 - 2))
                         .map(|(y, x)| self.reframe(tr, P3 { y, x, z: 3 }, 2));
                     stack.push(State::Pop4Into1);
-                    stack.push(State::Pop9Into4(depth));
                     if depth <= superspeed_depth {
-                        let subtree = l2_trees.map(|l2| State::Step(l2, depth - 1));
+                        // The second half starts wherever the first half
+                        // left the background.
+                        let gens = 1 << (depth - 1 - self.base_depth());
+                        let next = self.background_after(background, gens);
+                        stack.push(State::Pop9Into4(depth, next));
+                        let subtree = l2_trees.map(|l2| State::Step(l2, depth - 1, background));
                         stack.extend(subtree);
                     } else {
+                        stack.push(State::Pop9Into4(depth, background));
                         let subtree = l2_trees.map(|l2| self.reframe(l2, P3::origin(2), 1));
                         done.extend(subtree.into_iter().rev());
                     }
                 }
-                State::Pop9Into4(depth) => {
+                State::Pop9Into4(depth, background) => {
                     let l1_trees = [
                         [done.pop(), done.pop(), done.pop()].map(Option::unwrap),
                         [done.pop(), done.pop(), done.pop()].map(Option::unwrap),
@@ -187,7 +833,7 @@ impl Universe {
                     let l2_trees = [0, 1, 3, 4]
                         .map(|i| [0, 1, 3, 4].map(|j| l1_trees[(i + j) / 3][(i + j) % 3]))
                         .map(|subtree| self.canonicalise(Tree::Branch(subtree)));
-                    let subtree = l2_trees.map(|l2| State::Step(l2, depth - 1));
+                    let subtree = l2_trees.map(|l2| State::Step(l2, depth - 1, background));
                     stack.extend(subtree);
                 }
                 State::Pop4Into1 => {
@@ -196,56 +842,268 @@ impl Universe {
                     done.push(tr);
                 }
                 State::UpdateCache(key) => {
-                    self.next_gen.insert(key, *done.last().unwrap());
+                    self.next_gen
+                        .insert(key, (*done.last().unwrap(), self.steps));
                 }
             }
         }
-        done.pop().unwrap()
+        done.pop()
     }
 }
 
+/// Each 4 bit number with its bits in reverse order.
+const REVERSED: [u16; 16] = [0, 8, 4, 12, 2, 10, 6, 14, 1, 9, 5, 13, 3, 11, 7, 15];
+
+fn next_background(rule: &Rule) -> [bool; 2] {
+    [false, true].map(|alive| rule.next_cell(alive as u8, |_, _| alive as u8) == 1)
+}
+
 impl Universe {
     fn canonicalise(&mut self, tree: Tree) -> TreeRef {
         if tree.is_empty() {
             return TreeRef::EMPTY;
         }
+        if self.nodes.len() >= self.capacity && !self.interned_nodes.contains_key(&tree) {
+            self.full = true;
+            return TreeRef::EMPTY;
+        }
         *self.interned_nodes.entry(tree).or_insert_with_key(|&tree| {
+            let tr = TreeRef::at(self.nodes.len());
             let population = match tree {
-                Tree::Alive => 1,
-                Tree::Branch(subtree) => subtree.map(|TreeRef(i)| self.populations[i]).iter().sum(),
+                Tree::Cell(_) => 1,
+                Tree::Branch(subtree) => {
+                    subtree.map(|tr| self.populations[tr.index()]).iter().sum()
+                }
                 Tree::Empty => unreachable!(),
             };
             self.populations.push(population);
             self.nodes.push(tree);
-            TreeRef(self.nodes.len() - 1)
+            tr
         })
     }
 
-    fn l2_gen(&mut self, bitmask: u16) -> TreeRef {
-        fn leaf(bitmask: u16) -> Tree {
-            let center = bitmask & 0b0000_0010_0000 != 0;
-            let neighbours = (bitmask & 0b0111_0101_0111).count_ones();
-            match (center, neighbours) {
-                (true, 2 | 3) | (false, 3) => Tree::Alive,
-                _ => Tree::Empty,
-            }
-        }
-        let subtree = [
-            self.canonicalise(leaf(bitmask >> 5)),
-            self.canonicalise(leaf(bitmask >> 4)),
-            self.canonicalise(leaf(bitmask >> 1)),
-            self.canonicalise(leaf(bitmask >> 0)),
+    fn l2_gen(&mut self, bitmask: u16, states: [u8; 4], background: bool) -> TreeRef {
+        // Inverted cells are flipped back before applying the rule, and the
+        // results flipped to match the next background.
+        let (flip, flip_next) = (background, self.background_after(background, 1));
+        let leaf = |bitmask: u16, state: u8| {
+            // Pick the 3x3 window in the top-left of the 4x4 bitmask out into
+            // the layout the rule expects.
+            let neighbourhood = (0..9).fold(0, |n, i| {
+                let (y, x) = (i / 3, i % 3);
+                n | (bitmask >> (10 - 4 * y - x) & 1) << i
+            });
+            let neighbourhood = neighbourhood ^ (flip as u16 * 0x1ff);
+            self.rule.next(state ^ flip as u8, neighbourhood) ^ flip_next as u8
+        };
+        let [nw, ne, sw, se] = states;
+        let next = [
+            leaf(bitmask >> 5, nw),
+            leaf(bitmask >> 4, ne),
+            leaf(bitmask >> 1, sw),
+            leaf(bitmask, se),
         ];
+        let subtree = next.map(|state| self.leaf(state));
         self.canonicalise(Tree::Branch(subtree))
     }
 
+    /// Whether 8x8 nodes can be stepped as bitboards, which needs two states
+    /// and a radius of 1.
+    fn steps_bitboards(&self) -> bool {
+        self.base_depth() == 2 && self.rule.states() == 2 && self.rule.uses_live_neighbourhood()
+    }
+
+    /// Steps the center 4x4 of an 8x8 node forward one or two generations,
+    /// packing its cells into a `u64` rather than building the nine 4x4 nodes
+    /// within it.
+    fn l3_gen(&mut self, tr: TreeRef, gens: usize, background: bool) -> TreeRef {
+        let mut board = 0;
+        self.fill_bitboard(tr, 3, 0, &mut board);
+        let mut background = background;
+        for _ in 0..gens {
+            let next = self.background_after(background, 1);
+            board = self.bitboard_gen(board, background, next);
+            background = next;
+        }
+        let cell = |y: usize, x: usize| (board >> (8 * y + x) & 1) as u8;
+        let subtree = [(2, 2), (2, 4), (4, 2), (4, 4)].map(|(y, x)| {
+            let leaves = [(y, x), (y, x + 1), (y + 1, x), (y + 1, x + 1)];
+            let subtree = leaves.map(|(y, x)| self.leaf(cell(y, x)));
+            self.branch(subtree)
+        });
+        self.branch(subtree)
+    }
+
+    /// Sets the bits of the live cells of a node at depth z, row by row from
+    /// bit `at`.
+    fn fill_bitboard(&self, tr: TreeRef, z: usize, at: usize, board: &mut u64) {
+        if tr == TreeRef::EMPTY {
+            return;
+        }
+        if z == 0 {
+            *board |= 1 << at;
+            return;
+        }
+        let half = 1 << (z - 1);
+        for (i, tr) in self.subtree(tr).into_iter().enumerate() {
+            self.fill_bitboard(tr, z - 1, at + (i / 2 * 8 + i % 2) * half, board);
+        }
+    }
+
+    /// The next generation of the cells of an 8x8 bitboard away from its
+    /// edges, where the edges come back dead. Rules that only count live
+    /// neighbours step every cell at once, adding up the board shifted each
+    /// way a bit at a time.
+    fn bitboard_gen(&self, board: u64, background: bool, next: bool) -> u64 {
+        // Cells in the rows and columns 1 to 6.
+        const INNER: u64 = 0x007e_7e7e_7e7e_7e00;
+        let Some([birth, survival]) = self.totalistic else {
+            return self.bitboard_gen_blocks(board, background, next);
+        };
+        let board = board ^ (background as u64 * u64::MAX);
+        // Each cell's neighbour to the west and the east moved onto it. Cells
+        // wrap round between rows into the edge columns, which come back dead
+        // anyway.
+        let (w, e) = (board << 1, board >> 1);
+        let neighbours = [w, e, board << 8, board >> 8, w << 8, w >> 8, e << 8, e >> 8];
+        // The count of each cell's live neighbours in binary, a board per bit.
+        let mut count = [0u64; 4];
+        for mut carry in neighbours {
+            for bit in &mut count {
+                (*bit, carry) = (*bit ^ carry, *bit & carry);
+            }
+        }
+        let alive = (0..9).fold(0, |alive, n| {
+            let has_n =
+                (count.iter().enumerate()).fold(u64::MAX, |has_n, (i, &bit)| match n >> i & 1 {
+                    1 => has_n & bit,
+                    _ => has_n & !bit,
+                });
+            let born = if birth >> n & 1 == 1 { !board } else { 0 };
+            let survive = if survival >> n & 1 == 1 { board } else { 0 };
+            alive | has_n & (born | survive)
+        });
+        (alive ^ (next as u64 * u64::MAX)) & INNER
+    }
+
+    /// Like `bitboard_gen`, for any rule, looking up the table a 2x2 block at
+    /// a time.
+    fn bitboard_gen_blocks(&self, board: u64, background: bool, next: bool) -> u64 {
+        let mut out = 0;
+        for y in [1, 3, 5] {
+            for x in [1, 3, 5] {
+                // The 4x4 around the block, with the leftmost cell of each
+                // row in the highest bit.
+                let bitmask = (0..4).fold(0, |bitmask, r| {
+                    let row = board >> (8 * (y - 1 + r) + x - 1) & 0xf;
+                    bitmask << 4 | REVERSED[row as usize]
+                });
+                let block = self.l2_lookup(bitmask, background, next) as u64;
+                let at = 8 * y + x;
+                out |= (block >> 3 & 1) << at
+                    | (block >> 2 & 1) << (at + 1)
+                    | (block >> 1 & 1) << (at + 8)
+                    | (block & 1) << (at + 9);
+            }
+        }
+        out
+    }
+
+    /// The next generation of the center 2x2 of a 4x4 bitmask from the table,
+    /// flipped from and to the background as in `l2_gen`.
+    fn l2_lookup(&self, bitmask: u16, background: bool, next: bool) -> u8 {
+        let table = self.l2_table.as_deref().unwrap();
+        table[(bitmask ^ (background as u16 * 0xffff)) as usize] ^ (next as u8 * 0xf)
+    }
+
+    /// Which counts of live neighbours bring a dead cell alive and keep a live
+    /// one alive, unless the rule depends on where the neighbours are.
+    fn totalistic_counts(&self) -> Option<[u16; 2]> {
+        const CENTER: u16 = 1 << 4;
+        let mut counts = [0; 2];
+        for state in 0..2 {
+            let mut next = [None; 9];
+            for neighbourhood in (0..512).filter(|n| n & CENTER == 0) {
+                let alive = self.rule.next(state, neighbourhood);
+                let n = neighbourhood.count_ones() as usize;
+                if *next[n].get_or_insert(alive) != alive {
+                    return None;
+                }
+            }
+            counts[state as usize] =
+                (0..9).fold(0, |counts, n| counts | ((next[n] == Some(1)) as u16) << n);
+        }
+        Some(counts)
+    }
+
+    fn make_l2_table(&self) -> Arc<[u8]> {
+        // Each 3x3 window of the 4x4 packed into 9 bits, top row highest, so
+        // the rule only runs 512 times.
+        let window = |bitmask: u16| bitmask & 7 | (bitmask >> 4 & 7) << 3 | (bitmask >> 8 & 7) << 6;
+        let windows: Vec<u8> = (0..512)
+            .map(|rows: u16| {
+                let neighbourhood = (0..9).fold(0, |n, i| {
+                    let (y, x) = (i / 3, i % 3);
+                    n | (rows >> (8 - 3 * y - x) & 1) << i
+                });
+                self.rule.next((rows >> 4 & 1) as u8, neighbourhood)
+            })
+            .collect();
+        (0..=u16::MAX)
+            .map(|bitmask| {
+                let next = |shift: u16| windows[window(bitmask >> shift) as usize];
+                next(5) << 3 | next(4) << 2 | next(1) << 1 | next(0)
+            })
+            .collect()
+    }
+
+    /// Steps the center of a node at the base depth forward one generation,
+    /// for rules too wide or with too many states to use the 4x4 bitmask.
+    fn base_gen(&mut self, tr: TreeRef, depth: usize, background: bool) -> TreeRef {
+        let (flip, flip_next) = (background, self.background_after(background, 1));
+        let w = 1isize << (depth - 1);
+        let state = |y: isize, x: isize| {
+            let tr = self.get_node(tr, P3 { y, x, z: depth }).unwrap();
+            self.state(tr) ^ flip as u8
+        };
+        let states: Vec<Vec<u8>> = (-w..w)
+            .map(|y| (-w..w).map(|x| state(y, x)).collect())
+            .collect();
+        let neighbour = |y: isize, x: isize| states[(y + w) as usize][(x + w) as usize];
+        // The next states of the central w x w cells.
+        let next: Vec<Vec<u8>> = (-w / 2..w / 2)
+            .map(|y| {
+                (-w / 2..w / 2)
+                    .map(|x| {
+                        let state = states[(y + w) as usize][(x + w) as usize];
+                        self.rule
+                            .next_cell(state, |dy, dx| neighbour(y + dy, x + dx))
+                            ^ flip_next as u8
+                    })
+                    .collect()
+            })
+            .collect();
+        self.build(&next, 0, 0, depth - 1)
+    }
+
+    /// Builds the node at depth z whose top-left cell is `states[y][x]`.
+    pub fn build(&mut self, states: &[Vec<u8>], y: usize, x: usize, z: usize) -> TreeRef {
+        if z == 0 {
+            return self.leaf(states[y][x]);
+        }
+        let w = 1 << (z - 1);
+        let subtree = [(0, 0), (0, w), (w, 0), (w, w)]
+            .map(|(dy, dx)| self.build(states, y + dy, x + dx, z - 1));
+        self.branch(subtree)
+    }
+
     fn make_l2_bitmask(&self, tr: TreeRef) -> u16 {
         let mut bitmask = 0;
         for y in -2..2 {
             for x in -2..2 {
                 bitmask <<= 1;
                 let tr = self.get_node(tr, P3 { y, x, z: 2 }).unwrap();
-                if self.alive(tr) {
+                if self.state(tr) == 1 {
                     bitmask += 1;
                 }
             }
@@ -253,3 +1111,83 @@ impl Universe {
         bitmask
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+
+    use super::*;
+    use crate::basic_state::BasicState;
+
+    #[test]
+    fn test_bitboards_match_l2_table() {
+        let mut seed = 3u64;
+        let mut soup = || {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            seed ^ seed >> 29
+        };
+        let soups = (0..500).map(|_| soup()).collect_vec();
+        for (rule, totalistic) in [
+            ("B3/S23", true),
+            ("B36/S23", true),
+            ("B0/S8", true),
+            ("B2-a/S12", false),
+            ("B2/S34H", false),
+        ] {
+            let rule: Rule = rule.parse().unwrap();
+            let mut universe = Universe::with_rule(rule.clone());
+            universe.l2_table = Some(universe.make_l2_table());
+            universe.totalistic = universe.totalistic_counts();
+            assert_eq!(universe.totalistic.is_some(), totalistic, "{rule}");
+            for &board in &soups {
+                for background in [false, true] {
+                    let next = universe.background_after(background, 1);
+                    let expected = universe.bitboard_gen_blocks(board, background, next);
+                    assert_eq!(universe.bitboard_gen(board, background, next), expected);
+                }
+                if universe.background_after(false, 1) {
+                    continue;
+                }
+                let cells = (0..64).filter(|i| board >> i & 1 == 1);
+                let state = BasicState::from_iter(cells.map(|i| (i / 8, i % 8)));
+                let next = state.step_rule(&rule);
+                let inner = (next.cells.into_iter())
+                    .filter(|&(y, x)| (1..7).contains(&y) && (1..7).contains(&x));
+                let expected = inner.fold(0, |board, (y, x)| board | 1 << (8 * y + x));
+                assert_eq!(
+                    universe.bitboard_gen(board, false, false),
+                    expected,
+                    "{rule}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_l2_table_matches_basic_state() {
+        // Life takes the quicker path through `BasicState`.
+        for rule in ["B3/S23", "B2-a/S12"] {
+            let rule: Rule = rule.parse().unwrap();
+            let universe = Universe::with_rule(rule.clone());
+            let table = universe.make_l2_table();
+            let life = rule == Rule::default();
+            for bitmask in 0..=u16::MAX {
+                let cells = (0..16)
+                    .filter(|i| bitmask >> (15 - i) & 1 == 1)
+                    .map(|i| (i / 4, i % 4));
+                let state = BasicState::from_iter(cells);
+                let next = if life {
+                    state.step()
+                } else {
+                    state.step_rule(&rule)
+                };
+                let expected = [(1, 1), (1, 2), (2, 1), (2, 2)]
+                    .into_iter()
+                    .fold(0, |n, p| n << 1 | next.cells.contains(&p) as u8);
+                assert_eq!(table[bitmask as usize], expected, "{rule} {bitmask:016b}");
+            }
+        }
+    }
+}
@@ -0,0 +1,127 @@
+//! A `HashLife` class for JavaScript, built with
+//! `wasm-pack build hashlife --features wasm-bindgen`. Coordinates are
+//! `(y, x)` as they are here, and counts are numbers rather than BigInts, so
+//! they're exact up to 2^53.
+
+use wasm_bindgen::prelude::*;
+use web_time::{Duration, Instant};
+
+use crate::{
+    HashLife, formats,
+    render::{Rgba, Settings, View},
+};
+
+#[wasm_bindgen(js_name = HashLife)]
+pub struct WasmLife {
+    hl: HashLife,
+}
+
+impl Default for WasmLife {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[wasm_bindgen(js_class = HashLife)]
+impl WasmLife {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self {
+            hl: HashLife::new(),
+        }
+    }
+
+    /// Reads a pattern in any format `formats::load` knows, RLE included.
+    #[wasm_bindgen(js_name = fromRle)]
+    pub fn from_rle(text: &str) -> Result<WasmLife, JsError> {
+        let pattern = formats::load(text.as_bytes()).map_err(|e| JsError::new(&e.to_string()))?;
+        Ok(Self { hl: pattern.life })
+    }
+
+    #[wasm_bindgen(js_name = toRle)]
+    pub fn to_rle(&self) -> String {
+        formats::write_rle(&self.hl, &Default::default())
+    }
+
+    pub fn get(&self, y: isize, x: isize) -> bool {
+        self.hl.get((y, x))
+    }
+
+    pub fn set(&mut self, y: isize, x: isize, alive: bool) {
+        self.hl.set((y, x), alive);
+    }
+
+    pub fn population(&self) -> f64 {
+        self.hl.population() as f64
+    }
+
+    pub fn generation(&self) -> f64 {
+        self.hl.generation() as f64
+    }
+
+    /// Steps 2^`log2Steps` generations in one go, however long it takes. In
+    /// the page's main thread, use `stepFor` instead.
+    pub fn step(&mut self, log2_steps: usize) -> Result<(), JsError> {
+        self.hl
+            .step_checked(log2_steps)
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Steps 2^`log2Steps` generations if that takes no more than `budgetMs`
+    /// milliseconds, and returns whether it did. The work done towards it is
+    /// kept either way, so calling this once a frame until it returns true
+    /// steps without freezing the page.
+    #[wasm_bindgen(js_name = stepFor)]
+    pub fn step_for(&mut self, log2_steps: usize, budget_ms: f64) -> bool {
+        let deadline = Instant::now() + Duration::from_secs_f64(budget_ms.max(0.) / 1000.);
+        self.hl
+            .step_unless(log2_steps, &|| Instant::now() >= deadline)
+    }
+
+    /// The cells that aren't dead among `height` rows and `width` columns
+    /// from `(top, left)`, as a flat array of `y, x` pairs in row order.
+    #[wasm_bindgen(js_name = cellsInRect)]
+    pub fn cells_in_rect(&self, top: isize, left: isize, height: usize, width: usize) -> Vec<i32> {
+        let ys = top..top + height as isize;
+        let xs = left..left + width as isize;
+        let mut cells = self.hl.cells_in_rect(ys, xs).collect::<Vec<_>>();
+        cells.sort_unstable();
+        cells
+            .into_iter()
+            .flat_map(|(y, x)| [y as i32, x as i32])
+            .collect()
+    }
+
+    /// Draws black cells on white as `height` rows of `width` pixels, four
+    /// bytes of sRGB and alpha to a pixel, ready for an `ImageData`. `zoom`
+    /// is in pixels per cell, and `(centerY, centerX)` the cell drawn in the
+    /// middle.
+    #[wasm_bindgen(js_name = renderRgba)]
+    pub fn render_rgba(
+        &self,
+        height: usize,
+        width: usize,
+        center_y: f64,
+        center_x: f64,
+        zoom: f64,
+    ) -> Vec<u8> {
+        let settings = Settings {
+            height,
+            width,
+            cell_size: 1.,
+            dead_rgba: Rgba::WHITE,
+            alive_rgba: Rgba::BLACK,
+            row_offset: self.hl.rule().neighbourhood().row_offset(),
+            grid: None,
+            ages: None,
+            heatmap: None,
+            envelope: None,
+        };
+        let view = View {
+            zoom,
+            center: (center_y, center_x),
+            rotation: 0.,
+        };
+        self.hl.render_rgba8(settings, view)
+    }
+}